@@ -0,0 +1,488 @@
+//! Custom attributes attached to measurements and groups.
+//!
+//! Attributes are arbitrary, typed key/value metadata such as a room name, finish code,
+//! floor level or color. A measurement inherits the attributes of its group; a key set on
+//! the measurement itself overrides the inherited value (see [`merge_attributes`]).
+
+use crate::unit::{Unit, UnitValue, UnitValueItemType};
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use uom::si::f32::{Area, Length};
+
+/// Relative tolerance used when comparing two quantity attributes after unit conversion.
+///
+/// Quantities are stored in `f32`, so `12 in` and `1 ft` do not round-trip to exactly the
+/// same SI value; anything within one part per million is considered equal.
+pub const QUANTITY_EQUALITY_TOLERANCE: f64 = 1e-6;
+
+/// A typed custom attribute value.
+#[napi(discriminant = "type")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AttributeValue {
+  Text {
+    value: String,
+  },
+  Number {
+    value: f64,
+  },
+  Boolean {
+    value: bool,
+  },
+  /// A unit-aware quantity, e.g. a ceiling height of `9 ft`.
+  Quantity {
+    value: f64,
+    unit: Unit,
+    magnitude: UnitValueItemType,
+  },
+}
+
+impl AttributeValue {
+  pub fn text(value: impl Into<String>) -> Self {
+    AttributeValue::Text {
+      value: value.into(),
+    }
+  }
+
+  pub fn number(value: f64) -> Self {
+    AttributeValue::Number { value }
+  }
+
+  pub fn boolean(value: bool) -> Self {
+    AttributeValue::Boolean { value }
+  }
+
+  pub fn quantity(value: f64, unit: Unit, magnitude: UnitValueItemType) -> Self {
+    AttributeValue::Quantity {
+      value,
+      unit,
+      magnitude,
+    }
+  }
+
+  /// Get the value as a unit-aware quantity.
+  ///
+  /// Numbers are returned as dimensionless scalars; text and booleans return `None`.
+  pub fn to_unit_value(&self) -> Option<UnitValue> {
+    match self {
+      AttributeValue::Number { value } => Some(UnitValue::from_scalar(*value)),
+      AttributeValue::Quantity {
+        value,
+        unit,
+        magnitude,
+      } => Some(UnitValue::new(*value, *unit, *magnitude)),
+      AttributeValue::Text { .. } | AttributeValue::Boolean { .. } => None,
+    }
+  }
+
+  /// Get the numeric value, converting quantities to `unit` when given.
+  ///
+  /// Quantities are returned in their own unit when `unit` is `None`.
+  pub fn as_number(&self, unit: Option<Unit>) -> Option<f64> {
+    match self {
+      AttributeValue::Number { value } => Some(*value),
+      AttributeValue::Quantity {
+        value,
+        unit: own_unit,
+        magnitude,
+      } => match unit {
+        Some(unit) => Some(UnitValue::new(*value, *own_unit, *magnitude).get_converted_value(unit)),
+        None => Some(*value),
+      },
+      AttributeValue::Text { .. } | AttributeValue::Boolean { .. } => None,
+    }
+  }
+
+  /// Compare two values, treating quantities of the same magnitude as equal when they
+  /// describe the same amount in different units (within [`QUANTITY_EQUALITY_TOLERANCE`]).
+  pub fn equivalent(&self, other: &AttributeValue) -> bool {
+    match (self, other) {
+      (
+        AttributeValue::Quantity {
+          value: a,
+          unit: unit_a,
+          magnitude: magnitude_a,
+        },
+        AttributeValue::Quantity {
+          value: b,
+          magnitude: magnitude_b,
+          ..
+        },
+      ) => {
+        if magnitude_a != magnitude_b {
+          return false;
+        }
+        let b = other.as_number(Some(*unit_a)).unwrap_or(*b);
+        let scale = a.abs().max(b.abs()).max(1.0);
+        (a - b).abs() <= QUANTITY_EQUALITY_TOLERANCE * scale
+      }
+      _ => self == other,
+    }
+  }
+}
+
+impl fmt::Display for AttributeValue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AttributeValue::Text { value } => write!(f, "{value}"),
+      AttributeValue::Number { value } => write!(f, "{value}"),
+      AttributeValue::Boolean { value } => write!(f, "{value}"),
+      AttributeValue::Quantity {
+        value,
+        unit,
+        magnitude,
+      } => write!(
+        f,
+        "{}",
+        UnitValue::new(*value, *unit, *magnitude).display(*unit)
+      ),
+    }
+  }
+}
+
+/// A predicate on a single attribute, used to query measurements and groups.
+#[napi(discriminant = "type")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AttributeFilter {
+  /// The attribute is present.
+  Exists { key: String },
+  /// The attribute is absent.
+  Missing { key: String },
+  /// The attribute is present and equivalent to `value`.
+  Equals { key: String, value: AttributeValue },
+  /// The attribute is absent or not equivalent to `value`.
+  NotEquals { key: String, value: AttributeValue },
+  /// The attribute is numeric and within the inclusive range.
+  ///
+  /// Quantities are converted to `unit` before comparing, or compared in their own unit
+  /// when `unit` is omitted.
+  Range {
+    key: String,
+    min: Option<f64>,
+    max: Option<f64>,
+    unit: Option<Unit>,
+  },
+  /// The attribute is text containing `value` (case-insensitive).
+  Contains { key: String, value: String },
+}
+
+impl AttributeFilter {
+  /// Get the attribute key this filter applies to.
+  pub fn key(&self) -> &str {
+    match self {
+      AttributeFilter::Exists { key }
+      | AttributeFilter::Missing { key }
+      | AttributeFilter::Equals { key, .. }
+      | AttributeFilter::NotEquals { key, .. }
+      | AttributeFilter::Range { key, .. }
+      | AttributeFilter::Contains { key, .. } => key,
+    }
+  }
+
+  /// Check whether the attributes satisfy this filter.
+  pub fn matches(&self, attributes: &BTreeMap<String, AttributeValue>) -> bool {
+    let attribute = attributes.get(self.key());
+    match self {
+      AttributeFilter::Exists { .. } => attribute.is_some(),
+      AttributeFilter::Missing { .. } => attribute.is_none(),
+      AttributeFilter::Equals { value, .. } => attribute.is_some_and(|a| a.equivalent(value)),
+      AttributeFilter::NotEquals { value, .. } => !attribute.is_some_and(|a| a.equivalent(value)),
+      AttributeFilter::Range { min, max, unit, .. } => attribute
+        .and_then(|a| a.as_number(*unit))
+        .is_some_and(|n| min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max)),
+      AttributeFilter::Contains { value, .. } => match attribute {
+        Some(AttributeValue::Text { value: text }) => {
+          text.to_lowercase().contains(&value.to_lowercase())
+        }
+        _ => false,
+      },
+    }
+  }
+}
+
+/// Check whether the attributes satisfy every filter. An empty filter list matches everything.
+pub fn matches_all(
+  filters: &[AttributeFilter],
+  attributes: &BTreeMap<String, AttributeValue>,
+) -> bool {
+  filters.iter().all(|filter| filter.matches(attributes))
+}
+
+/// Merge inherited (group) attributes with own (measurement) attributes.
+///
+/// Keys present in `own` override the inherited value.
+pub fn merge_attributes(
+  inherited: Option<&BTreeMap<String, AttributeValue>>,
+  own: Option<&BTreeMap<String, AttributeValue>>,
+) -> BTreeMap<String, AttributeValue> {
+  let mut merged = inherited.cloned().unwrap_or_default();
+  if let Some(own) = own {
+    merged.extend(own.iter().map(|(k, v)| (k.clone(), v.clone())));
+  }
+  merged
+}
+
+/// The resolved attributes and scaled quantities of one measurement, as input to
+/// [`aggregate_by_attribute`].
+#[derive(Debug, Clone)]
+pub struct AttributedMeasurement {
+  pub id: String,
+  pub attributes: BTreeMap<String, AttributeValue>,
+  pub points: f64,
+  pub area: Option<Area>,
+  pub length: Option<Length>,
+}
+
+/// Totals for the measurements sharing one value of an attribute.
+#[napi]
+#[derive(Debug, Clone)]
+pub struct AttributeAggregate {
+  value: Option<AttributeValue>,
+  measurement_ids: Vec<String>,
+  area: Option<Area>,
+  length: Option<Length>,
+  points: f64,
+}
+
+#[napi]
+impl AttributeAggregate {
+  /// Get the attribute value shared by this bucket, or `None` for measurements without it.
+  #[napi(getter)]
+  pub fn value(&self) -> Option<AttributeValue> {
+    self.value.clone()
+  }
+
+  /// Get the ids of the measurements in this bucket, sorted.
+  #[napi(getter)]
+  pub fn measurement_ids(&self) -> Vec<String> {
+    self.measurement_ids.clone()
+  }
+
+  /// Get the number of measurements in this bucket.
+  #[napi(getter)]
+  pub fn count(&self) -> f64 {
+    self.measurement_ids.len() as f64
+  }
+
+  /// Get the total points of the measurements in this bucket.
+  #[napi(getter)]
+  pub fn points(&self) -> f64 {
+    self.points
+  }
+
+  /// Get the total area, or `None` if no measurement in this bucket has a scaled area.
+  #[napi(getter)]
+  pub fn area(&self) -> Option<UnitValue> {
+    self.area.map(UnitValue::from_area)
+  }
+
+  /// Get the total length, or `None` if no measurement in this bucket has a scaled length.
+  #[napi(getter)]
+  pub fn length(&self) -> Option<UnitValue> {
+    self.length.map(UnitValue::from_length)
+  }
+}
+
+/// Bucket measurements by the value of `key` in their resolved attributes.
+///
+/// Values are bucketed with [`AttributeValue::equivalent`], so `12 in` and `1 ft` share a
+/// bucket. Buckets are sorted by their display value, with the missing bucket last.
+pub fn aggregate_by_attribute(
+  key: &str,
+  measurements: &[AttributedMeasurement],
+) -> Vec<AttributeAggregate> {
+  let mut buckets: Vec<AttributeAggregate> = Vec::new();
+  for measurement in measurements {
+    let value = measurement.attributes.get(key);
+    let index = buckets
+      .iter()
+      .position(|bucket| match (&bucket.value, value) {
+        (Some(a), Some(b)) => a.equivalent(b),
+        (None, None) => true,
+        _ => false,
+      })
+      .unwrap_or_else(|| {
+        buckets.push(AttributeAggregate {
+          value: value.cloned(),
+          measurement_ids: Vec::new(),
+          area: None,
+          length: None,
+          points: 0.0,
+        });
+        buckets.len() - 1
+      });
+    let bucket = &mut buckets[index];
+    bucket.measurement_ids.push(measurement.id.clone());
+    bucket.points += measurement.points;
+    if let Some(area) = measurement.area {
+      bucket.area = Some(bucket.area.map_or(area, |acc| acc + area));
+    }
+    if let Some(length) = measurement.length {
+      bucket.length = Some(bucket.length.map_or(length, |acc| acc + length));
+    }
+  }
+  for bucket in &mut buckets {
+    bucket.measurement_ids.sort();
+  }
+  buckets.sort_by_key(|bucket| match &bucket.value {
+    Some(value) => (false, value.to_string()),
+    None => (true, String::new()),
+  });
+  buckets
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn attributes(entries: &[(&str, AttributeValue)]) -> BTreeMap<String, AttributeValue> {
+    entries
+      .iter()
+      .map(|(k, v)| (k.to_string(), v.clone()))
+      .collect()
+  }
+
+  #[test]
+  fn test_equivalent_quantities_across_units() {
+    let inches = AttributeValue::quantity(108.0, Unit::Inches, UnitValueItemType::Length);
+    let feet = AttributeValue::quantity(9.0, Unit::Feet, UnitValueItemType::Length);
+    assert!(inches.equivalent(&feet));
+    assert!(feet.equivalent(&inches));
+
+    let area = AttributeValue::quantity(9.0, Unit::Feet, UnitValueItemType::Area);
+    assert!(!feet.equivalent(&area));
+    assert!(!feet.equivalent(&AttributeValue::number(9.0)));
+  }
+
+  #[test]
+  fn test_filters() {
+    let attrs = attributes(&[
+      ("room", AttributeValue::text("Kitchen 101")),
+      ("level", AttributeValue::number(2.0)),
+      ("fire_rated", AttributeValue::boolean(true)),
+      (
+        "height",
+        AttributeValue::quantity(9.0, Unit::Feet, UnitValueItemType::Length),
+      ),
+    ]);
+
+    assert!(AttributeFilter::Exists { key: "room".into() }.matches(&attrs));
+    assert!(
+      AttributeFilter::Missing {
+        key: "color".into()
+      }
+      .matches(&attrs)
+    );
+    assert!(
+      AttributeFilter::Equals {
+        key: "fire_rated".into(),
+        value: AttributeValue::boolean(true),
+      }
+      .matches(&attrs)
+    );
+    assert!(
+      AttributeFilter::NotEquals {
+        key: "color".into(),
+        value: AttributeValue::text("red"),
+      }
+      .matches(&attrs)
+    );
+    assert!(
+      AttributeFilter::Contains {
+        key: "room".into(),
+        value: "kitchen".into(),
+      }
+      .matches(&attrs)
+    );
+    assert!(
+      AttributeFilter::Range {
+        key: "level".into(),
+        min: Some(1.0),
+        max: Some(2.0),
+        unit: None,
+      }
+      .matches(&attrs)
+    );
+    assert!(
+      AttributeFilter::Range {
+        key: "height".into(),
+        min: Some(2.7),
+        max: None,
+        unit: Some(Unit::Meters),
+      }
+      .matches(&attrs)
+    );
+    assert!(
+      !AttributeFilter::Range {
+        key: "room".into(),
+        min: None,
+        max: None,
+        unit: None,
+      }
+      .matches(&attrs)
+    );
+    assert!(matches_all(&[], &attrs));
+  }
+
+  #[test]
+  fn test_merge_attributes() {
+    let group = attributes(&[
+      ("finish", AttributeValue::text("PT-1")),
+      ("level", AttributeValue::number(1.0)),
+    ]);
+    let own = attributes(&[("level", AttributeValue::number(2.0))]);
+    let merged = merge_attributes(Some(&group), Some(&own));
+    assert_eq!(merged.get("finish"), Some(&AttributeValue::text("PT-1")));
+    assert_eq!(merged.get("level"), Some(&AttributeValue::number(2.0)));
+    assert!(merge_attributes(None, None).is_empty());
+  }
+
+  #[test]
+  fn test_aggregate_by_attribute() {
+    let measurement =
+      |id: &str, height: Option<f64>, unit: Unit, area: f32| AttributedMeasurement {
+        id: id.to_string(),
+        attributes: height
+          .map(|h| {
+            attributes(&[(
+              "height",
+              AttributeValue::quantity(h, unit, UnitValueItemType::Length),
+            )])
+          })
+          .unwrap_or_default(),
+        points: 1.0,
+        area: Some(Area::new::<uom::si::area::square_meter>(area)),
+        length: None,
+      };
+    let buckets = aggregate_by_attribute(
+      "height",
+      &[
+        measurement("b", Some(12.0), Unit::Inches, 2.0),
+        measurement("c", None, Unit::Feet, 4.0),
+        measurement("a", Some(1.0), Unit::Feet, 3.0),
+      ],
+    );
+
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0].measurement_ids(), vec!["a", "b"]);
+    assert_eq!(buckets[0].points(), 2.0);
+    assert_eq!(
+      buckets[0].area().unwrap().get_converted_value(Unit::Meters),
+      5.0
+    );
+    assert!(buckets[0].length().is_none());
+    assert!(buckets[1].value().is_none());
+    assert_eq!(buckets[1].measurement_ids(), vec!["c"]);
+  }
+
+  #[test]
+  fn test_display_and_serialization() {
+    assert_eq!(AttributeValue::text("A").to_string(), "A");
+    assert_eq!(AttributeValue::boolean(false).to_string(), "false");
+    let value = AttributeValue::number(3.5);
+    let json = serde_json::to_string(&value).unwrap();
+    let back: AttributeValue = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, value);
+  }
+}
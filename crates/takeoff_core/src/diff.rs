@@ -0,0 +1,455 @@
+//! Differences between two snapshots of the takeoff state.
+//!
+//! A [`SnapshotDiff`] compares an earlier snapshot (e.g. a bid set) with a later one (e.g. a
+//! revised set) and reports which measurements and scales changed and how each group's
+//! quantities moved. Quantities are taken from the snapshots' computed results and converted
+//! to a single unit.
+
+use crate::attribute::QUANTITY_EQUALITY_TOLERANCE;
+use crate::measurement::Measurement;
+use crate::scale::Scale;
+use crate::snapshot::{GroupResult, StateSnapshot};
+use crate::unit::{Unit, UnitValue, UnitValueItemType};
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A quantity in the earlier and later snapshot. Missing quantities count as zero.
+#[napi(object)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct QuantityDelta {
+  pub before: f64,
+  pub after: f64,
+  /// `after - before`.
+  pub delta: f64,
+}
+
+impl QuantityDelta {
+  pub fn new(before: f64, after: f64) -> Self {
+    Self {
+      before,
+      after,
+      delta: after - before,
+    }
+  }
+
+  /// Whether the quantity is the same in both snapshots, within
+  /// [`QUANTITY_EQUALITY_TOLERANCE`] of the larger value.
+  pub fn is_unchanged(&self) -> bool {
+    let scale = self.before.abs().max(self.after.abs()).max(1.0);
+    self.delta.abs() <= QUANTITY_EQUALITY_TOLERANCE * scale
+  }
+}
+
+/// A measurement that was added, removed or modified.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeasurementDiff {
+  pub measurement_id: String,
+  /// The measurement in the earlier snapshot, `None` if it was added.
+  pub before: Option<Measurement>,
+  /// The measurement in the later snapshot, `None` if it was removed.
+  pub after: Option<Measurement>,
+  pub before_scale_id: Option<String>,
+  pub after_scale_id: Option<String>,
+  /// The area in square diff units.
+  pub area: QuantityDelta,
+  /// The length in diff units.
+  pub length: QuantityDelta,
+}
+
+/// A scale that was added, removed or redefined.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScaleDiff {
+  pub scale_id: String,
+  /// The scale in the earlier snapshot, `None` if it was added.
+  pub before: Option<Scale>,
+  /// The scale in the later snapshot, `None` if it was removed.
+  pub after: Option<Scale>,
+}
+
+/// The change in a group's totals.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupDelta {
+  pub group_id: String,
+  /// The total area in square diff units.
+  pub area: QuantityDelta,
+  /// The total length in diff units.
+  pub length: QuantityDelta,
+  pub count: QuantityDelta,
+  /// The total volume in cubic diff units.
+  pub volume: QuantityDelta,
+}
+
+impl GroupDelta {
+  pub fn is_unchanged(&self) -> bool {
+    [self.area, self.length, self.count, self.volume]
+      .iter()
+      .all(QuantityDelta::is_unchanged)
+  }
+}
+
+/// The differences between two snapshots. Every list is sorted by id.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+  /// The unit lengths are reported in; areas and volumes use its square and cube.
+  pub unit: Unit,
+  pub added_measurements: Vec<MeasurementDiff>,
+  pub removed_measurements: Vec<MeasurementDiff>,
+  /// Measurements whose definition, scale or computed quantities changed.
+  pub modified_measurements: Vec<MeasurementDiff>,
+  pub scales: Vec<ScaleDiff>,
+  /// Groups whose area, length, count or volume changed.
+  pub groups: Vec<GroupDelta>,
+}
+
+impl SnapshotDiff {
+  /// Compare an earlier snapshot with a later one.
+  ///
+  /// # Arguments
+  ///
+  /// * `before` - The earlier snapshot.
+  /// * `after` - The later snapshot.
+  /// * `unit` - The unit to report quantities in.
+  pub fn new(before: &StateSnapshot, after: &StateSnapshot, unit: Unit) -> Self {
+    let mut diff = Self {
+      unit,
+      added_measurements: Vec::new(),
+      removed_measurements: Vec::new(),
+      modified_measurements: Vec::new(),
+      scales: diff_scales(&before.scales, &after.scales),
+      groups: diff_groups(&before.results.groups, &after.results.groups, unit),
+    };
+
+    let before_measurements = by_id(&before.measurements, |m| m.id().to_string());
+    let after_measurements = by_id(&after.measurements, |m| m.id().to_string());
+    let before_results = by_id(&before.results.measurements, |r| r.measurement_id.clone());
+    let after_results = by_id(&after.results.measurements, |r| r.measurement_id.clone());
+    let ids: BTreeSet<&String> = before_measurements
+      .keys()
+      .chain(after_measurements.keys())
+      .collect();
+    for id in ids {
+      let before_result = before_results.get(id);
+      let after_result = after_results.get(id);
+      let measurement = MeasurementDiff {
+        measurement_id: id.clone(),
+        before: before_measurements.get(id).map(|m| (*m).clone()),
+        after: after_measurements.get(id).map(|m| (*m).clone()),
+        before_scale_id: before_result.and_then(|r| r.scale_id.clone()),
+        after_scale_id: after_result.and_then(|r| r.scale_id.clone()),
+        area: quantity(
+          before_result.and_then(|r| r.area),
+          after_result.and_then(|r| r.area),
+          UnitValueItemType::Area,
+          unit,
+        ),
+        length: quantity(
+          before_result.and_then(|r| r.length),
+          after_result.and_then(|r| r.length),
+          UnitValueItemType::Length,
+          unit,
+        ),
+      };
+      match (&measurement.before, &measurement.after) {
+        (None, _) => diff.added_measurements.push(measurement),
+        (_, None) => diff.removed_measurements.push(measurement),
+        (Some(a), Some(b)) => {
+          if a != b
+            || measurement.before_scale_id != measurement.after_scale_id
+            || !measurement.area.is_unchanged()
+            || !measurement.length.is_unchanged()
+          {
+            diff.modified_measurements.push(measurement);
+          }
+        }
+      }
+    }
+    diff
+  }
+
+  /// Whether the snapshots have the same measurements, scales and group totals.
+  pub fn is_empty(&self) -> bool {
+    self.added_measurements.is_empty()
+      && self.removed_measurements.is_empty()
+      && self.modified_measurements.is_empty()
+      && self.scales.is_empty()
+      && self.groups.is_empty()
+  }
+}
+
+fn by_id<T>(items: &[T], id: impl Fn(&T) -> String) -> BTreeMap<String, &T> {
+  items.iter().map(|item| (id(item), item)).collect()
+}
+
+/// Convert a quantity stored in SI units (meters, square meters, cubic meters) to `unit`.
+fn convert(value: Option<f64>, magnitude: UnitValueItemType, unit: Unit) -> f64 {
+  value
+    .map(|value| UnitValue::new(value, Unit::Meters, magnitude).get_converted_value(unit))
+    .unwrap_or(0.0)
+}
+
+fn quantity(
+  before: Option<f64>,
+  after: Option<f64>,
+  magnitude: UnitValueItemType,
+  unit: Unit,
+) -> QuantityDelta {
+  QuantityDelta::new(
+    convert(before, magnitude, unit),
+    convert(after, magnitude, unit),
+  )
+}
+
+fn diff_scales(before: &[Scale], after: &[Scale]) -> Vec<ScaleDiff> {
+  let before = by_id(before, Scale::id);
+  let after = by_id(after, Scale::id);
+  let ids: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+  ids
+    .into_iter()
+    .filter_map(|id| {
+      let (before, after) = (before.get(id), after.get(id));
+      (before != after).then(|| ScaleDiff {
+        scale_id: id.clone(),
+        before: before.map(|s| (*s).clone()),
+        after: after.map(|s| (*s).clone()),
+      })
+    })
+    .collect()
+}
+
+fn diff_groups(before: &[GroupResult], after: &[GroupResult], unit: Unit) -> Vec<GroupDelta> {
+  let before = by_id(before, |g| g.group_id.clone());
+  let after = by_id(after, |g| g.group_id.clone());
+  let ids: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+  ids
+    .into_iter()
+    .map(|id| {
+      let (before, after) = (before.get(id), after.get(id));
+      GroupDelta {
+        group_id: id.clone(),
+        area: quantity(
+          before.and_then(|g| g.area),
+          after.and_then(|g| g.area),
+          UnitValueItemType::Area,
+          unit,
+        ),
+        length: quantity(
+          before.and_then(|g| g.length),
+          after.and_then(|g| g.length),
+          UnitValueItemType::Length,
+          unit,
+        ),
+        count: QuantityDelta::new(
+          before.and_then(|g| g.count).unwrap_or(0.0),
+          after.and_then(|g| g.count).unwrap_or(0.0),
+        ),
+        volume: quantity(
+          before.and_then(|g| g.volume),
+          after.and_then(|g| g.volume),
+          UnitValueItemType::Volume,
+          unit,
+        ),
+      }
+    })
+    .filter(|delta| !delta.is_unchanged())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coords::Point;
+  use crate::group::GroupValidationMode;
+  use crate::integrity::RemovalPolicy;
+  use crate::scale::ScaleDefinition;
+  use crate::snapshot::{MeasurementResult, SNAPSHOT_VERSION, SnapshotResults};
+
+  fn count(id: &str, x: f64) -> Measurement {
+    Measurement::Count {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(x, 0.0),),
+      attributes: None,
+    }
+  }
+
+  fn scale(pixel_distance: f64) -> Scale {
+    Scale::Default {
+      id: "s1".to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance,
+        real_distance: 1.0,
+        unit: Unit::Meters,
+      },
+    }
+  }
+
+  fn result(id: &str, area: f64) -> MeasurementResult {
+    MeasurementResult {
+      measurement_id: id.to_string(),
+      scale_id: Some("s1".to_string()),
+      area: Some(area),
+      length: None,
+    }
+  }
+
+  fn snapshot(
+    measurements: Vec<Measurement>,
+    scale: Scale,
+    results: Vec<MeasurementResult>,
+    group_area: f64,
+    group_count: f64,
+  ) -> StateSnapshot {
+    StateSnapshot {
+      version: SNAPSHOT_VERSION,
+      pages: vec![],
+      groups: vec![],
+      measurements,
+      scales: vec![scale],
+      contours: vec![],
+      group_validation_mode: GroupValidationMode::Flag,
+      removal_policy: RemovalPolicy::Cascade,
+      strict_references: false,
+      results: SnapshotResults {
+        measurements: results,
+        groups: vec![GroupResult {
+          group_id: "g1".to_string(),
+          area: Some(group_area),
+          length: None,
+          points: None,
+          count: Some(group_count),
+          volume: None,
+        }],
+        contours: vec![],
+      },
+    }
+  }
+
+  #[test]
+  fn test_snapshot_diff() {
+    let before = snapshot(
+      vec![count("m1", 0.0), count("m2", 0.0), count("m3", 0.0)],
+      scale(1.0),
+      vec![result("m1", 1.0), result("m2", 1.0), result("m3", 1.0)],
+      3.0,
+      3.0,
+    );
+    let after = snapshot(
+      vec![count("m1", 0.0), count("m2", 5.0), count("m4", 0.0)],
+      scale(2.0),
+      vec![result("m1", 1.0), result("m2", 1.0), result("m4", 1.0)],
+      4.0,
+      3.0,
+    );
+
+    assert!(SnapshotDiff::new(&before, &before, Unit::Meters).is_empty());
+
+    let diff = SnapshotDiff::new(&before, &after, Unit::Feet);
+    let ids = |diffs: &[MeasurementDiff]| -> Vec<String> {
+      diffs.iter().map(|d| d.measurement_id.clone()).collect()
+    };
+    assert_eq!(ids(&diff.added_measurements), ["m4"]);
+    assert_eq!(ids(&diff.removed_measurements), ["m3"]);
+    assert_eq!(ids(&diff.modified_measurements), ["m2"]);
+    assert_eq!(diff.scales.len(), 1);
+    assert_eq!(diff.scales[0].before, Some(scale(1.0)));
+
+    // Group quantities are reported in square feet, unchanged counts are zero deltas
+    assert_eq!(diff.groups.len(), 1);
+    let group = &diff.groups[0];
+    assert!((group.area.delta - 10.7639).abs() < 1e-3);
+    assert!(group.count.is_unchanged());
+    assert!(group.volume.is_unchanged());
+  }
+
+  #[test]
+  fn test_added_measurements() {
+    let before = snapshot(vec![], scale(1.0), vec![], 0.0, 0.0);
+    let after = snapshot(
+      vec![count("m1", 0.0)],
+      scale(1.0),
+      vec![result("m1", 2.0)],
+      2.0,
+      1.0,
+    );
+
+    let diff = SnapshotDiff::new(&before, &after, Unit::Meters);
+    assert_eq!(diff.added_measurements.len(), 1);
+    let added = &diff.added_measurements[0];
+    assert_eq!(added.before, None);
+    assert_eq!(added.after, Some(count("m1", 0.0)));
+    assert_eq!(added.after_scale_id, Some("s1".to_string()));
+    assert_eq!(added.area, QuantityDelta::new(0.0, 2.0));
+    assert!(diff.removed_measurements.is_empty());
+    assert!(diff.modified_measurements.is_empty());
+    assert_eq!(diff.groups[0].count, QuantityDelta::new(0.0, 1.0));
+  }
+
+  #[test]
+  fn test_removed_measurements() {
+    let before = snapshot(
+      vec![count("m1", 0.0)],
+      scale(1.0),
+      vec![result("m1", 2.0)],
+      2.0,
+      1.0,
+    );
+    let after = snapshot(vec![], scale(1.0), vec![], 0.0, 0.0);
+
+    let diff = SnapshotDiff::new(&before, &after, Unit::Meters);
+    assert_eq!(diff.removed_measurements.len(), 1);
+    let removed = &diff.removed_measurements[0];
+    assert_eq!(removed.before, Some(count("m1", 0.0)));
+    assert_eq!(removed.after, None);
+    assert_eq!(removed.area, QuantityDelta::new(2.0, 0.0));
+    assert!(diff.added_measurements.is_empty());
+    assert!(diff.modified_measurements.is_empty());
+    assert_eq!(diff.groups[0].area, QuantityDelta::new(2.0, 0.0));
+  }
+
+  #[test]
+  fn test_unchanged_entities() {
+    let measurements = vec![count("m1", 0.0), count("m2", 0.0)];
+    let results = vec![result("m1", 1.0), result("m2", 1.0)];
+    let before = snapshot(measurements.clone(), scale(1.0), results.clone(), 2.0, 2.0);
+    assert!(SnapshotDiff::new(&before, &before.clone(), Unit::Feet).is_empty());
+
+    // Only the moved measurement is reported; the unchanged one, scale and group are not
+    let after = snapshot(
+      vec![count("m1", 0.0), count("m2", 3.0)],
+      scale(1.0),
+      results,
+      2.0,
+      2.0,
+    );
+    let diff = SnapshotDiff::new(&before, &after, Unit::Feet);
+    assert_eq!(diff.modified_measurements.len(), 1);
+    assert_eq!(diff.modified_measurements[0].measurement_id, "m2");
+    assert!(diff.modified_measurements[0].area.is_unchanged());
+    assert!(diff.scales.is_empty());
+    assert!(diff.groups.is_empty());
+  }
+
+  #[test]
+  fn test_group_volume_delta() {
+    let mut before = snapshot(vec![], scale(1.0), vec![], 0.0, 0.0);
+    let mut after = before.clone();
+    before.results.groups[0].volume = Some(1.0);
+    after.results.groups[0].volume = Some(3.0);
+
+    let diff = SnapshotDiff::new(&before, &after, Unit::Meters);
+    assert_eq!(diff.groups.len(), 1);
+    assert_eq!(diff.groups[0].volume, QuantityDelta::new(1.0, 3.0));
+    assert!(diff.groups[0].area.is_unchanged());
+
+    // Volumes are converted to the cube of the diff unit
+    let diff = SnapshotDiff::new(&before, &after, Unit::Centimeters);
+    assert!((diff.groups[0].volume.delta - 2_000_000.0).abs() < 1.0);
+  }
+}
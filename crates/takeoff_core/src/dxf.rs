@@ -0,0 +1,1314 @@
+//! DXF import and export of measurements.
+//!
+//! Only ASCII DXF is read. From the ENTITIES section, closed LWPOLYLINE and POLYLINE
+//! entities and the outer boundary loops of HATCH entities become polygons, open polylines
+//! become polylines, and INSERT block references become count markers at their insertion
+//! point. Arc segments (polyline bulges and hatch arc edges) are approximated by straight
+//! segments of at most [`DXF_ARC_SEGMENT_DEGREES`]. Other entities are skipped with a warning.
+//!
+//! DXF's y axis points up while a page's points down, so coordinates are flipped and
+//! translated so that the extents of the imported entities start at the page origin.
+//!
+//! Exported drawings are R12 DXF with one layer per group. Polygons, rectangles and
+//! polylines are written as POLYLINE entities labelled with their area or length, and count
+//! markers as inserts of a [`DXF_COUNT_BLOCK`] block carrying the measurement id. Page
+//! pixels are converted to real-world drawing units through each measurement's scale, with
+//! the page's top-left corner at the drawing origin. R12 has no header variable for the
+//! drawing unit, so the unit is only recorded in the quantity labels.
+
+use crate::attribute::AttributeValue;
+use crate::coords::{DistanceTrait, Point};
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::group::{Group, MeasurementType};
+use crate::measurement::Measurement;
+use crate::scale::{Scale, ScaleDefinition};
+use crate::unit::{Unit, UnitValue, export_decimals};
+use crate::utils::generate_random_id;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+
+/// The largest sweep, in degrees, of one straight segment approximating an arc.
+pub const DXF_ARC_SEGMENT_DEGREES: f64 = 10.0;
+
+/// The attribute holding the layer an imported measurement was drawn on.
+pub const DXF_LAYER_ATTRIBUTE: &str = "layer";
+
+/// The attribute holding the block name of an imported count marker.
+pub const DXF_BLOCK_ATTRIBUTE: &str = "block";
+
+/// The name of the block inserted for each exported count marker.
+pub const DXF_COUNT_BLOCK: &str = "TAKEOFF_COUNT";
+
+/// The tag of the count marker block attribute holding the measurement id.
+pub const DXF_ID_TAG: &str = "ID";
+
+/// Options for [`read_dxf`].
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DxfImportOptions {
+  /// Page pixels per drawing unit. Defaults to 1.
+  pub pixels_per_unit: Option<f64>,
+  /// The group id of each layer. Entities on other layers go to a group whose id is the
+  /// layer name.
+  pub layer_groups: Option<BTreeMap<String, String>>,
+}
+
+/// The result of reading a DXF drawing.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DxfImport {
+  pub measurements: Vec<Measurement>,
+  /// One group per group id used, named after the first layer mapped to it. Its
+  /// measurement type follows the imported entities: `Count` for block references, `Area`
+  /// for closed shapes, or `Linear` once any open polyline is imported. Block references
+  /// and shapes on the same group id are split: whichever is imported second goes to a
+  /// group whose id and name get a `count` or `shapes` suffix, such as `Walls-count`.
+  pub groups: Vec<Group>,
+  /// A default scale for the page derived from the `$INSUNITS` header.
+  ///
+  /// `None` if the drawing is unitless or uses units without a supported equivalent.
+  pub scale: Option<Scale>,
+  /// A message for each entity, or part of one, that was not imported.
+  pub warnings: Vec<String>,
+}
+
+/// Read the entities of an ASCII DXF drawing as measurements on a page.
+///
+/// Measurements are given random ids and a `layer` attribute; count markers also get a
+/// `block` attribute with the referenced block name.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The drawing cannot be parsed ([`TakeoffError::InvalidDxf`])
+/// - `pixels_per_unit` is not positive ([`TakeoffError::InvalidScale`])
+pub fn read_dxf(data: &str, page_id: &str, options: &DxfImportOptions) -> TakeoffResult<DxfImport> {
+  let pixels_per_unit = options.pixels_per_unit.unwrap_or(1.0);
+  if pixels_per_unit <= 0.0 || !pixels_per_unit.is_finite() {
+    return Err(TakeoffError::invalid_scale(format!(
+      "pixels_per_unit must be positive, got {pixels_per_unit}"
+    )));
+  }
+  if data.starts_with("AutoCAD Binary DXF") {
+    return Err(TakeoffError::invalid_dxf("binary DXF is not supported"));
+  }
+
+  let pairs = parse_pairs(data)?;
+  let mut warnings = Vec::new();
+  let mut units = None;
+  let mut shapes = Vec::new();
+  for (name, section) in sections(&pairs) {
+    match name {
+      "HEADER" => units = header_units(section)?,
+      "ENTITIES" => shapes = read_entities(section, &mut warnings)?,
+      _ => {}
+    }
+  }
+
+  let points = shapes.iter().flat_map(|shape| shape.geometry.points());
+  let min_x = points.clone().map(|p| p.x).fold(f64::INFINITY, f64::min);
+  let max_y = points.map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+  let to_page = |p: &Point| {
+    Point::new(
+      (p.x - min_x) * pixels_per_unit,
+      (max_y - p.y) * pixels_per_unit,
+    )
+  };
+
+  let mut measurements = Vec::new();
+  let mut groups: Vec<Group> = Vec::new();
+  for shape in shapes {
+    let layer_group_id = options
+      .layer_groups
+      .as_ref()
+      .and_then(|layer_groups| layer_groups.get(&shape.layer))
+      .cloned()
+      .unwrap_or_else(|| shape.layer.clone());
+    // Count markers and shapes cannot share a group, so the kind imported second on a
+    // group id goes to a suffixed group
+    let measurement_type = shape.geometry.measurement_type();
+    let is_count = measurement_type == MeasurementType::Count;
+    let suffix = if is_count { "count" } else { "shapes" };
+    let mismatched = groups.iter().any(|group| {
+      group.id == layer_group_id && (group.measurement_type == MeasurementType::Count) != is_count
+    });
+    let (group_id, group_name) = if mismatched {
+      (
+        format!("{layer_group_id}-{suffix}"),
+        format!("{} {suffix}", shape.layer),
+      )
+    } else {
+      (layer_group_id, shape.layer.clone())
+    };
+    let mut attributes = BTreeMap::from([(
+      DXF_LAYER_ATTRIBUTE.to_string(),
+      AttributeValue::text(&shape.layer),
+    )]);
+    let id = generate_random_id();
+    let page_id = page_id.to_string();
+    let measurement = match &shape.geometry {
+      Geometry::Polygon(points) => Measurement::Polygon {
+        id,
+        page_id,
+        group_id: group_id.clone(),
+        points: points.iter().map(to_page).collect(),
+        attributes: Some(attributes),
+      },
+      Geometry::Polyline(points) => Measurement::Polyline {
+        id,
+        page_id,
+        group_id: group_id.clone(),
+        points: points.iter().map(to_page).collect(),
+        attributes: Some(attributes),
+      },
+      Geometry::Insert { point, block } => {
+        attributes.insert(DXF_BLOCK_ATTRIBUTE.to_string(), AttributeValue::text(block));
+        Measurement::Count {
+          id,
+          page_id,
+          group_id: group_id.clone(),
+          points: (to_page(point),),
+          attributes: Some(attributes),
+        }
+      }
+    };
+    if let Err(error) = measurement.validate() {
+      warnings.push(format!("{} on line {}: {error}", shape.kind, shape.line));
+      continue;
+    }
+
+    match groups.iter_mut().find(|group| group.id == group_id) {
+      Some(group) => {
+        if group.measurement_type == MeasurementType::Area
+          && measurement_type == MeasurementType::Linear
+        {
+          group.measurement_type = MeasurementType::Linear;
+        }
+      }
+      None => groups.push(Group {
+        id: group_id,
+        name: Some(group_name),
+        measurement_type,
+        attributes: None,
+      }),
+    }
+    measurements.push(measurement);
+  }
+
+  let scale = units.map(|(unit, real_distance)| Scale::Default {
+    id: generate_random_id(),
+    page_id: page_id.to_string(),
+    scale: ScaleDefinition {
+      pixel_distance: pixels_per_unit,
+      real_distance,
+      unit,
+    },
+  });
+
+  Ok(DxfImport {
+    measurements,
+    groups,
+    scale,
+    warnings,
+  })
+}
+
+/// A group code/value pair and the line its code is on.
+#[derive(Debug, Clone, Copy)]
+struct Pair<'a> {
+  code: i32,
+  value: &'a str,
+  line: usize,
+}
+
+fn parse_pairs(data: &str) -> TakeoffResult<Vec<Pair<'_>>> {
+  let mut lines = data.lines().enumerate();
+  let mut pairs = Vec::new();
+  while let Some((index, code)) = lines.next() {
+    let code = code.trim();
+    if code.is_empty() && pairs.is_empty() {
+      continue;
+    }
+    let line = index + 1;
+    let code = code.parse().map_err(|_| {
+      TakeoffError::invalid_dxf(format!(
+        "group code `{code}` on line {line} is not an integer"
+      ))
+    })?;
+    let (_, value) = lines.next().ok_or_else(|| {
+      TakeoffError::invalid_dxf(format!("group code on line {line} has no value"))
+    })?;
+    let value = value.trim();
+    if code == 0 && value == "EOF" {
+      break;
+    }
+    pairs.push(Pair { code, value, line });
+  }
+  Ok(pairs)
+}
+
+/// Split the pairs into named sections, without their SECTION, name and ENDSEC pairs.
+fn sections<'p, 'a>(pairs: &'p [Pair<'a>]) -> Vec<(&'a str, &'p [Pair<'a>])> {
+  let mut sections = Vec::new();
+  let mut index = 0;
+  while index < pairs.len() {
+    let pair = pairs[index];
+    if pair.code == 0 && pair.value == "SECTION" {
+      let name = pairs
+        .get(index + 1)
+        .filter(|pair| pair.code == 2)
+        .map_or("", |pair| pair.value);
+      let start = index + 2;
+      let end = pairs[start.min(pairs.len())..]
+        .iter()
+        .position(|pair| pair.code == 0 && pair.value == "ENDSEC")
+        .map_or(pairs.len(), |offset| start + offset);
+      sections.push((name, &pairs[start.min(end)..end]));
+      index = end;
+    }
+    index += 1;
+  }
+  sections
+}
+
+/// Get the unit and real distance of one drawing unit from the `$INSUNITS` header variable.
+fn header_units(header: &[Pair]) -> TakeoffResult<Option<(Unit, f64)>> {
+  let Some(index) = header
+    .iter()
+    .position(|pair| pair.code == 9 && pair.value == "$INSUNITS")
+  else {
+    return Ok(None);
+  };
+  let Some(pair) = header.get(index + 1).filter(|pair| pair.code == 70) else {
+    return Ok(None);
+  };
+  let units = match integer(pair)? {
+    1 => Some((Unit::Inches, 1.0)),
+    2 => Some((Unit::Feet, 1.0)),
+    3 => Some((Unit::Feet, 5280.0)),
+    4 => Some((Unit::Centimeters, 0.1)),
+    5 => Some((Unit::Centimeters, 1.0)),
+    6 => Some((Unit::Meters, 1.0)),
+    7 => Some((Unit::Meters, 1000.0)),
+    10 => Some((Unit::Yards, 1.0)),
+    _ => None,
+  };
+  Ok(units)
+}
+
+fn number(pair: &Pair) -> TakeoffResult<f64> {
+  pair.value.parse().map_err(|_| {
+    TakeoffError::invalid_dxf(format!(
+      "value `{}` of group code {} on line {} is not a number",
+      pair.value, pair.code, pair.line
+    ))
+  })
+}
+
+fn integer(pair: &Pair) -> TakeoffResult<i64> {
+  pair.value.parse().map_err(|_| {
+    TakeoffError::invalid_dxf(format!(
+      "value `{}` of group code {} on line {} is not an integer",
+      pair.value, pair.code, pair.line
+    ))
+  })
+}
+
+/// An imported entity in drawing coordinates.
+struct Shape {
+  kind: &'static str,
+  line: usize,
+  layer: String,
+  geometry: Geometry,
+}
+
+enum Geometry {
+  Polygon(Vec<Point>),
+  Polyline(Vec<Point>),
+  Insert { point: Point, block: String },
+}
+
+impl Geometry {
+  fn points(&self) -> Vec<Point> {
+    match self {
+      Geometry::Polygon(points) | Geometry::Polyline(points) => points.clone(),
+      Geometry::Insert { point, .. } => vec![*point],
+    }
+  }
+
+  fn measurement_type(&self) -> MeasurementType {
+    match self {
+      Geometry::Polygon(_) => MeasurementType::Area,
+      Geometry::Polyline(_) => MeasurementType::Linear,
+      Geometry::Insert { .. } => MeasurementType::Count,
+    }
+  }
+}
+
+/// An entity's pairs, from its type pair up to the next entity.
+struct Entity<'p, 'a> {
+  kind: &'a str,
+  line: usize,
+  pairs: &'p [Pair<'a>],
+}
+
+impl Entity<'_, '_> {
+  fn layer(&self) -> String {
+    self
+      .pairs
+      .iter()
+      .find(|pair| pair.code == 8)
+      .map_or("0", |pair| pair.value)
+      .to_string()
+  }
+
+  fn integer(&self, code: i32) -> TakeoffResult<i64> {
+    self
+      .pairs
+      .iter()
+      .find(|pair| pair.code == code)
+      .map_or(Ok(0), integer)
+  }
+
+  fn point(&self) -> TakeoffResult<Point> {
+    let mut point = Point::new(0.0, 0.0);
+    for pair in self.pairs {
+      match pair.code {
+        10 => point.x = number(pair)?,
+        20 => point.y = number(pair)?,
+        _ => {}
+      }
+    }
+    Ok(point)
+  }
+}
+
+fn entities<'p, 'a>(section: &'p [Pair<'a>]) -> Vec<Entity<'p, 'a>> {
+  let starts: Vec<usize> = (0..section.len())
+    .filter(|&index| section[index].code == 0)
+    .collect();
+  starts
+    .iter()
+    .enumerate()
+    .map(|(i, &start)| {
+      let end = starts.get(i + 1).copied().unwrap_or(section.len());
+      Entity {
+        kind: section[start].value,
+        line: section[start].line,
+        pairs: &section[start + 1..end],
+      }
+    })
+    .collect()
+}
+
+fn read_entities(section: &[Pair], warnings: &mut Vec<String>) -> TakeoffResult<Vec<Shape>> {
+  let entities = entities(section);
+  let mut shapes = Vec::new();
+  let mut index = 0;
+  while index < entities.len() {
+    let entity = &entities[index];
+    index += 1;
+    let shape = |kind, geometry| Shape {
+      kind,
+      line: entity.line,
+      layer: entity.layer(),
+      geometry,
+    };
+    match entity.kind {
+      "LWPOLYLINE" => {
+        let mut vertices: Vec<(Point, f64)> = Vec::new();
+        for pair in entity.pairs {
+          if pair.code == 10 {
+            vertices.push((Point::new(number(pair)?, 0.0), 0.0));
+          } else if let Some(vertex) = vertices.last_mut() {
+            match pair.code {
+              20 => vertex.0.y = number(pair)?,
+              42 => vertex.1 = number(pair)?,
+              _ => {}
+            }
+          }
+        }
+        let closed = entity.integer(70)? & 1 != 0;
+        shapes.push(shape("LWPOLYLINE", polyline(&vertices, closed)));
+      }
+      "POLYLINE" => {
+        let mut vertices = Vec::new();
+        while let Some(vertex) = entities.get(index).filter(|e| e.kind == "VERTEX") {
+          let bulge = vertex
+            .pairs
+            .iter()
+            .find(|pair| pair.code == 42)
+            .map_or(Ok(0.0), number)?;
+          vertices.push((vertex.point()?, bulge));
+          index += 1;
+        }
+        if entities.get(index).is_some_and(|e| e.kind == "SEQEND") {
+          index += 1;
+        }
+        let flags = entity.integer(70)?;
+        if flags & (16 | 64) != 0 {
+          warnings.push(format!(
+            "POLYLINE on line {}: polygon and polyface meshes are not supported",
+            entity.line
+          ));
+          continue;
+        }
+        shapes.push(shape("POLYLINE", polyline(&vertices, flags & 1 != 0)));
+      }
+      "HATCH" => {
+        for boundary in hatch_boundaries(entity, warnings)? {
+          shapes.push(shape("HATCH", Geometry::Polygon(boundary)));
+        }
+      }
+      "INSERT" => {
+        let block = entity
+          .pairs
+          .iter()
+          .find(|pair| pair.code == 2)
+          .map_or("", |pair| pair.value)
+          .to_string();
+        let point = entity.point()?;
+        // Attribute values of the insert
+        while entities.get(index).is_some_and(|e| e.kind == "ATTRIB") {
+          index += 1;
+        }
+        if entities.get(index).is_some_and(|e| e.kind == "SEQEND") {
+          index += 1;
+        }
+        shapes.push(shape("INSERT", Geometry::Insert { point, block }));
+      }
+      kind => warnings.push(format!(
+        "{kind} on line {}: entity type is not supported",
+        entity.line
+      )),
+    }
+  }
+  Ok(shapes)
+}
+
+/// Build a polygon or polyline from vertices and the bulge of the segment following each.
+fn polyline(vertices: &[(Point, f64)], closed: bool) -> Geometry {
+  let mut points = Vec::new();
+  let segments = if closed {
+    vertices.len()
+  } else {
+    vertices.len().saturating_sub(1)
+  };
+  for i in 0..segments {
+    let (start, bulge) = vertices[i];
+    let (end, _) = vertices[(i + 1) % vertices.len()];
+    points.extend(bulge_arc(start, end, bulge));
+  }
+  if closed {
+    if points.len() > 1 && points.first() == points.last() {
+      points.pop();
+    }
+    Geometry::Polygon(points)
+  } else {
+    points.extend(vertices.last().map(|(point, _)| *point));
+    Geometry::Polyline(points)
+  }
+}
+
+/// Approximate the segment from `start` to `end` with the given bulge, excluding `end`.
+///
+/// The bulge is the tangent of a quarter of the arc's sweep, positive counterclockwise.
+fn bulge_arc(start: Point, end: Point, bulge: f64) -> Vec<Point> {
+  let (dx, dy) = (end.x - start.x, end.y - start.y);
+  let chord = dx.hypot(dy);
+  if bulge.abs() < 1e-12 || chord == 0.0 {
+    return vec![start];
+  }
+  let sweep = 4.0 * bulge.atan();
+  // Signed distance from the chord midpoint to the center, to the left of the chord
+  let offset = chord / (2.0 * (sweep / 2.0).tan());
+  let center = Point::new(
+    (start.x + end.x) / 2.0 - dy / chord * offset,
+    (start.y + end.y) / 2.0 + dx / chord * offset,
+  );
+  arc(
+    center,
+    start.distance_to(&center),
+    (start.y - center.y).atan2(start.x - center.x),
+    sweep,
+  )
+}
+
+/// Approximate an arc from `start_angle` sweeping `sweep` radians, excluding its end.
+fn arc(center: Point, radius: f64, start_angle: f64, sweep: f64) -> Vec<Point> {
+  let steps = (sweep.abs() / DXF_ARC_SEGMENT_DEGREES.to_radians())
+    .ceil()
+    .max(1.0) as usize;
+  (0..steps)
+    .map(|step| {
+      let angle = start_angle + sweep * step as f64 / steps as f64;
+      Point::new(
+        center.x + radius * angle.cos(),
+        center.y + radius * angle.sin(),
+      )
+    })
+    .collect()
+}
+
+/// Read the pairs of a HATCH entity's boundary data in order.
+struct Cursor<'p, 'a> {
+  pairs: &'p [Pair<'a>],
+  index: usize,
+  line: usize,
+}
+
+impl<'a> Cursor<'_, 'a> {
+  fn next(&mut self, code: i32) -> TakeoffResult<Pair<'a>> {
+    match self.pairs.get(self.index) {
+      Some(pair) if pair.code == code => {
+        self.index += 1;
+        Ok(*pair)
+      }
+      Some(pair) => Err(TakeoffError::invalid_dxf(format!(
+        "HATCH on line {}: expected group code {code} on line {}, got {}",
+        self.line, pair.line, pair.code
+      ))),
+      None => Err(TakeoffError::invalid_dxf(format!(
+        "HATCH on line {}: boundary data ends before group code {code}",
+        self.line
+      ))),
+    }
+  }
+
+  fn number(&mut self, code: i32) -> TakeoffResult<f64> {
+    number(&self.next(code)?)
+  }
+
+  fn integer(&mut self, code: i32) -> TakeoffResult<i64> {
+    integer(&self.next(code)?)
+  }
+
+  fn point(&mut self, x: i32) -> TakeoffResult<Point> {
+    Ok(Point::new(self.number(x)?, self.number(x + 10)?))
+  }
+
+  fn skip_if(&mut self, code: i32) {
+    if self
+      .pairs
+      .get(self.index)
+      .is_some_and(|pair| pair.code == code)
+    {
+      self.index += 1;
+    }
+  }
+}
+
+/// Read the outer boundary loops of a HATCH entity.
+///
+/// Loops flagged external or outermost are returned; if no loop is flagged, all loops are.
+/// Inner loops (holes) cannot be represented by a polygon and are dropped with a warning.
+fn hatch_boundaries(entity: &Entity, warnings: &mut Vec<String>) -> TakeoffResult<Vec<Vec<Point>>> {
+  let Some(start) = entity.pairs.iter().position(|pair| pair.code == 91) else {
+    return Ok(Vec::new());
+  };
+  let mut cursor = Cursor {
+    pairs: &entity.pairs[start..],
+    index: 0,
+    line: entity.line,
+  };
+  let count = cursor.integer(91)?;
+  let mut loops = Vec::new();
+  for _ in 0..count {
+    let flags = cursor.integer(92)?;
+    let mut points = Vec::new();
+    if flags & 2 != 0 {
+      let has_bulge = cursor.integer(72)? != 0;
+      cursor.skip_if(73);
+      let vertex_count = cursor.integer(93)?;
+      let mut vertices = Vec::new();
+      for _ in 0..vertex_count {
+        let point = cursor.point(10)?;
+        let bulge = if has_bulge { cursor.number(42)? } else { 0.0 };
+        vertices.push((point, bulge));
+      }
+      if let Geometry::Polygon(polygon) = polyline(&vertices, true) {
+        points = polygon;
+      }
+    } else {
+      let edge_count = cursor.integer(93)?;
+      for _ in 0..edge_count {
+        match cursor.integer(72)? {
+          1 => {
+            points.push(cursor.point(10)?);
+            cursor.point(11)?;
+          }
+          2 => {
+            let center = cursor.point(10)?;
+            let radius = cursor.number(40)?;
+            let start_angle = cursor.number(50)?;
+            let end_angle = cursor.number(51)?;
+            let counterclockwise = cursor.integer(73)? != 0;
+            let sweep = (end_angle - start_angle).rem_euclid(360.0);
+            let sweep = if sweep == 0.0 { 360.0 } else { sweep };
+            // Clockwise arc angles are measured clockwise
+            let (start_angle, sweep) = if counterclockwise {
+              (start_angle, sweep)
+            } else {
+              (-start_angle, -sweep)
+            };
+            points.extend(arc(
+              center,
+              radius,
+              start_angle.to_radians(),
+              sweep.to_radians(),
+            ));
+          }
+          edge_type => {
+            warnings.push(format!(
+              "HATCH on line {}: {} boundary edges are not supported",
+              entity.line,
+              if edge_type == 3 { "ellipse" } else { "spline" }
+            ));
+            return Ok(Vec::new());
+          }
+        }
+      }
+    }
+    // Source boundary object references
+    cursor.skip_if(97);
+    while cursor
+      .pairs
+      .get(cursor.index)
+      .is_some_and(|pair| pair.code == 330)
+    {
+      cursor.index += 1;
+    }
+    loops.push((flags, points));
+  }
+
+  let outer = |flags: i64| flags & (1 | 16) != 0;
+  if !loops.iter().any(|(flags, _)| outer(*flags)) {
+    return Ok(loops.into_iter().map(|(_, points)| points).collect());
+  }
+  let inner = loops.iter().filter(|(flags, _)| !outer(*flags)).count();
+  if inner > 0 {
+    warnings.push(format!(
+      "HATCH on line {}: {inner} inner boundary loops were dropped",
+      entity.line
+    ));
+  }
+  Ok(
+    loops
+      .into_iter()
+      .filter(|(flags, _)| outer(*flags))
+      .map(|(_, points)| points)
+      .collect(),
+  )
+}
+
+/// A measurement with the layer and quantities written alongside its geometry.
+#[derive(Debug, Clone)]
+pub struct DxfMeasurement {
+  pub measurement: Measurement,
+  /// The layer to draw the measurement on, usually its group's name.
+  pub layer: String,
+  /// The scale of the measurement. Measurements without one are written at one drawing
+  /// unit per pixel.
+  pub scale: Option<Scale>,
+  pub area: Option<UnitValue>,
+  pub length: Option<UnitValue>,
+}
+
+/// Options for [`write_dxf`].
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DxfExportOptions {
+  /// The drawing unit. Defaults to feet.
+  ///
+  /// R12 drawings have no `$INSUNITS` header, so the unit only appears in the quantity
+  /// labels and is lost when the drawing is read back.
+  pub unit: Option<Unit>,
+  /// The height of quantity labels and the size of count markers, in drawing units. Must
+  /// be positive. Defaults to 1.
+  pub text_height: Option<f64>,
+  /// The number of decimal places of quantity labels, at most
+  /// [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS). Defaults to
+  /// [`DEFAULT_EXPORT_DECIMALS`](crate::unit::DEFAULT_EXPORT_DECIMALS).
+  pub decimals: Option<u32>,
+}
+
+/// Write measurements as an ASCII DXF drawing.
+///
+/// Each layer name has the characters DXF does not allow in names replaced by `_`. Names
+/// that would then match another layer, ignoring case, get a `_2`, `_3`, ... suffix.
+/// Closed shapes are labelled with their area and polylines with their length, in the
+/// drawing unit, at their centroid.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - A measurement has invalid geometry ([`TakeoffError::EmptyGeometry`])
+/// - A measurement's scale is invalid ([`TakeoffError::InvalidScale`])
+/// - `text_height` is not positive ([`TakeoffError::InvalidDxf`])
+/// - `decimals` exceeds [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS)
+///   ([`TakeoffError::InvalidDecimals`])
+pub fn write_dxf(
+  measurements: &[DxfMeasurement],
+  options: &DxfExportOptions,
+) -> TakeoffResult<String> {
+  let unit = options.unit.unwrap_or(Unit::Feet);
+  let text_height = options.text_height.unwrap_or(1.0);
+  if text_height <= 0.0 || !text_height.is_finite() {
+    return Err(TakeoffError::invalid_dxf(format!(
+      "text_height must be positive, got {text_height}"
+    )));
+  }
+  let decimals = export_decimals(options.decimals)?;
+  let mut layers = vec!["0".to_string()];
+  let mut layer_names: BTreeMap<&str, String> = BTreeMap::new();
+  for measurement in measurements {
+    if layer_names.contains_key(measurement.layer.as_str()) {
+      continue;
+    }
+    let layer = layer_name(&measurement.layer, &layers);
+    if !layers.contains(&layer) {
+      layers.push(layer.clone());
+    }
+    layer_names.insert(&measurement.layer, layer);
+  }
+
+  let mut dxf = DxfWriter::default();
+  dxf.section("HEADER");
+  dxf.pair(9, "$ACADVER");
+  dxf.pair(1, "AC1009");
+  dxf.pair(0, "ENDSEC");
+
+  dxf.section("TABLES");
+  dxf.pairs(&[(0, "TABLE"), (2, "LTYPE"), (70, "1")]);
+  dxf.pairs(&[
+    (0, "LTYPE"),
+    (2, "CONTINUOUS"),
+    (70, "0"),
+    (3, "Solid line"),
+  ]);
+  dxf.pairs(&[(72, "65"), (73, "0"), (40, "0.0"), (0, "ENDTAB")]);
+  dxf.pairs(&[(0, "TABLE"), (2, "LAYER")]);
+  dxf.pair(70, layers.len());
+  for (index, layer) in layers.iter().enumerate() {
+    dxf.pairs(&[(0, "LAYER"), (2, layer), (70, "0")]);
+    // Cycle through the standard colors 1-6, keeping white (7) for layer 0
+    dxf.pair(62, if index == 0 { 7 } else { (index - 1) % 6 + 1 });
+    dxf.pair(6, "CONTINUOUS");
+  }
+  dxf.pairs(&[(0, "ENDTAB"), (0, "ENDSEC")]);
+
+  dxf.section("BLOCKS");
+  dxf.pairs(&[(0, "BLOCK"), (8, "0"), (2, DXF_COUNT_BLOCK), (70, "2")]);
+  dxf.pairs(&[(10, "0.0"), (20, "0.0"), (30, "0.0"), (3, DXF_COUNT_BLOCK)]);
+  dxf.pairs(&[
+    (0, "CIRCLE"),
+    (8, "0"),
+    (10, "0.0"),
+    (20, "0.0"),
+    (40, "0.5"),
+  ]);
+  dxf.pairs(&[
+    (0, "ATTDEF"),
+    (8, "0"),
+    (10, "0.0"),
+    (20, "0.0"),
+    (40, "0.25"),
+  ]);
+  dxf.pairs(&[(1, ""), (3, "Measurement id"), (2, DXF_ID_TAG), (70, "1")]);
+  dxf.pairs(&[(0, "ENDBLK"), (8, "0"), (0, "ENDSEC")]);
+
+  dxf.section("ENTITIES");
+  for item in measurements {
+    let layer = &layer_names[item.layer.as_str()];
+    let factor = match &item.scale {
+      Some(scale) => scale.get_unit().meters_per_unit() / unit.meters_per_unit() / scale.ratio()?,
+      None => 1.0,
+    };
+    // Pages grow downward, drawings upward
+    let to_drawing = |point: &Point| (point.x * factor, -point.y * factor);
+
+    let (points, closed) = match &item.measurement {
+      Measurement::Count { id, points, .. } => {
+        let (x, y) = to_drawing(&points.0);
+        dxf.pairs(&[(0, "INSERT"), (8, layer), (66, "1"), (2, DXF_COUNT_BLOCK)]);
+        dxf.point(10, x, y);
+        dxf.pair(41, text_height);
+        dxf.pair(42, text_height);
+        dxf.pairs(&[(0, "ATTRIB"), (8, layer)]);
+        dxf.point(10, x, y);
+        dxf.pair(40, text_height / 4.0);
+        dxf.pairs(&[(1, id), (2, DXF_ID_TAG), (70, "1")]);
+        dxf.pairs(&[(0, "SEQEND"), (8, layer)]);
+        continue;
+      }
+      Measurement::Polygon { points, .. } => (points.clone(), true),
+      Measurement::Polyline { points, .. } => (points.clone(), false),
+      Measurement::Rectangle { points: (a, b), .. } => (
+        vec![*a, Point::new(b.x, a.y), *b, Point::new(a.x, b.y)],
+        true,
+      ),
+    };
+
+    dxf.pairs(&[(0, "POLYLINE"), (8, layer), (66, "1")]);
+    dxf.point(10, 0.0, 0.0);
+    dxf.pair(70, i32::from(closed));
+    for point in &points {
+      let (x, y) = to_drawing(point);
+      dxf.pairs(&[(0, "VERTEX"), (8, layer)]);
+      dxf.point(10, x, y);
+    }
+    dxf.pairs(&[(0, "SEQEND"), (8, layer)]);
+
+    let label = if closed {
+      item
+        .area
+        .as_ref()
+        .map(|area| area.export_label(unit, decimals))
+    } else {
+      item
+        .length
+        .as_ref()
+        .map(|length| length.export_label(unit, decimals))
+    };
+    if let Some(label) = label {
+      let (x, y) = to_drawing(&item.measurement.get_centroid()?);
+      dxf.pairs(&[(0, "TEXT"), (8, layer)]);
+      dxf.point(10, x, y);
+      dxf.pair(40, text_height);
+      dxf.pair(1, label);
+      // Middle-centered on the alignment point
+      dxf.pair(72, 4);
+      dxf.point(11, x, y);
+    }
+  }
+  dxf.pairs(&[(0, "ENDSEC"), (0, "EOF")]);
+  Ok(dxf.output)
+}
+
+/// Replace the characters DXF does not allow in a layer name, and suffix it if it matches
+/// one of the `taken` names. Blank names map to layer `0`.
+fn layer_name(name: &str, taken: &[String]) -> String {
+  let name: String = name
+    .chars()
+    .map(|c| match c {
+      '<' | '>' | '/' | '\\' | '"' | ':' | ';' | '?' | '*' | '|' | '=' | ',' | '`' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .collect();
+  if name.trim().is_empty() {
+    return "0".to_string();
+  }
+  let is_taken = |name: &str| taken.iter().any(|taken| taken.eq_ignore_ascii_case(name));
+  if !is_taken(&name) {
+    return name;
+  }
+  let mut suffix = 2;
+  while is_taken(&format!("{name}_{suffix}")) {
+    suffix += 1;
+  }
+  format!("{name}_{suffix}")
+}
+
+#[derive(Default)]
+struct DxfWriter {
+  output: String,
+}
+
+impl DxfWriter {
+  fn pair(&mut self, code: i32, value: impl Display) {
+    let _ = write!(self.output, "{code}\n{value}\n");
+  }
+
+  fn pairs(&mut self, pairs: &[(i32, &str)]) {
+    for (code, value) in pairs {
+      self.pair(*code, value);
+    }
+  }
+
+  fn point(&mut self, code: i32, x: f64, y: f64) {
+    self.pair(code, x);
+    self.pair(code + 10, y);
+    self.pair(code + 20, 0.0);
+  }
+
+  fn section(&mut self, name: &str) {
+    self.pairs(&[(0, "SECTION"), (2, name)]);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f64::consts::PI;
+
+  fn dxf(header: &str, entities: &str) -> String {
+    let body = |pairs: &str| {
+      pairs
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .chunks(2)
+        .map(|pair| format!("{}\n{}\n", pair[0], pair[1]))
+        .collect::<String>()
+    };
+    format!(
+      "0\nSECTION\n2\nHEADER\n{}0\nENDSEC\n0\nSECTION\n2\nENTITIES\n{}0\nENDSEC\n0\nEOF\n",
+      body(header),
+      body(entities)
+    )
+  }
+
+  fn area(measurement: &Measurement) -> f64 {
+    use geo::Area;
+    measurement.to_geometry().unwrap().unsigned_area()
+  }
+
+  #[test]
+  fn test_read_dxf() {
+    let data = dxf(
+      "9 $INSUNITS 70 2",
+      "0 LWPOLYLINE 8 Walls 90 4 70 1 \
+         10 0 20 0 10 10 20 0 10 10 20 10 10 0 20 10 \
+       0 LWPOLYLINE 8 Pipes 90 2 70 0 10 0 20 0 10 10 20 10 \
+       0 POLYLINE 8 Walls 66 1 70 0 \
+       0 VERTEX 8 Walls 10 0 20 0 \
+       0 VERTEX 8 Walls 10 4 20 0 \
+       0 SEQEND 8 Walls \
+       0 INSERT 8 Fixtures 2 OUTLET 10 5 20 10 \
+       0 CIRCLE 8 Walls 10 0 20 0 40 1",
+    );
+    let options = DxfImportOptions {
+      pixels_per_unit: Some(2.0),
+      layer_groups: Some(BTreeMap::from([("Pipes".to_string(), "g1".to_string())])),
+    };
+    let import = read_dxf(&data, "p1", &options).unwrap();
+
+    let kinds: Vec<&str> = import.measurements.iter().map(Measurement::kind).collect();
+    assert_eq!(kinds, ["Polygon", "Polyline", "Polyline", "Count"]);
+    assert!((area(&import.measurements[0]) - 400.0).abs() < 1e-9);
+    // The y axis is flipped: the top of the drawing (y = 10) is the top of the page
+    assert_eq!(
+      import.measurements[3],
+      Measurement::Count {
+        id: import.measurements[3].id().to_string(),
+        page_id: "p1".to_string(),
+        group_id: "Fixtures".to_string(),
+        points: (Point::new(10.0, 0.0),),
+        attributes: Some(BTreeMap::from([
+          (
+            DXF_BLOCK_ATTRIBUTE.to_string(),
+            AttributeValue::text("OUTLET")
+          ),
+          (
+            DXF_LAYER_ATTRIBUTE.to_string(),
+            AttributeValue::text("Fixtures")
+          ),
+        ])),
+      }
+    );
+
+    let groups: Vec<(&str, MeasurementType)> = import
+      .groups
+      .iter()
+      .map(|group| (group.id.as_str(), group.measurement_type))
+      .collect();
+    assert_eq!(
+      groups,
+      [
+        ("Walls", MeasurementType::Linear),
+        ("g1", MeasurementType::Linear),
+        ("Fixtures", MeasurementType::Count),
+      ]
+    );
+    assert_eq!(import.groups[1].name.as_deref(), Some("Pipes"));
+
+    let Some(Scale::Default { scale, .. }) = import.scale else {
+      panic!("expected a default scale");
+    };
+    assert_eq!(scale.unit, Unit::Feet);
+    assert_eq!(scale.ratio().unwrap(), 2.0);
+
+    assert_eq!(import.warnings.len(), 1);
+    assert!(import.warnings[0].starts_with("CIRCLE"));
+  }
+
+  #[test]
+  fn test_read_dxf_mixed_layer() {
+    let data = dxf(
+      "",
+      "0 INSERT 8 Walls 2 DOOR 10 0 20 0 \
+       0 LWPOLYLINE 8 Walls 90 4 70 1 \
+         10 0 20 0 10 10 20 0 10 10 20 10 10 0 20 10 \
+       0 LWPOLYLINE 8 Walls 90 2 70 0 10 0 20 0 10 10 20 10 \
+       0 INSERT 8 Walls 2 DOOR 10 5 20 5",
+    );
+    let import = read_dxf(&data, "p1", &DxfImportOptions::default()).unwrap();
+    let group_ids: Vec<&str> = import
+      .measurements
+      .iter()
+      .map(Measurement::group_id)
+      .collect();
+    assert_eq!(
+      group_ids,
+      ["Walls", "Walls-shapes", "Walls-shapes", "Walls"]
+    );
+    let groups: Vec<(&str, Option<&str>, MeasurementType)> = import
+      .groups
+      .iter()
+      .map(|group| {
+        (
+          group.id.as_str(),
+          group.name.as_deref(),
+          group.measurement_type,
+        )
+      })
+      .collect();
+    assert_eq!(
+      groups,
+      [
+        ("Walls", Some("Walls"), MeasurementType::Count),
+        (
+          "Walls-shapes",
+          Some("Walls shapes"),
+          MeasurementType::Linear
+        ),
+      ]
+    );
+    // Every measurement is accepted by its group
+    for measurement in &import.measurements {
+      let group = import
+        .groups
+        .iter()
+        .find(|group| group.id == measurement.group_id())
+        .unwrap();
+      assert!(group.check_measurement(measurement).is_ok());
+    }
+  }
+
+  #[test]
+  fn test_read_dxf_bulges_and_hatches() {
+    // A 2 x 2 square whose right side bulges out into a half circle
+    let data = dxf(
+      "",
+      "0 LWPOLYLINE 8 0 70 1 \
+         10 0 20 0 10 2 20 0 42 1 10 2 20 2 10 0 20 2 \
+       0 HATCH 8 Slab 91 2 \
+         92 3 72 0 73 1 93 3 10 0 20 0 10 4 20 0 10 0 20 3 97 0 \
+         92 0 93 2 \
+           72 2 10 0 20 0 40 1 50 0 51 180 73 1 \
+           72 1 10 -1 20 0 11 1 21 0",
+    );
+    let import = read_dxf(&data, "p1", &DxfImportOptions::default()).unwrap();
+    assert!(import.scale.is_none());
+    assert_eq!(import.measurements.len(), 2);
+
+    let expected = 4.0 + PI / 2.0;
+    let actual = area(&import.measurements[0]);
+    assert!((actual - expected).abs() < 0.05, "area was {actual}");
+    assert!((area(&import.measurements[1]) - 6.0).abs() < 1e-9);
+    assert_eq!(import.groups[1].measurement_type, MeasurementType::Area);
+    assert_eq!(import.warnings.len(), 1);
+    assert!(import.warnings[0].contains("1 inner boundary loops"));
+
+    let Measurement::Polygon { points, .. } = &import.measurements[0] else {
+      panic!("expected a polygon");
+    };
+    // The bulge passes through (3, 1), which is (3, 2) on the page as the hatch reaches y = 3
+    assert!(
+      points
+        .iter()
+        .any(|p| p.distance_to(&Point::new(3.0, 2.0)) < 1e-9)
+    );
+  }
+
+  #[test]
+  fn test_write_dxf_roundtrip() {
+    use crate::unit::UnitValueItemType;
+
+    let scale = Scale::Default {
+      id: "s1".to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance: 10.0,
+        real_distance: 1.0,
+        unit: Unit::Feet,
+      },
+    };
+    let item = |measurement: Measurement, layer: &str, area: Option<f64>| DxfMeasurement {
+      measurement,
+      layer: layer.to_string(),
+      scale: Some(scale.clone()),
+      area: area.map(|area| UnitValue::new(area, Unit::Feet, UnitValueItemType::Area)),
+      length: Some(UnitValue::new(30.0, Unit::Feet, UnitValueItemType::Length)),
+    };
+    let measurements = vec![
+      item(
+        Measurement::Rectangle {
+          id: "r1".to_string(),
+          page_id: "p1".to_string(),
+          group_id: "g1".to_string(),
+          points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+          attributes: None,
+        },
+        "Floor: Carpet",
+        Some(50.0),
+      ),
+      item(
+        Measurement::Polyline {
+          id: "l1".to_string(),
+          page_id: "p1".to_string(),
+          group_id: "g2".to_string(),
+          points: vec![Point::new(0.0, 0.0), Point::new(300.0, 0.0)],
+          attributes: None,
+        },
+        "Base",
+        None,
+      ),
+      item(
+        Measurement::Count {
+          id: "c1".to_string(),
+          page_id: "p1".to_string(),
+          group_id: "g3".to_string(),
+          points: (Point::new(20.0, 30.0),),
+          attributes: None,
+        },
+        "Outlets",
+        None,
+      ),
+    ];
+    let data = write_dxf(&measurements, &DxfExportOptions::default()).unwrap();
+    assert!(data.contains("2\nFloor_ Carpet\n"));
+    assert!(data.contains("1\n50.00 sq ft\n"));
+    assert!(data.contains("1\n30.00 ft\n"));
+    assert!(data.contains("1\nc1\n2\nID\n"));
+    // 10 pixels per foot: the rectangle is 10 ft wide and extends below the origin
+    assert!(data.contains("10\n10\n20\n-5\n"));
+
+    let options = DxfImportOptions {
+      pixels_per_unit: Some(10.0),
+      layer_groups: None,
+    };
+    let import = read_dxf(&data, "p1", &options).unwrap();
+    // R12 has no unit header
+    assert!(import.scale.is_none());
+    let Measurement::Polygon { points, .. } = &import.measurements[0] else {
+      panic!("expected a polygon");
+    };
+    assert_eq!(points[2], Point::new(100.0, 50.0));
+    assert_eq!(
+      import.measurements[2].get_centroid().unwrap(),
+      Point::new(20.0, 30.0)
+    );
+    let group_ids: Vec<&str> = import
+      .groups
+      .iter()
+      .map(|group| group.id.as_str())
+      .collect();
+    assert_eq!(group_ids, ["Floor_ Carpet", "Base", "Outlets"]);
+    // The quantity labels are not imported
+    assert_eq!(import.warnings.len(), 2);
+  }
+
+  #[test]
+  fn test_write_dxf_layer_names() {
+    let item = |id: &str, layer: &str| DxfMeasurement {
+      measurement: Measurement::Count {
+        id: id.to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0),),
+        attributes: None,
+      },
+      layer: layer.to_string(),
+      scale: None,
+      area: None,
+      length: None,
+    };
+    let measurements = vec![
+      item("c1", "Floor: Carpet"),
+      item("c2", "Floor; Carpet"),
+      item("c3", "floor_ carpet"),
+      item("c4", "Floor: Carpet"),
+      item("c5", " "),
+    ];
+    let data = write_dxf(&measurements, &DxfExportOptions::default()).unwrap();
+    let import = read_dxf(&data, "p1", &DxfImportOptions::default()).unwrap();
+    let layers: Vec<&str> = import
+      .measurements
+      .iter()
+      .map(Measurement::group_id)
+      .collect();
+    assert_eq!(
+      layers,
+      [
+        "Floor_ Carpet",
+        "Floor_ Carpet_2",
+        "floor_ carpet_3",
+        "Floor_ Carpet",
+        "0"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_write_dxf_errors() {
+    let options = DxfExportOptions {
+      text_height: Some(0.0),
+      ..Default::default()
+    };
+    assert!(matches!(
+      write_dxf(&[], &options),
+      Err(TakeoffError::InvalidDxf { .. })
+    ));
+    let options = DxfExportOptions {
+      text_height: Some(f64::NAN),
+      ..Default::default()
+    };
+    assert!(matches!(
+      write_dxf(&[], &options),
+      Err(TakeoffError::InvalidDxf { .. })
+    ));
+    let options = DxfExportOptions {
+      decimals: Some(100),
+      ..Default::default()
+    };
+    assert!(matches!(
+      write_dxf(&[], &options),
+      Err(TakeoffError::InvalidDecimals { .. })
+    ));
+  }
+
+  #[test]
+  fn test_read_dxf_errors() {
+    assert!(matches!(
+      read_dxf(
+        "0\nSECTION\nx\nHEADER\n",
+        "p1",
+        &DxfImportOptions::default()
+      ),
+      Err(TakeoffError::InvalidDxf { .. })
+    ));
+    assert!(matches!(
+      read_dxf("0\nSECTION\n2", "p1", &DxfImportOptions::default()),
+      Err(TakeoffError::InvalidDxf { .. })
+    ));
+    assert!(matches!(
+      read_dxf(
+        &dxf("", "0 INSERT 10 a 20 0"),
+        "p1",
+        &DxfImportOptions::default()
+      ),
+      Err(TakeoffError::InvalidDxf { .. })
+    ));
+    let options = DxfImportOptions {
+      pixels_per_unit: Some(0.0),
+      layer_groups: None,
+    };
+    assert!(matches!(
+      read_dxf("", "p1", &options),
+      Err(TakeoffError::InvalidScale { .. })
+    ));
+  }
+}
@@ -3,6 +3,10 @@ use thiserror::Error;
 
 use napi::{Error as NapiError, Status};
 
+use crate::coords::Point;
+use crate::group::MeasurementType;
+use crate::integrity::EntityKind;
+
 /// Error type for takeoff_core operations.
 ///
 /// All invalid-input cases return a variant of `TakeoffError`; no silent fallbacks
@@ -47,6 +51,79 @@ pub enum TakeoffError {
     unit: String,
   },
 
+  /// An export was asked for more decimal places than it supports.
+  ///
+  /// This error is returned when:
+  /// - The `decimals` option of a report, DXF or SVG export exceeds
+  ///   [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS)
+  #[error("invalid decimals: {decimals} is more than the maximum of {max}")]
+  InvalidDecimals {
+    /// The number of decimal places requested
+    decimals: u32,
+    /// The largest number of decimal places supported
+    max: u32,
+  },
+
+  // Geometry Errors
+  /// A geometry operation could not produce a result.
+  ///
+  /// This error is returned when:
+  /// - The operation does not apply to the measurement kind
+  /// - The input is too far from the shape the operation expects (e.g., squaring up a
+  ///   polygon whose corners are not close to right angles)
+  /// - The result would change the geometry by more than the allowed tolerance
+  #[error("{operation} failed: {message}")]
+  GeometryOperation {
+    /// The name of the operation (e.g., "square up")
+    operation: String,
+    /// Human-readable message describing why the operation failed
+    message: String,
+  },
+
+  /// A polygon outline crosses itself, so its area is miscounted.
+  ///
+  /// This error is returned when two non-adjacent edges of an outline cross or overlap.
+  /// The zero-width cut that joins a hole to its outline is not a crossing.
+  #[error(
+    "measurement {measurement_id} crosses itself at ({x}, {y}) between edges {index} and {other_index}",
+    x = point.x,
+    y = point.y
+  )]
+  SelfIntersection {
+    measurement_id: String,
+    /// The vertex starting the first edge.
+    index: u32,
+    /// The vertex starting the second edge.
+    other_index: u32,
+    point: Point,
+  },
+
+  /// A vertex is at the same position as the vertex before it, or a polygon's last vertex
+  /// repeats its first.
+  #[error("vertex {index} of measurement {measurement_id} repeats its neighbour")]
+  DuplicateVertex {
+    measurement_id: String,
+    index: u32,
+    point: Point,
+  },
+
+  /// An edge is shorter than the minimum segment length, but not exactly zero.
+  #[error("edge {index} of measurement {measurement_id} has a length of almost zero")]
+  ZeroLengthSegment {
+    measurement_id: String,
+    /// The vertex starting the edge.
+    index: u32,
+    point: Point,
+  },
+
+  /// The outline turns back on itself at a vertex, leaving a sliver with no width.
+  #[error("measurement {measurement_id} doubles back on itself at vertex {index}")]
+  Sliver {
+    measurement_id: String,
+    index: u32,
+    point: Point,
+  },
+
   // Contour Errors
   /// Too few points for triangulation (need at least 3).
   #[error("too few points for triangulation: {count} (need at least 3)")]
@@ -59,6 +136,206 @@ pub enum TakeoffError {
   #[error("no scale found for contour {contour_id} on page")]
   ContourMissingScale { contour_id: String },
 
+  // Formula Errors
+  /// A formula could not be parsed or evaluated.
+  ///
+  /// This error is returned when:
+  /// - The formula is empty, too long or nested too deeply
+  /// - The formula has a syntax error (e.g. unbalanced parentheses, dangling operator)
+  /// - An unknown function or unit suffix is used
+  /// - A function is called with the wrong number of arguments
+  /// - Evaluation divides by zero or produces a non-finite number
+  #[error("invalid formula: {message}")]
+  InvalidFormula {
+    /// Human-readable message describing why the formula is invalid
+    message: String,
+  },
+
+  /// Operands of a formula have incompatible dimensions.
+  ///
+  /// This error is returned when:
+  /// - Values of different dimensions are added, subtracted or compared (e.g. `length + area`)
+  /// - A rounding function is applied to a value that is not a scalar
+  /// - The result is not a scalar, length, area or volume (e.g. `area * area`)
+  #[error("formula dimension mismatch: {message}")]
+  FormulaDimensionMismatch {
+    /// Human-readable message describing the mismatched dimensions
+    message: String,
+  },
+
+  /// A formula references a variable that is not bound.
+  ///
+  /// This error is returned when a variable (e.g. `area` on a polyline) has no value
+  /// in the evaluation context.
+  #[error("unknown formula variable: {name}")]
+  UnknownFormulaVariable {
+    /// The name of the unbound variable
+    name: String,
+  },
+
+  // Group Errors
+  /// A measurement's kind is incompatible with its group's measurement type.
+  ///
+  /// This error is returned when:
+  /// - A polyline or count is added to an `Area` group
+  /// - A count is added to a `Linear` group
+  /// - A polygon, polyline or rectangle is added to a `Count` group
+  #[error(
+    "measurement {measurement_id} is a {measurement_kind}, which is incompatible with {measurement_type:?} group {group_id}"
+  )]
+  IncompatibleMeasurementType {
+    /// The id of the rejected measurement
+    measurement_id: String,
+    /// The kind of the measurement (e.g. `Polyline`)
+    measurement_kind: String,
+    /// The id of the group the measurement belongs to
+    group_id: String,
+    /// The measurement type the group expects
+    measurement_type: MeasurementType,
+  },
+
+  // Integrity Errors
+  /// An entity references another entity that does not exist.
+  ///
+  /// This error is returned when:
+  /// - A measurement references a missing page or group
+  /// - A scale or contour references a missing page
+  #[error("{entity_kind} {entity_id} references missing {reference_kind} {reference_id}")]
+  DanglingReference {
+    /// The kind of the entity holding the reference
+    entity_kind: EntityKind,
+    /// The id of the entity holding the reference
+    entity_id: String,
+    /// The kind of the missing entity
+    reference_kind: EntityKind,
+    /// The id of the missing entity
+    reference_id: String,
+  },
+
+  /// An entity cannot be removed because other entities still reference it.
+  ///
+  /// This error is returned when the removal policy is `Reject` and:
+  /// - A page still has measurements, scales or contours
+  /// - A group still has measurements
+  #[error("{entity_kind} {entity_id} is still referenced by {} entities", .dependent_ids.len())]
+  EntityInUse {
+    /// The kind of the entity being removed
+    entity_kind: EntityKind,
+    /// The id of the entity being removed
+    entity_id: String,
+    /// The ids of the entities that reference it
+    dependent_ids: Vec<String>,
+  },
+
+  // History Errors
+  /// The history cannot be navigated while a transaction is open.
+  ///
+  /// This error is returned when:
+  /// - `undo` or `redo` is called between beginning and committing a transaction
+  #[error("cannot undo or redo while a transaction is open")]
+  TransactionInProgress,
+
+  // Snapshot Errors
+  /// A snapshot could not be read.
+  ///
+  /// This error is returned when:
+  /// - The document is not valid JSON
+  /// - A required field is missing or has the wrong type
+  #[error("invalid snapshot: {message}")]
+  InvalidSnapshot {
+    /// Human-readable message describing why the snapshot is invalid
+    message: String,
+  },
+
+  /// A snapshot was written by a newer version of the format.
+  ///
+  /// This error is returned when:
+  /// - The snapshot's version is greater than the newest version this build can read
+  #[error("unsupported snapshot version {version} (newest supported is {supported})")]
+  UnsupportedSnapshotVersion {
+    /// The version of the snapshot
+    version: u32,
+    /// The newest version this build can read
+    supported: u32,
+  },
+
+  // Project File Errors
+  /// A project file could not be read.
+  ///
+  /// This error is returned when:
+  /// - The bytes are not valid JSON or MessagePack
+  /// - The document has no schema version
+  /// - The document does not match the project schema after migration
+  #[error("invalid project file: {message}")]
+  InvalidProjectFile {
+    /// Human-readable message describing why the project file is invalid
+    message: String,
+  },
+
+  /// A project file was written with a newer schema.
+  ///
+  /// This error is returned when:
+  /// - The document's schema version is greater than the newest version this build can read
+  #[error("unsupported project schema version {version} (newest supported is {supported})")]
+  UnsupportedProjectVersion {
+    /// The schema version of the document
+    version: u32,
+    /// The newest schema version this build can read
+    supported: u32,
+  },
+
+  // Report Errors
+  /// A quantity report could not be written.
+  ///
+  /// This error is returned when:
+  /// - The CSV or XLSX writer fails to encode the report
+  #[error("report export failed: {message}")]
+  ReportExport {
+    /// Human-readable message describing why the report could not be written
+    message: String,
+  },
+
+  // GeoJSON Errors
+  /// A GeoJSON document could not be imported.
+  ///
+  /// This error is returned when:
+  /// - The document is not valid GeoJSON
+  /// - A feature has no geometry, or a geometry other than a Point, LineString or Polygon
+  /// - A polygon has holes, which measurements cannot represent
+  /// - A feature has no group and no default group was given
+  #[error("invalid GeoJSON: {message}")]
+  InvalidGeoJson {
+    /// Human-readable message describing why the document could not be imported
+    message: String,
+  },
+
+  // DXF Errors
+  /// A DXF drawing could not be read or written.
+  ///
+  /// This error is returned when:
+  /// - The data is not an ASCII DXF file (binary DXF is not supported)
+  /// - A group code is not an integer, or a coordinate is not a number
+  /// - The file ends in the middle of a group code/value pair
+  /// - An export option such as the text height is not positive
+  #[error("invalid DXF: {message}")]
+  InvalidDxf {
+    /// Human-readable message describing why the drawing could not be read or written
+    message: String,
+  },
+
+  // Image Errors
+  /// An image could not be rendered or encoded.
+  ///
+  /// This error is returned when:
+  /// - The rendered area is empty, or the image would be too large
+  /// - A rendering option such as the cell size is not positive
+  /// - The image encoder fails
+  #[error("image export failed: {message}")]
+  ImageExport {
+    /// Human-readable message describing why the image could not be exported
+    message: String,
+  },
+
   // System Errors
   /// A mutex or lock was poisoned (a thread panicked while holding the lock).
   ///
@@ -98,6 +375,56 @@ impl TakeoffError {
     Self::UnknownUnit { unit: unit.into() }
   }
 
+  /// Create a `GeometryOperation` error for an operation with a message.
+  pub fn geometry_operation(operation: impl Into<String>, message: impl Into<String>) -> Self {
+    Self::GeometryOperation {
+      operation: operation.into(),
+      message: message.into(),
+    }
+  }
+
+  /// Create a `SelfIntersection` error for two crossing edges of a measurement.
+  pub fn self_intersection(
+    measurement_id: impl Into<String>,
+    index: u32,
+    other_index: u32,
+    point: Point,
+  ) -> Self {
+    Self::SelfIntersection {
+      measurement_id: measurement_id.into(),
+      index,
+      other_index,
+      point,
+    }
+  }
+
+  /// Create a `DuplicateVertex` error for a repeated vertex of a measurement.
+  pub fn duplicate_vertex(measurement_id: impl Into<String>, index: u32, point: Point) -> Self {
+    Self::DuplicateVertex {
+      measurement_id: measurement_id.into(),
+      index,
+      point,
+    }
+  }
+
+  /// Create a `ZeroLengthSegment` error for an almost zero-length edge of a measurement.
+  pub fn zero_length_segment(measurement_id: impl Into<String>, index: u32, point: Point) -> Self {
+    Self::ZeroLengthSegment {
+      measurement_id: measurement_id.into(),
+      index,
+      point,
+    }
+  }
+
+  /// Create a `Sliver` error for a vertex where a measurement doubles back on itself.
+  pub fn sliver(measurement_id: impl Into<String>, index: u32, point: Point) -> Self {
+    Self::Sliver {
+      measurement_id: measurement_id.into(),
+      index,
+      point,
+    }
+  }
+
   /// Create a `ContourMissingScale` error.
   pub fn contour_missing_scale(contour_id: impl Into<String>) -> Self {
     Self::ContourMissingScale {
@@ -105,6 +432,67 @@ impl TakeoffError {
     }
   }
 
+  /// Create an `InvalidFormula` error with a message.
+  pub fn invalid_formula(message: impl Into<String>) -> Self {
+    Self::InvalidFormula {
+      message: message.into(),
+    }
+  }
+
+  /// Create a `FormulaDimensionMismatch` error with a message.
+  pub fn formula_dimension_mismatch(message: impl Into<String>) -> Self {
+    Self::FormulaDimensionMismatch {
+      message: message.into(),
+    }
+  }
+
+  /// Create an `UnknownFormulaVariable` error with the variable name.
+  pub fn unknown_formula_variable(name: impl Into<String>) -> Self {
+    Self::UnknownFormulaVariable { name: name.into() }
+  }
+
+  /// Create an `InvalidSnapshot` error with a message.
+  pub fn invalid_snapshot(message: impl Into<String>) -> Self {
+    Self::InvalidSnapshot {
+      message: message.into(),
+    }
+  }
+
+  /// Create an `InvalidProjectFile` error with a message.
+  pub fn invalid_project_file(message: impl Into<String>) -> Self {
+    Self::InvalidProjectFile {
+      message: message.into(),
+    }
+  }
+
+  /// Create a `ReportExport` error with a message.
+  pub fn report_export(message: impl Into<String>) -> Self {
+    Self::ReportExport {
+      message: message.into(),
+    }
+  }
+
+  /// Create an `InvalidGeoJson` error with a message.
+  pub fn invalid_geojson(message: impl Into<String>) -> Self {
+    Self::InvalidGeoJson {
+      message: message.into(),
+    }
+  }
+
+  /// Create an `InvalidDxf` error with a message.
+  pub fn invalid_dxf(message: impl Into<String>) -> Self {
+    Self::InvalidDxf {
+      message: message.into(),
+    }
+  }
+
+  /// Create an `ImageExport` error with a message.
+  pub fn image_export(message: impl Into<String>) -> Self {
+    Self::ImageExport {
+      message: message.into(),
+    }
+  }
+
   /// Create a `PoisonError` error for a poisoned mutex lock.
   pub fn poison_error(resource: impl Into<String>) -> Self {
     Self::PoisonError {
@@ -119,6 +507,14 @@ impl From<TakeoffError> for NapiError {
       TakeoffError::EmptyGeometry { message } => NapiError::new(Status::InvalidArg, message),
       TakeoffError::InvalidScale { message } => NapiError::new(Status::InvalidArg, message),
       TakeoffError::UnknownUnit { unit } => NapiError::new(Status::InvalidArg, unit),
+      TakeoffError::InvalidDecimals { .. } => NapiError::new(Status::InvalidArg, error.to_string()),
+      TakeoffError::GeometryOperation { .. } => {
+        NapiError::new(Status::InvalidArg, error.to_string())
+      }
+      TakeoffError::SelfIntersection { .. }
+      | TakeoffError::DuplicateVertex { .. }
+      | TakeoffError::ZeroLengthSegment { .. }
+      | TakeoffError::Sliver { .. } => NapiError::new(Status::InvalidArg, error.to_string()),
       TakeoffError::SurfaceMeshTooFewPoints { .. } => {
         NapiError::new(Status::InvalidArg, error.to_string())
       }
@@ -128,6 +524,30 @@ impl From<TakeoffError> for NapiError {
       TakeoffError::ContourMissingScale { .. } => {
         NapiError::new(Status::InvalidArg, error.to_string())
       }
+      TakeoffError::InvalidFormula { .. }
+      | TakeoffError::FormulaDimensionMismatch { .. }
+      | TakeoffError::UnknownFormulaVariable { .. } => {
+        NapiError::new(Status::InvalidArg, error.to_string())
+      }
+      TakeoffError::IncompatibleMeasurementType { .. } => {
+        NapiError::new(Status::InvalidArg, error.to_string())
+      }
+      TakeoffError::DanglingReference { .. } | TakeoffError::EntityInUse { .. } => {
+        NapiError::new(Status::InvalidArg, error.to_string())
+      }
+      TakeoffError::TransactionInProgress => NapiError::new(Status::InvalidArg, error.to_string()),
+      TakeoffError::InvalidSnapshot { .. } | TakeoffError::UnsupportedSnapshotVersion { .. } => {
+        NapiError::new(Status::InvalidArg, error.to_string())
+      }
+      TakeoffError::InvalidProjectFile { .. } | TakeoffError::UnsupportedProjectVersion { .. } => {
+        NapiError::new(Status::InvalidArg, error.to_string())
+      }
+      TakeoffError::InvalidGeoJson { .. } => NapiError::new(Status::InvalidArg, error.to_string()),
+      TakeoffError::InvalidDxf { .. } => NapiError::new(Status::InvalidArg, error.to_string()),
+      TakeoffError::ImageExport { .. } => NapiError::new(Status::GenericFailure, error.to_string()),
+      TakeoffError::ReportExport { .. } => {
+        NapiError::new(Status::GenericFailure, error.to_string())
+      }
       TakeoffError::PoisonError { resource } => NapiError::new(
         Status::GenericFailure,
         format!("mutex lock poisoned: {}", resource),
@@ -180,6 +600,30 @@ mod tests {
     let serialized = serde_json::to_string(&err).unwrap();
     assert!(serialized.contains("poisonError"));
     assert!(serialized.contains("scale"));
+
+    let err = TakeoffError::unknown_formula_variable("area");
+    let serialized = serde_json::to_string(&err).unwrap();
+    assert!(serialized.contains("unknownFormulaVariable"));
+    assert!(serialized.contains("area"));
+
+    let err = TakeoffError::IncompatibleMeasurementType {
+      measurement_id: "m1".to_string(),
+      measurement_kind: "Polyline".to_string(),
+      group_id: "g1".to_string(),
+      measurement_type: MeasurementType::Area,
+    };
+    let serialized = serde_json::to_string(&err).unwrap();
+    assert!(serialized.contains("incompatibleMeasurementType"));
+    assert!(serialized.contains(r#""measurement_type":"Area""#));
+
+    let err = TakeoffError::EntityInUse {
+      entity_kind: EntityKind::Page,
+      entity_id: "p1".to_string(),
+      dependent_ids: vec!["m1".to_string()],
+    };
+    let serialized = serde_json::to_string(&err).unwrap();
+    assert!(serialized.contains("entityInUse"));
+    assert!(serialized.contains(r#""dependent_ids":["m1"]"#));
   }
 
   #[test]
@@ -218,6 +662,28 @@ mod tests {
     assert!(display.contains("unknown or unsupported unit"));
     assert!(display.contains("kilometers"));
 
+    let err = TakeoffError::geometry_operation("square up", "polygon has an odd number of corners");
+    let display = format!("{}", err);
+    assert!(display.contains("square up failed"));
+    assert!(display.contains("odd number of corners"));
+
+    let err = TakeoffError::self_intersection("m1", 0, 2, Point::new(5.0, 5.0));
+    let display = format!("{}", err);
+    assert!(display.contains("m1 crosses itself at (5, 5)"));
+    assert!(display.contains("edges 0 and 2"));
+
+    let err = TakeoffError::duplicate_vertex("m1", 3, Point::new(0.0, 0.0));
+    let display = format!("{}", err);
+    assert!(display.contains("vertex 3 of measurement m1 repeats its neighbour"));
+
+    let err = TakeoffError::zero_length_segment("m1", 1, Point::new(0.0, 0.0));
+    let display = format!("{}", err);
+    assert!(display.contains("edge 1 of measurement m1"));
+
+    let err = TakeoffError::sliver("m1", 2, Point::new(0.0, 0.0));
+    let display = format!("{}", err);
+    assert!(display.contains("doubles back on itself at vertex 2"));
+
     let err = TakeoffError::SurfaceMeshTooFewPoints { count: 2 };
     let display = format!("{}", err);
     assert!(display.contains("too few points for triangulation"));
@@ -230,9 +696,78 @@ mod tests {
     assert!(display.contains("all points are collinear"));
     assert!(display.contains("cannot create triangulated surface"));
 
+    let err = TakeoffError::InvalidDecimals {
+      decimals: 20,
+      max: 15,
+    };
+    let display = format!("{}", err);
+    assert!(display.contains("invalid decimals"));
+    assert!(display.contains("20 is more than the maximum of 15"));
+
+    let err = TakeoffError::report_export("sheet name is too long");
+    let display = format!("{}", err);
+    assert!(display.contains("report export failed"));
+    assert!(display.contains("sheet name is too long"));
+
+    let err = TakeoffError::invalid_geojson("feature 3 has no geometry");
+    let display = format!("{}", err);
+    assert!(display.contains("invalid GeoJSON"));
+    assert!(display.contains("feature 3 has no geometry"));
+
+    let err = TakeoffError::invalid_dxf("group code `x` on line 3 is not an integer");
+    let display = format!("{}", err);
+    assert!(display.contains("invalid DXF"));
+    assert!(display.contains("on line 3"));
+
+    let err = TakeoffError::image_export("the reference surface is empty");
+    let display = format!("{}", err);
+    assert!(display.contains("image export failed"));
+    assert!(display.contains("reference surface is empty"));
+
     let err = TakeoffError::poison_error("scale");
     let display = format!("{}", err);
     assert!(display.contains("mutex lock poisoned"));
     assert!(display.contains("scale"));
+
+    let err = TakeoffError::formula_dimension_mismatch("cannot add length and area");
+    let display = format!("{}", err);
+    assert!(display.contains("formula dimension mismatch"));
+    assert!(display.contains("cannot add length and area"));
+
+    let err = TakeoffError::IncompatibleMeasurementType {
+      measurement_id: "m1".to_string(),
+      measurement_kind: "Polyline".to_string(),
+      group_id: "g1".to_string(),
+      measurement_type: MeasurementType::Area,
+    };
+    assert_eq!(
+      err.to_string(),
+      "measurement m1 is a Polyline, which is incompatible with Area group g1"
+    );
+
+    let err = TakeoffError::EntityInUse {
+      entity_kind: EntityKind::Group,
+      entity_id: "g1".to_string(),
+      dependent_ids: vec!["m1".to_string(), "m2".to_string()],
+    };
+    assert_eq!(
+      err.to_string(),
+      "group g1 is still referenced by 2 entities"
+    );
+
+    let err = TakeoffError::UnsupportedSnapshotVersion {
+      version: 3,
+      supported: 1,
+    };
+    assert_eq!(
+      err.to_string(),
+      "unsupported snapshot version 3 (newest supported is 1)"
+    );
+
+    let err = TakeoffError::TransactionInProgress;
+    assert_eq!(
+      err.to_string(),
+      "cannot undo or redo while a transaction is open"
+    );
   }
 }
@@ -0,0 +1,94 @@
+//! Change events emitted when the takeoff state recomputes derived values.
+//!
+//! Events are collected in an [`EventQueue`] and drained by the caller, so a single scale
+//! change that recomputes dozens of measurements can be handled in one pass.
+
+use crate::integrity::EntityKind;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A change to a value derived by the takeoff state.
+#[napi(discriminant = "type")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StateEvent {
+  /// A measurement's area and length were recomputed.
+  MeasurementRecomputed { measurement_id: String },
+  /// A group's area, length, points or count changed.
+  GroupTotalsChanged { group_id: String },
+  /// A measurement or contour was assigned a different scale.
+  ScaleReassigned {
+    entity_kind: EntityKind,
+    entity_id: String,
+    scale_id: String,
+  },
+  /// A contour's surface mesh was rebuilt.
+  ContourMeshRebuilt { contour_id: String },
+}
+
+/// Pending events in the order they were first emitted.
+///
+/// An event that is already pending is not queued again, so the queue holds at most a
+/// few events per entity however often it is recomputed between drains.
+#[derive(Debug, Clone, Default)]
+pub struct EventQueue {
+  events: Vec<StateEvent>,
+  pending: HashSet<StateEvent>,
+}
+
+impl EventQueue {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Queue an event.
+  ///
+  /// # Returns
+  ///
+  /// * `false` - If an identical event was already pending.
+  pub fn push(&mut self, event: StateEvent) -> bool {
+    if !self.pending.insert(event.clone()) {
+      return false;
+    }
+    self.events.push(event);
+    true
+  }
+
+  /// Take every pending event, oldest first.
+  pub fn drain(&mut self) -> Vec<StateEvent> {
+    self.pending.clear();
+    std::mem::take(&mut self.events)
+  }
+
+  pub fn len(&self) -> usize {
+    self.events.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.events.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_event_queue_coalesces_pending_events() {
+    let mut queue = EventQueue::new();
+    let recomputed = StateEvent::MeasurementRecomputed {
+      measurement_id: "m1".to_string(),
+    };
+    assert!(queue.push(recomputed.clone()));
+    assert!(queue.push(StateEvent::GroupTotalsChanged {
+      group_id: "g1".to_string(),
+    }));
+    assert!(!queue.push(recomputed.clone()));
+    assert_eq!(queue.len(), 2);
+
+    let events = queue.drain();
+    assert_eq!(events[0], recomputed);
+    assert!(queue.is_empty());
+    assert!(queue.push(recomputed));
+  }
+}
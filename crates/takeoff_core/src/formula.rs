@@ -0,0 +1,993 @@
+//! Unit-aware formula expressions for custom quantities.
+//!
+//! A formula is a small arithmetic expression such as `ceil(length / 16in) + 1` or
+//! `area * 1.1 / 32sf`. Variables are bound through a [`FormulaContext`] (typically the
+//! computed length, area, count and points of a measurement or group) and the result is a
+//! [`UnitValue`].
+//!
+//! Every value carries a dimension (the exponent of length): scalars are 0, lengths 1,
+//! areas 2 and volumes 3. Multiplication and division add and subtract dimensions
+//! (`length * length` is an area), while addition, subtraction, `min` and `max` require
+//! both operands to have the same dimension.
+//!
+//! Formulas are sandboxed: they can only read the variables in their context, have a
+//! bounded length and nesting depth, and never produce a non-finite number.
+//!
+//! # Syntax
+//!
+//! - Numbers, optionally followed directly by a unit suffix: `16`, `1.5ft`, `32sf`, `2cy`
+//! - Operators: `+`, `-`, `*`, `/`, `^` and parentheses
+//! - Functions: `ceil`, `floor`, `round`, `abs`, `sqrt`, `min`, `max`
+//! - Variables: any identifier bound in the context, e.g. `length`, `area`, `count`
+
+use std::collections::HashMap;
+
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::unit::{Unit, UnitValue, UnitValueItem};
+use uom::si::area::square_meter;
+use uom::si::f32::{Area, Length, Volume};
+use uom::si::length::meter;
+use uom::si::volume::cubic_meter;
+
+/// Maximum number of characters accepted in a formula.
+const MAX_FORMULA_LENGTH: usize = 1024;
+/// Maximum nesting depth of parentheses, function calls and unary operators.
+const MAX_NESTING_DEPTH: usize = 64;
+/// Relative tolerance within which `ceil`, `floor` and `round` treat a value as a whole
+/// number. Bound quantities are stored as `f32`, so `40ft / 16in` evaluates to
+/// `30.0000001`; without this, `ceil` would report 31.
+const WHOLE_NUMBER_TOLERANCE: f64 = 1e-6;
+
+/// Variable bound to the real-world length (or perimeter) of a measurement or group.
+pub const LENGTH_VARIABLE: &str = "length";
+/// Variable bound to the real-world perimeter of a measurement or group.
+pub const PERIMETER_VARIABLE: &str = "perimeter";
+/// Variable bound to the real-world area of a measurement or group.
+pub const AREA_VARIABLE: &str = "area";
+/// Variable bound to the number of measurements.
+pub const COUNT_VARIABLE: &str = "count";
+/// Variable bound to the number of vertices.
+pub const POINTS_VARIABLE: &str = "points";
+
+/// A value with a dimension, stored in SI base units (meters raised to `dimension`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Quantity {
+  value: f64,
+  dimension: i32,
+}
+
+impl Quantity {
+  fn scalar(value: f64) -> Self {
+    Self {
+      value,
+      dimension: 0,
+    }
+  }
+
+  fn describe_dimension(dimension: i32) -> String {
+    match dimension {
+      0 => "scalar".to_string(),
+      1 => "length".to_string(),
+      2 => "area".to_string(),
+      3 => "volume".to_string(),
+      d => format!("length^{}", d),
+    }
+  }
+
+  /// The value as a scalar, snapped to the nearest whole number when within
+  /// [`WHOLE_NUMBER_TOLERANCE`].
+  fn require_rounding_input(&self, context: &str) -> TakeoffResult<f64> {
+    let value = self.require_dimensionless(context)?;
+    let nearest = value.round();
+    if (value - nearest).abs() <= WHOLE_NUMBER_TOLERANCE * nearest.abs().max(1.0) {
+      return Ok(nearest);
+    }
+    Ok(value)
+  }
+
+  fn require_dimensionless(&self, context: &str) -> TakeoffResult<f64> {
+    if self.dimension != 0 {
+      return Err(TakeoffError::formula_dimension_mismatch(format!(
+        "{} requires a scalar, got {}; divide by a unit first (e.g. length / 1ft)",
+        context,
+        Self::describe_dimension(self.dimension)
+      )));
+    }
+    Ok(self.value)
+  }
+
+  /// Unwrap the result of a checked dimension operation, rejecting overflow.
+  fn checked_dimension(dimension: Option<i32>) -> TakeoffResult<i32> {
+    dimension
+      .ok_or_else(|| TakeoffError::formula_dimension_mismatch("result dimension is out of range"))
+  }
+
+  fn require_same_dimension(&self, other: &Quantity, context: &str) -> TakeoffResult<()> {
+    if self.dimension != other.dimension {
+      return Err(TakeoffError::formula_dimension_mismatch(format!(
+        "cannot {} {} and {}",
+        context,
+        Self::describe_dimension(self.dimension),
+        Self::describe_dimension(other.dimension)
+      )));
+    }
+    Ok(())
+  }
+}
+
+impl From<UnitValue> for Quantity {
+  fn from(value: UnitValue) -> Self {
+    match value.item() {
+      UnitValueItem::Length { value } => Self {
+        value: value.get::<meter>() as f64,
+        dimension: 1,
+      },
+      UnitValueItem::Area { value } => Self {
+        value: value.get::<square_meter>() as f64,
+        dimension: 2,
+      },
+      UnitValueItem::Volume { value } => Self {
+        value: value.get::<cubic_meter>() as f64,
+        dimension: 3,
+      },
+      UnitValueItem::Scalar { value } => Self::scalar(value),
+    }
+  }
+}
+
+impl TryFrom<Quantity> for UnitValue {
+  type Error = TakeoffError;
+
+  fn try_from(quantity: Quantity) -> TakeoffResult<Self> {
+    let value = quantity.value;
+    match quantity.dimension {
+      0 => Ok(UnitValue::from_scalar(value)),
+      1 => Ok(UnitValue::from_length(Length::new::<meter>(value as f32))),
+      2 => Ok(UnitValue::from_area(Area::new::<square_meter>(
+        value as f32,
+      ))),
+      3 => Ok(UnitValue::from_volume(Volume::new::<cubic_meter>(
+        value as f32,
+      ))),
+      d => Err(TakeoffError::formula_dimension_mismatch(format!(
+        "result has unsupported dimension {}",
+        Quantity::describe_dimension(d)
+      ))),
+    }
+  }
+}
+
+/// Parse a unit suffix such as `ft`, `sf` or `cy` into its unit and dimension.
+fn parse_unit_suffix(suffix: &str) -> Option<(Unit, i32)> {
+  if let Ok(unit) = Unit::from_str(suffix) {
+    return Some((unit, 1));
+  }
+  let parsed = match suffix.to_lowercase().as_str() {
+    "sf" | "sqft" | "ft2" => (Unit::Feet, 2),
+    "sy" | "sqyd" | "yd2" => (Unit::Yards, 2),
+    "sqin" | "in2" => (Unit::Inches, 2),
+    "sqm" | "m2" => (Unit::Meters, 2),
+    "sqcm" | "cm2" => (Unit::Centimeters, 2),
+    "cf" | "cuft" | "ft3" => (Unit::Feet, 3),
+    "cy" | "cuyd" | "yd3" => (Unit::Yards, 3),
+    "cuin" | "in3" => (Unit::Inches, 3),
+    "cum" | "m3" => (Unit::Meters, 3),
+    "cucm" | "cm3" => (Unit::Centimeters, 3),
+    _ => return None,
+  };
+  Some(parsed)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Number(Quantity),
+  Ident(String),
+  Plus,
+  Minus,
+  Star,
+  Slash,
+  Caret,
+  LParen,
+  RParen,
+  Comma,
+}
+
+impl Token {
+  fn describe(&self) -> String {
+    match self {
+      Token::Number(q) => format!("number {}", q.value),
+      Token::Ident(name) => format!("`{}`", name),
+      Token::Plus => "`+`".to_string(),
+      Token::Minus => "`-`".to_string(),
+      Token::Star => "`*`".to_string(),
+      Token::Slash => "`/`".to_string(),
+      Token::Caret => "`^`".to_string(),
+      Token::LParen => "`(`".to_string(),
+      Token::RParen => "`)`".to_string(),
+      Token::Comma => "`,`".to_string(),
+    }
+  }
+}
+
+fn tokenize(source: &str) -> TakeoffResult<Vec<(Token, usize)>> {
+  let chars: Vec<char> = source.chars().collect();
+  let mut tokens = Vec::new();
+  let mut i = 0;
+
+  while i < chars.len() {
+    let c = chars[i];
+    let start = i;
+    if c.is_whitespace() {
+      i += 1;
+      continue;
+    }
+    let token = match c {
+      '+' => Token::Plus,
+      '-' => Token::Minus,
+      '*' => Token::Star,
+      '/' => Token::Slash,
+      '^' => Token::Caret,
+      '(' => Token::LParen,
+      ')' => Token::RParen,
+      ',' => Token::Comma,
+      c if c.is_ascii_digit() || c == '.' => {
+        while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+          i += 1;
+        }
+        let literal: String = chars[start..i].iter().collect();
+        let number: f64 = literal.parse().map_err(|_| {
+          TakeoffError::invalid_formula(format!(
+            "invalid number `{}` at position {}",
+            literal, start
+          ))
+        })?;
+
+        let suffix_start = i;
+        while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+          i += 1;
+        }
+        if suffix_start == i {
+          tokens.push((Token::Number(Quantity::scalar(number)), start));
+          continue;
+        }
+        let suffix: String = chars[suffix_start..i].iter().collect();
+        let (unit, dimension) = parse_unit_suffix(&suffix).ok_or_else(|| {
+          TakeoffError::invalid_formula(format!(
+            "unknown unit `{}` at position {}",
+            suffix, suffix_start
+          ))
+        })?;
+        let value = number * unit.meters_per_unit().powi(dimension);
+        tokens.push((Token::Number(Quantity { value, dimension }), start));
+        continue;
+      }
+      c if c.is_ascii_alphabetic() || c == '_' => {
+        while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+          i += 1;
+        }
+        let name: String = chars[start..i].iter().collect();
+        tokens.push((Token::Ident(name), start));
+        continue;
+      }
+      other => {
+        return Err(TakeoffError::invalid_formula(format!(
+          "unexpected character `{}` at position {}",
+          other, start
+        )));
+      }
+    };
+    tokens.push((token, start));
+    i += 1;
+  }
+
+  Ok(tokens)
+}
+
+/// Built-in functions available in formulas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+  Ceil,
+  Floor,
+  Round,
+  Abs,
+  Sqrt,
+  Min,
+  Max,
+}
+
+impl Function {
+  fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "ceil" => Some(Function::Ceil),
+      "floor" => Some(Function::Floor),
+      "round" => Some(Function::Round),
+      "abs" => Some(Function::Abs),
+      "sqrt" => Some(Function::Sqrt),
+      "min" => Some(Function::Min),
+      "max" => Some(Function::Max),
+      _ => None,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      Function::Ceil => "ceil",
+      Function::Floor => "floor",
+      Function::Round => "round",
+      Function::Abs => "abs",
+      Function::Sqrt => "sqrt",
+      Function::Min => "min",
+      Function::Max => "max",
+    }
+  }
+
+  fn check_arity(&self, count: usize) -> TakeoffResult<()> {
+    let ok = match self {
+      Function::Min | Function::Max => count >= 1,
+      _ => count == 1,
+    };
+    if !ok {
+      return Err(TakeoffError::invalid_formula(format!(
+        "{}() called with {} argument(s)",
+        self.name(),
+        count
+      )));
+    }
+    Ok(())
+  }
+
+  fn apply(&self, args: &[Quantity]) -> TakeoffResult<Quantity> {
+    let first = args[0];
+    let context = format!("{}()", self.name());
+    match self {
+      Function::Ceil => Ok(Quantity::scalar(
+        first.require_rounding_input(&context)?.ceil(),
+      )),
+      Function::Floor => Ok(Quantity::scalar(
+        first.require_rounding_input(&context)?.floor(),
+      )),
+      Function::Round => Ok(Quantity::scalar(
+        first.require_rounding_input(&context)?.round(),
+      )),
+      Function::Abs => Ok(Quantity {
+        value: first.value.abs(),
+        dimension: first.dimension,
+      }),
+      Function::Sqrt => {
+        if first.dimension % 2 != 0 {
+          return Err(TakeoffError::formula_dimension_mismatch(format!(
+            "sqrt() of {} has no valid dimension",
+            Quantity::describe_dimension(first.dimension)
+          )));
+        }
+        Ok(Quantity {
+          value: first.value.sqrt(),
+          dimension: first.dimension / 2,
+        })
+      }
+      Function::Min | Function::Max => {
+        let mut best = first;
+        for arg in &args[1..] {
+          best.require_same_dimension(arg, &format!("compare in {}", context))?;
+          let better = match self {
+            Function::Min => arg.value < best.value,
+            _ => arg.value > best.value,
+          };
+          if better {
+            best = *arg;
+          }
+        }
+        Ok(best)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+  Add,
+  Subtract,
+  Multiply,
+  Divide,
+  Power,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+  Number(Quantity),
+  Variable(String),
+  Negate(Box<Expr>),
+  Binary(BinaryOp, Box<Expr>, Box<Expr>),
+  Call(Function, Vec<Expr>),
+}
+
+struct Parser {
+  tokens: Vec<(Token, usize)>,
+  position: usize,
+  depth: usize,
+  source_length: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position).map(|(token, _)| token)
+  }
+
+  fn offset(&self) -> usize {
+    self
+      .tokens
+      .get(self.position)
+      .map(|(_, offset)| *offset)
+      .unwrap_or(self.source_length)
+  }
+
+  fn unexpected(&self) -> TakeoffError {
+    match self.peek() {
+      Some(token) => TakeoffError::invalid_formula(format!(
+        "unexpected {} at position {}",
+        token.describe(),
+        self.offset()
+      )),
+      None => TakeoffError::invalid_formula("unexpected end of formula"),
+    }
+  }
+
+  fn expect(&mut self, expected: Token) -> TakeoffResult<()> {
+    if self.peek() == Some(&expected) {
+      self.position += 1;
+      return Ok(());
+    }
+    Err(match self.peek() {
+      Some(_) => self.unexpected(),
+      None => TakeoffError::invalid_formula(format!(
+        "expected {} at end of formula",
+        expected.describe()
+      )),
+    })
+  }
+
+  fn enter(&mut self) -> TakeoffResult<()> {
+    self.depth += 1;
+    if self.depth > MAX_NESTING_DEPTH {
+      return Err(TakeoffError::invalid_formula(format!(
+        "formula is nested deeper than {} levels",
+        MAX_NESTING_DEPTH
+      )));
+    }
+    Ok(())
+  }
+
+  fn leave(&mut self) {
+    self.depth -= 1;
+  }
+
+  /// expression := term (('+' | '-') term)*
+  fn expression(&mut self) -> TakeoffResult<Expr> {
+    let mut left = self.term()?;
+    loop {
+      let op = match self.peek() {
+        Some(Token::Plus) => BinaryOp::Add,
+        Some(Token::Minus) => BinaryOp::Subtract,
+        _ => return Ok(left),
+      };
+      self.position += 1;
+      let right = self.term()?;
+      left = Expr::Binary(op, Box::new(left), Box::new(right));
+    }
+  }
+
+  /// term := unary (('*' | '/') unary)*
+  fn term(&mut self) -> TakeoffResult<Expr> {
+    let mut left = self.unary()?;
+    loop {
+      let op = match self.peek() {
+        Some(Token::Star) => BinaryOp::Multiply,
+        Some(Token::Slash) => BinaryOp::Divide,
+        _ => return Ok(left),
+      };
+      self.position += 1;
+      let right = self.unary()?;
+      left = Expr::Binary(op, Box::new(left), Box::new(right));
+    }
+  }
+
+  /// unary := ('-' | '+') unary | power
+  fn unary(&mut self) -> TakeoffResult<Expr> {
+    match self.peek() {
+      Some(Token::Minus) => {
+        self.position += 1;
+        self.enter()?;
+        let operand = self.unary()?;
+        self.leave();
+        Ok(Expr::Negate(Box::new(operand)))
+      }
+      Some(Token::Plus) => {
+        self.position += 1;
+        self.enter()?;
+        let operand = self.unary()?;
+        self.leave();
+        Ok(operand)
+      }
+      _ => self.power(),
+    }
+  }
+
+  /// power := primary ('^' unary)?
+  fn power(&mut self) -> TakeoffResult<Expr> {
+    let base = self.primary()?;
+    if self.peek() == Some(&Token::Caret) {
+      self.position += 1;
+      self.enter()?;
+      let exponent = self.unary()?;
+      self.leave();
+      return Ok(Expr::Binary(
+        BinaryOp::Power,
+        Box::new(base),
+        Box::new(exponent),
+      ));
+    }
+    Ok(base)
+  }
+
+  /// primary := number | identifier | identifier '(' arguments ')' | '(' expression ')'
+  fn primary(&mut self) -> TakeoffResult<Expr> {
+    let start = self.offset();
+    match self.peek().cloned() {
+      Some(Token::Number(quantity)) => {
+        self.position += 1;
+        Ok(Expr::Number(quantity))
+      }
+      Some(Token::Ident(name)) => {
+        self.position += 1;
+        if self.peek() != Some(&Token::LParen) {
+          return Ok(Expr::Variable(name));
+        }
+        let function = Function::from_name(&name).ok_or_else(|| {
+          TakeoffError::invalid_formula(format!(
+            "unknown function `{}` at position {}",
+            name, start
+          ))
+        })?;
+        self.position += 1;
+        self.enter()?;
+        let mut args = Vec::new();
+        if self.peek() != Some(&Token::RParen) {
+          args.push(self.expression()?);
+          while self.peek() == Some(&Token::Comma) {
+            self.position += 1;
+            args.push(self.expression()?);
+          }
+        }
+        self.expect(Token::RParen)?;
+        self.leave();
+        function.check_arity(args.len())?;
+        Ok(Expr::Call(function, args))
+      }
+      Some(Token::LParen) => {
+        self.position += 1;
+        self.enter()?;
+        let inner = self.expression()?;
+        self.expect(Token::RParen)?;
+        self.leave();
+        Ok(inner)
+      }
+      _ => Err(self.unexpected()),
+    }
+  }
+}
+
+impl Expr {
+  fn evaluate(&self, context: &FormulaContext) -> TakeoffResult<Quantity> {
+    match self {
+      Expr::Number(quantity) => Ok(*quantity),
+      Expr::Variable(name) => context
+        .variables
+        .get(name)
+        .copied()
+        .ok_or_else(|| TakeoffError::unknown_formula_variable(name.clone())),
+      Expr::Negate(operand) => {
+        let value = operand.evaluate(context)?;
+        Ok(Quantity {
+          value: -value.value,
+          dimension: value.dimension,
+        })
+      }
+      Expr::Binary(op, left, right) => {
+        let left = left.evaluate(context)?;
+        let right = right.evaluate(context)?;
+        match op {
+          BinaryOp::Add => {
+            left.require_same_dimension(&right, "add")?;
+            Ok(Quantity {
+              value: left.value + right.value,
+              dimension: left.dimension,
+            })
+          }
+          BinaryOp::Subtract => {
+            left.require_same_dimension(&right, "subtract")?;
+            Ok(Quantity {
+              value: left.value - right.value,
+              dimension: left.dimension,
+            })
+          }
+          BinaryOp::Multiply => Ok(Quantity {
+            value: left.value * right.value,
+            dimension: Quantity::checked_dimension(left.dimension.checked_add(right.dimension))?,
+          }),
+          BinaryOp::Divide => {
+            if right.value == 0.0 {
+              return Err(TakeoffError::invalid_formula("division by zero"));
+            }
+            Ok(Quantity {
+              value: left.value / right.value,
+              dimension: Quantity::checked_dimension(left.dimension.checked_sub(right.dimension))?,
+            })
+          }
+          BinaryOp::Power => {
+            let exponent = right.require_dimensionless("an exponent")?;
+            if left.dimension == 0 {
+              return Ok(Quantity::scalar(left.value.powf(exponent)));
+            }
+            if exponent.fract() != 0.0 || exponent.abs() > MAX_NESTING_DEPTH as f64 {
+              return Err(TakeoffError::formula_dimension_mismatch(format!(
+                "{} can only be raised to a small whole number, got {}",
+                Quantity::describe_dimension(left.dimension),
+                exponent
+              )));
+            }
+            let exponent = exponent as i32;
+            Ok(Quantity {
+              value: left.value.powi(exponent),
+              dimension: Quantity::checked_dimension(left.dimension.checked_mul(exponent))?,
+            })
+          }
+        }
+      }
+      Expr::Call(function, args) => {
+        let values = args
+          .iter()
+          .map(|arg| arg.evaluate(context))
+          .collect::<TakeoffResult<Vec<Quantity>>>()?;
+        function.apply(&values)
+      }
+    }
+  }
+
+  fn collect_variables(&self, names: &mut Vec<String>) {
+    match self {
+      Expr::Number(_) => {}
+      Expr::Variable(name) => {
+        if !names.contains(name) {
+          names.push(name.clone());
+        }
+      }
+      Expr::Negate(operand) => operand.collect_variables(names),
+      Expr::Binary(_, left, right) => {
+        left.collect_variables(names);
+        right.collect_variables(names);
+      }
+      Expr::Call(_, args) => {
+        for arg in args {
+          arg.collect_variables(names);
+        }
+      }
+    }
+  }
+}
+
+/// The variables a formula can read, keyed by name.
+#[derive(Debug, Clone, Default)]
+pub struct FormulaContext {
+  variables: HashMap<String, Quantity>,
+}
+
+impl FormulaContext {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Bind a variable to a unit-aware value.
+  pub fn with_value(mut self, name: impl Into<String>, value: UnitValue) -> Self {
+    self.set_value(name, value);
+    self
+  }
+
+  /// Bind a variable to a dimensionless number.
+  pub fn with_scalar(self, name: impl Into<String>, value: f64) -> Self {
+    self.with_value(name, UnitValue::from_scalar(value))
+  }
+
+  /// Bind `length` and `perimeter` to the given length.
+  pub fn with_length(self, length: Length) -> Self {
+    self
+      .with_value(LENGTH_VARIABLE, UnitValue::from_length(length))
+      .with_value(PERIMETER_VARIABLE, UnitValue::from_length(length))
+  }
+
+  /// Bind `area` to the given area.
+  pub fn with_area(self, area: Area) -> Self {
+    self.with_value(AREA_VARIABLE, UnitValue::from_area(area))
+  }
+
+  /// Bind `count` to the given number of measurements.
+  pub fn with_count(self, count: f64) -> Self {
+    self.with_scalar(COUNT_VARIABLE, count)
+  }
+
+  /// Bind `points` to the given number of vertices.
+  pub fn with_points(self, points: f64) -> Self {
+    self.with_scalar(POINTS_VARIABLE, points)
+  }
+
+  /// Bind or replace a variable in place.
+  pub fn set_value(&mut self, name: impl Into<String>, value: UnitValue) {
+    self.variables.insert(name.into(), Quantity::from(value));
+  }
+
+  /// Whether a variable with the given name is bound.
+  pub fn contains(&self, name: &str) -> bool {
+    self.variables.contains_key(name)
+  }
+}
+
+/// A parsed formula, ready to be evaluated against any number of contexts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+  source: String,
+  expr: Expr,
+}
+
+impl Formula {
+  /// Parse a formula.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::InvalidFormula`] if the formula is empty, too long, too deeply
+  /// nested, or not syntactically valid (unknown unit suffix, unknown function, wrong number
+  /// of arguments, unbalanced parentheses).
+  pub fn parse(source: &str) -> TakeoffResult<Self> {
+    if source.chars().count() > MAX_FORMULA_LENGTH {
+      return Err(TakeoffError::invalid_formula(format!(
+        "formula is longer than {} characters",
+        MAX_FORMULA_LENGTH
+      )));
+    }
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+      return Err(TakeoffError::invalid_formula("formula is empty"));
+    }
+    let mut parser = Parser {
+      tokens,
+      position: 0,
+      depth: 0,
+      source_length: source.chars().count(),
+    };
+    let expr = parser.expression()?;
+    if parser.peek().is_some() {
+      return Err(parser.unexpected());
+    }
+    Ok(Self {
+      source: source.to_string(),
+      expr,
+    })
+  }
+
+  /// The original formula text.
+  pub fn source(&self) -> &str {
+    &self.source
+  }
+
+  /// Names of the variables referenced by the formula, in order of first use.
+  pub fn variables(&self) -> Vec<String> {
+    let mut names = Vec::new();
+    self.expr.collect_variables(&mut names);
+    names
+  }
+
+  /// Evaluate the formula against a context.
+  ///
+  /// # Errors
+  ///
+  /// - [`TakeoffError::UnknownFormulaVariable`] if a variable is not bound in `context`
+  /// - [`TakeoffError::FormulaDimensionMismatch`] if operands have incompatible dimensions
+  ///   or the result is not a scalar, length, area or volume
+  /// - [`TakeoffError::InvalidFormula`] on division by zero or a non-finite result
+  pub fn evaluate(&self, context: &FormulaContext) -> TakeoffResult<UnitValue> {
+    let result = self.expr.evaluate(context)?;
+    if !result.value.is_finite() {
+      return Err(TakeoffError::invalid_formula(format!(
+        "`{}` does not evaluate to a finite number",
+        self.source
+      )));
+    }
+    UnitValue::try_from(result)
+  }
+}
+
+/// Parse and evaluate a formula in one step.
+///
+/// # Errors
+///
+/// See [`Formula::parse`] and [`Formula::evaluate`].
+pub fn evaluate_formula(source: &str, context: &FormulaContext) -> TakeoffResult<UnitValue> {
+  Formula::parse(source)?.evaluate(context)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::unit::UnitValueItemType;
+
+  const EPSILON: f64 = 1e-3;
+
+  fn wall_context() -> FormulaContext {
+    FormulaContext::new()
+      .with_length(Unit::Feet.get_unit(40.0))
+      .with_area(Unit::Feet.get_area_unit(100.0))
+      .with_count(3.0)
+      .with_points(4.0)
+  }
+
+  #[test]
+  fn test_scalar_arithmetic_precedence() {
+    let result = evaluate_formula("1 + 2 * 3 ^ 2 - (4 - 2) / 2", &FormulaContext::new()).unwrap();
+    assert!(matches!(result.magnitude(), UnitValueItemType::Scalar));
+    assert_eq!(result.get_converted_value(Unit::Feet), 18.0);
+    let result = evaluate_formula("-2 ^ 2", &FormulaContext::new()).unwrap();
+    assert_eq!(result.get_converted_value(Unit::Feet), -4.0);
+  }
+
+  #[test]
+  fn test_stud_count_formula() {
+    // 40 ft of wall at 16 in on center, plus one end stud
+    let result = evaluate_formula("ceil(length / 16in) + 1", &wall_context()).unwrap();
+    assert!(matches!(result.magnitude(), UnitValueItemType::Scalar));
+    assert_eq!(result.get_converted_value(Unit::Feet), 31.0);
+  }
+
+  #[test]
+  fn test_area_waste_factor_formula() {
+    // 100 sf plus 10% waste, in 32 sf sheets
+    let result = evaluate_formula("area * 1.1 / 32sf", &wall_context()).unwrap();
+    assert!((result.get_converted_value(Unit::Feet) - 3.4375).abs() < EPSILON);
+  }
+
+  #[test]
+  fn test_dimensions_multiply() {
+    let result = evaluate_formula("length * 8ft", &wall_context()).unwrap();
+    assert!(matches!(result.magnitude(), UnitValueItemType::Area));
+    assert!((result.get_converted_value(Unit::Feet) - 320.0).abs() < EPSILON);
+
+    let result = evaluate_formula("area * 4in", &wall_context()).unwrap();
+    assert!(matches!(result.magnitude(), UnitValueItemType::Volume));
+    assert!((result.get_converted_value(Unit::Feet) - 100.0 / 3.0).abs() < EPSILON);
+
+    let result = evaluate_formula("sqrt(area)", &wall_context()).unwrap();
+    assert!(matches!(result.magnitude(), UnitValueItemType::Length));
+    assert!((result.get_converted_value(Unit::Feet) - 10.0).abs() < EPSILON);
+
+    let result = evaluate_formula("(2m) ^ 2", &FormulaContext::new()).unwrap();
+    assert!(matches!(result.magnitude(), UnitValueItemType::Area));
+    assert!((result.get_converted_value(Unit::Meters) - 4.0).abs() < EPSILON);
+  }
+
+  #[test]
+  fn test_mixed_units_add() {
+    let result = evaluate_formula("1yd + 3ft + 12in", &FormulaContext::new()).unwrap();
+    assert!((result.get_converted_value(Unit::Feet) - 7.0).abs() < EPSILON);
+    let result = evaluate_formula("max(perimeter, 1m, 2cm)", &wall_context()).unwrap();
+    assert!((result.get_converted_value(Unit::Feet) - 40.0).abs() < EPSILON);
+  }
+
+  #[test]
+  fn test_dimension_mismatch() {
+    assert!(matches!(
+      evaluate_formula("length + area", &wall_context()),
+      Err(TakeoffError::FormulaDimensionMismatch { .. })
+    ));
+    assert!(matches!(
+      evaluate_formula("ceil(length)", &wall_context()),
+      Err(TakeoffError::FormulaDimensionMismatch { .. })
+    ));
+    assert!(matches!(
+      evaluate_formula("sqrt(length)", &wall_context()),
+      Err(TakeoffError::FormulaDimensionMismatch { .. })
+    ));
+    assert!(matches!(
+      evaluate_formula("length ^ 1.5", &wall_context()),
+      Err(TakeoffError::FormulaDimensionMismatch { .. })
+    ));
+    assert!(matches!(
+      evaluate_formula("area * area", &wall_context()),
+      Err(TakeoffError::FormulaDimensionMismatch { .. })
+    ));
+    assert!(matches!(
+      evaluate_formula("1 / length", &wall_context()),
+      Err(TakeoffError::FormulaDimensionMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn test_nested_power_overflow() {
+    // 64^6 and 2 * 64^5 both exceed the range of a dimension exponent
+    for formula in [
+      "((((((length ^ 64) ^ 64) ^ 64) ^ 64) ^ 64) ^ 64)",
+      "((((length ^ 64) ^ 64) ^ 64) ^ 64) ^ 64 * ((((length ^ 64) ^ 64) ^ 64) ^ 64) ^ 64",
+    ] {
+      let err = evaluate_formula(formula, &wall_context()).unwrap_err();
+      assert_eq!(
+        err,
+        TakeoffError::formula_dimension_mismatch("result dimension is out of range")
+      );
+    }
+  }
+
+  #[test]
+  fn test_unknown_variable() {
+    let err = evaluate_formula("area * 2", &FormulaContext::new()).unwrap_err();
+    assert_eq!(
+      err,
+      TakeoffError::UnknownFormulaVariable {
+        name: "area".to_string()
+      }
+    );
+  }
+
+  #[test]
+  fn test_custom_variables() {
+    let context = wall_context()
+      .with_scalar("waste", 0.15)
+      .with_value("height", UnitValue::from_length(Unit::Feet.get_unit(9.0)));
+    let formula = Formula::parse("length * height * (1 + waste)").unwrap();
+    assert_eq!(formula.variables(), vec!["length", "height", "waste"]);
+    let result = formula.evaluate(&context).unwrap();
+    assert!((result.get_converted_value(Unit::Feet) - 414.0).abs() < 0.01);
+  }
+
+  #[test]
+  fn test_invalid_formulas() {
+    for source in [
+      "",
+      "   ",
+      "1 +",
+      "(1 + 2",
+      "1 + 2)",
+      "2 3",
+      "16furlongs",
+      "foo(1)",
+      "ceil(1, 2)",
+      "min()",
+      "1.2.3",
+      "length $ 2",
+      "1 / 0",
+      "1, 2",
+    ] {
+      assert!(
+        matches!(
+          evaluate_formula(source, &wall_context()),
+          Err(TakeoffError::InvalidFormula { .. })
+        ),
+        "expected `{}` to be rejected",
+        source
+      );
+    }
+  }
+
+  #[test]
+  fn test_limits() {
+    let deep = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+    assert!(matches!(
+      Formula::parse(&deep),
+      Err(TakeoffError::InvalidFormula { .. })
+    ));
+    let long = "1+".repeat(MAX_FORMULA_LENGTH) + "1";
+    assert!(matches!(
+      Formula::parse(&long),
+      Err(TakeoffError::InvalidFormula { .. })
+    ));
+    assert!(matches!(
+      evaluate_formula("10 ^ 400", &FormulaContext::new()),
+      Err(TakeoffError::InvalidFormula { .. })
+    ));
+  }
+}
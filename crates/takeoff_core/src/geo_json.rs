@@ -0,0 +1,365 @@
+//! GeoJSON import and export of measurements.
+//!
+//! Coordinates are written as they are stored: page pixels, with no coordinate reference
+//! system and no axis flip. Each measurement becomes one feature whose properties carry its
+//! ids, kind, attributes and real-world quantities. On import, Point, LineString and
+//! Polygon features become count, polyline and polygon measurements; polygons whose `type`
+//! property is `Rectangle` and that are axis-aligned rectangles become rectangles again.
+
+use crate::attribute::AttributeValue;
+use crate::coords::Point;
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::measurement::Measurement;
+use crate::unit::{Unit, UnitValue};
+use crate::utils::generate_random_id;
+use geojson::feature::Id;
+use geojson::{Feature, FeatureCollection, GeoJson, JsonObject, JsonValue, Value};
+use std::collections::BTreeMap;
+
+/// A measurement with the values exported alongside its geometry.
+#[derive(Debug, Clone)]
+pub struct MeasurementFeature {
+  pub measurement: Measurement,
+  /// The id of the scale assigned to the measurement.
+  pub scale_id: Option<String>,
+  pub area: Option<UnitValue>,
+  pub length: Option<UnitValue>,
+}
+
+/// Build a feature collection with one feature per measurement.
+///
+/// Quantities are written in `unit` (areas in its square) and the unit is recorded in each
+/// feature's `unit` property.
+///
+/// # Errors
+///
+/// Returns [`TakeoffError::EmptyGeometry`] if a measurement has invalid geometry.
+pub fn to_feature_collection(
+  features: &[MeasurementFeature],
+  unit: Unit,
+) -> TakeoffResult<FeatureCollection> {
+  let features = features
+    .iter()
+    .map(|feature| to_feature(feature, unit))
+    .collect::<TakeoffResult<Vec<Feature>>>()?;
+  Ok(FeatureCollection {
+    bbox: None,
+    features,
+    foreign_members: None,
+  })
+}
+
+fn to_feature(feature: &MeasurementFeature, unit: Unit) -> TakeoffResult<Feature> {
+  let measurement = &feature.measurement;
+  let geometry = Value::from(&measurement.to_geometry()?);
+
+  let mut properties = JsonObject::new();
+  properties.insert("id".to_string(), measurement.id().into());
+  properties.insert("page_id".to_string(), measurement.page_id().into());
+  properties.insert("group_id".to_string(), measurement.group_id().into());
+  properties.insert("type".to_string(), measurement.kind().into());
+  properties.insert("scale_id".to_string(), feature.scale_id.clone().into());
+  properties.insert(
+    "area".to_string(),
+    feature
+      .area
+      .as_ref()
+      .map(|area| area.get_converted_value(unit))
+      .into(),
+  );
+  properties.insert(
+    "length".to_string(),
+    feature
+      .length
+      .as_ref()
+      .map(|length| length.get_converted_value(unit))
+      .into(),
+  );
+  properties.insert("unit".to_string(), format!("{unit:?}").into());
+  if let Some(attributes) = measurement.attributes() {
+    let attributes =
+      serde_json::to_value(attributes).map_err(|e| TakeoffError::invalid_geojson(e.to_string()))?;
+    properties.insert("attributes".to_string(), attributes);
+  }
+
+  Ok(Feature {
+    bbox: None,
+    geometry: Some(geometry.into()),
+    id: Some(Id::String(measurement.id().to_string())),
+    properties: Some(properties),
+    foreign_members: None,
+  })
+}
+
+/// Read measurements from a GeoJSON feature collection or single feature.
+///
+/// # Arguments
+///
+/// * `json` - The GeoJSON document.
+/// * `page_id` - The page the measurements are placed on.
+/// * `group_id` - The group of features without a `group_id` property.
+///
+/// Features keep their `id` (the feature id, then the `id` property) or are given a random
+/// one.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The document or a feature cannot be imported ([`TakeoffError::InvalidGeoJson`])
+/// - A geometry has too few points ([`TakeoffError::EmptyGeometry`])
+pub fn from_geojson(
+  json: &str,
+  page_id: &str,
+  group_id: Option<&str>,
+) -> TakeoffResult<Vec<Measurement>> {
+  let document: GeoJson = json
+    .parse()
+    .map_err(|e: geojson::Error| TakeoffError::invalid_geojson(e.to_string()))?;
+  let features = match document {
+    GeoJson::FeatureCollection(collection) => collection.features,
+    GeoJson::Feature(feature) => vec![feature],
+    GeoJson::Geometry(_) => {
+      return Err(TakeoffError::invalid_geojson(
+        "expected a feature or feature collection, got a bare geometry",
+      ));
+    }
+  };
+  features
+    .into_iter()
+    .enumerate()
+    .map(|(index, feature)| from_feature(index, feature, page_id, group_id))
+    .collect()
+}
+
+fn from_feature(
+  index: usize,
+  feature: Feature,
+  page_id: &str,
+  default_group_id: Option<&str>,
+) -> TakeoffResult<Measurement> {
+  let properties = feature.properties.unwrap_or_default();
+  let text = |key: &str| properties.get(key).and_then(JsonValue::as_str);
+
+  let id = match feature.id {
+    Some(Id::String(id)) => id,
+    Some(Id::Number(id)) => id.to_string(),
+    None => text("id").map_or_else(generate_random_id, str::to_string),
+  };
+  let group_id = text("group_id")
+    .or(default_group_id)
+    .ok_or_else(|| TakeoffError::invalid_geojson(format!("feature {index} has no group_id")))?
+    .to_string();
+  let attributes: Option<BTreeMap<String, AttributeValue>> = properties
+    .get("attributes")
+    .filter(|attributes| !attributes.is_null())
+    .map(|attributes| serde_json::from_value(attributes.clone()))
+    .transpose()
+    .map_err(|e| {
+      TakeoffError::invalid_geojson(format!("feature {index} has invalid attributes: {e}"))
+    })?;
+  let page_id = page_id.to_string();
+
+  let geometry = feature
+    .geometry
+    .ok_or_else(|| TakeoffError::invalid_geojson(format!("feature {index} has no geometry")))?;
+  let measurement = match geometry.value {
+    Value::Point(position) => Measurement::Count {
+      id,
+      page_id,
+      group_id,
+      points: (point(index, &position)?,),
+      attributes,
+    },
+    Value::LineString(positions) => Measurement::Polyline {
+      id,
+      page_id,
+      group_id,
+      points: points(index, &positions)?,
+      attributes,
+    },
+    Value::Polygon(rings) => {
+      if rings.len() > 1 {
+        return Err(TakeoffError::invalid_geojson(format!(
+          "feature {index} is a polygon with holes"
+        )));
+      }
+      let mut points = points(index, rings.first().map_or(&[][..], Vec::as_slice))?;
+      if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+      }
+      match rectangle_corners(&points).filter(|_| text("type") == Some("Rectangle")) {
+        Some(corners) => Measurement::Rectangle {
+          id,
+          page_id,
+          group_id,
+          points: corners,
+          attributes,
+        },
+        None => Measurement::Polygon {
+          id,
+          page_id,
+          group_id,
+          points,
+          attributes,
+        },
+      }
+    }
+    other => {
+      return Err(TakeoffError::invalid_geojson(format!(
+        "feature {index} has unsupported geometry {}",
+        other.type_name()
+      )));
+    }
+  };
+  measurement.validate()?;
+  Ok(measurement)
+}
+
+fn point(index: usize, position: &[f64]) -> TakeoffResult<Point> {
+  match position {
+    [x, y, ..] => Ok(Point::new(*x, *y)),
+    _ => Err(TakeoffError::invalid_geojson(format!(
+      "feature {index} has a position with fewer than 2 coordinates"
+    ))),
+  }
+}
+
+fn points(index: usize, positions: &[Vec<f64>]) -> TakeoffResult<Vec<Point>> {
+  positions
+    .iter()
+    .map(|position| point(index, position))
+    .collect()
+}
+
+/// Get the opposite corners of an axis-aligned rectangle given by its four corners.
+fn rectangle_corners(points: &[Point]) -> Option<(Point, Point)> {
+  if points.len() != 4 {
+    return None;
+  }
+  let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+  let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+  let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+  let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+  let corners = [
+    Point::new(min_x, min_y),
+    Point::new(max_x, min_y),
+    Point::new(max_x, max_y),
+    Point::new(min_x, max_y),
+  ];
+  corners
+    .iter()
+    .all(|corner| points.contains(corner))
+    .then(|| (Point::new(min_x, min_y), Point::new(max_x, max_y)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::unit::UnitValueItemType;
+
+  fn measurements() -> Vec<Measurement> {
+    vec![
+      Measurement::Rectangle {
+        id: "r1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(10.0, 5.0)),
+        attributes: Some(BTreeMap::from([(
+          "finish".to_string(),
+          AttributeValue::text("CPT-1"),
+        )])),
+      },
+      Measurement::Polygon {
+        id: "a1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: vec![
+          Point::new(0.0, 0.0),
+          Point::new(4.0, 0.0),
+          Point::new(0.0, 3.0),
+        ],
+        attributes: None,
+      },
+      Measurement::Polyline {
+        id: "l1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g2".to_string(),
+        points: vec![Point::new(0.0, 0.0), Point::new(3.0, 4.0)],
+        attributes: None,
+      },
+      Measurement::Count {
+        id: "c1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g3".to_string(),
+        points: (Point::new(1.0, 2.0),),
+        attributes: None,
+      },
+    ]
+  }
+
+  #[test]
+  fn test_geojson_roundtrip() {
+    let features: Vec<MeasurementFeature> = measurements()
+      .into_iter()
+      .map(|measurement| MeasurementFeature {
+        measurement,
+        scale_id: Some("s1".to_string()),
+        area: Some(UnitValue::new(1.0, Unit::Meters, UnitValueItemType::Area)),
+        length: None,
+      })
+      .collect();
+    let collection = to_feature_collection(&features, Unit::Feet).unwrap();
+    let properties = collection.features[0].properties.as_ref().unwrap();
+    assert_eq!(properties["type"], "Rectangle");
+    assert_eq!(properties["unit"], "Feet");
+    assert!((properties["area"].as_f64().unwrap() - 10.7639).abs() < 1e-3);
+    assert!(properties["length"].is_null());
+
+    let json = collection.to_string();
+    assert_eq!(from_geojson(&json, "p1", None).unwrap(), measurements());
+
+    // Features are placed on the target page
+    let imported = from_geojson(&json, "p2", None).unwrap();
+    assert!(imported.iter().all(|m| m.page_id() == "p2"));
+  }
+
+  #[test]
+  fn test_geojson_import() {
+    let json = r#"{
+      "type": "FeatureCollection",
+      "features": [
+        {
+          "type": "Feature",
+          "properties": { "type": "Rectangle" },
+          "geometry": { "type": "Polygon", "coordinates": [[[0, 0], [4, 1], [4, 4], [0, 4], [0, 0]]] }
+        },
+        {
+          "type": "Feature",
+          "id": 7,
+          "properties": null,
+          "geometry": { "type": "Point", "coordinates": [1, 2] }
+        }
+      ]
+    }"#;
+    let imported = from_geojson(json, "p1", Some("g1")).unwrap();
+    // Not axis-aligned, so it stays a polygon
+    assert_eq!(imported[0].kind(), "Polygon");
+    assert_eq!(imported[0].group_id(), "g1");
+    assert_eq!(imported[1].id(), "7");
+
+    assert!(matches!(
+      from_geojson(json, "p1", None),
+      Err(TakeoffError::InvalidGeoJson { .. })
+    ));
+    assert!(matches!(
+      from_geojson("{}", "p1", Some("g1")),
+      Err(TakeoffError::InvalidGeoJson { .. })
+    ));
+    let multi = r#"{ "type": "Feature", "properties": {},
+      "geometry": { "type": "MultiPoint", "coordinates": [[1, 2]] } }"#;
+    assert!(matches!(
+      from_geojson(multi, "p1", Some("g1")),
+      Err(TakeoffError::InvalidGeoJson { .. })
+    ));
+  }
+}
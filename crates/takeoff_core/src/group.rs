@@ -1,5 +1,14 @@
+use crate::attribute::AttributeValue;
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::measurement::Measurement;
+use crate::unit::UnitValueItem;
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uom::si::f32::Length;
+
+/// The group attribute whose length extrudes the group's area into a volume.
+pub const DEPTH_ATTRIBUTE: &str = "depth";
 
 #[napi(string_enum)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
@@ -9,6 +18,61 @@ pub enum MeasurementType {
   Count,
 }
 
+impl MeasurementType {
+  /// Whether a measurement contributes to a group of this type.
+  ///
+  /// - `Area` accepts polygons and rectangles.
+  /// - `Linear` accepts polylines, and polygons and rectangles by their perimeter.
+  /// - `Count` accepts count markers.
+  pub fn accepts(&self, measurement: &Measurement) -> bool {
+    matches!(
+      (self, measurement),
+      (
+        MeasurementType::Area,
+        Measurement::Polygon { .. } | Measurement::Rectangle { .. }
+      ) | (
+        MeasurementType::Linear,
+        Measurement::Polyline { .. } | Measurement::Polygon { .. } | Measurement::Rectangle { .. }
+      ) | (MeasurementType::Count, Measurement::Count { .. })
+    )
+  }
+}
+
+/// How `upsert_measurement` treats a measurement whose kind does not match its group's
+/// [`MeasurementType`].
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash, Default)]
+pub enum GroupValidationMode {
+  /// Accept the measurement and list it in the mismatch report.
+  #[default]
+  Flag,
+  /// Reject the measurement with [`TakeoffError::IncompatibleMeasurementType`].
+  Reject,
+}
+
+/// A measurement whose kind does not match its group's [`MeasurementType`].
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeasurementTypeMismatch {
+  pub measurement_id: String,
+  /// The kind of the measurement (e.g. `Polyline`).
+  pub measurement_kind: String,
+  pub group_id: String,
+  /// The measurement type the group expects.
+  pub measurement_type: MeasurementType,
+}
+
+impl From<MeasurementTypeMismatch> for TakeoffError {
+  fn from(mismatch: MeasurementTypeMismatch) -> Self {
+    TakeoffError::IncompatibleMeasurementType {
+      measurement_id: mismatch.measurement_id,
+      measurement_kind: mismatch.measurement_kind,
+      group_id: mismatch.group_id,
+      measurement_type: mismatch.measurement_type,
+    }
+  }
+}
+
 #[napi(object)]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Group {
@@ -16,4 +80,145 @@ pub struct Group {
   pub name: Option<String>,
   //   pub measurements: Vec<Measurement>,
   pub measurement_type: MeasurementType,
+  /// Custom attributes, inherited by every measurement in the group.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub attributes: Option<BTreeMap<String, AttributeValue>>,
+}
+
+impl Group {
+  /// Describe how a measurement conflicts with this group's measurement type.
+  ///
+  /// Returns `None` if the measurement is compatible.
+  pub fn mismatch(&self, measurement: &Measurement) -> Option<MeasurementTypeMismatch> {
+    if self.measurement_type.accepts(measurement) {
+      return None;
+    }
+    Some(MeasurementTypeMismatch {
+      measurement_id: measurement.id().to_string(),
+      measurement_kind: measurement.kind().to_string(),
+      group_id: self.id.clone(),
+      measurement_type: self.measurement_type,
+    })
+  }
+
+  /// Check that a measurement is compatible with this group's measurement type.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::IncompatibleMeasurementType`] if the group does not accept
+  /// the measurement's kind.
+  pub fn check_measurement(&self, measurement: &Measurement) -> TakeoffResult<()> {
+    match self.mismatch(measurement) {
+      Some(mismatch) => Err(mismatch.into()),
+      None => Ok(()),
+    }
+  }
+
+  /// Get the depth set by the group's [`DEPTH_ATTRIBUTE`].
+  ///
+  /// Returns `None` if the attribute is missing or is not a length quantity.
+  pub fn depth(&self) -> Option<Length> {
+    let depth = self.attributes.as_ref()?.get(DEPTH_ATTRIBUTE)?;
+    match depth.to_unit_value()?.item() {
+      UnitValueItem::Length { value } => Some(value),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coords::Point;
+  use crate::unit::{Unit, UnitValueItemType};
+
+  fn group(measurement_type: MeasurementType) -> Group {
+    Group {
+      id: "g1".to_string(),
+      name: None,
+      measurement_type,
+      attributes: None,
+    }
+  }
+
+  #[test]
+  fn test_accepts() {
+    let polyline = Measurement::Polyline {
+      id: "1".to_string(),
+      page_id: "1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+      attributes: None,
+    };
+    let rectangle = Measurement::Rectangle {
+      id: "2".to_string(),
+      page_id: "1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+      attributes: None,
+    };
+    let count = Measurement::Count {
+      id: "3".to_string(),
+      page_id: "1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0),),
+      attributes: None,
+    };
+
+    assert!(!MeasurementType::Area.accepts(&polyline));
+    assert!(MeasurementType::Area.accepts(&rectangle));
+    assert!(!MeasurementType::Area.accepts(&count));
+    assert!(MeasurementType::Linear.accepts(&polyline));
+    assert!(MeasurementType::Linear.accepts(&rectangle));
+    assert!(!MeasurementType::Linear.accepts(&count));
+    assert!(MeasurementType::Count.accepts(&count));
+    assert!(!MeasurementType::Count.accepts(&rectangle));
+  }
+
+  #[test]
+  fn test_check_measurement() {
+    let polyline = Measurement::Polyline {
+      id: "m1".to_string(),
+      page_id: "1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+      attributes: None,
+    };
+    assert!(
+      group(MeasurementType::Linear)
+        .check_measurement(&polyline)
+        .is_ok()
+    );
+    let err = group(MeasurementType::Area)
+      .check_measurement(&polyline)
+      .unwrap_err();
+    assert_eq!(
+      err,
+      TakeoffError::IncompatibleMeasurementType {
+        measurement_id: "m1".to_string(),
+        measurement_kind: "Polyline".to_string(),
+        group_id: "g1".to_string(),
+        measurement_type: MeasurementType::Area,
+      }
+    );
+  }
+
+  #[test]
+  fn test_depth() {
+    let mut group = group(MeasurementType::Area);
+    assert_eq!(group.depth(), None);
+    group.attributes = Some(BTreeMap::from([(
+      DEPTH_ATTRIBUTE.to_string(),
+      AttributeValue::quantity(6.0, Unit::Inches, UnitValueItemType::Length),
+    )]));
+    let depth = group.depth().unwrap();
+    assert!((Unit::Feet.convert_length_to_unit(depth) - 0.5).abs() < 1e-6);
+
+    // Depths must be lengths
+    group.attributes = Some(BTreeMap::from([(
+      DEPTH_ATTRIBUTE.to_string(),
+      AttributeValue::number(6.0),
+    )]));
+    assert_eq!(group.depth(), None);
+  }
 }
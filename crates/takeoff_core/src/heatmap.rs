@@ -0,0 +1,462 @@
+//! Raster cut/fill maps of a surface mesh against a reference surface.
+//!
+//! The mesh is sampled at the center of each cell of a grid over the reference surface's
+//! bounding box, as in [`SurfaceMesh::volume_against`]. Cells where the terrain is above
+//! the reference (cut) are shaded red and cells below it (fill) blue, saturating at the
+//! map's maximum depth. Cells outside the reference surface are transparent and cells
+//! without terrain data are gray. Isolines of the difference surface are drawn where
+//! neighbouring cells fall in different depth bands, and an optional legend with the depth
+//! range is drawn to the right of the map.
+
+use crate::contour::SurfaceMesh;
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::volume::ReferenceSurface;
+use geo::{BoundingRect, Contains, Point as GeoPoint};
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+
+/// The number of cells along the longer side of a map without a cell size.
+pub const DEFAULT_HEATMAP_RESOLUTION: u32 = 256;
+
+/// The largest number of cells along either side of a map.
+pub const MAX_HEATMAP_RESOLUTION: u32 = 4096;
+
+/// The number of depth bands between the reference and the maximum depth, on each side,
+/// when no contour interval is given.
+pub const DEFAULT_HEATMAP_BANDS: f64 = 4.0;
+
+/// The width of the legend in pixels.
+const LEGEND_WIDTH: u32 = 72;
+/// The smallest height of a map with a legend, so the labels fit.
+const LEGEND_MIN_HEIGHT: u32 = 64;
+
+const CUT_COLOR: [u8; 3] = [214, 39, 40];
+const FILL_COLOR: [u8; 3] = [31, 119, 180];
+const UNCOVERED_COLOR: [u8; 4] = [200, 200, 200, 255];
+const ISOLINE_COLOR: [u8; 4] = [64, 64, 64, 255];
+const LEGEND_BACKGROUND: [u8; 4] = [255, 255, 255, 255];
+const LEGEND_TEXT_COLOR: [u8; 4] = [0, 0, 0, 255];
+
+/// The encoding of a rendered heatmap.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeatmapFormat {
+  #[default]
+  Png,
+  /// Raw 8-bit RGBA pixels, row by row from the top left.
+  Rgba,
+}
+
+/// Options for [`SurfaceMesh::cut_fill_heatmap`].
+///
+/// Depths are real-world values, in the unit of the contour's scale.
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeatmapOptions {
+  /// The size of one cell in page pixels. Defaults to the size giving
+  /// [`DEFAULT_HEATMAP_RESOLUTION`] cells along the longer side.
+  pub cell_size: Option<f64>,
+  /// The depth at which colors saturate. Defaults to the largest sampled depth.
+  pub max_depth: Option<f64>,
+  /// The depth between isolines. Defaults to a [`DEFAULT_HEATMAP_BANDS`]th of the maximum
+  /// depth; 0 draws no isolines.
+  pub contour_interval: Option<f64>,
+  /// Whether to draw the legend. Defaults to true.
+  pub legend: Option<bool>,
+  /// The encoding of the image. Defaults to PNG.
+  pub format: Option<HeatmapFormat>,
+}
+
+/// An encoded cut/fill map.
+#[napi(object)]
+pub struct HeatmapImage {
+  pub width: u32,
+  pub height: u32,
+  pub format: HeatmapFormat,
+  /// The depth at which colors saturate, shown at the ends of the legend.
+  pub max_depth: f64,
+  pub data: Buffer,
+}
+
+/// A rendered cut/fill map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CutFillHeatmap {
+  pub width: u32,
+  pub height: u32,
+  /// 8-bit RGBA pixels, row by row from the top left.
+  pub pixels: Vec<u8>,
+  /// The depth at which colors saturate.
+  pub max_depth: f64,
+}
+
+impl CutFillHeatmap {
+  fn pixel_mut(&mut self, x: u32, y: u32) -> &mut [u8] {
+    let offset = ((y * self.width + x) * 4) as usize;
+    &mut self.pixels[offset..offset + 4]
+  }
+
+  fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+    if x < self.width && y < self.height {
+      self.pixel_mut(x, y).copy_from_slice(&color);
+    }
+  }
+
+  /// Encode the map as a PNG image.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::ImageExport`] if the image cannot be encoded.
+  pub fn to_png(&self) -> TakeoffResult<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, self.width, self.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let image_error = |e: png::EncodingError| TakeoffError::image_export(e.to_string());
+    let mut writer = encoder.write_header().map_err(image_error)?;
+    writer.write_image_data(&self.pixels).map_err(image_error)?;
+    writer.finish().map_err(image_error)?;
+    Ok(data)
+  }
+
+  /// Encode the map in the given format.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::ImageExport`] if the image cannot be encoded.
+  pub fn to_image(&self, format: HeatmapFormat) -> TakeoffResult<HeatmapImage> {
+    let data = match format {
+      HeatmapFormat::Png => self.to_png()?,
+      HeatmapFormat::Rgba => self.pixels.clone(),
+    };
+    Ok(HeatmapImage {
+      width: self.width,
+      height: self.height,
+      format,
+      max_depth: self.max_depth,
+      data: data.into(),
+    })
+  }
+}
+
+impl SurfaceMesh {
+  /// Render a cut/fill map of this mesh against a reference surface.
+  ///
+  /// # Arguments
+  ///
+  /// * `reference` - The reference surface, with the elevation in mesh units.
+  /// * `depth_ratio` - Mesh elevation units per real-world depth unit (the scale ratio for
+  ///   meshes built by [`ContourInput::to_surface_mesh`](crate::contour::ContourInput::to_surface_mesh)).
+  /// * `options` - The cell size, depth range, isolines and legend.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::ImageExport`] if:
+  /// - The reference surface is empty
+  /// - The cell size or depth ratio is not positive
+  /// - The map would be larger than [`MAX_HEATMAP_RESOLUTION`] cells along a side
+  pub fn cut_fill_heatmap(
+    &self,
+    reference: &ReferenceSurface,
+    depth_ratio: f64,
+    options: &HeatmapOptions,
+  ) -> TakeoffResult<CutFillHeatmap> {
+    let rect = reference
+      .polygon
+      .bounding_rect()
+      .filter(|rect| rect.width() > 0.0 && rect.height() > 0.0)
+      .ok_or_else(|| TakeoffError::image_export("the reference surface is empty"))?;
+    if depth_ratio <= 0.0 || !depth_ratio.is_finite() {
+      return Err(TakeoffError::image_export(format!(
+        "depth ratio must be positive, got {depth_ratio}"
+      )));
+    }
+    let cell_size = options
+      .cell_size
+      .unwrap_or_else(|| rect.width().max(rect.height()) / DEFAULT_HEATMAP_RESOLUTION as f64);
+    if cell_size <= 0.0 || !cell_size.is_finite() {
+      return Err(TakeoffError::image_export(format!(
+        "cell size must be positive, got {cell_size}"
+      )));
+    }
+    let columns = (rect.width() / cell_size).ceil();
+    let rows = (rect.height() / cell_size).ceil();
+    if columns.max(rows) > MAX_HEATMAP_RESOLUTION as f64 {
+      return Err(TakeoffError::image_export(format!(
+        "a cell size of {cell_size} gives a {columns} x {rows} map, larger than {MAX_HEATMAP_RESOLUTION} cells"
+      )));
+    }
+    let (columns, rows) = (columns as u32, rows as u32);
+
+    // Real-world depth of each cell: `None` outside the reference, `Some(None)` uncovered
+    let depths: Vec<Option<Option<f64>>> = (0..rows)
+      .flat_map(|row| (0..columns).map(move |column| (column, row)))
+      .map(|(column, row)| {
+        let x = rect.min().x + (column as f64 + 0.5) * cell_size;
+        let y = rect.min().y + (row as f64 + 0.5) * cell_size;
+        reference.polygon.contains(&GeoPoint::new(x, y)).then(|| {
+          self
+            .z_at(x, y)
+            .map(|z| (z - reference.elevation) / depth_ratio)
+        })
+      })
+      .collect();
+
+    let max_depth = options.max_depth.unwrap_or_else(|| {
+      depths
+        .iter()
+        .flatten()
+        .flatten()
+        .fold(0.0, |max: f64, depth| max.max(depth.abs()))
+    });
+    let interval = options
+      .contour_interval
+      .unwrap_or(max_depth / DEFAULT_HEATMAP_BANDS);
+    let band = |depth: f64| (depth / interval).floor() as i64;
+    let depth_at = |column: u32, row: u32| -> Option<f64> {
+      depths[(row * columns + column) as usize].flatten()
+    };
+
+    let legend = options.legend.unwrap_or(true);
+    let width = columns + if legend { LEGEND_WIDTH } else { 0 };
+    let height = if legend {
+      rows.max(LEGEND_MIN_HEIGHT)
+    } else {
+      rows
+    };
+    let mut map = CutFillHeatmap {
+      width,
+      height,
+      pixels: vec![0; (width * height * 4) as usize],
+      max_depth,
+    };
+
+    for row in 0..rows {
+      for column in 0..columns {
+        let color = match depths[(row * columns + column) as usize] {
+          None => continue,
+          Some(None) => UNCOVERED_COLOR,
+          Some(Some(depth)) => {
+            let on_isoline = interval > 0.0
+              && [(column + 1, row), (column, row + 1)]
+                .into_iter()
+                .filter(|&(c, r)| c < columns && r < rows)
+                .filter_map(|(c, r)| depth_at(c, r))
+                .any(|neighbour| band(neighbour) != band(depth));
+            if on_isoline {
+              ISOLINE_COLOR
+            } else {
+              depth_color(depth, max_depth)
+            }
+          }
+        };
+        map.set_pixel(column, row, color);
+      }
+    }
+
+    if legend {
+      draw_legend(&mut map, columns, max_depth);
+    }
+    Ok(map)
+  }
+}
+
+/// Get the color of a depth, positive for cut and negative for fill.
+fn depth_color(depth: f64, max_depth: f64) -> [u8; 4] {
+  let t = if max_depth > 0.0 {
+    (depth / max_depth).clamp(-1.0, 1.0)
+  } else {
+    0.0
+  };
+  let target = if t >= 0.0 { CUT_COLOR } else { FILL_COLOR };
+  let channel = |c: u8| (255.0 + (c as f64 - 255.0) * t.abs()).round() as u8;
+  [
+    channel(target[0]),
+    channel(target[1]),
+    channel(target[2]),
+    255,
+  ]
+}
+
+/// Draw a color bar from the maximum cut at the top to the maximum fill at the bottom,
+/// labelled with its ends and zero.
+fn draw_legend(map: &mut CutFillHeatmap, left: u32, max_depth: f64) {
+  for y in 0..map.height {
+    for x in left..map.width {
+      map.set_pixel(x, y, LEGEND_BACKGROUND);
+    }
+  }
+  let (top, bottom) = (8, map.height - 8);
+  let bar_left = left + 6;
+  for y in top..bottom {
+    let t = 1.0 - 2.0 * (y - top) as f64 / (bottom - top - 1) as f64;
+    let color = depth_color(t * max_depth, max_depth);
+    for x in bar_left..bar_left + 10 {
+      map.set_pixel(x, y, color);
+    }
+  }
+  let text_left = bar_left + 14;
+  draw_text(
+    map,
+    text_left,
+    top,
+    &format!("+{}", format_depth(max_depth)),
+  );
+  draw_text(map, text_left, (top + bottom) / 2 - 5, "0");
+  draw_text(
+    map,
+    text_left,
+    bottom - 10,
+    &format!("-{}", format_depth(max_depth)),
+  );
+}
+
+fn format_depth(depth: f64) -> String {
+  let text = format!("{depth:.2}");
+  text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Draw text in a 3 x 5 pixel font doubled in size, with its top left at `(x, y)`.
+///
+/// Only digits, signs and the decimal point are drawn.
+fn draw_text(map: &mut CutFillHeatmap, x: u32, y: u32, text: &str) {
+  for (index, c) in text.chars().enumerate() {
+    let Some(glyph) = glyph(c) else {
+      continue;
+    };
+    let left = x + index as u32 * 8;
+    for (row, bits) in glyph.iter().enumerate() {
+      for column in 0..3 {
+        if bits & (0b100 >> column) != 0 {
+          for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            map.set_pixel(
+              left + column * 2 + dx,
+              y + row as u32 * 2 + dy,
+              LEGEND_TEXT_COLOR,
+            );
+          }
+        }
+      }
+    }
+  }
+}
+
+fn glyph(c: char) -> Option<[u8; 5]> {
+  let glyph = match c {
+    '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+    '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+    '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+    '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+    '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+    '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+    '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+    '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+    '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+    '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+    '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+    '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+    '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+    _ => return None,
+  };
+  Some(glyph)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coords::{Point, Point3D};
+
+  /// A plane sloping from elevation 0 at x = 0 to 20 at x = 100.
+  fn sloped_mesh() -> SurfaceMesh {
+    SurfaceMesh {
+      vertices: vec![
+        Point3D::new(0.0, 0.0, 0.0),
+        Point3D::new(100.0, 0.0, 20.0),
+        Point3D::new(100.0, 50.0, 20.0),
+        Point3D::new(0.0, 50.0, 0.0),
+      ],
+      triangles: vec![[0, 1, 2], [0, 2, 3]],
+    }
+  }
+
+  fn reference() -> ReferenceSurface {
+    ReferenceSurface::new(
+      vec![
+        Point::new(0.0, 0.0),
+        Point::new(100.0, 0.0),
+        Point::new(100.0, 50.0),
+        Point::new(0.0, 50.0),
+      ],
+      10.0,
+    )
+  }
+
+  fn pixel(map: &CutFillHeatmap, x: u32, y: u32) -> [u8; 4] {
+    let offset = ((y * map.width + x) * 4) as usize;
+    map.pixels[offset..offset + 4].try_into().unwrap()
+  }
+
+  #[test]
+  fn test_cut_fill_heatmap() {
+    let options = HeatmapOptions {
+      cell_size: Some(10.0),
+      contour_interval: Some(0.0),
+      legend: Some(false),
+      ..HeatmapOptions::default()
+    };
+    // Two mesh units per real-world unit: depths range from -5 to 5
+    let map = sloped_mesh()
+      .cut_fill_heatmap(&reference(), 2.0, &options)
+      .unwrap();
+    assert_eq!((map.width, map.height), (10, 5));
+    assert!((map.max_depth - 4.5).abs() < 1e-9);
+    // Fill on the left, cut on the right
+    assert_eq!(pixel(&map, 0, 0), [31, 119, 180, 255]);
+    assert_eq!(pixel(&map, 9, 4), [214, 39, 40, 255]);
+    let middle = pixel(&map, 5, 2);
+    assert!(middle[0] > middle[2] && middle[1] > 200);
+
+    let png = map.to_png().unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+    let image = map.to_image(HeatmapFormat::Rgba).unwrap();
+    assert_eq!(image.data.len(), 10 * 5 * 4);
+  }
+
+  #[test]
+  fn test_cut_fill_heatmap_isolines_and_legend() {
+    let triangle = ReferenceSurface::new(
+      vec![
+        Point::new(0.0, 0.0),
+        Point::new(100.0, 0.0),
+        Point::new(0.0, 50.0),
+      ],
+      10.0,
+    );
+    let options = HeatmapOptions {
+      cell_size: Some(1.0),
+      max_depth: Some(10.0),
+      ..HeatmapOptions::default()
+    };
+    let map = sloped_mesh()
+      .cut_fill_heatmap(&triangle, 1.0, &options)
+      .unwrap();
+    assert_eq!((map.width, map.height), (100 + LEGEND_WIDTH, 64));
+    // Outside the triangle is transparent
+    assert_eq!(pixel(&map, 99, 49), [0, 0, 0, 0]);
+    // The reference elevation is reached at x = 50, where the isoline is drawn
+    assert_eq!(pixel(&map, 49, 0), ISOLINE_COLOR);
+    assert_ne!(pixel(&map, 40, 0), ISOLINE_COLOR);
+    // The legend runs from cut at the top to fill at the bottom
+    assert_eq!(pixel(&map, 106, 8), [214, 39, 40, 255]);
+    assert_eq!(pixel(&map, 106, 55), [31, 119, 180, 255]);
+
+    let options = HeatmapOptions {
+      cell_size: Some(0.01),
+      ..HeatmapOptions::default()
+    };
+    assert!(matches!(
+      sloped_mesh().cut_fill_heatmap(&reference(), 1.0, &options),
+      Err(TakeoffError::ImageExport { .. })
+    ));
+  }
+}
@@ -0,0 +1,311 @@
+//! Undo/redo history for the takeoff state.
+//!
+//! Every mutation of the state is recorded as a [`Change`] holding the entity's value
+//! before and after. Changes are grouped into [`HistoryStep`]s: one step per mutation, or
+//! one step per transaction when changes are made between [`History::begin`] and
+//! [`History::commit`].
+
+use crate::contour::ContourInput;
+use crate::group::Group;
+use crate::integrity::EntityKind;
+use crate::measurement::Measurement;
+use crate::page::Page;
+use crate::scale::Scale;
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of steps kept on the undo stack.
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// A value of any entity held by the takeoff state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntityValue {
+  Page(Page),
+  Group(Group),
+  Measurement(Measurement),
+  Scale(Scale),
+  Contour(ContourInput),
+}
+
+impl EntityValue {
+  pub fn kind(&self) -> EntityKind {
+    match self {
+      EntityValue::Page(_) => EntityKind::Page,
+      EntityValue::Group(_) => EntityKind::Group,
+      EntityValue::Measurement(_) => EntityKind::Measurement,
+      EntityValue::Scale(_) => EntityKind::Scale,
+      EntityValue::Contour(_) => EntityKind::Contour,
+    }
+  }
+}
+
+/// A single recorded mutation of one entity.
+///
+/// `before` is `None` if the entity was inserted, `after` is `None` if it was removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+  pub entity_kind: EntityKind,
+  pub entity_id: String,
+  pub before: Option<EntityValue>,
+  pub after: Option<EntityValue>,
+}
+
+impl Change {
+  pub fn new(
+    entity_kind: EntityKind,
+    entity_id: impl Into<String>,
+    before: Option<EntityValue>,
+    after: Option<EntityValue>,
+  ) -> Self {
+    Self {
+      entity_kind,
+      entity_id: entity_id.into(),
+      before,
+      after,
+    }
+  }
+
+  /// Whether applying the change leaves the entity as it was.
+  pub fn is_noop(&self) -> bool {
+    self.before == self.after
+  }
+}
+
+/// The changes undone or redone together.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct HistoryStep {
+  pub label: Option<String>,
+  /// The changes in the order they were made.
+  pub changes: Vec<Change>,
+}
+
+/// The undo and redo stacks plus the open transaction, if any.
+///
+/// Transactions nest: only the outermost [`commit`](Self::commit) closes the step.
+#[derive(Debug, Clone)]
+pub struct History {
+  undo: Vec<HistoryStep>,
+  redo: Vec<HistoryStep>,
+  pending: Option<HistoryStep>,
+  depth: usize,
+  limit: usize,
+}
+
+impl Default for History {
+  fn default() -> Self {
+    Self::new(DEFAULT_HISTORY_LIMIT)
+  }
+}
+
+impl History {
+  /// Create an empty history keeping at most `limit` undo steps.
+  pub fn new(limit: usize) -> Self {
+    Self {
+      undo: Vec::new(),
+      redo: Vec::new(),
+      pending: None,
+      depth: 0,
+      limit,
+    }
+  }
+
+  /// Open a transaction. Nested calls join the outermost transaction and keep its label.
+  pub fn begin(&mut self, label: Option<String>) {
+    if self.depth == 0 {
+      self.pending = Some(HistoryStep {
+        label,
+        changes: Vec::new(),
+      });
+    }
+    self.depth += 1;
+  }
+
+  /// Close the innermost transaction.
+  ///
+  /// # Returns
+  ///
+  /// * `false` - If no transaction was open.
+  pub fn commit(&mut self) -> bool {
+    if self.depth == 0 {
+      return false;
+    }
+    self.depth -= 1;
+    if self.depth == 0
+      && let Some(step) = self.pending.take()
+    {
+      self.push(step);
+    }
+    true
+  }
+
+  /// Close every open transaction without recording it.
+  ///
+  /// # Returns
+  ///
+  /// * `Some(step)` - The discarded step, whose changes the caller should revert.
+  pub fn discard(&mut self) -> Option<HistoryStep> {
+    self.depth = 0;
+    self.pending.take()
+  }
+
+  pub fn in_transaction(&self) -> bool {
+    self.depth > 0
+  }
+
+  /// The label of the open transaction.
+  pub fn transaction_label(&self) -> Option<String> {
+    self.pending.as_ref().and_then(|step| step.label.clone())
+  }
+
+  /// Record a change, either into the open transaction or as a step of its own.
+  pub fn record(&mut self, change: Change) {
+    if change.is_noop() {
+      return;
+    }
+    match self.pending.as_mut() {
+      Some(step) => step.changes.push(change),
+      None => self.push(HistoryStep {
+        label: None,
+        changes: vec![change],
+      }),
+    }
+  }
+
+  fn push(&mut self, step: HistoryStep) {
+    if step.changes.is_empty() {
+      return;
+    }
+    self.redo.clear();
+    self.push_undo(step);
+  }
+
+  fn push_undo(&mut self, step: HistoryStep) {
+    self.undo.push(step);
+    if self.undo.len() > self.limit {
+      self.undo.remove(0);
+    }
+  }
+
+  /// Move the latest step from the undo stack to the redo stack.
+  ///
+  /// # Returns
+  ///
+  /// * `Some(step)` - The step whose changes the caller should revert, last change first.
+  pub fn undo(&mut self) -> Option<HistoryStep> {
+    let step = self.undo.pop()?;
+    self.redo.push(step.clone());
+    Some(step)
+  }
+
+  /// Move the latest step from the redo stack back to the undo stack.
+  ///
+  /// # Returns
+  ///
+  /// * `Some(step)` - The step whose changes the caller should reapply, first change first.
+  pub fn redo(&mut self) -> Option<HistoryStep> {
+    let step = self.redo.pop()?;
+    self.push_undo(step.clone());
+    Some(step)
+  }
+
+  pub fn can_undo(&self) -> bool {
+    !self.undo.is_empty()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    !self.redo.is_empty()
+  }
+
+  pub fn undo_label(&self) -> Option<String> {
+    self.undo.last().and_then(|step| step.label.clone())
+  }
+
+  pub fn redo_label(&self) -> Option<String> {
+    self.redo.last().and_then(|step| step.label.clone())
+  }
+
+  /// Drop every step and any open transaction.
+  pub fn clear(&mut self) {
+    self.undo.clear();
+    self.redo.clear();
+    self.pending = None;
+    self.depth = 0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn page(id: &str, name: &str) -> EntityValue {
+    EntityValue::Page(Page {
+      id: id.to_string(),
+      name: Some(name.to_string()),
+      width: None,
+      height: None,
+      viewport: None,
+    })
+  }
+
+  fn rename(id: &str, from: &str, to: &str) -> Change {
+    Change::new(
+      EntityKind::Page,
+      id,
+      Some(page(id, from)),
+      Some(page(id, to)),
+    )
+  }
+
+  fn create(id: &str, name: &str) -> Change {
+    Change::new(EntityKind::Page, id, None, Some(page(id, name)))
+  }
+
+  #[test]
+  fn test_nested_transactions_form_one_step() {
+    let mut history = History::default();
+    history.begin(Some("rename".to_string()));
+    history.begin(None);
+    history.record(rename("p1", "a", "b"));
+    assert!(history.commit());
+    assert!(history.in_transaction());
+    history.record(rename("p1", "b", "c"));
+    assert!(history.commit());
+    assert!(!history.commit());
+
+    assert_eq!(history.undo_label(), Some("rename".to_string()));
+    let step = history.undo().unwrap();
+    assert_eq!(step.changes.len(), 2);
+    assert_eq!(history.redo_label(), Some("rename".to_string()));
+    assert_eq!(history.redo(), Some(step));
+  }
+
+  #[test]
+  fn test_empty_transactions_and_noops_are_not_recorded() {
+    let mut history = History::default();
+    history.begin(None);
+    history.commit();
+    history.record(rename("p1", "a", "a"));
+    assert!(!history.can_undo());
+  }
+
+  #[test]
+  fn test_new_change_clears_redo() {
+    let mut history = History::default();
+    history.record(create("p1", "a"));
+    history.undo();
+    assert!(history.can_redo());
+    history.record(create("p2", "b"));
+    assert!(!history.can_redo());
+  }
+
+  #[test]
+  fn test_limit_drops_oldest_step() {
+    let mut history = History::new(2);
+    history.record(create("p1", "a"));
+    history.record(create("p2", "b"));
+    history.record(create("p3", "c"));
+    assert_eq!(history.undo().unwrap().changes[0].entity_id, "p3");
+    assert_eq!(history.undo().unwrap().changes[0].entity_id, "p2");
+    assert!(history.undo().is_none());
+  }
+}
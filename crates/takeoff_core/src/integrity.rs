@@ -0,0 +1,217 @@
+//! Referential integrity between pages, groups, measurements, scales and contours.
+//!
+//! Measurements reference a page and a group; scales and contours reference a page.
+//! [`find_dangling_references`] reports every reference whose target does not exist.
+
+use crate::contour::ContourInput;
+use crate::error::TakeoffError;
+use crate::measurement::Measurement;
+use crate::scale::Scale;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// The kinds of entity held by the takeoff state.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum EntityKind {
+  Page,
+  Group,
+  Measurement,
+  Scale,
+  Contour,
+}
+
+impl fmt::Display for EntityKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      EntityKind::Page => "page",
+      EntityKind::Group => "group",
+      EntityKind::Measurement => "measurement",
+      EntityKind::Scale => "scale",
+      EntityKind::Contour => "contour",
+    };
+    write!(f, "{name}")
+  }
+}
+
+/// What happens to dependents when a page or group is removed.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash, Default)]
+pub enum RemovalPolicy {
+  /// Keep the behavior from before policies existed, which differs by entity: a page is
+  /// removed on its own, leaving its measurements, scales and contours dangling, while a
+  /// group is removed along with its measurements.
+  #[default]
+  Legacy,
+  /// Remove the dependents along with the page or group.
+  Cascade,
+  /// Refuse to remove a page or group that still has dependents.
+  Reject,
+}
+
+/// A reference from one entity to another entity that does not exist.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DanglingReference {
+  /// The kind of the entity holding the reference.
+  pub entity_kind: EntityKind,
+  pub entity_id: String,
+  /// The kind of the missing entity.
+  pub reference_kind: EntityKind,
+  pub reference_id: String,
+}
+
+impl From<DanglingReference> for TakeoffError {
+  fn from(reference: DanglingReference) -> Self {
+    TakeoffError::DanglingReference {
+      entity_kind: reference.entity_kind,
+      entity_id: reference.entity_id,
+      reference_kind: reference.reference_kind,
+      reference_id: reference.reference_id,
+    }
+  }
+}
+
+/// The ids of the entities that other entities may reference.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceTargets<'a> {
+  pub pages: HashSet<&'a str>,
+  pub groups: HashSet<&'a str>,
+}
+
+impl ReferenceTargets<'_> {
+  /// Find the references of a measurement whose page or group is missing.
+  pub fn check_measurement(&self, measurement: &Measurement) -> Vec<DanglingReference> {
+    let mut dangling = Vec::new();
+    if !self.pages.contains(measurement.page_id()) {
+      dangling.push(DanglingReference {
+        entity_kind: EntityKind::Measurement,
+        entity_id: measurement.id().to_string(),
+        reference_kind: EntityKind::Page,
+        reference_id: measurement.page_id().to_string(),
+      });
+    }
+    if !self.groups.contains(measurement.group_id()) {
+      dangling.push(DanglingReference {
+        entity_kind: EntityKind::Measurement,
+        entity_id: measurement.id().to_string(),
+        reference_kind: EntityKind::Group,
+        reference_id: measurement.group_id().to_string(),
+      });
+    }
+    dangling
+  }
+
+  /// Find the reference of a scale whose page is missing.
+  pub fn check_scale(&self, scale: &Scale) -> Option<DanglingReference> {
+    let page_id = scale.page_id();
+    (!self.pages.contains(page_id.as_str())).then(|| DanglingReference {
+      entity_kind: EntityKind::Scale,
+      entity_id: scale.id(),
+      reference_kind: EntityKind::Page,
+      reference_id: page_id,
+    })
+  }
+
+  /// Find the reference of a contour whose page is missing.
+  pub fn check_contour(&self, contour: &ContourInput) -> Option<DanglingReference> {
+    (!self.pages.contains(contour.page_id.as_str())).then(|| DanglingReference {
+      entity_kind: EntityKind::Contour,
+      entity_id: contour.id.clone(),
+      reference_kind: EntityKind::Page,
+      reference_id: contour.page_id.clone(),
+    })
+  }
+}
+
+/// Find every dangling reference, sorted by entity kind, entity id and reference kind.
+pub fn find_dangling_references(
+  targets: &ReferenceTargets,
+  measurements: &[Measurement],
+  scales: &[Scale],
+  contours: &[ContourInput],
+) -> Vec<DanglingReference> {
+  let mut dangling: Vec<DanglingReference> = measurements
+    .iter()
+    .flat_map(|measurement| targets.check_measurement(measurement))
+    .chain(scales.iter().filter_map(|scale| targets.check_scale(scale)))
+    .chain(
+      contours
+        .iter()
+        .filter_map(|contour| targets.check_contour(contour)),
+    )
+    .collect();
+  dangling.sort_by(|a, b| {
+    (a.entity_kind as u8, &a.entity_id, a.reference_kind as u8).cmp(&(
+      b.entity_kind as u8,
+      &b.entity_id,
+      b.reference_kind as u8,
+    ))
+  });
+  dangling
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coords::Point;
+  use crate::scale::ScaleDefinition;
+  use crate::unit::Unit;
+
+  #[test]
+  fn test_find_dangling_references() {
+    let targets = ReferenceTargets {
+      pages: HashSet::from(["p1"]),
+      groups: HashSet::from(["g1"]),
+    };
+    let measurements = vec![
+      Measurement::Count {
+        id: "m1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0),),
+        attributes: None,
+      },
+      Measurement::Count {
+        id: "m2".to_string(),
+        page_id: "p2".to_string(),
+        group_id: "g2".to_string(),
+        points: (Point::new(0.0, 0.0),),
+        attributes: None,
+      },
+    ];
+    let scales = vec![Scale::Default {
+      id: "s1".to_string(),
+      page_id: "p2".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance: 1.0,
+        real_distance: 1.0,
+        unit: Unit::Feet,
+      },
+    }];
+    let contours = vec![ContourInput {
+      id: "c1".to_string(),
+      name: None,
+      page_id: "p1".to_string(),
+      lines: vec![],
+      points_of_interest: vec![],
+    }];
+
+    let dangling = find_dangling_references(&targets, &measurements, &scales, &contours);
+    assert_eq!(dangling.len(), 3);
+    assert_eq!(dangling[0].entity_id, "m2");
+    assert_eq!(dangling[0].reference_kind, EntityKind::Page);
+    assert_eq!(dangling[1].entity_id, "m2");
+    assert_eq!(dangling[1].reference_kind, EntityKind::Group);
+    assert_eq!(dangling[1].reference_id, "g2");
+    assert_eq!(dangling[2].entity_kind, EntityKind::Scale);
+
+    let error: TakeoffError = dangling[1].clone().into();
+    assert_eq!(
+      error.to_string(),
+      "measurement m2 references missing group g2"
+    );
+  }
+}
@@ -1,13 +1,35 @@
+pub mod attribute;
 pub mod contour;
 pub mod coords;
+pub mod diff;
+pub mod dxf;
 pub mod error;
+pub mod event;
+pub mod formula;
+#[cfg(feature = "geojson")]
+pub mod geo_json;
 pub mod group;
+#[cfg(feature = "heatmap")]
+pub mod heatmap;
+pub mod history;
+pub mod integrity;
 pub mod measurement;
+pub mod offset;
+pub mod overlap;
 pub mod page;
+#[cfg(feature = "project")]
+pub mod project;
+#[cfg(feature = "report")]
+pub mod report;
 pub mod scale;
+pub mod snap;
+pub mod snapshot;
+pub mod spatial;
 pub mod state;
+pub mod svg;
 pub mod unit;
 pub mod utils;
+pub mod validity;
 pub mod volume;
 
 // Re-export error type for convenience
@@ -1,8 +1,16 @@
+use crate::attribute::AttributeValue;
 use crate::coords::{DistanceTrait, Point};
 use crate::error::{TakeoffError, TakeoffResult};
-use geo::{Area, Centroid, Coord, CoordsIter, Geometry, LineString, Polygon as GeoPolygon, Rect};
+use crate::snap::closest_point;
+use crate::utils::{generate_random_id, polygons_to_measurements};
+use geo::line_intersection::{LineIntersection, line_intersection};
+use geo::{
+  Area, BooleanOps, Centroid, Contains, Coord, CoordsIter, Geometry, Line, LineString,
+  Point as GeoPoint, Polygon as GeoPolygon, Rect,
+};
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 
 #[napi(discriminant = "type")]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -12,24 +20,36 @@ pub enum Measurement {
     page_id: String,
     group_id: String,
     points: (Point,),
+    /// Custom attributes. Keys set here override those inherited from the group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attributes: Option<BTreeMap<String, AttributeValue>>,
   },
   Polygon {
     id: String,
     page_id: String,
     group_id: String,
     points: Vec<Point>,
+    /// Custom attributes. Keys set here override those inherited from the group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attributes: Option<BTreeMap<String, AttributeValue>>,
   },
   Polyline {
     id: String,
     page_id: String,
     group_id: String,
     points: Vec<Point>,
+    /// Custom attributes. Keys set here override those inherited from the group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attributes: Option<BTreeMap<String, AttributeValue>>,
   },
   Rectangle {
     id: String,
     page_id: String,
     group_id: String,
     points: (Point, Point),
+    /// Custom attributes. Keys set here override those inherited from the group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attributes: Option<BTreeMap<String, AttributeValue>>,
   },
 }
 
@@ -102,6 +122,37 @@ impl Measurement {
       Measurement::Rectangle { group_id, .. } => group_id,
     }
   }
+  /// Get the kind of the measurement (its `type` discriminant)
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Measurement::Count { .. } => "Count",
+      Measurement::Polygon { .. } => "Polygon",
+      Measurement::Polyline { .. } => "Polyline",
+      Measurement::Rectangle { .. } => "Rectangle",
+    }
+  }
+  /// Get the custom attributes set on the measurement itself
+  pub fn attributes(&self) -> Option<&BTreeMap<String, AttributeValue>> {
+    match self {
+      Measurement::Count { attributes, .. } => attributes.as_ref(),
+      Measurement::Polygon { attributes, .. } => attributes.as_ref(),
+      Measurement::Polyline { attributes, .. } => attributes.as_ref(),
+      Measurement::Rectangle { attributes, .. } => attributes.as_ref(),
+    }
+  }
+  /// Get a single custom attribute set on the measurement itself
+  pub fn attribute(&self, key: &str) -> Option<&AttributeValue> {
+    self.attributes().and_then(|attributes| attributes.get(key))
+  }
+  /// Replace the custom attributes of the measurement
+  pub fn set_attributes(&mut self, value: Option<BTreeMap<String, AttributeValue>>) {
+    match self {
+      Measurement::Count { attributes, .. } => *attributes = value,
+      Measurement::Polygon { attributes, .. } => *attributes = value,
+      Measurement::Polyline { attributes, .. } => *attributes = value,
+      Measurement::Rectangle { attributes, .. } => *attributes = value,
+    }
+  }
 
   /// Convert the measurement to a polygon.
   ///
@@ -239,44 +290,52 @@ impl Measurement {
         page_id,
         group_id,
         points: (_,),
+        attributes,
       } => Measurement::Count {
         id,
         page_id,
         group_id,
         points: (new_centroid,),
+        attributes,
       },
       Measurement::Polygon {
         id,
         page_id,
         group_id,
         points,
+        attributes,
       } => Measurement::Polygon {
         id,
         page_id,
         group_id,
         points: points.into_iter().map(translate).collect(),
+        attributes,
       },
       Measurement::Polyline {
         id,
         page_id,
         group_id,
         points,
+        attributes,
       } => Measurement::Polyline {
         id,
         page_id,
         group_id,
         points: points.into_iter().map(translate).collect(),
+        attributes,
       },
       Measurement::Rectangle {
         id,
         page_id,
         group_id,
         points: (p1, p2),
+        attributes,
       } => Measurement::Rectangle {
         id,
         page_id,
         group_id,
         points: (translate(p1), translate(p2)),
+        attributes,
       },
     })
   }
@@ -296,12 +355,21 @@ impl Measurement {
     self.validate()?;
     match self {
       Measurement::Polygon { points, .. } => {
-        let mut perimeter = 0.0;
-        for i in 0..points.len() {
-          let j = (i + 1) % points.len();
-          perimeter += points[i].distance_to(&points[j]);
-        }
-        Ok(perimeter)
+        // A keyhole cut joining a hole to the outline runs along the same edge in both
+        // directions. It is not part of the boundary, so both passes are left out.
+        let key =
+          |a: &Point, b: &Point| (a.x.to_bits(), a.y.to_bits(), b.x.to_bits(), b.y.to_bits());
+        let edges: Vec<(&Point, &Point)> = (0..points.len())
+          .map(|i| (&points[i], &points[(i + 1) % points.len()]))
+          .collect();
+        let keys: HashSet<_> = edges.iter().map(|(a, b)| key(a, b)).collect();
+        Ok(
+          edges
+            .iter()
+            .filter(|(a, b)| !keys.contains(&key(b, a)))
+            .map(|(a, b)| a.distance_to(b))
+            .sum(),
+        )
       }
       Measurement::Rectangle { .. } => {
         let polygon = self.to_polygon()?;
@@ -328,6 +396,340 @@ impl Measurement {
       Measurement::Count { .. } => Ok(0.0),
     }
   }
+
+  /// Split a polygon or rectangle along a cut line.
+  ///
+  /// The cut is a polyline drawn across the outline. Every stretch of it that runs through
+  /// the area from one crossing of the outline to the next divides the piece it runs
+  /// through, so a cut that weaves in and out can give more than two pieces. Each piece is a
+  /// new polygon measurement with a random id and the page, group and attributes of this one.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::GeometryOperation`] if the measurement is not a polygon or
+  /// rectangle or the cut does not run across it, and [`TakeoffError::EmptyGeometry`] if the
+  /// geometry is invalid.
+  pub fn split_by_line(&self, cut: &[Point]) -> TakeoffResult<Vec<Measurement>> {
+    if !matches!(
+      self,
+      Measurement::Polygon { .. } | Measurement::Rectangle { .. }
+    ) {
+      return Err(TakeoffError::geometry_operation(
+        "split",
+        format!(
+          "measurement {} is a {}, not an area",
+          self.id(),
+          self.kind()
+        ),
+      ));
+    }
+    let mut ring = self.to_polygon()?.exterior().0.clone();
+    ring.pop();
+    ring.dedup();
+    let mut cut: Vec<Coord<f64>> = cut.iter().map(|p| (*p).into()).collect();
+    cut.dedup();
+
+    let pieces: Vec<Measurement> = split_ring(ring, &cut)
+      .into_iter()
+      .map(|ring| self.derived_polygon(ring.into_iter().map(Point::from).collect()))
+      .filter(|piece| piece.pixel_area().is_ok_and(|area| area > 0.0))
+      .collect();
+    if pieces.len() < 2 {
+      return Err(TakeoffError::geometry_operation(
+        "split",
+        format!("the cut line does not run across measurement {}", self.id()),
+      ));
+    }
+    Ok(pieces)
+  }
+
+  /// Split a polyline in two at the point on it nearest to `point`.
+  ///
+  /// Both halves are new polyline measurements with random ids and the page, group and
+  /// attributes of this one.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::GeometryOperation`] if the measurement is not a polyline or the
+  /// nearest point is one of its ends, and [`TakeoffError::EmptyGeometry`] if the geometry is
+  /// invalid.
+  pub fn split_at(&self, point: Point) -> TakeoffResult<(Measurement, Measurement)> {
+    let Measurement::Polyline { points, .. } = self else {
+      return Err(TakeoffError::geometry_operation(
+        "split",
+        format!(
+          "measurement {} is a {}, not a polyline",
+          self.id(),
+          self.kind()
+        ),
+      ));
+    };
+    self.validate()?;
+    let (segment, at) = points
+      .windows(2)
+      .map(|pair| closest_point(point.into(), &Line::new(pair[0], pair[1])))
+      .enumerate()
+      .min_by(|(_, a), (_, b)| a.distance_to(&point).total_cmp(&b.distance_to(&point)))
+      .expect("BUG: a valid polyline has a segment");
+
+    let mut first = points[..=segment].to_vec();
+    first.push(at);
+    first.dedup();
+    let mut second = vec![at];
+    second.extend_from_slice(&points[segment + 1..]);
+    second.dedup();
+    if first.len() < 2 || second.len() < 2 {
+      return Err(TakeoffError::geometry_operation(
+        "split",
+        format!("the split point is at an end of polyline {}", self.id()),
+      ));
+    }
+    Ok((self.derived_polyline(first), self.derived_polyline(second)))
+  }
+
+  /// Merge this measurement with an adjacent one on the same page.
+  ///
+  /// Polylines are joined where an end of one meets an end of the other, and the result runs
+  /// in the direction of this polyline. Polygons and rectangles are dissolved into a single
+  /// polygon when their outlines share an edge. The result is a new measurement with a random
+  /// id and the page, group and attributes of this one.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::GeometryOperation`] if the measurements are on different pages,
+  /// are not both polylines or both areas, or do not meet, and
+  /// [`TakeoffError::EmptyGeometry`] if either geometry is invalid.
+  pub fn merge(&self, other: &Measurement) -> TakeoffResult<Measurement> {
+    let fail = |message: String| TakeoffError::geometry_operation("merge", message);
+    if self.page_id() != other.page_id() {
+      return Err(fail(format!(
+        "measurements {} and {} are on different pages",
+        self.id(),
+        other.id()
+      )));
+    }
+    self.validate()?;
+    other.validate()?;
+    match (self, other) {
+      (Measurement::Polyline { points: a, .. }, Measurement::Polyline { points: b, .. }) => {
+        let meets = |p: &Point, q: &Point| p.distance_to(q) <= COINCIDENT_DISTANCE;
+        let (a_first, a_last) = (&a[0], &a[a.len() - 1]);
+        let (b_first, b_last) = (&b[0], &b[b.len() - 1]);
+        let reversed = || b.iter().rev().copied();
+        let points: Vec<Point> = if meets(a_last, b_first) {
+          a.iter().copied().chain(b.iter().skip(1).copied()).collect()
+        } else if meets(a_last, b_last) {
+          a.iter().copied().chain(reversed().skip(1)).collect()
+        } else if meets(a_first, b_last) {
+          b.iter().copied().chain(a.iter().skip(1).copied()).collect()
+        } else if meets(a_first, b_first) {
+          reversed().chain(a.iter().skip(1).copied()).collect()
+        } else {
+          return Err(fail(format!(
+            "polylines {} and {} do not share an end",
+            self.id(),
+            other.id()
+          )));
+        };
+        Ok(self.derived_polyline(points))
+      }
+      (
+        Measurement::Polygon { .. } | Measurement::Rectangle { .. },
+        Measurement::Polygon { .. } | Measurement::Rectangle { .. },
+      ) => {
+        let (a, b) = (self.to_polygon()?, other.to_polygon()?);
+        if !shares_edge(a.exterior(), b.exterior()) {
+          return Err(fail(format!(
+            "measurements {} and {} do not share an edge",
+            self.id(),
+            other.id()
+          )));
+        }
+        let mut merged = polygons_to_measurements(&a.union(&b), self);
+        if merged.len() != 1 {
+          return Err(fail(format!(
+            "measurements {} and {} do not merge into one polygon",
+            self.id(),
+            other.id()
+          )));
+        }
+        Ok(merged.remove(0))
+      }
+      _ => Err(fail(format!(
+        "cannot merge a {} with a {}",
+        self.kind(),
+        other.kind()
+      ))),
+    }
+  }
+
+  /// A new polygon with a random id and the page, group and attributes of this measurement.
+  fn derived_polygon(&self, points: Vec<Point>) -> Measurement {
+    Measurement::Polygon {
+      id: generate_random_id(),
+      page_id: self.page_id().to_string(),
+      group_id: self.group_id().to_string(),
+      points,
+      attributes: self.attributes().cloned(),
+    }
+  }
+
+  /// A new polyline with a random id and the page, group and attributes of this measurement.
+  fn derived_polyline(&self, points: Vec<Point>) -> Measurement {
+    Measurement::Polyline {
+      id: generate_random_id(),
+      page_id: self.page_id().to_string(),
+      group_id: self.group_id().to_string(),
+      points,
+      attributes: self.attributes().cloned(),
+    }
+  }
+}
+
+/// The distance in page pixels below which two points are treated as the same.
+const COINCIDENT_DISTANCE: f64 = 1e-9;
+
+/// Where a cut line crosses a polygon outline.
+#[derive(Debug, Clone, Copy)]
+struct Crossing {
+  point: Coord<f64>,
+  /// The cut segment and the position along it, from 0 to 1.
+  segment: usize,
+  along: f64,
+  /// The outline edge and the position along it, from 0 to 1.
+  edge: usize,
+  along_edge: f64,
+}
+
+/// The position of a point along a line, from 0 at its start to 1 at its end.
+fn position_along(line: &Line<f64>, point: Coord<f64>) -> f64 {
+  let delta = line.delta();
+  ((point.x - line.start.x) * delta.x + (point.y - line.start.y) * delta.y)
+    / (delta.x * delta.x + delta.y * delta.y)
+}
+
+/// The point halfway along a path.
+fn path_midpoint(path: &[Coord<f64>]) -> Coord<f64> {
+  let lines: Vec<Line<f64>> = path.windows(2).map(|p| Line::new(p[0], p[1])).collect();
+  let mut remaining = lines
+    .iter()
+    .map(|line| line.delta().x.hypot(line.delta().y))
+    .sum::<f64>()
+    / 2.0;
+  for line in &lines {
+    let length = line.delta().x.hypot(line.delta().y);
+    if remaining <= length && length > 0.0 {
+      return line.start + line.delta() * (remaining / length);
+    }
+    remaining -= length;
+  }
+  path[0]
+}
+
+/// Find the first stretch of the cut that runs through the outline from one crossing to the
+/// next.
+///
+/// Returns the crossings at either end and the path of the cut between them.
+fn find_chord(
+  ring: &[Coord<f64>],
+  cut: &[Coord<f64>],
+) -> Option<(Crossing, Crossing, Vec<Coord<f64>>)> {
+  let polygon = GeoPolygon::new(LineString::from(ring.to_vec()), vec![]);
+  let mut crossings = Vec::new();
+  for (segment, pair) in cut.windows(2).enumerate() {
+    let line = Line::new(pair[0], pair[1]);
+    for edge in 0..ring.len() {
+      let edge_line = Line::new(ring[edge], ring[(edge + 1) % ring.len()]);
+      if let Some(LineIntersection::SinglePoint { intersection, .. }) =
+        line_intersection(line, edge_line)
+      {
+        crossings.push(Crossing {
+          point: intersection,
+          segment,
+          along: position_along(&line, intersection),
+          edge,
+          along_edge: position_along(&edge_line, intersection),
+        });
+      }
+    }
+  }
+  crossings.sort_by(|a, b| {
+    a.segment
+      .cmp(&b.segment)
+      .then_with(|| a.along.total_cmp(&b.along))
+  });
+  // A cut through a vertex crosses both of its edges
+  crossings.dedup_by(|b, a| {
+    Point::from(a.point).distance_to(&Point::from(b.point)) <= COINCIDENT_DISTANCE
+  });
+
+  crossings.windows(2).find_map(|pair| {
+    let (start, end) = (pair[0], pair[1]);
+    let mut path = vec![start.point];
+    path.extend_from_slice(&cut[start.segment + 1..=end.segment]);
+    path.push(end.point);
+    path.dedup();
+    polygon
+      .contains(&GeoPoint::from(path_midpoint(&path)))
+      .then_some((start, end, path))
+  })
+}
+
+/// Split an outline, without its closing point, along each stretch of the cut through it.
+fn split_ring(ring: Vec<Coord<f64>>, cut: &[Coord<f64>]) -> Vec<Vec<Coord<f64>>> {
+  let Some((first, second, chord)) = find_chord(&ring, cut) else {
+    return vec![ring];
+  };
+  let mut rest = vec![second.point];
+  rest.extend_from_slice(&cut[second.segment + 1..]);
+
+  // Walk the outline from the crossing it reaches first
+  let first_along_outline = (first.edge, first.along_edge) <= (second.edge, second.along_edge);
+  let (start, end, chord) = if first_along_outline {
+    (first, second, chord)
+  } else {
+    (second, first, chord.into_iter().rev().collect())
+  };
+  let interior = &chord[1..chord.len() - 1];
+
+  let mut inner = vec![start.point];
+  inner.extend_from_slice(&ring[start.edge + 1..=end.edge]);
+  inner.push(end.point);
+  inner.extend(interior.iter().rev());
+  let mut outer = vec![end.point];
+  outer.extend_from_slice(&ring[end.edge + 1..]);
+  outer.extend_from_slice(&ring[..=start.edge]);
+  outer.push(start.point);
+  outer.extend_from_slice(interior);
+
+  [inner, outer]
+    .into_iter()
+    .flat_map(|mut piece| {
+      piece.dedup();
+      if piece.len() > 1 && piece.first() == piece.last() {
+        piece.pop();
+      }
+      if rest.len() < 2 {
+        vec![piece]
+      } else {
+        split_ring(piece, &rest)
+      }
+    })
+    .collect()
+}
+
+/// Whether two outlines overlap along a stretch of non-zero length.
+fn shares_edge(a: &LineString<f64>, b: &LineString<f64>) -> bool {
+  a.lines().any(|a| {
+    b.lines().any(|b| {
+      matches!(
+        line_intersection(a, b),
+        Some(LineIntersection::Collinear { intersection })
+          if Point::from(intersection.start).distance_to(&Point::from(intersection.end))
+            > COINCIDENT_DISTANCE
+      )
+    })
+  })
 }
 
 #[cfg(test)]
@@ -343,6 +745,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+      attributes: None,
     };
     let area_before = m.pixel_area().unwrap();
     let perimeter_before = m.pixel_perimeter().unwrap();
@@ -370,6 +773,7 @@ mod tests {
         Point::new(10.0, 10.0),
         Point::new(0.0, 10.0),
       ],
+      attributes: None,
     };
     let area_before = m.pixel_area().unwrap();
     let perimeter_before = m.pixel_perimeter().unwrap();
@@ -391,6 +795,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0), Point::new(4.0, 0.0)],
+      attributes: None,
     };
     let length_before = m.pixel_perimeter().unwrap();
     let new_centroid = Point::new(100.0, 200.0);
@@ -410,6 +815,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(7.0, 8.0),),
+      attributes: None,
     };
     let new_centroid = Point::new(1.0, 2.0);
     let repositioned = m.with_centroid_at(new_centroid).unwrap();
@@ -433,6 +839,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+      attributes: None,
     };
     assert!(matches!(
       m.with_centroid_at(Point::new(0.0, 0.0)),
@@ -447,6 +854,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0)],
+      attributes: None,
     };
     assert!(matches!(
       m.with_centroid_at(Point::new(0.0, 0.0)),
@@ -461,6 +869,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(0.0, 0.0)),
+      attributes: None,
     };
     assert!(matches!(
       m.with_centroid_at(Point::new(0.0, 0.0)),
@@ -475,6 +884,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+      attributes: None,
     };
     assert!(measurement.pixel_area().unwrap() == 5000.0);
   }
@@ -485,6 +895,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+      attributes: None,
     };
     assert!(measurement.pixel_perimeter().unwrap() == 300.0);
   }
@@ -496,6 +907,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+      attributes: None,
     };
     assert!(measurement.pixel_perimeter().unwrap() == 1.0);
   }
@@ -507,6 +919,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)], // Only 2 points
+      attributes: None,
     };
     assert!(matches!(
       measurement.validate(),
@@ -525,6 +938,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0)], // Only 1 point
+      attributes: None,
     };
     assert!(matches!(
       measurement.validate(),
@@ -539,10 +953,193 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(0.0, 0.0)), // Same point
+      attributes: None,
     };
     assert!(matches!(
       measurement.validate(),
       Err(crate::error::TakeoffError::EmptyGeometry { .. })
     ));
   }
+
+  fn polygon(id: &str, points: &[(f64, f64)]) -> Measurement {
+    Measurement::Polygon {
+      id: id.to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points: points.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+      attributes: None,
+    }
+  }
+
+  fn polyline(id: &str, points: &[(f64, f64)]) -> Measurement {
+    Measurement::Polyline {
+      id: id.to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points: points.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+      attributes: None,
+    }
+  }
+
+  fn points(measurement: &Measurement) -> Vec<Point> {
+    match measurement {
+      Measurement::Polygon { points, .. } | Measurement::Polyline { points, .. } => points.clone(),
+      _ => panic!("expected a polygon or polyline"),
+    }
+  }
+
+  #[test]
+  fn test_split_by_line() {
+    let rectangle = Measurement::Rectangle {
+      id: "1".to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+      attributes: None,
+    };
+    let pieces = rectangle
+      .split_by_line(&[Point::new(40.0, -10.0), Point::new(40.0, 60.0)])
+      .unwrap();
+    let mut areas: Vec<f64> = pieces.iter().map(|p| p.pixel_area().unwrap()).collect();
+    areas.sort_by(f64::total_cmp);
+    assert_eq!(areas, vec![2000.0, 3000.0]);
+    assert!(
+      pieces
+        .iter()
+        .all(|p| p.kind() == "Polygon" && p.id() != "1")
+    );
+    assert!(
+      pieces
+        .iter()
+        .all(|p| p.page_id() == "1" && p.group_id() == "1")
+    );
+
+    // A bent cut divides the area along its whole path
+    let zigzag = [
+      Point::new(20.0, -10.0),
+      Point::new(20.0, 25.0),
+      Point::new(80.0, 25.0),
+      Point::new(80.0, 60.0),
+    ];
+    let pieces = rectangle.split_by_line(&zigzag).unwrap();
+    assert_eq!(pieces.len(), 2);
+    let total: f64 = pieces.iter().map(|p| p.pixel_area().unwrap()).sum();
+    assert!((total - 5000.0).abs() < 1e-9);
+
+    // A U-shaped room cut straight across both arms
+    let u_shape = polygon(
+      "u",
+      &[
+        (0.0, 0.0),
+        (30.0, 0.0),
+        (30.0, 70.0),
+        (70.0, 70.0),
+        (70.0, 0.0),
+        (100.0, 0.0),
+        (100.0, 100.0),
+        (0.0, 100.0),
+      ],
+    );
+    let pieces = u_shape
+      .split_by_line(&[Point::new(-10.0, 50.0), Point::new(110.0, 50.0)])
+      .unwrap();
+    assert_eq!(pieces.len(), 3);
+    let total: f64 = pieces.iter().map(|p| p.pixel_area().unwrap()).sum();
+    assert!((total - u_shape.pixel_area().unwrap()).abs() < 1e-9);
+
+    // A cut that stops inside, or misses, does not split
+    assert!(matches!(
+      rectangle.split_by_line(&[Point::new(40.0, -10.0), Point::new(40.0, 20.0)]),
+      Err(TakeoffError::GeometryOperation { .. })
+    ));
+    assert!(
+      rectangle
+        .split_by_line(&[Point::new(200.0, 0.0), Point::new(200.0, 50.0)])
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn test_split_at() {
+    let line = polyline("l", &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+    let (first, second) = line.split_at(Point::new(4.0, 1.0)).unwrap();
+    assert_eq!(
+      points(&first),
+      vec![Point::new(0.0, 0.0), Point::new(4.0, 0.0)]
+    );
+    assert_eq!(
+      points(&second),
+      vec![
+        Point::new(4.0, 0.0),
+        Point::new(10.0, 0.0),
+        Point::new(10.0, 10.0)
+      ]
+    );
+    assert_ne!(first.id(), second.id());
+
+    // At a vertex, the vertex ends the first half and starts the second
+    let (first, second) = line.split_at(Point::new(10.0, 0.0)).unwrap();
+    assert_eq!(points(&first).len(), 2);
+    assert_eq!(points(&second).len(), 2);
+
+    assert!(line.split_at(Point::new(-5.0, 0.0)).is_err());
+    assert!(
+      polygon("a", &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)])
+        .split_at(Point::new(0.5, 0.0))
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn test_merge_polylines() {
+    let a = polyline("a", &[(0.0, 0.0), (10.0, 0.0)]);
+    let b = polyline("b", &[(20.0, 0.0), (10.0, 0.0)]);
+    let merged = a.merge(&b).unwrap();
+    assert_eq!(
+      points(&merged),
+      vec![
+        Point::new(0.0, 0.0),
+        Point::new(10.0, 0.0),
+        Point::new(20.0, 0.0)
+      ]
+    );
+    assert_eq!(merged.pixel_perimeter().unwrap(), 20.0);
+    // Merging from the other side keeps that polyline's direction
+    assert_eq!(points(&b.merge(&a).unwrap())[0], Point::new(20.0, 0.0));
+
+    let apart = polyline("c", &[(30.0, 0.0), (40.0, 0.0)]);
+    assert!(matches!(
+      a.merge(&apart),
+      Err(TakeoffError::GeometryOperation { .. })
+    ));
+  }
+
+  #[test]
+  fn test_merge_areas() {
+    let left = polygon("a", &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+    // Shares half of the left room's right edge
+    let right = Measurement::Rectangle {
+      id: "b".to_string(),
+      page_id: "1".to_string(),
+      group_id: "2".to_string(),
+      points: (Point::new(10.0, 5.0), Point::new(20.0, 15.0)),
+      attributes: None,
+    };
+    let merged = left.merge(&right).unwrap();
+    assert_eq!(merged.kind(), "Polygon");
+    assert_eq!(merged.group_id(), "1");
+    assert!((merged.pixel_area().unwrap() - 200.0).abs() < 1e-9);
+
+    // Touching at a corner only
+    let corner = polygon(
+      "c",
+      &[(10.0, 10.0), (20.0, 10.0), (20.0, 20.0), (10.0, 20.0)],
+    );
+    assert!(left.merge(&corner).is_err());
+    assert!(
+      left
+        .merge(&polyline("l", &[(0.0, 0.0), (10.0, 0.0)]))
+        .is_err()
+    );
+  }
 }
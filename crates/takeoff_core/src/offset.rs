@@ -0,0 +1,484 @@
+//! Offsetting of measurements by a real-world distance.
+//!
+//! Areas are buffered outwards or inwards, which may split them into several polygons or
+//! remove them entirely. Polylines are moved sideways to a parallel polyline, joining the
+//! offset edges around each bend.
+
+use crate::coords::Point;
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::measurement::Measurement;
+use crate::scale::Scale;
+use crate::unit::Unit;
+use crate::utils::{generate_random_id, polygons_to_measurements};
+use geo::algorithm::buffer::{Buffer, BufferStyle, LineJoin};
+use geo::orient::Direction;
+use geo::{BooleanOps, Coord, LineString, MultiPolygon, Orient, Polygon};
+use napi::bindgen_prelude::Result;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+
+/// The sharpest corner, in degrees, that a miter join keeps pointed.
+pub const MIN_MITER_ANGLE: f64 = 30.0;
+
+/// The angle in radians between the segments approximating a round join.
+const ROUND_JOIN_STEP: f64 = 0.2;
+
+/// How offset edges are joined around a convex corner.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OffsetJoin {
+  /// Extends the edges to a point. Corners sharper than [`MIN_MITER_ANGLE`] are cut off.
+  #[default]
+  Miter,
+  /// Rounds the corner with an arc centered on the original vertex.
+  Round,
+  /// Extends each offset edge by the offset distance past the corner and joins the ends
+  /// with a straight segment. Corners of 90 degrees or less meet before that, as with a
+  /// miter.
+  Square,
+  /// Cuts the corner off flat, joining the ends of the offset edges with a straight
+  /// segment.
+  Bevel,
+}
+
+/// Offset a polygon, rectangle or polyline measurement by a real-world distance.
+///
+/// A positive distance grows areas and a negative one shrinks them; an area that shrinks
+/// away entirely gives no measurements, and one pinched in two gives several polygons. A
+/// positive distance moves a polyline to the right of the direction it was drawn in, as seen
+/// on the page, and a negative one to the left. Very large polyline offsets around tight
+/// bends may cross themselves.
+///
+/// Each result is a new measurement with a random id and the page, group and attributes of
+/// the original.
+///
+/// # Arguments
+///
+/// * `measurement` - The measurement to offset.
+/// * `scale` - The scale of the measurement, used to convert the distance to page pixels.
+/// * `distance` - The offset distance in `unit`.
+/// * `unit` - The unit of the distance.
+/// * `join` - How edges are joined around corners. Defaults to [`OffsetJoin::Miter`].
+///
+/// # Errors
+///
+/// Returns [`TakeoffError::GeometryOperation`] if the measurement is a count or the distance
+/// is not finite, [`TakeoffError::EmptyGeometry`] if the measurement has invalid geometry,
+/// and a scale error if the scale is invalid.
+#[napi]
+pub fn offset_measurement(
+  measurement: Measurement,
+  scale: Scale,
+  distance: f64,
+  unit: Unit,
+  join: Option<OffsetJoin>,
+) -> Result<Vec<Measurement>> {
+  let pixels = pixel_distance(distance, unit, &scale)?;
+  offset_by_pixels(&measurement, pixels, join.unwrap_or_default()).map_err(Into::into)
+}
+
+/// Convert a real-world distance to page pixels.
+pub fn pixel_distance(distance: f64, unit: Unit, scale: &Scale) -> TakeoffResult<f64> {
+  let meters = distance * unit.meters_per_unit();
+  Ok(meters / scale.get_unit().meters_per_unit() * scale.ratio()?)
+}
+
+/// Offset a polygon, rectangle or polyline measurement by a distance in page pixels.
+///
+/// See [`offset_measurement`].
+pub fn offset_by_pixels(
+  measurement: &Measurement,
+  distance: f64,
+  join: OffsetJoin,
+) -> TakeoffResult<Vec<Measurement>> {
+  if !distance.is_finite() {
+    return Err(TakeoffError::geometry_operation(
+      "offset",
+      format!("distance {distance} is not finite"),
+    ));
+  }
+  match measurement {
+    Measurement::Polygon { .. } | Measurement::Rectangle { .. } => {
+      let polygon = measurement.to_polygon()?;
+      let line_join = match join {
+        OffsetJoin::Miter => LineJoin::Miter(MIN_MITER_ANGLE.to_radians()),
+        OffsetJoin::Round => LineJoin::Round(ROUND_JOIN_STEP),
+        // Square corners are added to, or cut from, the beveled outline below
+        OffsetJoin::Square | OffsetJoin::Bevel => LineJoin::Bevel,
+      };
+      let mut buffered = polygon.buffer_with_style(BufferStyle::new(distance).line_join(line_join));
+      if join == OffsetJoin::Square {
+        let corners = square_corners(&polygon, distance);
+        buffered = if distance > 0.0 {
+          buffered.union(&corners)
+        } else {
+          buffered.difference(&corners)
+        };
+      }
+      Ok(polygons_to_measurements(&buffered, measurement))
+    }
+    Measurement::Polyline {
+      page_id,
+      group_id,
+      attributes,
+      ..
+    } => Ok(vec![Measurement::Polyline {
+      id: generate_random_id(),
+      page_id: page_id.clone(),
+      group_id: group_id.clone(),
+      points: offset_polyline(measurement, distance, join)?,
+      attributes: attributes.clone(),
+    }]),
+    Measurement::Count { id, .. } => Err(TakeoffError::geometry_operation(
+      "offset",
+      format!("measurement {id} is a count"),
+    )),
+  }
+}
+
+/// The regions between the beveled and the square joins of a polygon offset.
+///
+/// Each region spans from an original vertex out to the square join on the outer side of
+/// its corner, so it lies outside the polygon when growing and inside when shrinking.
+fn square_corners(polygon: &Polygon<f64>, distance: f64) -> MultiPolygon<f64> {
+  // With the default orientation the polygon lies to the left of every ring
+  let polygon = polygon.orient(Direction::Default);
+  let mut corners = Vec::new();
+  for ring in std::iter::once(polygon.exterior()).chain(polygon.interiors()) {
+    let mut coords: Vec<Coord<f64>> = ring.coords().copied().collect();
+    coords.dedup();
+    if coords.len() > 1 && coords.first() == coords.last() {
+      coords.pop();
+    }
+    let n = coords.len();
+    if n < 3 {
+      continue;
+    }
+    for i in 0..n {
+      let vertex = coords[i];
+      let unit = |delta: Coord<f64>| delta / delta.x.hypot(delta.y);
+      let d0 = unit(vertex - coords[(i + n - 1) % n]);
+      let d1 = unit(coords[(i + 1) % n] - vertex);
+      let cross = d0.x * d1.y - d0.y * d1.x;
+      // Only corners turning away from the offset side need a join
+      if cross.abs() < 1e-12 || cross * distance <= 0.0 {
+        continue;
+      }
+      let dot = d0.x * d1.x + d0.y * d1.y;
+      let normal = |d: Coord<f64>| Coord { x: d.y, y: -d.x } * distance;
+      let (end0, start1) = (vertex + normal(d0), vertex + normal(d1));
+      let mut corner = vec![vertex, end0];
+      corner.extend(square_join(
+        end0,
+        d0,
+        start1,
+        d1,
+        distance.abs(),
+        cross,
+        dot,
+      ));
+      corner.push(start1);
+      corners.push(Polygon::new(LineString::from(corner), vec![]));
+    }
+  }
+  MultiPolygon(corners)
+}
+
+/// The corner points of a square join between an offset edge ending at `end0` in direction
+/// `d0` and the next one starting at `start1` in direction `d1`.
+///
+/// Each edge is extended by `distance` past the corner, unless the extended edges cross
+/// first, in which case the join is their crossing.
+fn square_join(
+  end0: Coord<f64>,
+  d0: Coord<f64>,
+  start1: Coord<f64>,
+  d1: Coord<f64>,
+  distance: f64,
+  cross: f64,
+  dot: f64,
+) -> Vec<Coord<f64>> {
+  if dot >= 0.0 && cross.abs() >= 1e-12 {
+    let t = ((start1.x - end0.x) * d1.y - (start1.y - end0.y) * d1.x) / cross;
+    vec![end0 + d0 * t]
+  } else {
+    vec![end0 + d0 * distance, start1 - d1 * distance]
+  }
+}
+
+/// Move each edge of a polyline sideways and join the moved edges around each bend.
+fn offset_polyline(
+  measurement: &Measurement,
+  distance: f64,
+  join: OffsetJoin,
+) -> TakeoffResult<Vec<Point>> {
+  let line = measurement.to_line_string()?;
+  let mut coords: Vec<Coord<f64>> = line.into_inner();
+  coords.dedup();
+  if coords.len() < 2 {
+    return Err(TakeoffError::empty_geometry(
+      "polyline has no segment of non-zero length",
+    ));
+  }
+
+  // Unit direction and offset start and end of each edge
+  let edges: Vec<(Coord<f64>, Coord<f64>, Coord<f64>)> = coords
+    .windows(2)
+    .map(|pair| {
+      let delta = pair[1] - pair[0];
+      let direction = delta / delta.x.hypot(delta.y);
+      let normal = Coord {
+        x: -direction.y,
+        y: direction.x,
+      } * distance;
+      (direction, pair[0] + normal, pair[1] + normal)
+    })
+    .collect();
+
+  let mut points = vec![Point::from(edges[0].1)];
+  for (i, pair) in edges.windows(2).enumerate() {
+    let vertex = coords[i + 1];
+    let ((d0, start0, end0), (d1, start1, _)) = (pair[0], pair[1]);
+    let cross = d0.x * d1.y - d0.y * d1.x;
+    let dot = d0.x * d1.x + d0.y * d1.y;
+    let reversed = cross.abs() < 1e-12 && dot < 0.0;
+    if cross.abs() < 1e-12 && !reversed {
+      points.push(end0.into());
+      continue;
+    }
+    // Where the two offset edges, extended, cross
+    let crossing = || {
+      let t = ((start1.x - start0.x) * d1.y - (start1.y - start0.y) * d1.x) / cross;
+      Point::from(start0 + d0 * t)
+    };
+    let outer = reversed || cross * distance < 0.0;
+    if !outer {
+      points.push(crossing());
+      continue;
+    }
+    match join {
+      OffsetJoin::Miter if !reversed && dot.acos() <= (180.0 - MIN_MITER_ANGLE).to_radians() => {
+        points.push(crossing());
+      }
+      OffsetJoin::Round => {
+        let sweep = if reversed {
+          -std::f64::consts::PI * distance.signum()
+        } else {
+          cross.atan2(dot)
+        };
+        let start = (end0.y - vertex.y).atan2(end0.x - vertex.x);
+        let steps = (sweep.abs() / ROUND_JOIN_STEP).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+          let angle = start + sweep * step as f64 / steps as f64;
+          points.push(Point::new(
+            vertex.x + distance.abs() * angle.cos(),
+            vertex.y + distance.abs() * angle.sin(),
+          ));
+        }
+      }
+      OffsetJoin::Square => {
+        let corner = square_join(end0, d0, start1, d1, distance.abs(), cross, dot);
+        points.extend(corner.into_iter().map(Point::from));
+      }
+      _ => {
+        points.push(end0.into());
+        points.push(start1.into());
+      }
+    }
+  }
+  points.push(edges[edges.len() - 1].2.into());
+  Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::scale::ScaleDefinition;
+
+  fn polyline(points: Vec<Point>) -> Measurement {
+    Measurement::Polyline {
+      id: "l1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points,
+      attributes: None,
+    }
+  }
+
+  fn rectangle() -> Measurement {
+    Measurement::Rectangle {
+      id: "r1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+      attributes: None,
+    }
+  }
+
+  fn points(measurement: &Measurement) -> Vec<Point> {
+    match measurement {
+      Measurement::Polyline { points, .. } | Measurement::Polygon { points, .. } => points.clone(),
+      _ => panic!("expected a polyline or polygon"),
+    }
+  }
+
+  fn assert_close(actual: Point, expected: Point) {
+    assert!(
+      (actual.x - expected.x).abs() < 1e-9 && (actual.y - expected.y).abs() < 1e-9,
+      "{actual:?} != {expected:?}"
+    );
+  }
+
+  #[test]
+  fn test_offset_polygon() {
+    let grown = offset_by_pixels(&rectangle(), 10.0, OffsetJoin::Miter).unwrap();
+    assert_eq!(grown.len(), 1);
+    assert!((grown[0].pixel_area().unwrap() - 120.0 * 70.0).abs() < 1e-6);
+    assert_eq!(grown[0].group_id(), "g1");
+    assert_ne!(grown[0].id(), "r1");
+
+    // Beveled corners lose a right triangle of 10 px legs each
+    let bevel = offset_by_pixels(&rectangle(), 10.0, OffsetJoin::Bevel).unwrap();
+    assert!((bevel[0].pixel_area().unwrap() - (120.0 * 70.0 - 200.0)).abs() < 1e-6);
+    // Square joins on right angles meet at the miter point
+    let square = offset_by_pixels(&rectangle(), 10.0, OffsetJoin::Square).unwrap();
+    assert!((square[0].pixel_area().unwrap() - 120.0 * 70.0).abs() < 1e-6);
+    let round = offset_by_pixels(&rectangle(), 10.0, OffsetJoin::Round).unwrap();
+    let round_area = round[0].pixel_area().unwrap();
+    assert!(round_area > 120.0 * 70.0 - 200.0 && round_area < 120.0 * 70.0);
+
+    let shrunk = offset_by_pixels(&rectangle(), -10.0, OffsetJoin::Miter).unwrap();
+    assert!((shrunk[0].pixel_area().unwrap() - 80.0 * 30.0).abs() < 1e-6);
+    // Shrinking past the half width removes the area
+    assert!(
+      offset_by_pixels(&rectangle(), -30.0, OffsetJoin::Miter)
+        .unwrap()
+        .is_empty()
+    );
+  }
+
+  #[test]
+  fn test_offset_polygon_square() {
+    let triangle = Measurement::Polygon {
+      id: "t1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![
+        Point::new(0.0, 0.0),
+        Point::new(100.0, 0.0),
+        Point::new(0.0, 100.0),
+      ],
+      attributes: None,
+    };
+    let grown = offset_by_pixels(&triangle, 10.0, OffsetJoin::Square).unwrap();
+    assert_eq!(grown.len(), 1);
+    // The right angle gains a 10 px square; each 45 degree corner extends both edges by
+    // 10 px, closing a pentagon of 100 + 50 * sqrt(2) square pixels
+    let sqrt2 = std::f64::consts::SQRT_2;
+    let expected = 5000.0 + (200.0 + 100.0 * sqrt2) * 10.0 + 100.0 + 2.0 * (100.0 + 50.0 * sqrt2);
+    assert!((grown[0].pixel_area().unwrap() - expected).abs() < 1e-6);
+    let outline = points(&grown[0]);
+    for corner in [
+      Point::new(110.0, -10.0),
+      Point::new(100.0 + 10.0 * sqrt2, 0.0),
+      Point::new(-10.0, 110.0),
+      Point::new(0.0, 100.0 + 10.0 * sqrt2),
+    ] {
+      assert!(
+        outline
+          .iter()
+          .any(|point| (point.x - corner.x).abs() < 1e-6 && (point.y - corner.y).abs() < 1e-6),
+        "{corner:?} not in {outline:?}"
+      );
+    }
+
+    // Shrinking has no outer corners on a convex polygon, so it matches the miter
+    let shrunk = offset_by_pixels(&rectangle(), -10.0, OffsetJoin::Square).unwrap();
+    assert!((shrunk[0].pixel_area().unwrap() - 80.0 * 30.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn test_offset_polyline() {
+    // Right, then down the page
+    let line = polyline(vec![
+      Point::new(0.0, 0.0),
+      Point::new(100.0, 0.0),
+      Point::new(100.0, 100.0),
+    ]);
+
+    // The inside of the bend meets at the crossing of the offset edges
+    let inside = offset_by_pixels(&line, 10.0, OffsetJoin::Round).unwrap();
+    assert_eq!(
+      points(&inside[0]),
+      vec![
+        Point::new(0.0, 10.0),
+        Point::new(90.0, 10.0),
+        Point::new(90.0, 100.0)
+      ]
+    );
+
+    let miter = points(&offset_by_pixels(&line, -10.0, OffsetJoin::Miter).unwrap()[0]);
+    assert_eq!(miter.len(), 3);
+    assert_close(miter[1], Point::new(110.0, -10.0));
+    assert_close(miter[2], Point::new(110.0, 100.0));
+
+    let bevel = points(&offset_by_pixels(&line, -10.0, OffsetJoin::Bevel).unwrap()[0]);
+    assert_eq!(bevel.len(), 4);
+    assert_close(bevel[1], Point::new(100.0, -10.0));
+    assert_close(bevel[2], Point::new(110.0, 0.0));
+
+    let square = points(&offset_by_pixels(&line, -10.0, OffsetJoin::Square).unwrap()[0]);
+    assert_eq!(square.len(), 3);
+    assert_close(square[1], Point::new(110.0, -10.0));
+
+    // A 135 degree bend extends both edges 10 px past the corner
+    let sharp = polyline(vec![
+      Point::new(0.0, 0.0),
+      Point::new(100.0, 0.0),
+      Point::new(0.0, 100.0),
+    ]);
+    let square = points(&offset_by_pixels(&sharp, -10.0, OffsetJoin::Square).unwrap()[0]);
+    assert_eq!(square.len(), 4);
+    assert_close(square[1], Point::new(110.0, -10.0));
+    assert_close(
+      square[2],
+      Point::new(100.0 + 10.0 * std::f64::consts::SQRT_2, 0.0),
+    );
+
+    let round = points(&offset_by_pixels(&line, -10.0, OffsetJoin::Round).unwrap()[0]);
+    assert!(round.len() > 4);
+    for point in &round[1..round.len() - 1] {
+      let radius = (point.x - 100.0).hypot(point.y);
+      assert!((radius - 10.0).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn test_offset_measurement_scaled() {
+    let scale = Scale::Default {
+      id: "s1".to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance: 10.0,
+        real_distance: 1.0,
+        unit: Unit::Feet,
+      },
+    };
+    // 12 in is 1 ft, or 10 px
+    let grown = offset_measurement(rectangle(), scale.clone(), 12.0, Unit::Inches, None).unwrap();
+    assert!((grown[0].pixel_area().unwrap() - 120.0 * 70.0).abs() < 1e-3);
+
+    let count = Measurement::Count {
+      id: "c1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0),),
+      attributes: None,
+    };
+    assert!(matches!(
+      offset_by_pixels(&count, 1.0, OffsetJoin::Miter),
+      Err(TakeoffError::GeometryOperation { .. })
+    ));
+    assert!(offset_by_pixels(&rectangle(), f64::NAN, OffsetJoin::Miter).is_err());
+  }
+}
@@ -0,0 +1,248 @@
+//! Overlap analysis of area measurements.
+//!
+//! Group totals add up every measurement, so a region outlined twice is counted twice. These
+//! helpers find the overlapping pairs and compute the dissolved area, which counts every
+//! covered region once.
+
+use crate::error::TakeoffResult;
+use crate::measurement::Measurement;
+use crate::scale::Scale;
+use geo::{Area, BooleanOps, BoundingRect, Intersects, MultiPolygon, Polygon, Rect};
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A measurement with the scale assigned to it.
+#[derive(Debug, Clone)]
+pub struct ScaledMeasurement {
+  pub measurement: Measurement,
+  pub scale: Option<Scale>,
+}
+
+/// Two area measurements that cover the same region.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeasurementOverlap {
+  /// The smaller of the two measurement ids.
+  pub measurement_id: String,
+  pub other_measurement_id: String,
+  /// The overlapping area in square page pixels.
+  pub pixel_area: f64,
+  /// The overlapping area in square meters, using the scale of the first measurement.
+  pub area: Option<f64>,
+}
+
+/// The area covered by a set of measurements, counting overlapping regions once.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DissolvedArea {
+  /// The covered area in square page pixels.
+  pub pixel_area: f64,
+  /// The covered area in square meters, from the measurements that have a scale.
+  pub area: Option<f64>,
+}
+
+struct AreaShape<'a> {
+  id: &'a str,
+  page_id: &'a str,
+  scale: Option<&'a Scale>,
+  polygon: Polygon<f64>,
+  bounds: Rect<f64>,
+}
+
+/// Get the polygon and rectangle measurements with valid geometry.
+fn area_shapes(measurements: &[ScaledMeasurement]) -> Vec<AreaShape<'_>> {
+  measurements
+    .iter()
+    .filter(|scaled| {
+      matches!(
+        scaled.measurement,
+        Measurement::Polygon { .. } | Measurement::Rectangle { .. }
+      )
+    })
+    .filter_map(|scaled| {
+      let polygon = scaled.measurement.to_polygon().ok()?;
+      let bounds = polygon.bounding_rect()?;
+      Some(AreaShape {
+        id: scaled.measurement.id(),
+        page_id: scaled.measurement.page_id(),
+        scale: scaled.scale.as_ref(),
+        polygon,
+        bounds,
+      })
+    })
+    .collect()
+}
+
+/// Convert an area in square page pixels to square meters.
+fn square_meters(pixel_area: f64, scale: &Scale) -> TakeoffResult<f64> {
+  let ratio = scale.ratio()?;
+  let meters_per_unit = scale.get_unit().meters_per_unit();
+  Ok(pixel_area / (ratio * ratio) * meters_per_unit * meters_per_unit)
+}
+
+/// Find the pairs of area measurements on the same page that overlap.
+///
+/// Counts and polylines are ignored, as are measurements with invalid geometry. Pairs that
+/// only touch along an edge have no overlapping area and are not reported.
+///
+/// # Arguments
+///
+/// * `measurements` - The measurements to compare, usually those of one group.
+/// * `min_pixel_area` - The smallest overlap to report, in square page pixels.
+///
+/// # Returns
+///
+/// * `Vec<MeasurementOverlap>` - The overlaps, sorted by measurement ids.
+pub fn find_overlaps(
+  measurements: &[ScaledMeasurement],
+  min_pixel_area: f64,
+) -> Vec<MeasurementOverlap> {
+  let mut shapes = area_shapes(measurements);
+  shapes.sort_by(|a, b| a.id.cmp(b.id));
+  let mut overlaps = Vec::new();
+  for (i, a) in shapes.iter().enumerate() {
+    for b in &shapes[i + 1..] {
+      if a.page_id != b.page_id || !a.bounds.intersects(&b.bounds) {
+        continue;
+      }
+      let pixel_area = a.polygon.intersection(&b.polygon).unsigned_area();
+      if pixel_area <= min_pixel_area.max(0.0) {
+        continue;
+      }
+      overlaps.push(MeasurementOverlap {
+        measurement_id: a.id.to_string(),
+        other_measurement_id: b.id.to_string(),
+        pixel_area,
+        area: a
+          .scale
+          .and_then(|scale| square_meters(pixel_area, scale).ok()),
+      });
+    }
+  }
+  overlaps
+}
+
+/// Get the area covered by the area measurements, counting overlapping regions once.
+///
+/// Measurements are dissolved per page and scale, since regions drawn at different scales
+/// have no common real-world size.
+///
+/// # Returns
+///
+/// * `None` - If there are no area measurements with valid geometry.
+/// * `Some(area)` - The dissolved area.
+pub fn dissolved_area(measurements: &[ScaledMeasurement]) -> Option<DissolvedArea> {
+  let shapes = area_shapes(measurements);
+  if shapes.is_empty() {
+    return None;
+  }
+  let mut partitions: BTreeMap<(&str, Option<String>), Vec<AreaShape>> = BTreeMap::new();
+  for shape in shapes {
+    partitions
+      .entry((shape.page_id, shape.scale.map(Scale::id)))
+      .or_default()
+      .push(shape);
+  }
+
+  let mut pixel_area = 0.0;
+  let mut area = None;
+  for shapes in partitions.into_values() {
+    let scale = shapes[0].scale;
+    let covered = shapes
+      .into_iter()
+      .fold(MultiPolygon::new(vec![]), |union, shape| {
+        union.union(&MultiPolygon::new(vec![shape.polygon]))
+      })
+      .unsigned_area();
+    pixel_area += covered;
+    if let Some(meters) = scale.and_then(|scale| square_meters(covered, scale).ok()) {
+      area = Some(area.unwrap_or(0.0) + meters);
+    }
+  }
+  Some(DissolvedArea { pixel_area, area })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coords::Point;
+  use crate::scale::ScaleDefinition;
+  use crate::unit::Unit;
+
+  fn rectangle(id: &str, min: (f64, f64), max: (f64, f64)) -> Measurement {
+    Measurement::Rectangle {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(min.0, min.1), Point::new(max.0, max.1)),
+      attributes: None,
+    }
+  }
+
+  fn scaled(measurements: Vec<Measurement>) -> Vec<ScaledMeasurement> {
+    let scale = Scale::Default {
+      id: "s1".to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance: 10.0,
+        real_distance: 1.0,
+        unit: Unit::Meters,
+      },
+    };
+    measurements
+      .into_iter()
+      .map(|measurement| ScaledMeasurement {
+        measurement,
+        scale: Some(scale.clone()),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_find_overlaps() {
+    let measurements = scaled(vec![
+      rectangle("b", (50.0, 0.0), (150.0, 100.0)),
+      rectangle("a", (0.0, 0.0), (100.0, 100.0)),
+      // Touches `b` along an edge only
+      rectangle("c", (150.0, 0.0), (200.0, 100.0)),
+      Measurement::Polyline {
+        id: "l".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: vec![Point::new(0.0, 0.0), Point::new(200.0, 100.0)],
+        attributes: None,
+      },
+    ]);
+    let overlaps = find_overlaps(&measurements, 0.0);
+    assert_eq!(overlaps.len(), 1);
+    assert_eq!(overlaps[0].measurement_id, "a");
+    assert_eq!(overlaps[0].other_measurement_id, "b");
+    assert!((overlaps[0].pixel_area - 5000.0).abs() < 1e-9);
+    assert!((overlaps[0].area.unwrap() - 50.0).abs() < 1e-9);
+
+    assert!(find_overlaps(&measurements, 6000.0).is_empty());
+  }
+
+  #[test]
+  fn test_dissolved_area() {
+    let measurements = scaled(vec![
+      rectangle("a", (0.0, 0.0), (100.0, 100.0)),
+      // The same room outlined twice
+      rectangle("b", (0.0, 0.0), (100.0, 100.0)),
+      rectangle("c", (50.0, 50.0), (150.0, 150.0)),
+    ]);
+    let dissolved = dissolved_area(&measurements).unwrap();
+    assert!((dissolved.pixel_area - 17500.0).abs() < 1e-6);
+    assert!((dissolved.area.unwrap() - 175.0).abs() < 1e-6);
+
+    let unscaled = vec![ScaledMeasurement {
+      measurement: rectangle("a", (0.0, 0.0), (10.0, 10.0)),
+      scale: None,
+    }];
+    let dissolved = dissolved_area(&unscaled).unwrap();
+    assert_eq!(dissolved.area, None);
+    assert!((dissolved.pixel_area - 100.0).abs() < 1e-9);
+    assert_eq!(dissolved_area(&[]), None);
+  }
+}
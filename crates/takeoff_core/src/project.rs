@@ -0,0 +1,247 @@
+//! Versioned on-disk project files.
+//!
+//! A project file wraps a [`StateOptions`] payload with a schema version. Documents are
+//! encoded as JSON or MessagePack; both are decoded into a JSON value first so that older
+//! documents can be upgraded by the migration chain before they are deserialized.
+//!
+//! # Schema versions
+//!
+//! - **1** — Contour line and point of interest elevations have no unit.
+//! - **2** — Contour elevations carry an explicit `unit`.
+
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::state::StateOptions;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The schema version this build writes and the newest it reads.
+pub const PROJECT_SCHEMA_VERSION: u32 = 2;
+
+/// The unit assigned to contour elevations written before they carried one.
+const LEGACY_ELEVATION_UNIT: &str = "Feet";
+
+/// A migration upgrading a document from the schema version at its index plus one.
+type Migration = fn(Value) -> TakeoffResult<Value>;
+
+const MIGRATIONS: [Migration; 1] = [migrate_v1_to_v2];
+
+/// The encoding of a project file.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash, Default)]
+pub enum ProjectFormat {
+  /// Pretty-printed UTF-8 JSON.
+  #[default]
+  Json,
+  /// Compact binary MessagePack.
+  MessagePack,
+}
+
+/// A project document at the current schema version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectFile {
+  pub schema_version: u32,
+  pub state: StateOptions,
+}
+
+impl ProjectFile {
+  /// Wrap a state at the current schema version.
+  pub fn new(state: StateOptions) -> Self {
+    Self {
+      schema_version: PROJECT_SCHEMA_VERSION,
+      state,
+    }
+  }
+
+  /// Encode the project in the given format.
+  pub fn encode(&self, format: ProjectFormat) -> TakeoffResult<Vec<u8>> {
+    match format {
+      ProjectFormat::Json => serde_json::to_vec_pretty(self).map_err(invalid_project_file),
+      ProjectFormat::MessagePack => rmp_serde::to_vec_named(self).map_err(invalid_project_file),
+    }
+  }
+
+  /// Decode a project in the given format, upgrading it to the current schema version.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if:
+  /// - The bytes are not a valid document in `format` ([`TakeoffError::InvalidProjectFile`])
+  /// - The document has no schema version or does not match the schema after migration
+  ///   ([`TakeoffError::InvalidProjectFile`])
+  /// - The document is newer than this build supports
+  ///   ([`TakeoffError::UnsupportedProjectVersion`])
+  pub fn decode(bytes: &[u8], format: ProjectFormat) -> TakeoffResult<Self> {
+    let document: Value = match format {
+      ProjectFormat::Json => serde_json::from_slice(bytes).map_err(invalid_project_file)?,
+      ProjectFormat::MessagePack => rmp_serde::from_slice(bytes).map_err(invalid_project_file)?,
+    };
+    serde_json::from_value(migrate(document)?).map_err(invalid_project_file)
+  }
+}
+
+fn invalid_project_file(error: impl std::fmt::Display) -> TakeoffError {
+  TakeoffError::invalid_project_file(error.to_string())
+}
+
+/// Read the schema version of a document.
+pub fn schema_version(document: &Value) -> TakeoffResult<u32> {
+  document
+    .get("schema_version")
+    .and_then(Value::as_u64)
+    .and_then(|version| u32::try_from(version).ok())
+    .filter(|version| *version >= 1)
+    .ok_or_else(|| TakeoffError::invalid_project_file("missing or invalid schema_version"))
+}
+
+/// Upgrade a document to [`PROJECT_SCHEMA_VERSION`] by applying each migration in turn.
+///
+/// # Errors
+///
+/// Returns [`TakeoffError::UnsupportedProjectVersion`] if the document is newer than this
+/// build, or [`TakeoffError::InvalidProjectFile`] if a migration cannot be applied.
+pub fn migrate(mut document: Value) -> TakeoffResult<Value> {
+  let mut version = schema_version(&document)?;
+  if version > PROJECT_SCHEMA_VERSION {
+    return Err(TakeoffError::UnsupportedProjectVersion {
+      version,
+      supported: PROJECT_SCHEMA_VERSION,
+    });
+  }
+  while version < PROJECT_SCHEMA_VERSION {
+    document = MIGRATIONS[version as usize - 1](document)?;
+    version += 1;
+    document["schema_version"] = Value::from(version);
+  }
+  Ok(document)
+}
+
+/// Version 2 added an explicit unit to contour line and point of interest elevations.
+///
+/// Elevations without a unit are assumed to be in feet.
+fn migrate_v1_to_v2(mut document: Value) -> TakeoffResult<Value> {
+  let Some(contours) = document
+    .get_mut("state")
+    .and_then(|state| state.get_mut("contours"))
+    .and_then(Value::as_array_mut)
+  else {
+    return Ok(document);
+  };
+  for contour in contours {
+    for key in ["lines", "points_of_interest"] {
+      let Some(items) = contour.get_mut(key).and_then(Value::as_array_mut) else {
+        continue;
+      };
+      for item in items {
+        let item = item.as_object_mut().ok_or_else(|| {
+          TakeoffError::invalid_project_file(format!("contour {key} must be objects"))
+        })?;
+        item
+          .entry("unit")
+          .or_insert_with(|| Value::from(LEGACY_ELEVATION_UNIT));
+      }
+    }
+  }
+  Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::contour::{ContourInput, ContourLineInput};
+  use crate::coords::Point;
+  use crate::page::Page;
+  use crate::unit::Unit;
+  use serde_json::json;
+
+  fn state() -> StateOptions {
+    StateOptions {
+      pages: vec![Page {
+        id: "p1".to_string(),
+        name: None,
+        width: Some(100.0),
+        height: Some(50.0),
+        viewport: None,
+      }],
+      groups: vec![],
+      measurements: vec![],
+      scales: vec![],
+      contours: Some(vec![ContourInput {
+        id: "c1".to_string(),
+        name: None,
+        page_id: "p1".to_string(),
+        lines: vec![ContourLineInput {
+          elevation: 5.0,
+          unit: Unit::Meters,
+          points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+        }],
+        points_of_interest: vec![],
+      }]),
+      group_validation_mode: None,
+      removal_policy: None,
+      strict_references: Some(true),
+    }
+  }
+
+  #[test]
+  fn test_project_file_roundtrip() {
+    let project = ProjectFile::new(state());
+    for format in [ProjectFormat::Json, ProjectFormat::MessagePack] {
+      let bytes = project.encode(format).unwrap();
+      assert_eq!(ProjectFile::decode(&bytes, format).unwrap(), project);
+    }
+    let json = project.encode(ProjectFormat::Json).unwrap();
+    let msgpack = project.encode(ProjectFormat::MessagePack).unwrap();
+    assert!(msgpack.len() < json.len());
+  }
+
+  #[test]
+  fn test_migrate_v1_to_v2() {
+    let document = json!({
+      "schema_version": 1,
+      "state": {
+        "pages": [],
+        "groups": [],
+        "measurements": [],
+        "scales": [],
+        "contours": [{
+          "id": "c1",
+          "name": null,
+          "page_id": "p1",
+          "lines": [{ "elevation": 5.0, "points": [{ "x": 0.0, "y": 0.0 }] }],
+          "points_of_interest": [
+            { "elevation": 2.0, "unit": "Meters", "point": { "x": 1.0, "y": 1.0 } }
+          ]
+        }]
+      }
+    });
+    let bytes = serde_json::to_vec(&document).unwrap();
+    let project = ProjectFile::decode(&bytes, ProjectFormat::Json).unwrap();
+    assert_eq!(project.schema_version, PROJECT_SCHEMA_VERSION);
+    let contour = &project.state.contours.unwrap()[0];
+    assert_eq!(contour.lines[0].unit, Unit::Feet);
+    // Units already present are kept
+    assert_eq!(contour.points_of_interest[0].unit, Unit::Meters);
+
+    // Documents without contours need no changes
+    let document = json!({ "schema_version": 1, "state": { "pages": [] } });
+    assert_eq!(migrate_v1_to_v2(document.clone()).unwrap(), document);
+  }
+
+  #[test]
+  fn test_project_file_version_errors() {
+    let newer = json!({ "schema_version": PROJECT_SCHEMA_VERSION + 1, "state": {} });
+    assert!(matches!(
+      migrate(newer),
+      Err(TakeoffError::UnsupportedProjectVersion { .. })
+    ));
+    assert!(matches!(
+      migrate(json!({ "state": {} })),
+      Err(TakeoffError::InvalidProjectFile { .. })
+    ));
+    assert!(matches!(
+      ProjectFile::decode(b"not json", ProjectFormat::Json),
+      Err(TakeoffError::InvalidProjectFile { .. })
+    ));
+  }
+}
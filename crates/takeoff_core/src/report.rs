@@ -0,0 +1,411 @@
+//! Quantity reports of groups and measurements, exported as CSV or XLSX.
+//!
+//! The caller collects one [`ReportRow`] per group or measurement; [`export_report`] sorts
+//! the rows, picks the requested columns and encodes them. Quantities are converted to the
+//! report's unit and labelled with the abbreviations of [`UnitFormatter`]. XLSX reports keep
+//! quantities as numbers, with the unit shown by the cell's number format, so they can be
+//! summed.
+
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::group::MeasurementType;
+use crate::unit::{Unit, UnitFormatter, UnitValue, export_decimals};
+use napi_derive::napi;
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// The encoding of a report.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash, Default)]
+pub enum ReportFormat {
+  /// Comma-separated UTF-8 text.
+  #[default]
+  Csv,
+  /// An Excel workbook with a single sheet.
+  Xlsx,
+}
+
+/// Whether a report has one row per group or one row per measurement.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash, Default)]
+pub enum ReportLevel {
+  #[default]
+  Groups,
+  Measurements,
+}
+
+/// A column of a report.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum ReportColumn {
+  /// The group name, or its id if it has none.
+  Group,
+  /// The page name, or its id if it has none. Group rows list every page of the group.
+  Page,
+  /// The measurement type of the group.
+  Type,
+  /// The measurement id. Empty for group rows.
+  Measurement,
+  Area,
+  Length,
+  Count,
+}
+
+impl ReportColumn {
+  /// The header of the column.
+  pub fn header(&self) -> &'static str {
+    match self {
+      ReportColumn::Group => "Group",
+      ReportColumn::Page => "Page",
+      ReportColumn::Type => "Type",
+      ReportColumn::Measurement => "Measurement",
+      ReportColumn::Area => "Area",
+      ReportColumn::Length => "Length",
+      ReportColumn::Count => "Count",
+    }
+  }
+}
+
+/// Options for [`export_report`].
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ReportOptions {
+  /// Defaults to `Csv`.
+  pub format: Option<ReportFormat>,
+  /// Defaults to `Groups`.
+  pub level: Option<ReportLevel>,
+  /// The unit lengths are reported in; areas use its square. Defaults to `Feet`.
+  pub unit: Option<Unit>,
+  /// The columns to include, in order. Defaults to every column that applies to the level.
+  pub columns: Option<Vec<ReportColumn>>,
+  /// The columns to sort by, most significant first. Defaults to group, page, then
+  /// measurement.
+  pub sort_by: Option<Vec<ReportColumn>>,
+  /// Whether to sort in descending order. Defaults to `false`.
+  pub descending: Option<bool>,
+  /// The number of decimal places quantities are rounded to, at most
+  /// [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS). Defaults to
+  /// [`DEFAULT_EXPORT_DECIMALS`](crate::unit::DEFAULT_EXPORT_DECIMALS).
+  pub decimals: Option<u32>,
+}
+
+impl ReportOptions {
+  fn columns(&self) -> Vec<ReportColumn> {
+    if let Some(columns) = &self.columns {
+      return columns.clone();
+    }
+    let mut columns = vec![
+      ReportColumn::Group,
+      ReportColumn::Page,
+      ReportColumn::Type,
+      ReportColumn::Measurement,
+      ReportColumn::Area,
+      ReportColumn::Length,
+      ReportColumn::Count,
+    ];
+    if self.level.unwrap_or_default() == ReportLevel::Groups {
+      columns.retain(|column| *column != ReportColumn::Measurement);
+    }
+    columns
+  }
+}
+
+/// The values of one group or measurement in a report.
+#[derive(Debug, Clone)]
+pub struct ReportRow {
+  pub group: String,
+  pub page: String,
+  /// The measurement type of the group, `None` if the group does not exist.
+  pub measurement_type: Option<MeasurementType>,
+  pub measurement: Option<String>,
+  pub area: Option<UnitValue>,
+  pub length: Option<UnitValue>,
+  pub count: Option<f64>,
+}
+
+impl ReportRow {
+  fn compare(&self, other: &Self, column: ReportColumn) -> Ordering {
+    match column {
+      ReportColumn::Group => self.group.cmp(&other.group),
+      ReportColumn::Page => self.page.cmp(&other.page),
+      ReportColumn::Type => type_name(self).cmp(&type_name(other)),
+      ReportColumn::Measurement => self.measurement.cmp(&other.measurement),
+      ReportColumn::Area => compare_quantity(self.area.as_ref(), other.area.as_ref()),
+      ReportColumn::Length => compare_quantity(self.length.as_ref(), other.length.as_ref()),
+      ReportColumn::Count => compare_values(self.count, other.count),
+    }
+  }
+}
+
+fn type_name(row: &ReportRow) -> Option<String> {
+  row.measurement_type.map(|t| format!("{t:?}"))
+}
+
+fn compare_quantity(a: Option<&UnitValue>, b: Option<&UnitValue>) -> Ordering {
+  let si = |value: &UnitValue| value.get_converted_value(Unit::Meters);
+  compare_values(a.map(si), b.map(si))
+}
+
+/// Missing values sort before present ones.
+fn compare_values(a: Option<f64>, b: Option<f64>) -> Ordering {
+  match (a, b) {
+    (Some(a), Some(b)) => a.total_cmp(&b),
+    (a, b) => a.is_some().cmp(&b.is_some()),
+  }
+}
+
+/// A formatted report cell.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+  Empty,
+  Text(String),
+  /// A quantity rounded to the report's decimals.
+  Number {
+    value: f64,
+    /// The value formatted with its unit, e.g. `12.5 ft²`.
+    text: String,
+    /// The unit abbreviation, e.g. `ft²`. Empty for counts.
+    suffix: String,
+  },
+}
+
+impl Cell {
+  fn text(&self) -> String {
+    match self {
+      Cell::Empty => String::new(),
+      Cell::Text(text) | Cell::Number { text, .. } => text.clone(),
+    }
+  }
+}
+
+fn round(value: f64, decimals: u32) -> f64 {
+  let factor = 10f64.powi(decimals as i32);
+  (value * factor).round() / factor
+}
+
+/// Build a quantity cell, taking the unit abbreviation from `formatter`.
+///
+/// The value is formatted here rather than by the formatter, which round-trips it through
+/// `f32` and would undo the rounding.
+fn quantity_cell(value: f64, formatter: UnitFormatter) -> Cell {
+  let formatted = formatter.format();
+  let suffix = formatted.split_once(' ').map_or("", |(_, suffix)| suffix);
+  Cell::Number {
+    value,
+    text: format!("{value} {suffix}"),
+    suffix: suffix.to_string(),
+  }
+}
+
+fn cell(row: &ReportRow, column: ReportColumn, unit: Unit, decimals: u32) -> Cell {
+  match column {
+    ReportColumn::Group => Cell::Text(row.group.clone()),
+    ReportColumn::Page => Cell::Text(row.page.clone()),
+    ReportColumn::Type => type_name(row).map_or(Cell::Empty, Cell::Text),
+    ReportColumn::Measurement => row.measurement.clone().map_or(Cell::Empty, Cell::Text),
+    ReportColumn::Area => row.area.as_ref().map_or(Cell::Empty, |area| {
+      quantity_cell(
+        round(area.get_converted_value(unit), decimals),
+        UnitFormatter::Area { unit, value: 0.0 },
+      )
+    }),
+    ReportColumn::Length => row.length.as_ref().map_or(Cell::Empty, |length| {
+      quantity_cell(
+        round(length.get_converted_value(unit), decimals),
+        UnitFormatter::Length { unit, value: 0.0 },
+      )
+    }),
+    ReportColumn::Count => row.count.map_or(Cell::Empty, |count| Cell::Number {
+      value: count,
+      text: count.to_string(),
+      suffix: String::new(),
+    }),
+  }
+}
+
+/// Sort the rows and encode them as a report.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - `decimals` exceeds [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS)
+///   ([`TakeoffError::InvalidDecimals`])
+/// - The report cannot be encoded ([`TakeoffError::ReportExport`])
+pub fn export_report(mut rows: Vec<ReportRow>, options: &ReportOptions) -> TakeoffResult<Vec<u8>> {
+  let decimals = export_decimals(options.decimals)?;
+  let sort_by = options.sort_by.clone().unwrap_or_else(|| {
+    vec![
+      ReportColumn::Group,
+      ReportColumn::Page,
+      ReportColumn::Measurement,
+    ]
+  });
+  rows.sort_by(|a, b| {
+    let ordering = sort_by.iter().fold(Ordering::Equal, |ordering, column| {
+      ordering.then_with(|| a.compare(b, *column))
+    });
+    if options.descending.unwrap_or(false) {
+      ordering.reverse()
+    } else {
+      ordering
+    }
+  });
+
+  let columns = options.columns();
+  let unit = options.unit.unwrap_or(Unit::Feet);
+  let cells: Vec<Vec<Cell>> = rows
+    .iter()
+    .map(|row| {
+      columns
+        .iter()
+        .map(|column| cell(row, *column, unit, decimals))
+        .collect()
+    })
+    .collect();
+
+  match options.format.unwrap_or_default() {
+    ReportFormat::Csv => write_csv(&columns, &cells),
+    ReportFormat::Xlsx => write_xlsx(&columns, &cells, decimals),
+  }
+}
+
+fn report_error(error: impl std::fmt::Display) -> TakeoffError {
+  TakeoffError::report_export(error.to_string())
+}
+
+fn write_csv(columns: &[ReportColumn], cells: &[Vec<Cell>]) -> TakeoffResult<Vec<u8>> {
+  let mut writer = csv::Writer::from_writer(Vec::new());
+  writer
+    .write_record(columns.iter().map(ReportColumn::header))
+    .map_err(report_error)?;
+  for row in cells {
+    writer
+      .write_record(row.iter().map(Cell::text))
+      .map_err(report_error)?;
+  }
+  writer.into_inner().map_err(report_error)
+}
+
+fn write_xlsx(
+  columns: &[ReportColumn],
+  cells: &[Vec<Cell>],
+  decimals: u32,
+) -> TakeoffResult<Vec<u8>> {
+  let mut workbook = Workbook::new();
+  let sheet = workbook.add_worksheet();
+  sheet.set_name("Quantities").map_err(report_error)?;
+  let header = Format::new().set_bold();
+  for (col, column) in columns.iter().enumerate() {
+    sheet
+      .write_string_with_format(0, col as u16, column.header(), &header)
+      .map_err(report_error)?;
+  }
+
+  let places = "0".repeat(decimals as usize);
+  let number_format = |suffix: &str| {
+    let number = if places.is_empty() {
+      "0".to_string()
+    } else {
+      format!("0.{places}")
+    };
+    if suffix.is_empty() {
+      Format::new().set_num_format("General")
+    } else {
+      Format::new().set_num_format(format!("{number} \"{suffix}\""))
+    }
+  };
+  for (row_index, row) in cells.iter().enumerate() {
+    let row_index = row_index as u32 + 1;
+    for (col, cell) in row.iter().enumerate() {
+      let col = col as u16;
+      match cell {
+        Cell::Empty => {}
+        Cell::Text(text) => {
+          sheet
+            .write_string(row_index, col, text)
+            .map_err(report_error)?;
+        }
+        Cell::Number { value, suffix, .. } => {
+          sheet
+            .write_number_with_format(row_index, col, *value, &number_format(suffix))
+            .map_err(report_error)?;
+        }
+      }
+    }
+  }
+  workbook.save_to_buffer().map_err(report_error)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::unit::{MAX_EXPORT_DECIMALS, UnitValueItemType};
+
+  fn rows() -> Vec<ReportRow> {
+    vec![
+      ReportRow {
+        group: "Walls".to_string(),
+        page: "Level 1".to_string(),
+        measurement_type: Some(MeasurementType::Linear),
+        measurement: None,
+        area: None,
+        length: Some(UnitValue::new(
+          12.345,
+          Unit::Feet,
+          UnitValueItemType::Length,
+        )),
+        count: Some(2.0),
+      },
+      ReportRow {
+        group: "Floors".to_string(),
+        page: "Level 1, Level 2".to_string(),
+        measurement_type: Some(MeasurementType::Area),
+        measurement: None,
+        area: Some(UnitValue::new(100.0, Unit::Feet, UnitValueItemType::Area)),
+        length: Some(UnitValue::new(40.0, Unit::Feet, UnitValueItemType::Length)),
+        count: Some(1.0),
+      },
+    ]
+  }
+
+  #[test]
+  fn test_export_csv() {
+    let csv = export_report(rows(), &ReportOptions::default()).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "Group,Page,Type,Area,Length,Count");
+    assert_eq!(lines[1], "Floors,\"Level 1, Level 2\",Area,100 ft²,40 ft,1");
+    assert_eq!(lines[2], "Walls,Level 1,Linear,,12.35 ft,2");
+
+    let options = ReportOptions {
+      unit: Some(Unit::Inches),
+      columns: Some(vec![ReportColumn::Group, ReportColumn::Length]),
+      sort_by: Some(vec![ReportColumn::Length]),
+      descending: Some(true),
+      decimals: Some(0),
+      ..Default::default()
+    };
+    let csv = String::from_utf8(export_report(rows(), &options).unwrap()).unwrap();
+    assert_eq!(csv, "Group,Length\nFloors,480 in\nWalls,148 in\n");
+
+    let options = ReportOptions {
+      decimals: Some(MAX_EXPORT_DECIMALS + 1),
+      ..Default::default()
+    };
+    assert!(matches!(
+      export_report(rows(), &options),
+      Err(TakeoffError::InvalidDecimals { .. })
+    ));
+  }
+
+  #[test]
+  fn test_export_xlsx() {
+    let options = ReportOptions {
+      format: Some(ReportFormat::Xlsx),
+      ..Default::default()
+    };
+    let xlsx = export_report(rows(), &options).unwrap();
+    // XLSX files are zip archives
+    assert_eq!(&xlsx[..2], b"PK");
+  }
+}
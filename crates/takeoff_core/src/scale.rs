@@ -5,6 +5,7 @@ use geo::Contains;
 use geo::{Coord, Geometry, Polygon as GeoPolygon, Rect};
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[napi(object)]
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -77,17 +78,29 @@ impl Scale {
   }
 
   pub fn bounding_box_to_polygon(&self) -> Option<GeoPolygon<f64>> {
+    self.bounding_rect().map(|rect| rect.to_polygon())
+  }
+
+  fn bounding_rect(&self) -> Option<Rect<f64>> {
     match self {
       Scale::Area { bounding_box, .. } => {
         let start: Coord<f64> = bounding_box.0.into();
         let end: Coord<f64> = bounding_box.1.into();
-        let rect = Rect::new(start, end);
-        Some(rect.to_polygon())
+        Some(Rect::new(start, end))
       }
       _ => None,
     }
   }
 
+  /// Get the area of the bounding box in square pixels.
+  ///
+  /// Returns `None` for default scales.
+  pub fn bounding_box_area(&self) -> Option<f64> {
+    self
+      .bounding_rect()
+      .map(|rect| rect.width() * rect.height())
+  }
+
   pub fn get_unit(&self) -> Unit {
     match self {
       Scale::Area { scale, .. } => scale.unit,
@@ -129,6 +142,107 @@ impl Scale {
   }
 }
 
+/// Pick the scale that applies to a geometry from the scales of its page.
+///
+/// The area scale with the smallest bounding box containing the geometry wins; if none
+/// contains it, the page's default scale is used. Ties are broken by the smaller scale id, so
+/// the result does not depend on the order of `scales`.
+pub fn resolve_scale<'a>(scales: &'a [Scale], geometry: &Geometry<f64>) -> Option<&'a Scale> {
+  let area = scales
+    .iter()
+    .filter(|scale| scale.is_in_bounding_box(geometry))
+    .min_by(|a, b| {
+      let (area_a, area_b) = (a.bounding_box_area(), b.bounding_box_area());
+      area_a
+        .partial_cmp(&area_b)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.id().cmp(&b.id()))
+    });
+  area.or_else(|| {
+    scales
+      .iter()
+      .filter(|scale| matches!(scale, Scale::Default { .. }))
+      .min_by_key(|scale| scale.id())
+  })
+}
+
+/// A set of scales on one page that makes scale resolution ambiguous.
+///
+/// Conflicts are warnings: [`resolve_scale`] still picks a scale deterministically.
+#[napi(discriminant = "type")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScaleConflict {
+  /// The page has more than one default scale. The one with the smallest id is used.
+  MultipleDefaultScales {
+    page_id: String,
+    /// The ids of the default scales, sorted.
+    scale_ids: Vec<String>,
+  },
+  /// Two area scales overlap without one being strictly smaller and nested inside the
+  /// other. In the overlap, the smaller bounding box, then the smaller id, is used.
+  OverlappingAreaScales {
+    page_id: String,
+    /// The ids of the two area scales, sorted.
+    scale_ids: Vec<String>,
+  },
+}
+
+/// Find every scale conflict, sorted by page id and scale ids.
+pub fn find_scale_conflicts(scales: &[Scale]) -> Vec<ScaleConflict> {
+  let mut pages: BTreeMap<String, Vec<&Scale>> = BTreeMap::new();
+  for scale in scales {
+    pages.entry(scale.page_id()).or_default().push(scale);
+  }
+
+  let mut conflicts = Vec::new();
+  for (page_id, mut scales) in pages {
+    scales.sort_by_key(|scale| scale.id());
+    let defaults: Vec<String> = scales
+      .iter()
+      .filter(|scale| matches!(scale, Scale::Default { .. }))
+      .map(|scale| scale.id())
+      .collect();
+    if defaults.len() > 1 {
+      conflicts.push(ScaleConflict::MultipleDefaultScales {
+        page_id: page_id.clone(),
+        scale_ids: defaults,
+      });
+    }
+
+    let areas: Vec<(String, Rect<f64>)> = scales
+      .iter()
+      .filter_map(|scale| Some((scale.id(), scale.bounding_rect()?)))
+      .collect();
+    for (i, (id_a, a)) in areas.iter().enumerate() {
+      for (id_b, b) in &areas[i + 1..] {
+        if rects_overlap(a, b) && !strictly_nested(a, b) {
+          conflicts.push(ScaleConflict::OverlappingAreaScales {
+            page_id: page_id.clone(),
+            scale_ids: vec![id_a.clone(), id_b.clone()],
+          });
+        }
+      }
+    }
+  }
+  conflicts
+}
+
+/// Whether two rectangles share some area, not just an edge.
+fn rects_overlap(a: &Rect<f64>, b: &Rect<f64>) -> bool {
+  a.min().x < b.max().x && b.min().x < a.max().x && a.min().y < b.max().y && b.min().y < a.max().y
+}
+
+/// Whether one rectangle lies inside the other and is smaller.
+fn strictly_nested(a: &Rect<f64>, b: &Rect<f64>) -> bool {
+  let encloses = |outer: &Rect<f64>, inner: &Rect<f64>| {
+    outer.min().x <= inner.min().x
+      && outer.min().y <= inner.min().y
+      && outer.max().x >= inner.max().x
+      && outer.max().y >= inner.max().y
+  };
+  (encloses(a, b) || encloses(b, a)) && a.width() * a.height() != b.width() * b.height()
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -246,4 +360,83 @@ mod tests {
       Err(TakeoffError::InvalidScale { .. })
     ));
   }
+
+  fn area_scale(id: &str, start: (f64, f64), end: (f64, f64)) -> Scale {
+    Scale::Area {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance: 1.0,
+        real_distance: 1.0,
+        unit: Unit::Feet,
+      },
+      bounding_box: (Point::new(start.0, start.1), Point::new(end.0, end.1)),
+    }
+  }
+
+  fn default_scale(id: &str) -> Scale {
+    Scale::Default {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance: 1.0,
+        real_distance: 1.0,
+        unit: Unit::Feet,
+      },
+    }
+  }
+
+  #[test]
+  fn test_resolve_scale() {
+    let point = Geometry::Point(geo::Point::new(5.0, 5.0));
+    let outer = area_scale("a", (0.0, 0.0), (100.0, 100.0));
+    let inner = area_scale("b", (0.0, 0.0), (10.0, 10.0));
+    let mut scales = vec![default_scale("d2"), outer, default_scale("d1"), inner];
+    for _ in 0..scales.len() {
+      assert_eq!(resolve_scale(&scales, &point).unwrap().id(), "b");
+      scales.rotate_left(1);
+    }
+
+    let outside = Geometry::Point(geo::Point::new(500.0, 500.0));
+    for _ in 0..scales.len() {
+      assert_eq!(resolve_scale(&scales, &outside).unwrap().id(), "d1");
+      scales.rotate_left(1);
+    }
+    assert!(resolve_scale(&[], &point).is_none());
+  }
+
+  #[test]
+  fn test_find_scale_conflicts() {
+    let scales = vec![
+      default_scale("d2"),
+      default_scale("d1"),
+      // Nested boxes of different sizes resolve without ambiguity
+      area_scale("a", (0.0, 0.0), (100.0, 100.0)),
+      area_scale("b", (10.0, 10.0), (20.0, 20.0)),
+      // Partial overlap
+      area_scale("c", (90.0, 90.0), (200.0, 200.0)),
+      // Touching edges do not overlap
+      area_scale("e", (200.0, 0.0), (300.0, 90.0)),
+    ];
+    assert_eq!(
+      find_scale_conflicts(&scales),
+      vec![
+        ScaleConflict::MultipleDefaultScales {
+          page_id: "p1".to_string(),
+          scale_ids: vec!["d1".to_string(), "d2".to_string()],
+        },
+        ScaleConflict::OverlappingAreaScales {
+          page_id: "p1".to_string(),
+          scale_ids: vec!["a".to_string(), "c".to_string()],
+        },
+      ]
+    );
+
+    // Identical boxes are ambiguous
+    let scales = vec![
+      area_scale("a", (0.0, 0.0), (10.0, 10.0)),
+      area_scale("b", (10.0, 10.0), (0.0, 0.0)),
+    ];
+    assert_eq!(find_scale_conflicts(&scales).len(), 1);
+  }
 }
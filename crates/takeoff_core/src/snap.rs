@@ -0,0 +1,354 @@
+//! Snapping of a cursor to existing geometry.
+//!
+//! Candidates are collected from measurement vertices, edge midpoints, the feet of
+//! perpendiculars dropped from the cursor onto edges, intersections between edges and the
+//! nearest points on contour lines. Among the candidates within the tolerance, the kind
+//! listed first in [`SnapKind`] wins and ties go to the nearest.
+
+use crate::contour::ContourInput;
+use crate::coords::{DistanceTrait, Point};
+use crate::measurement::Measurement;
+use geo::{Coord, Line, LineIntersection, line_intersection::line_intersection};
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+
+/// The kind of geometry a snapped point lies on, in order of precedence.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SnapKind {
+  /// A measurement vertex or count point.
+  Vertex,
+  /// A crossing of two measurement edges.
+  Intersection,
+  /// The middle of a measurement edge.
+  Midpoint,
+  /// The foot of the perpendicular from the cursor to a measurement edge.
+  Perpendicular,
+  /// The nearest point on a contour line.
+  Contour,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapOptions {
+  /// The kinds to snap to. Defaults to all kinds.
+  pub kinds: Option<Vec<SnapKind>>,
+  /// Measurements to ignore, such as the one being drawn.
+  pub exclude_ids: Option<Vec<String>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapCandidate {
+  pub point: Point,
+  pub kind: SnapKind,
+  /// The distance from the cursor in page pixels.
+  pub distance: f64,
+  /// The measurement the point lies on. For intersections, the first of the two by id.
+  pub measurement_id: Option<String>,
+  /// The contour the point lies on.
+  pub contour_id: Option<String>,
+  /// The elevation of the contour line the point lies on.
+  pub elevation: Option<f64>,
+}
+
+/// An edge of a measurement, tagged with its measurement and position along the outline.
+struct Edge<'a> {
+  measurement_id: &'a str,
+  index: usize,
+  /// The number of edges in the measurement's outline.
+  count: usize,
+  closed: bool,
+  line: Line<f64>,
+}
+
+impl Edge<'_> {
+  /// Whether two edges of the same outline share an endpoint.
+  fn is_adjacent(&self, other: &Edge) -> bool {
+    if self.measurement_id != other.measurement_id {
+      return false;
+    }
+    let gap = self.index.abs_diff(other.index);
+    gap <= 1 || (self.closed && gap == self.count - 1)
+  }
+}
+
+/// Find the best snap for a cursor.
+///
+/// # Arguments
+///
+/// * `cursor` - The cursor in page pixels.
+/// * `tolerance` - The maximum distance from the cursor in page pixels.
+/// * `measurements` - The measurements to snap to. Those with invalid geometry are ignored.
+/// * `contours` - The contours to snap to.
+/// * `options` - The kinds to snap to and the measurements to ignore.
+///
+/// # Returns
+///
+/// * `None` - If nothing is within the tolerance.
+/// * `Some(candidate)` - The candidate of the first [`SnapKind`], nearest first.
+pub fn find_snap(
+  cursor: Point,
+  tolerance: f64,
+  measurements: &[Measurement],
+  contours: &[ContourInput],
+  options: &SnapOptions,
+) -> Option<SnapCandidate> {
+  let enabled = |kind: SnapKind| {
+    options
+      .kinds
+      .as_ref()
+      .is_none_or(|kinds| kinds.contains(&kind))
+  };
+  let excluded = |id: &str| {
+    options
+      .exclude_ids
+      .as_ref()
+      .is_some_and(|ids| ids.iter().any(|excluded| excluded == id))
+  };
+
+  let mut candidates = Vec::new();
+  let mut push = |point: Point, kind: SnapKind, measurement_id: Option<&str>| {
+    candidates.push(SnapCandidate {
+      point,
+      kind,
+      distance: point.distance_to(&cursor),
+      measurement_id: measurement_id.map(str::to_string),
+      contour_id: None,
+      elevation: None,
+    });
+  };
+
+  let mut edges = Vec::new();
+  for measurement in measurements {
+    let id = measurement.id();
+    if excluded(id) {
+      continue;
+    }
+    if let Measurement::Count { points, .. } = measurement {
+      push(points.0, SnapKind::Vertex, Some(id));
+      continue;
+    }
+    let Ok(outline) = measurement.to_line_string() else {
+      continue;
+    };
+    let closed = !matches!(measurement, Measurement::Polyline { .. });
+    let count = outline.lines().count();
+    for (index, line) in outline.lines().enumerate() {
+      edges.push(Edge {
+        measurement_id: id,
+        index,
+        count,
+        closed,
+        line,
+      });
+    }
+    // Closed outlines repeat their first point at the end
+    let vertices = &outline.0[..outline.0.len() - usize::from(closed)];
+    for vertex in vertices {
+      push((*vertex).into(), SnapKind::Vertex, Some(id));
+    }
+  }
+
+  for edge in &edges {
+    let (start, end) = (edge.line.start, edge.line.end);
+    push(
+      Point::new((start.x + end.x) / 2.0, (start.y + end.y) / 2.0),
+      SnapKind::Midpoint,
+      Some(edge.measurement_id),
+    );
+    if let Some(foot) = perpendicular_foot(cursor.into(), &edge.line) {
+      push(foot, SnapKind::Perpendicular, Some(edge.measurement_id));
+    }
+  }
+
+  if enabled(SnapKind::Intersection) {
+    // Only edges near the cursor can cross near it
+    let near: Vec<&Edge> = edges
+      .iter()
+      .filter(|edge| closest_point(cursor.into(), &edge.line).distance_to(&cursor) <= tolerance)
+      .collect();
+    for (i, a) in near.iter().enumerate() {
+      for b in &near[i + 1..] {
+        if a.is_adjacent(b) {
+          continue;
+        }
+        if let Some(LineIntersection::SinglePoint { intersection, .. }) =
+          line_intersection(a.line, b.line)
+        {
+          push(
+            intersection.into(),
+            SnapKind::Intersection,
+            Some(a.measurement_id.min(b.measurement_id)),
+          );
+        }
+      }
+    }
+  }
+
+  for contour in contours {
+    for line in &contour.lines {
+      let nearest = line
+        .points
+        .windows(2)
+        .map(|pair| closest_point(cursor.into(), &Line::new(pair[0], pair[1])))
+        .min_by(|a, b| a.distance_to(&cursor).total_cmp(&b.distance_to(&cursor)));
+      if let Some(point) = nearest {
+        candidates.push(SnapCandidate {
+          point,
+          kind: SnapKind::Contour,
+          distance: point.distance_to(&cursor),
+          measurement_id: None,
+          contour_id: Some(contour.id.clone()),
+          elevation: Some(line.elevation),
+        });
+      }
+    }
+  }
+
+  candidates
+    .into_iter()
+    .filter(|candidate| candidate.distance <= tolerance && enabled(candidate.kind))
+    .min_by(|a, b| {
+      a.kind
+        .cmp(&b.kind)
+        .then_with(|| a.distance.total_cmp(&b.distance))
+        .then_with(|| a.measurement_id.cmp(&b.measurement_id))
+        .then_with(|| a.contour_id.cmp(&b.contour_id))
+    })
+}
+
+/// The foot of the perpendicular from a point to a line, if it falls within the segment.
+fn perpendicular_foot(point: Coord<f64>, line: &Line<f64>) -> Option<Point> {
+  let delta = line.delta();
+  let length_2 = delta.x * delta.x + delta.y * delta.y;
+  if length_2 == 0.0 {
+    return None;
+  }
+  let t = ((point.x - line.start.x) * delta.x + (point.y - line.start.y) * delta.y) / length_2;
+  (0.0..=1.0)
+    .contains(&t)
+    .then(|| Point::new(line.start.x + t * delta.x, line.start.y + t * delta.y))
+}
+
+/// The point on a segment nearest to a point.
+pub(crate) fn closest_point(point: Coord<f64>, line: &Line<f64>) -> Point {
+  perpendicular_foot(point, line).unwrap_or_else(|| {
+    let start = Point::from(line.start);
+    let end = Point::from(line.end);
+    let cursor = Point::from(point);
+    if start.distance_to(&cursor) <= end.distance_to(&cursor) {
+      start
+    } else {
+      end
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::contour::ContourLineInput;
+  use crate::unit::Unit;
+
+  fn measurements() -> Vec<Measurement> {
+    vec![
+      Measurement::Rectangle {
+        id: "r1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(10.0, 10.0)),
+        attributes: None,
+      },
+      Measurement::Polyline {
+        id: "l1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g2".to_string(),
+        points: vec![Point::new(5.0, -5.0), Point::new(5.0, 20.0)],
+        attributes: None,
+      },
+    ]
+  }
+
+  fn snap(x: f64, y: f64, tolerance: f64, options: &SnapOptions) -> Option<SnapCandidate> {
+    find_snap(Point::new(x, y), tolerance, &measurements(), &[], options)
+  }
+
+  #[test]
+  fn test_snap_kinds() {
+    let options = SnapOptions::default();
+
+    let vertex = snap(9.5, 0.5, 1.0, &options).unwrap();
+    assert_eq!(vertex.kind, SnapKind::Vertex);
+    assert_eq!(vertex.point, Point::new(10.0, 0.0));
+    assert_eq!(vertex.measurement_id.as_deref(), Some("r1"));
+
+    // The polyline crosses the rectangle's top and bottom edges at their midpoints
+    let crossing = snap(5.5, 10.5, 1.0, &options).unwrap();
+    assert_eq!(crossing.kind, SnapKind::Intersection);
+    assert_eq!(crossing.point, Point::new(5.0, 10.0));
+    assert_eq!(crossing.measurement_id.as_deref(), Some("l1"));
+
+    let midpoint = snap(0.5, 5.5, 1.0, &options).unwrap();
+    assert_eq!(midpoint.kind, SnapKind::Midpoint);
+    assert_eq!(midpoint.point, Point::new(0.0, 5.0));
+
+    let foot = snap(0.5, 2.0, 1.0, &options).unwrap();
+    assert_eq!(foot.kind, SnapKind::Perpendicular);
+    assert_eq!(foot.point, Point::new(0.0, 2.0));
+    assert!((foot.distance - 0.5).abs() < 1e-9);
+
+    assert!(snap(30.0, 30.0, 1.0, &options).is_none());
+  }
+
+  #[test]
+  fn test_snap_options() {
+    let only_edges = SnapOptions {
+      kinds: Some(vec![SnapKind::Perpendicular]),
+      exclude_ids: None,
+    };
+    let foot = snap(9.5, 0.5, 1.0, &only_edges).unwrap();
+    assert_eq!(foot.kind, SnapKind::Perpendicular);
+    assert_eq!(foot.distance, 0.5);
+
+    // Without the polyline there is no crossing, only the rectangle's midpoint
+    let without_line = SnapOptions {
+      kinds: None,
+      exclude_ids: Some(vec!["l1".to_string()]),
+    };
+    let midpoint = snap(5.5, 10.5, 1.0, &without_line).unwrap();
+    assert_eq!(midpoint.kind, SnapKind::Midpoint);
+    assert_eq!(midpoint.measurement_id.as_deref(), Some("r1"));
+
+    // Adjacent edges meeting at a corner are not reported as intersections
+    let corner = snap(0.2, 0.2, 1.0, &without_line).unwrap();
+    assert_eq!(corner.kind, SnapKind::Vertex);
+  }
+
+  #[test]
+  fn test_snap_contour() {
+    let contours = vec![ContourInput {
+      id: "c1".to_string(),
+      name: None,
+      page_id: "p1".to_string(),
+      lines: vec![ContourLineInput {
+        elevation: 12.0,
+        points: vec![Point::new(0.0, 50.0), Point::new(100.0, 50.0)],
+        unit: Unit::Feet,
+      }],
+      points_of_interest: vec![],
+    }];
+    let snapped = find_snap(
+      Point::new(40.0, 52.0),
+      3.0,
+      &measurements(),
+      &contours,
+      &SnapOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(snapped.kind, SnapKind::Contour);
+    assert_eq!(snapped.point, Point::new(40.0, 50.0));
+    assert_eq!(snapped.contour_id.as_deref(), Some("c1"));
+    assert_eq!(snapped.elevation, Some(12.0));
+  }
+}
@@ -0,0 +1,224 @@
+//! Versioned snapshots of the full takeoff state.
+//!
+//! A [`StateSnapshot`] holds every entity and setting needed to rebuild a state, plus the
+//! values computed from them so a saved project can be read without recomputing.
+
+use crate::contour::ContourInput;
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::group::{Group, GroupValidationMode};
+use crate::integrity::RemovalPolicy;
+use crate::measurement::Measurement;
+use crate::page::Page;
+use crate::scale::Scale;
+use crate::state::StateOptions;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+
+/// The newest snapshot version this build writes and reads.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The computed values of a measurement.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeasurementResult {
+  pub measurement_id: String,
+  /// The id of the scale assigned to the measurement.
+  pub scale_id: Option<String>,
+  /// The area in square meters.
+  pub area: Option<f64>,
+  /// The length in meters.
+  pub length: Option<f64>,
+}
+
+/// The computed totals of a group.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupResult {
+  pub group_id: String,
+  /// The total area in square meters.
+  pub area: Option<f64>,
+  /// The total length in meters.
+  pub length: Option<f64>,
+  pub points: Option<f64>,
+  pub count: Option<f64>,
+  /// The total area extruded by the group's `depth` attribute, in cubic meters.
+  #[serde(default)]
+  pub volume: Option<f64>,
+}
+
+/// The computed values of a contour.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContourResult {
+  pub contour_id: String,
+  /// The id of the scale assigned to the contour.
+  pub scale_id: Option<String>,
+  /// Whether a surface mesh could be built for the contour.
+  pub has_surface_mesh: bool,
+}
+
+/// The values computed from a snapshot's entities, sorted by id.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SnapshotResults {
+  pub measurements: Vec<MeasurementResult>,
+  pub groups: Vec<GroupResult>,
+  pub contours: Vec<ContourResult>,
+}
+
+/// The full takeoff state at one point in time.
+///
+/// Entities are sorted by id so equal states produce identical documents.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+  /// The snapshot format version. See [`SNAPSHOT_VERSION`].
+  pub version: u32,
+  pub pages: Vec<Page>,
+  pub groups: Vec<Group>,
+  pub measurements: Vec<Measurement>,
+  pub scales: Vec<Scale>,
+  pub contours: Vec<ContourInput>,
+  pub group_validation_mode: GroupValidationMode,
+  pub removal_policy: RemovalPolicy,
+  pub strict_references: bool,
+  /// Values computed from the entities. They are recomputed, not read, on import.
+  #[serde(default)]
+  pub results: SnapshotResults,
+}
+
+impl StateSnapshot {
+  /// Check that this build can read the snapshot.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::UnsupportedSnapshotVersion`] if the snapshot is newer than
+  /// [`SNAPSHOT_VERSION`].
+  pub fn check_version(&self) -> TakeoffResult<()> {
+    if self.version > SNAPSHOT_VERSION {
+      return Err(TakeoffError::UnsupportedSnapshotVersion {
+        version: self.version,
+        supported: SNAPSHOT_VERSION,
+      });
+    }
+    Ok(())
+  }
+
+  /// Serialize the snapshot as pretty-printed JSON.
+  #[cfg(feature = "json")]
+  pub fn to_json(&self) -> TakeoffResult<String> {
+    serde_json::to_string_pretty(self).map_err(|e| TakeoffError::invalid_snapshot(e.to_string()))
+  }
+
+  /// Parse a snapshot from JSON and check its version.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if:
+  /// - The document is not a valid snapshot ([`TakeoffError::InvalidSnapshot`])
+  /// - The snapshot is newer than this build supports
+  ///   ([`TakeoffError::UnsupportedSnapshotVersion`])
+  #[cfg(feature = "json")]
+  pub fn from_json(json: &str) -> TakeoffResult<Self> {
+    let snapshot: Self =
+      serde_json::from_str(json).map_err(|e| TakeoffError::invalid_snapshot(e.to_string()))?;
+    snapshot.check_version()?;
+    Ok(snapshot)
+  }
+
+  /// Get the options that seed a state with this snapshot's entities and settings.
+  pub fn to_state_options(&self) -> StateOptions {
+    StateOptions {
+      pages: self.pages.clone(),
+      groups: self.groups.clone(),
+      measurements: self.measurements.clone(),
+      scales: self.scales.clone(),
+      contours: Some(self.contours.clone()),
+      group_validation_mode: Some(self.group_validation_mode),
+      removal_policy: Some(self.removal_policy),
+      strict_references: Some(self.strict_references),
+    }
+  }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+  use super::*;
+  use crate::coords::Point;
+  use crate::scale::ScaleDefinition;
+  use crate::unit::Unit;
+
+  fn snapshot() -> StateSnapshot {
+    StateSnapshot {
+      version: SNAPSHOT_VERSION,
+      pages: vec![Page {
+        id: "p1".to_string(),
+        name: Some("Level 1".to_string()),
+        width: None,
+        height: None,
+        viewport: None,
+      }],
+      groups: vec![],
+      measurements: vec![Measurement::Count {
+        id: "m1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(1.0, 2.0),),
+        attributes: None,
+      }],
+      scales: vec![Scale::Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      }],
+      contours: vec![],
+      group_validation_mode: GroupValidationMode::Reject,
+      removal_policy: RemovalPolicy::Cascade,
+      strict_references: false,
+      results: SnapshotResults {
+        measurements: vec![MeasurementResult {
+          measurement_id: "m1".to_string(),
+          scale_id: Some("s1".to_string()),
+          area: None,
+          length: None,
+        }],
+        groups: vec![],
+        contours: vec![],
+      },
+    }
+  }
+
+  #[test]
+  fn test_snapshot_json_roundtrip() {
+    let snapshot = snapshot();
+    let json = snapshot.to_json().unwrap();
+    assert_eq!(StateSnapshot::from_json(&json).unwrap(), snapshot);
+
+    let options = snapshot.to_state_options();
+    assert_eq!(options.contours, Some(vec![]));
+    assert_eq!(
+      options.group_validation_mode,
+      Some(GroupValidationMode::Reject)
+    );
+  }
+
+  #[test]
+  fn test_snapshot_version_and_errors() {
+    let mut snapshot = snapshot();
+    snapshot.version = SNAPSHOT_VERSION + 1;
+    let json = snapshot.to_json().unwrap();
+    assert!(matches!(
+      StateSnapshot::from_json(&json),
+      Err(TakeoffError::UnsupportedSnapshotVersion { .. })
+    ));
+
+    assert!(matches!(
+      StateSnapshot::from_json(r#"{"version":1}"#),
+      Err(TakeoffError::InvalidSnapshot { .. })
+    ));
+  }
+}
@@ -0,0 +1,259 @@
+//! Spatial indexing of measurements.
+//!
+//! Measurements are stored in an R-tree keyed by their bounding boxes. Queries narrow the
+//! candidates by bounding box and then test the exact geometry, so a point inside a polygon
+//! is at distance 0 from it and a point beside a polyline is measured to its nearest segment.
+
+use crate::coords::Point;
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::measurement::Measurement;
+use geo::{BoundingRect, Distance, Euclidean, Geometry, Intersects, Point as GeoPoint, Rect};
+use rstar::{AABB, PointDistance, RTree, RTreeObject};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+struct IndexedMeasurement {
+  id: String,
+  geometry: Geometry<f64>,
+  envelope: AABB<[f64; 2]>,
+}
+
+impl PartialEq for IndexedMeasurement {
+  fn eq(&self, other: &Self) -> bool {
+    self.id == other.id
+  }
+}
+
+impl RTreeObject for IndexedMeasurement {
+  type Envelope = AABB<[f64; 2]>;
+
+  fn envelope(&self) -> Self::Envelope {
+    self.envelope
+  }
+}
+
+impl PointDistance for IndexedMeasurement {
+  fn distance_2(&self, point: &[f64; 2]) -> f64 {
+    let distance = Euclidean.distance(&GeoPoint::new(point[0], point[1]), &self.geometry);
+    distance * distance
+  }
+}
+
+/// An R-tree of the measurements on one page.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialIndex {
+  tree: RTree<IndexedMeasurement>,
+  entries: HashMap<String, IndexedMeasurement>,
+}
+
+impl SpatialIndex {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Build an index of the given measurements, skipping those with invalid geometry.
+  pub fn from_measurements<'a>(measurements: impl IntoIterator<Item = &'a Measurement>) -> Self {
+    let entries: HashMap<String, IndexedMeasurement> = measurements
+      .into_iter()
+      .filter_map(|measurement| indexed(measurement).ok())
+      .map(|entry| (entry.id.clone(), entry))
+      .collect();
+    Self {
+      tree: RTree::bulk_load(entries.values().cloned().collect()),
+      entries,
+    }
+  }
+
+  /// Insert a measurement, replacing any entry with the same id.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::EmptyGeometry`] if the measurement has invalid geometry. Any
+  /// previous entry is still removed.
+  pub fn insert(&mut self, measurement: &Measurement) -> TakeoffResult<()> {
+    self.remove(measurement.id());
+    let entry = indexed(measurement)?;
+    self.tree.insert(entry.clone());
+    self.entries.insert(entry.id.clone(), entry);
+    Ok(())
+  }
+
+  /// Remove a measurement by id.
+  ///
+  /// # Returns
+  ///
+  /// * `bool` - Whether the measurement was indexed.
+  pub fn remove(&mut self, id: &str) -> bool {
+    let Some(entry) = self.entries.remove(id) else {
+      return false;
+    };
+    self.tree.remove(&entry);
+    true
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Get the ids of the measurements within `tolerance` of a point, nearest first.
+  ///
+  /// Polygons and rectangles contain the points inside them. Ties are ordered by id.
+  pub fn at_point(&self, point: Point, tolerance: f64) -> Vec<String> {
+    let tolerance = tolerance.max(0.0);
+    let mut hits: Vec<(f64, &str)> = self
+      .tree
+      .locate_within_distance([point.x, point.y], tolerance * tolerance)
+      .map(|entry| (entry.distance_2(&[point.x, point.y]), entry.id.as_str()))
+      .collect();
+    hits.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+    hits.into_iter().map(|(_, id)| id.to_string()).collect()
+  }
+
+  /// Get the ids of the measurements whose geometry intersects a rectangle, sorted by id.
+  ///
+  /// The rectangle is given by any two opposite corners.
+  pub fn in_rect(&self, min: Point, max: Point) -> Vec<String> {
+    let rect = Rect::new(min, max);
+    let envelope = AABB::from_corners([rect.min().x, rect.min().y], [rect.max().x, rect.max().y]);
+    let mut ids: Vec<String> = self
+      .tree
+      .locate_in_envelope_intersecting(&envelope)
+      .filter(|entry| rect.intersects(&entry.geometry))
+      .map(|entry| entry.id.clone())
+      .collect();
+    ids.sort();
+    ids
+  }
+
+  /// Get the id of the measurement nearest to a point and its distance.
+  ///
+  /// When several measurements are equally near, the smallest id wins.
+  pub fn nearest(&self, point: Point) -> Option<(String, f64)> {
+    let mut candidates = self
+      .tree
+      .nearest_neighbor_iter_with_distance_2(&[point.x, point.y]);
+    let (first, distance_2) = candidates.next()?;
+    let id = candidates
+      .take_while(|(_, candidate)| *candidate <= distance_2)
+      .map(|(entry, _)| entry.id.as_str())
+      .fold(first.id.as_str(), |best, id| best.min(id));
+    Some((id.to_string(), distance_2.sqrt()))
+  }
+}
+
+fn indexed(measurement: &Measurement) -> TakeoffResult<IndexedMeasurement> {
+  let geometry = measurement.to_geometry()?;
+  let bounds = geometry.bounding_rect().ok_or_else(|| {
+    TakeoffError::empty_geometry("cannot compute bounding box for empty geometry")
+  })?;
+  Ok(IndexedMeasurement {
+    id: measurement.id().to_string(),
+    envelope: AABB::from_corners(
+      [bounds.min().x, bounds.min().y],
+      [bounds.max().x, bounds.max().y],
+    ),
+    geometry,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn measurements() -> Vec<Measurement> {
+    vec![
+      Measurement::Rectangle {
+        id: "r1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(10.0, 10.0)),
+        attributes: None,
+      },
+      Measurement::Polyline {
+        id: "l1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g2".to_string(),
+        points: vec![Point::new(20.0, 0.0), Point::new(20.0, 10.0)],
+        attributes: None,
+      },
+      Measurement::Count {
+        id: "c1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g3".to_string(),
+        points: (Point::new(5.0, 5.0),),
+        attributes: None,
+      },
+    ]
+  }
+
+  #[test]
+  fn test_spatial_queries() {
+    let index = SpatialIndex::from_measurements(&measurements());
+    assert_eq!(index.len(), 3);
+
+    // Inside the rectangle, and exactly on the count
+    assert_eq!(index.at_point(Point::new(5.0, 5.0), 0.0), ["c1", "r1"]);
+    assert_eq!(index.at_point(Point::new(2.0, 2.0), 1.0), ["r1"]);
+    // Beside the polyline, within and outside the tolerance
+    assert_eq!(index.at_point(Point::new(21.0, 5.0), 1.5), ["l1"]);
+    assert!(index.at_point(Point::new(22.0, 5.0), 1.5).is_empty());
+
+    assert_eq!(
+      index.in_rect(Point::new(8.0, 12.0), Point::new(25.0, 8.0)),
+      ["l1", "r1"]
+    );
+    assert!(
+      index
+        .in_rect(Point::new(11.0, 0.0), Point::new(19.0, 10.0))
+        .is_empty()
+    );
+
+    let (id, distance) = index.nearest(Point::new(16.0, 5.0)).unwrap();
+    assert_eq!(id, "l1");
+    assert!((distance - 4.0).abs() < 1e-9);
+    // Equidistant from the rectangle and the polyline
+    assert_eq!(index.nearest(Point::new(15.0, 20.0)).unwrap().0, "l1");
+    assert_eq!(index.nearest(Point::new(15.0, 5.0)).unwrap().0, "l1");
+  }
+
+  #[test]
+  fn test_spatial_insert_remove() {
+    let mut index = SpatialIndex::new();
+    for measurement in measurements() {
+      index.insert(&measurement).unwrap();
+    }
+
+    // Re-inserting replaces the previous geometry
+    index
+      .insert(&Measurement::Count {
+        id: "c1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g3".to_string(),
+        points: (Point::new(50.0, 50.0),),
+        attributes: None,
+      })
+      .unwrap();
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.at_point(Point::new(5.0, 5.0), 0.0), ["r1"]);
+    assert_eq!(index.nearest(Point::new(49.0, 50.0)).unwrap().0, "c1");
+
+    assert!(index.remove("r1"));
+    assert!(!index.remove("r1"));
+    assert!(index.at_point(Point::new(5.0, 5.0), 0.0).is_empty());
+
+    // Invalid geometry is not indexed
+    let invalid = Measurement::Polygon {
+      id: "a1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![Point::new(0.0, 0.0)],
+      attributes: None,
+    };
+    assert!(index.insert(&invalid).is_err());
+    assert_eq!(index.len(), 2);
+  }
+}
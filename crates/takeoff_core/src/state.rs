@@ -1,7 +1,9 @@
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
 
-use crate::group::Group;
+use crate::contour::ContourInput;
+use crate::group::{Group, GroupValidationMode};
+use crate::integrity::RemovalPolicy;
 use crate::measurement::Measurement;
 use crate::page::Page;
 use crate::scale::Scale;
@@ -13,4 +15,32 @@ pub struct StateOptions {
   pub groups: Vec<Group>,
   pub measurements: Vec<Measurement>,
   pub scales: Vec<Scale>,
+  #[serde(default)]
+  pub contours: Option<Vec<ContourInput>>,
+  /// How measurements incompatible with their group are handled. Defaults to `Flag`.
+  #[serde(default)]
+  pub group_validation_mode: Option<GroupValidationMode>,
+  /// What happens to dependents when a page or group is removed. Defaults to `Legacy`.
+  #[serde(default)]
+  pub removal_policy: Option<RemovalPolicy>,
+  /// Reject initial entities and upserts that reference a missing page or group. Defaults
+  /// to `false`.
+  #[serde(default)]
+  pub strict_references: Option<bool>,
+}
+
+/// A set of entities to upsert together, with a single recomputation pass at the end.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct StateBatch {
+  #[serde(default)]
+  pub pages: Option<Vec<Page>>,
+  #[serde(default)]
+  pub groups: Option<Vec<Group>>,
+  #[serde(default)]
+  pub measurements: Option<Vec<Measurement>>,
+  #[serde(default)]
+  pub scales: Option<Vec<Scale>>,
+  #[serde(default)]
+  pub contours: Option<Vec<ContourInput>>,
 }
@@ -0,0 +1,471 @@
+//! SVG rendering of a page's measurements, scales and contours.
+//!
+//! The drawing uses page pixels as its user units, so it overlays the page image directly.
+//! Layers are drawn bottom to top: the viewports of area scales, contour lines, the
+//! measurements of each group and finally the quantity labels at each measurement's
+//! centroid.
+
+use crate::contour::ContourInput;
+use crate::coords::Point;
+use crate::error::TakeoffResult;
+use crate::measurement::Measurement;
+use crate::page::Page;
+use crate::scale::Scale;
+use crate::unit::{Unit, UnitValue, export_decimals};
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The colors given to groups without a style, in order of their ids.
+pub const SVG_GROUP_PALETTE: [&str; 8] = [
+  "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+/// The default font size of labels, in page pixels.
+pub const DEFAULT_SVG_FONT_SIZE: f64 = 12.0;
+
+/// How the measurements of a group are drawn.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SvgGroupStyle {
+  /// A CSS color for strokes, fills and count markers.
+  pub color: String,
+  /// The opacity of area fills, from 0 to 1. Defaults to 0.25.
+  pub fill_opacity: Option<f64>,
+  /// The stroke width in page pixels. Defaults to 2.
+  pub stroke_width: Option<f64>,
+}
+
+impl SvgGroupStyle {
+  fn new(color: &str) -> Self {
+    Self {
+      color: color.to_string(),
+      fill_opacity: None,
+      stroke_width: None,
+    }
+  }
+}
+
+/// Options for [`render_svg`].
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SvgOptions {
+  /// The unit of quantity labels. Defaults to feet.
+  pub unit: Option<Unit>,
+  /// The number of decimal places of quantity labels, at most
+  /// [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS). Defaults to
+  /// [`DEFAULT_EXPORT_DECIMALS`](crate::unit::DEFAULT_EXPORT_DECIMALS).
+  pub decimals: Option<u32>,
+  /// The style of each group by id. Other groups are colored from [`SVG_GROUP_PALETTE`].
+  pub group_styles: Option<BTreeMap<String, SvgGroupStyle>>,
+  /// The font size of labels in page pixels. Defaults to [`DEFAULT_SVG_FONT_SIZE`].
+  pub font_size: Option<f64>,
+  /// Whether to draw quantity labels. Defaults to true.
+  pub show_labels: Option<bool>,
+  /// Whether to draw the viewports of area scales. Defaults to true.
+  pub show_scales: Option<bool>,
+  /// Whether to draw contour lines and points of interest. Defaults to true.
+  pub show_contours: Option<bool>,
+}
+
+/// A measurement with the quantities shown in its label.
+#[derive(Debug, Clone)]
+pub struct SvgMeasurement {
+  pub measurement: Measurement,
+  pub area: Option<UnitValue>,
+  pub length: Option<UnitValue>,
+}
+
+/// Render a page's measurements, scales and contours as an SVG document.
+///
+/// The document is the size of the page when the page has a width and height (or a
+/// viewport), and otherwise just large enough for its content. Polygons and rectangles are
+/// labelled with their area, polylines with their length; count markers are not labelled.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - A labelled measurement has invalid geometry
+///   ([`TakeoffError::EmptyGeometry`](crate::TakeoffError::EmptyGeometry))
+/// - `decimals` exceeds [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS)
+///   ([`TakeoffError::InvalidDecimals`](crate::TakeoffError::InvalidDecimals))
+pub fn render_svg(
+  page: Option<&Page>,
+  measurements: &[SvgMeasurement],
+  scales: &[Scale],
+  contours: &[ContourInput],
+  options: &SvgOptions,
+) -> TakeoffResult<String> {
+  let unit = options.unit.unwrap_or(Unit::Feet);
+  let decimals = export_decimals(options.decimals)?;
+  let font_size = options.font_size.unwrap_or(DEFAULT_SVG_FONT_SIZE);
+  let show_contours = options.show_contours.unwrap_or(true);
+  let show_scales = options.show_scales.unwrap_or(true);
+
+  let mut group_ids: Vec<&str> = measurements
+    .iter()
+    .map(|item| item.measurement.group_id())
+    .collect();
+  group_ids.sort();
+  group_ids.dedup();
+  let styles: BTreeMap<&str, SvgGroupStyle> = group_ids
+    .iter()
+    .enumerate()
+    .map(|(index, id)| {
+      let style = options
+        .group_styles
+        .as_ref()
+        .and_then(|styles| styles.get(*id))
+        .cloned()
+        .unwrap_or_else(|| SvgGroupStyle::new(SVG_GROUP_PALETTE[index % SVG_GROUP_PALETTE.len()]));
+      (*id, style)
+    })
+    .collect();
+
+  let (width, height) = match page_size(page) {
+    Some(size) => size,
+    None => {
+      let mut points: Vec<Point> = measurements
+        .iter()
+        .flat_map(|item| measurement_points(&item.measurement))
+        .collect();
+      if show_contours {
+        points.extend(contours.iter().flat_map(contour_points));
+      }
+      if show_scales {
+        points.extend(
+          scales
+            .iter()
+            .filter_map(scale_viewport)
+            .flat_map(|(a, b)| [a, b]),
+        );
+      }
+      let max_x = points.iter().map(|p| p.x).fold(0.0, f64::max);
+      let max_y = points.iter().map(|p| p.y).fold(0.0, f64::max);
+      (max_x + font_size, max_y + font_size)
+    }
+  };
+
+  let mut svg = String::new();
+  let _ = writeln!(
+    svg,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif" font-size="{font_size}">"#
+  );
+
+  if show_scales {
+    svg.push_str("<g id=\"scales\" fill=\"none\" stroke=\"#666666\" stroke-dasharray=\"8 4\">\n");
+    for scale in scales {
+      let Some((min, max)) = scale_viewport(scale) else {
+        continue;
+      };
+      let Scale::Area { id, scale, .. } = scale else {
+        continue;
+      };
+      let _ = writeln!(
+        svg,
+        r#"<rect data-scale-id="{}" x="{}" y="{}" width="{}" height="{}"/>"#,
+        escape(id),
+        min.x,
+        min.y,
+        max.x - min.x,
+        max.y - min.y
+      );
+      let _ = writeln!(
+        svg,
+        r##"<text x="{}" y="{}" fill="#666666" stroke="none">{} px = {} {}</text>"##,
+        min.x + font_size / 4.0,
+        min.y + font_size,
+        scale.pixel_distance,
+        scale.real_distance,
+        scale.unit.display()
+      );
+    }
+    svg.push_str("</g>\n");
+  }
+
+  if show_contours {
+    svg.push_str("<g id=\"contours\" fill=\"none\" stroke=\"#8c6d31\" stroke-width=\"1\">\n");
+    for contour in contours {
+      let _ = writeln!(svg, r#"<g data-contour-id="{}">"#, escape(&contour.id));
+      for line in &contour.lines {
+        let _ = writeln!(
+          svg,
+          r#"<polyline points="{}"><title>{} {}</title></polyline>"#,
+          points_attribute(&line.points),
+          line.elevation,
+          line.unit.display()
+        );
+      }
+      for point in &contour.points_of_interest {
+        let _ = writeln!(
+          svg,
+          r##"<circle cx="{}" cy="{}" r="{}" fill="#8c6d31"><title>{} {}</title></circle>"##,
+          point.point.x,
+          point.point.y,
+          font_size / 6.0,
+          point.elevation,
+          point.unit.display()
+        );
+      }
+      svg.push_str("</g>\n");
+    }
+    svg.push_str("</g>\n");
+  }
+
+  svg.push_str("<g id=\"measurements\">\n");
+  for (group_id, style) in &styles {
+    let _ = writeln!(
+      svg,
+      r#"<g data-group-id="{}" stroke="{color}" fill="{color}" fill-opacity="{}" stroke-width="{}" stroke-linejoin="round">"#,
+      escape(group_id),
+      style.fill_opacity.unwrap_or(0.25),
+      style.stroke_width.unwrap_or(2.0),
+      color = escape(&style.color),
+    );
+    for item in measurements
+      .iter()
+      .filter(|item| item.measurement.group_id() == *group_id)
+    {
+      let id = escape(item.measurement.id());
+      let _ = match &item.measurement {
+        Measurement::Polygon { points, .. } => writeln!(
+          svg,
+          r#"<polygon data-measurement-id="{id}" points="{}"/>"#,
+          points_attribute(points)
+        ),
+        Measurement::Rectangle { points: (a, b), .. } => writeln!(
+          svg,
+          r#"<rect data-measurement-id="{id}" x="{}" y="{}" width="{}" height="{}"/>"#,
+          a.x.min(b.x),
+          a.y.min(b.y),
+          (b.x - a.x).abs(),
+          (b.y - a.y).abs()
+        ),
+        Measurement::Polyline { points, .. } => writeln!(
+          svg,
+          r#"<polyline data-measurement-id="{id}" points="{}" fill="none"/>"#,
+          points_attribute(points)
+        ),
+        Measurement::Count { points, .. } => writeln!(
+          svg,
+          r#"<circle data-measurement-id="{id}" cx="{}" cy="{}" r="{}" fill-opacity="1"/>"#,
+          points.0.x,
+          points.0.y,
+          font_size / 3.0
+        ),
+      };
+    }
+    svg.push_str("</g>\n");
+  }
+  svg.push_str("</g>\n");
+
+  if options.show_labels.unwrap_or(true) {
+    svg.push_str(
+      "<g id=\"labels\" text-anchor=\"middle\" dominant-baseline=\"middle\" fill=\"#000000\" stroke=\"#ffffff\" stroke-width=\"3\" paint-order=\"stroke\">\n",
+    );
+    for item in measurements {
+      let label = match &item.measurement {
+        Measurement::Polygon { .. } | Measurement::Rectangle { .. } => item
+          .area
+          .as_ref()
+          .map(|area| area.export_label(unit, decimals)),
+        Measurement::Polyline { .. } => item
+          .length
+          .as_ref()
+          .map(|length| length.export_label(unit, decimals)),
+        Measurement::Count { .. } => None,
+      };
+      if let Some(label) = label {
+        let centroid = item.measurement.get_centroid()?;
+        let _ = writeln!(
+          svg,
+          r#"<text data-measurement-id="{}" x="{}" y="{}">{}</text>"#,
+          escape(item.measurement.id()),
+          centroid.x,
+          centroid.y,
+          escape(&label)
+        );
+      }
+    }
+    svg.push_str("</g>\n");
+  }
+
+  svg.push_str("</svg>\n");
+  Ok(svg)
+}
+
+/// Get the size of a page from its dimensions, or else its viewport.
+fn page_size(page: Option<&Page>) -> Option<(f64, f64)> {
+  let page = page?;
+  match (page.width, page.height, &page.viewport) {
+    (Some(width), Some(height), _) => Some((width, height)),
+    (_, _, Some(viewport)) => Some((viewport.width, viewport.height)),
+    _ => None,
+  }
+}
+
+/// Get the corners of an area scale's bounding box, ordered min then max.
+fn scale_viewport(scale: &Scale) -> Option<(Point, Point)> {
+  match scale {
+    Scale::Area {
+      bounding_box: (a, b),
+      ..
+    } => Some((
+      Point::new(a.x.min(b.x), a.y.min(b.y)),
+      Point::new(a.x.max(b.x), a.y.max(b.y)),
+    )),
+    Scale::Default { .. } => None,
+  }
+}
+
+fn measurement_points(measurement: &Measurement) -> Vec<Point> {
+  match measurement {
+    Measurement::Polygon { points, .. } | Measurement::Polyline { points, .. } => points.clone(),
+    Measurement::Rectangle { points: (a, b), .. } => vec![*a, *b],
+    Measurement::Count { points, .. } => vec![points.0],
+  }
+}
+
+fn contour_points(contour: &ContourInput) -> Vec<Point> {
+  let lines = contour
+    .lines
+    .iter()
+    .flat_map(|line| line.points.iter().copied());
+  let points = contour.points_of_interest.iter().map(|point| point.point);
+  lines.chain(points).collect()
+}
+
+fn points_attribute(points: &[Point]) -> String {
+  points
+    .iter()
+    .map(|point| format!("{},{}", point.x, point.y))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Escape text for use in XML content and attribute values.
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::contour::ContourLineInput;
+  use crate::page::PageViewport;
+  use crate::scale::ScaleDefinition;
+  use crate::unit::UnitValueItemType;
+
+  fn measurements() -> Vec<SvgMeasurement> {
+    vec![
+      SvgMeasurement {
+        measurement: Measurement::Rectangle {
+          id: "r1".to_string(),
+          page_id: "p1".to_string(),
+          group_id: "<floors>".to_string(),
+          points: (Point::new(10.0, 20.0), Point::new(110.0, 70.0)),
+          attributes: None,
+        },
+        area: Some(UnitValue::new(50.0, Unit::Feet, UnitValueItemType::Area)),
+        length: None,
+      },
+      SvgMeasurement {
+        measurement: Measurement::Count {
+          id: "c1".to_string(),
+          page_id: "p1".to_string(),
+          group_id: "outlets".to_string(),
+          points: (Point::new(200.0, 150.0),),
+          attributes: None,
+        },
+        area: None,
+        length: None,
+      },
+    ]
+  }
+
+  #[test]
+  fn test_render_svg() {
+    let scales = vec![Scale::Area {
+      id: "s1".to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance: 10.0,
+        real_distance: 1.0,
+        unit: Unit::Feet,
+      },
+      bounding_box: (Point::new(300.0, 300.0), Point::new(0.0, 0.0)),
+    }];
+    let contours = vec![ContourInput {
+      id: "k1".to_string(),
+      name: None,
+      page_id: "p1".to_string(),
+      lines: vec![ContourLineInput {
+        elevation: 100.0,
+        points: vec![Point::new(0.0, 0.0), Point::new(50.0, 50.0)],
+        unit: Unit::Feet,
+      }],
+      points_of_interest: vec![],
+    }];
+    let options = SvgOptions {
+      group_styles: Some(BTreeMap::from([(
+        "outlets".to_string(),
+        SvgGroupStyle::new("red"),
+      )])),
+      ..SvgOptions::default()
+    };
+    let svg = render_svg(None, &measurements(), &scales, &contours, &options).unwrap();
+
+    // Sized to fit the scale viewport plus a margin
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="312" height="312""#));
+    assert!(svg.contains(r#"<rect data-scale-id="s1" x="0" y="0" width="300" height="300"/>"#));
+    assert!(svg.contains("10 px = 1 ft"));
+    assert!(svg.contains(r#"<polyline points="0,0 50,50"><title>100 ft</title></polyline>"#));
+    assert!(svg.contains(r##"<g data-group-id="&lt;floors&gt;" stroke="#1f77b4""##));
+    assert!(svg.contains(r#"<g data-group-id="outlets" stroke="red""#));
+    assert!(
+      svg.contains(r#"<rect data-measurement-id="r1" x="10" y="20" width="100" height="50"/>"#)
+    );
+    assert!(svg.contains(r#"<text data-measurement-id="r1" x="60" y="45">50.00 sq ft</text>"#));
+    assert!(!svg.contains(r#"<text data-measurement-id="c1""#));
+    assert!(svg.trim_end().ends_with("</svg>"));
+  }
+
+  #[test]
+  fn test_render_svg_page_size_and_layers() {
+    let page = Page {
+      id: "p1".to_string(),
+      name: None,
+      width: None,
+      height: None,
+      viewport: Some(PageViewport {
+        width: 800.0,
+        height: 600.0,
+      }),
+    };
+    let options = SvgOptions {
+      show_labels: Some(false),
+      show_scales: Some(false),
+      show_contours: Some(false),
+      ..SvgOptions::default()
+    };
+    let svg = render_svg(Some(&page), &measurements(), &[], &[], &options).unwrap();
+    assert!(svg.contains(r#"width="800" height="600" viewBox="0 0 800 600""#));
+    assert!(!svg.contains(r#"id="labels""#));
+    assert!(!svg.contains(r#"id="scales""#));
+    assert!(!svg.contains(r#"id="contours""#));
+
+    let options = SvgOptions {
+      decimals: Some(16),
+      ..SvgOptions::default()
+    };
+    assert!(matches!(
+      render_svg(Some(&page), &measurements(), &[], &[], &options),
+      Err(crate::TakeoffError::InvalidDecimals { .. })
+    ));
+  }
+}
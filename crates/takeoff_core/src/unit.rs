@@ -1,4 +1,4 @@
-use crate::error::TakeoffError;
+use crate::error::{TakeoffError, TakeoffResult};
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
 use uom::fmt::DisplayStyle::Abbreviation;
@@ -6,6 +6,32 @@ use uom::si::area::{square_centimeter, square_foot, square_inch, square_meter, s
 use uom::si::f32::{Area, Length, Volume};
 use uom::si::length::{centimeter, foot, inch, meter, yard};
 use uom::si::volume::{cubic_centimeter, cubic_foot, cubic_inch, cubic_meter, cubic_yard};
+
+/// The number of decimal places exported quantities are rounded to by default.
+pub const DEFAULT_EXPORT_DECIMALS: u32 = 2;
+
+/// The largest number of decimal places exported quantities can be rounded to.
+///
+/// An `f64` holds about 15 significant decimal digits, so further places would only show
+/// noise.
+pub const MAX_EXPORT_DECIMALS: u32 = 15;
+
+/// Resolve the `decimals` option of an export, defaulting to [`DEFAULT_EXPORT_DECIMALS`].
+///
+/// # Errors
+///
+/// Returns [`TakeoffError::InvalidDecimals`] if `decimals` exceeds [`MAX_EXPORT_DECIMALS`].
+pub fn export_decimals(decimals: Option<u32>) -> TakeoffResult<u32> {
+  let decimals = decimals.unwrap_or(DEFAULT_EXPORT_DECIMALS);
+  if decimals > MAX_EXPORT_DECIMALS {
+    return Err(TakeoffError::InvalidDecimals {
+      decimals,
+      max: MAX_EXPORT_DECIMALS,
+    });
+  }
+  Ok(decimals)
+}
+
 /// Measurement units supported by the system
 #[napi(string_enum)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -79,6 +105,18 @@ impl Unit {
     }
   }
 
+  /// Length of one of this unit in meters, at full `f64` precision.
+  pub fn meters_per_unit(&self) -> f64 {
+    let one = match self {
+      Unit::Yards => uom::si::f64::Length::new::<yard>(1.0),
+      Unit::Feet => uom::si::f64::Length::new::<foot>(1.0),
+      Unit::Inches => uom::si::f64::Length::new::<inch>(1.0),
+      Unit::Meters => uom::si::f64::Length::new::<meter>(1.0),
+      Unit::Centimeters => uom::si::f64::Length::new::<centimeter>(1.0),
+    };
+    one.get::<meter>()
+  }
+
   /// Convert a value from one unit to another
   pub fn convert(&self, value: f32, to: &Unit) -> f32 {
     let from = self.get_unit(value);
@@ -141,6 +179,7 @@ impl Unit {
   ///
   /// Returns an error if the string is not recognized as a supported unit.
   /// Case-insensitive matching is performed.
+  #[allow(clippy::should_implement_trait)]
   pub fn from_str(s: &str) -> Result<Unit, TakeoffError> {
     match s.to_lowercase().as_str() {
       "yards" | "yard" | "yd" => Ok(Unit::Yards),
@@ -319,17 +358,28 @@ impl UnitFormatter {
 // #[napi(discriminant = "type")]
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum UnitValueItem {
-  Area { value: Area },
-  Length { value: Length },
-  Volume { value: Volume },
+  Area {
+    value: Area,
+  },
+  Length {
+    value: Length,
+  },
+  Volume {
+    value: Volume,
+  },
+  /// A dimensionless number (e.g. a piece count derived from a formula)
+  Scalar {
+    value: f64,
+  },
 }
 
 #[napi(string_enum)]
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UnitValueItemType {
   Area,
   Length,
   Volume,
+  Scalar,
 }
 
 #[napi]
@@ -358,6 +408,7 @@ impl UnitValue {
           value: unit.get_volume_unit(value as f32),
         },
       },
+      UnitValueItemType::Scalar => Self::from_scalar(value),
     }
   }
 
@@ -376,6 +427,27 @@ impl UnitValue {
       value: UnitValueItem::Volume { value },
     }
   }
+  pub fn from_scalar(value: f64) -> Self {
+    Self {
+      value: UnitValueItem::Scalar { value },
+    }
+  }
+
+  /// Get the underlying quantity.
+  pub fn item(&self) -> UnitValueItem {
+    self.value
+  }
+
+  /// Get the kind of quantity held by this value.
+  #[napi(getter)]
+  pub fn magnitude(&self) -> UnitValueItemType {
+    match self.value {
+      UnitValueItem::Area { .. } => UnitValueItemType::Area,
+      UnitValueItem::Length { .. } => UnitValueItemType::Length,
+      UnitValueItem::Volume { .. } => UnitValueItemType::Volume,
+      UnitValueItem::Scalar { .. } => UnitValueItemType::Scalar,
+    }
+  }
 
   #[napi]
   pub fn display(&self, unit: Unit) -> String {
@@ -395,6 +467,7 @@ impl UnitValue {
         value: unit.convert_volume_to_unit(value),
       }
       .format(),
+      UnitValueItem::Scalar { value } => value.to_string(),
     }
   }
 
@@ -404,6 +477,23 @@ impl UnitValue {
       UnitValueItem::Area { value } => to.convert_area_to_unit(value) as f64,
       UnitValueItem::Length { value } => to.convert_length_to_unit(value) as f64,
       UnitValueItem::Volume { value } => to.convert_volume_to_unit(value) as f64,
+      UnitValueItem::Scalar { value } => value,
+    }
+  }
+
+  /// Format the value for a label in an exported drawing, such as `12.50 ft` or
+  /// `3.00 sq ft`.
+  ///
+  /// Areas and volumes are written with `sq` and `cu` rather than `²` and `³` so the label
+  /// is plain ASCII, which R12 DXF text requires.
+  pub fn export_label(&self, unit: Unit, decimals: u32) -> String {
+    let value = self.get_converted_value(unit);
+    let decimals = decimals as usize;
+    match self.value {
+      UnitValueItem::Area { .. } => format!("{value:.decimals$} sq {}", unit.display()),
+      UnitValueItem::Length { .. } => format!("{value:.decimals$} {}", unit.display()),
+      UnitValueItem::Volume { .. } => format!("{value:.decimals$} cu {}", unit.display()),
+      UnitValueItem::Scalar { .. } => format!("{value:.decimals$}"),
     }
   }
 }
@@ -453,6 +543,36 @@ mod tests {
     let unit_value = UnitValue::new(1.0, Unit::Meters, UnitValueItemType::Volume);
     assert_eq!(unit_value.display(Unit::Meters), "1 m³");
     assert_eq!(unit_value.get_converted_value(Unit::Meters), 1.0);
+
+    let unit_value = UnitValue::new(12.0, Unit::Meters, UnitValueItemType::Scalar);
+    assert_eq!(unit_value.display(Unit::Feet), "12");
+    assert_eq!(unit_value.get_converted_value(Unit::Feet), 12.0);
+  }
+
+  #[test]
+  fn test_export_label() {
+    let area = UnitValue::new(1.0, Unit::Meters, UnitValueItemType::Area);
+    assert_eq!(area.export_label(Unit::Meters, 2), "1.00 sq m");
+    let length = UnitValue::new(1.0, Unit::Yards, UnitValueItemType::Length);
+    assert_eq!(length.export_label(Unit::Feet, 0), "3 ft");
+    let volume = UnitValue::new(2.5, Unit::Feet, UnitValueItemType::Volume);
+    assert_eq!(volume.export_label(Unit::Feet, 1), "2.5 cu ft");
+  }
+
+  #[test]
+  fn test_export_decimals() {
+    assert_eq!(export_decimals(None).unwrap(), DEFAULT_EXPORT_DECIMALS);
+    assert_eq!(
+      export_decimals(Some(MAX_EXPORT_DECIMALS)).unwrap(),
+      MAX_EXPORT_DECIMALS
+    );
+    assert_eq!(
+      export_decimals(Some(u32::MAX)),
+      Err(TakeoffError::InvalidDecimals {
+        decimals: u32::MAX,
+        max: MAX_EXPORT_DECIMALS,
+      })
+    );
   }
 
   #[test]
@@ -1,9 +1,13 @@
-use crate::coords::Point;
+use crate::coords::{DistanceTrait, Point};
+use crate::error::{TakeoffError, TakeoffResult};
 use crate::measurement::Measurement;
-use geo::LineString;
 use geo::Simplify;
+use geo::line_intersection::{LineIntersection, line_intersection};
+use geo::orient::{Direction, Orient};
+use geo::{BooleanOps, Coord, Line, LineString, MultiPolygon, Polygon as GeoPolygon};
 use napi::bindgen_prelude::Result;
 use napi_derive::napi;
+use serde::{Deserialize, Serialize};
 
 /// Simplify a polyline using the Ramer-Douglas-Peucker algorithm
 #[napi]
@@ -39,6 +43,381 @@ pub fn reposition_measurement_to_centroid(
     .map_err(Into::into)
 }
 
+/// The directions [`constrain_angle`] snaps to by default, in degrees: every 45°.
+pub const DEFAULT_CONSTRAINT_ANGLES: [f64; 4] = [0.0, 45.0, 90.0, 135.0];
+
+/// The relative change in area [`square_up_polygon`] allows by default.
+pub const DEFAULT_SQUARE_UP_AREA_TOLERANCE: f64 = 0.01;
+
+/// The largest angle, in degrees, by which an edge may stray from the polygon's axes to be
+/// squared up.
+pub const MAX_SQUARE_UP_EDGE_DEVIATION: f64 = 15.0;
+
+/// Constrain the segment from a previous point to the cursor to a set of directions.
+///
+/// The cursor is projected onto the nearest line through `previous` at one of the angles.
+/// Angles are in degrees from the positive x axis toward the positive y axis of the page;
+/// each angle also allows the opposite direction.
+///
+/// # Arguments
+///
+/// * `previous` - The point the segment starts at.
+/// * `cursor` - The unconstrained end of the segment.
+/// * `angles` - The allowed directions. Defaults to [`DEFAULT_CONSTRAINT_ANGLES`].
+#[napi]
+pub fn constrain_angle(previous: Point, cursor: Point, angles: Option<Vec<f64>>) -> Point {
+  let angles = angles
+    .filter(|angles| !angles.is_empty())
+    .unwrap_or_else(|| DEFAULT_CONSTRAINT_ANGLES.to_vec());
+  let (dx, dy) = (cursor.x - previous.x, cursor.y - previous.y);
+  let (cos, sin, along) = angles
+    .iter()
+    .map(|angle| {
+      let (sin, cos) = angle.to_radians().sin_cos();
+      (cos, sin, dx * cos + dy * sin)
+    })
+    // The longest projection is onto the nearest line
+    .max_by(|a, b| a.2.abs().total_cmp(&b.2.abs()))
+    .expect("BUG: the angle set is not empty");
+  Point::new(previous.x + along * cos, previous.y + along * sin)
+}
+
+/// Square up a nearly rectangular polygon measurement.
+///
+/// Edges are aligned to the polygon's dominant pair of perpendicular axes, so every corner
+/// becomes a right angle. Rectilinear outlines such as L-shapes are squared up too; vertices
+/// between two edges along the same axis are dropped. Rectangle measurements are returned
+/// unchanged.
+///
+/// # Arguments
+///
+/// * `measurement` - The polygon to square up.
+/// * `area_tolerance` - The relative change in area allowed. Defaults to
+///   [`DEFAULT_SQUARE_UP_AREA_TOLERANCE`].
+///
+/// # Errors
+///
+/// Returns [`TakeoffError::GeometryOperation`] if the measurement is not a polygon, an edge
+/// strays more than [`MAX_SQUARE_UP_EDGE_DEVIATION`] from the axes, or the area changes by
+/// more than the tolerance, and [`TakeoffError::EmptyGeometry`] if the polygon is invalid.
+#[napi]
+pub fn square_up_polygon(
+  measurement: Measurement,
+  area_tolerance: Option<f64>,
+) -> Result<Measurement> {
+  square_up(
+    measurement,
+    area_tolerance.unwrap_or(DEFAULT_SQUARE_UP_AREA_TOLERANCE),
+  )
+  .map_err(Into::into)
+}
+
+fn square_up(measurement: Measurement, area_tolerance: f64) -> TakeoffResult<Measurement> {
+  let fail = |message: &str| TakeoffError::geometry_operation("square up", message);
+  let points = match &measurement {
+    Measurement::Polygon { points, .. } => points.clone(),
+    Measurement::Rectangle { .. } => return Ok(measurement),
+    other => {
+      return Err(fail(&format!(
+        "cannot square up a {} measurement",
+        other.kind()
+      )));
+    }
+  };
+  let area = measurement.pixel_area()?;
+
+  let mut corners = points;
+  corners.dedup();
+  if corners.len() > 1 && corners.first() == corners.last() {
+    corners.pop();
+  }
+  let edge_angle = |i: usize| {
+    let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+    (b.y - a.y).atan2(b.x - a.x)
+  };
+
+  // The length-weighted mean of the edge directions, folded onto a quarter turn
+  let (sin, cos) = (0..corners.len()).fold((0.0, 0.0), |(sin, cos), i| {
+    let length = corners[i].distance_to(&corners[(i + 1) % corners.len()]);
+    let angle = 4.0 * edge_angle(i);
+    (sin + length * angle.sin(), cos + length * angle.cos())
+  });
+  let axis = sin.atan2(cos) / 4.0;
+
+  // Classify each edge as running along the first axis (true) or the second (false)
+  let quarter = std::f64::consts::FRAC_PI_2;
+  let mut along_first = Vec::with_capacity(corners.len());
+  for i in 0..corners.len() {
+    let turns = (edge_angle(i) - axis) / quarter;
+    if (turns - turns.round()).abs() * 90.0 > MAX_SQUARE_UP_EDGE_DEVIATION {
+      return Err(fail(&format!(
+        "edge {i} is more than {MAX_SQUARE_UP_EDGE_DEVIATION}° from the polygon's axes"
+      )));
+    }
+    along_first.push((turns.round() as i64).rem_euclid(2) == 0);
+  }
+
+  // Drop corners between two edges along the same axis
+  let n = corners.len();
+  let kept: Vec<usize> = (0..n)
+    .filter(|&i| along_first[(i + n - 1) % n] != along_first[i])
+    .collect();
+  if kept.len() < 4 {
+    return Err(fail("polygon has fewer than 4 corners"));
+  }
+
+  // Work in coordinates aligned to the axes, where each edge is horizontal or vertical
+  let (axis_sin, axis_cos) = axis.sin_cos();
+  let to_local = |p: Point| {
+    Point::new(
+      p.x * axis_cos + p.y * axis_sin,
+      -p.x * axis_sin + p.y * axis_cos,
+    )
+  };
+  let to_page = |p: Point| {
+    Point::new(
+      p.x * axis_cos - p.y * axis_sin,
+      p.x * axis_sin + p.y * axis_cos,
+    )
+  };
+  let local: Vec<Point> = corners.iter().map(|p| to_local(*p)).collect();
+  // Each merged edge runs from one kept corner to the next; its offset is the mean of the
+  // original vertices along it
+  let offsets: Vec<f64> = (0..kept.len())
+    .map(|k| {
+      let (start, end) = (kept[k], kept[(k + 1) % kept.len()]);
+      let span = (end + n - start) % n;
+      let vertices = (0..=span).map(|j| local[(start + j) % n]);
+      let sum: f64 = if along_first[start] {
+        vertices.map(|p| p.y).sum()
+      } else {
+        vertices.map(|p| p.x).sum()
+      };
+      sum / (span + 1) as f64
+    })
+    .collect();
+  let squared: Vec<Point> = (0..kept.len())
+    .map(|k| {
+      let previous = (k + kept.len() - 1) % kept.len();
+      let corner = if along_first[kept[k]] {
+        Point::new(offsets[previous], offsets[k])
+      } else {
+        Point::new(offsets[k], offsets[previous])
+      };
+      to_page(corner)
+    })
+    .collect();
+
+  let mut result = measurement;
+  if let Measurement::Polygon { points, .. } = &mut result {
+    *points = squared;
+  }
+  let squared_area = result.pixel_area()?;
+  if (squared_area - area).abs() > area_tolerance * area {
+    return Err(fail(&format!(
+      "area would change from {area} to {squared_area}, more than the tolerance of {area_tolerance}"
+    )));
+  }
+  Ok(result)
+}
+
+/// A boolean operation between two area measurements.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BooleanOperation {
+  /// The region covered by either measurement.
+  Union,
+  /// The region covered by the first measurement but not the second.
+  Difference,
+  /// The region covered by both measurements.
+  Intersection,
+  /// The region covered by exactly one of the measurements.
+  Xor,
+}
+
+/// Combine two polygon or rectangle measurements with a boolean operation.
+///
+/// Each part of the result becomes a new polygon measurement with a random id and the page,
+/// group and attributes of `a`. A hole is joined to its outline by a zero-width cut, so the
+/// polygon's area already excludes it. Results with no area, such as the intersection of
+/// disjoint measurements, are empty.
+///
+/// # Errors
+///
+/// Returns [`TakeoffError::GeometryOperation`] if a measurement is not a polygon or
+/// rectangle or the two are on different pages, and [`TakeoffError::EmptyGeometry`] if a
+/// measurement has invalid geometry.
+#[napi]
+pub fn boolean_op(
+  a: Measurement,
+  b: Measurement,
+  operation: BooleanOperation,
+) -> Result<Vec<Measurement>> {
+  boolean_measurements(&a, &b, operation).map_err(Into::into)
+}
+
+/// Combine two polygon or rectangle measurements with a boolean operation.
+///
+/// See [`boolean_op`].
+pub fn boolean_measurements(
+  a: &Measurement,
+  b: &Measurement,
+  operation: BooleanOperation,
+) -> TakeoffResult<Vec<Measurement>> {
+  let name = format!("{operation:?}").to_lowercase();
+  for measurement in [a, b] {
+    if !matches!(
+      measurement,
+      Measurement::Polygon { .. } | Measurement::Rectangle { .. }
+    ) {
+      return Err(TakeoffError::geometry_operation(
+        &name,
+        format!(
+          "measurement {} is a {}, not an area",
+          measurement.id(),
+          measurement.kind()
+        ),
+      ));
+    }
+  }
+  if a.page_id() != b.page_id() {
+    return Err(TakeoffError::geometry_operation(
+      &name,
+      format!(
+        "measurements {} and {} are on different pages",
+        a.id(),
+        b.id()
+      ),
+    ));
+  }
+  let (a_polygon, b_polygon) = (a.to_polygon()?, b.to_polygon()?);
+  let result = match operation {
+    BooleanOperation::Union => a_polygon.union(&b_polygon),
+    BooleanOperation::Difference => a_polygon.difference(&b_polygon),
+    BooleanOperation::Intersection => a_polygon.intersection(&b_polygon),
+    BooleanOperation::Xor => a_polygon.xor(&b_polygon),
+  };
+  Ok(polygons_to_measurements(&result, a))
+}
+
+/// Turn each polygon into a polygon measurement on the page and in the group of `template`.
+pub(crate) fn polygons_to_measurements(
+  polygons: &MultiPolygon<f64>,
+  template: &Measurement,
+) -> Vec<Measurement> {
+  polygons
+    .iter()
+    .map(keyhole)
+    .filter(|points| points.len() >= 3)
+    .map(|points| Measurement::Polygon {
+      id: generate_random_id(),
+      page_id: template.page_id().to_string(),
+      group_id: template.group_id().to_string(),
+      points,
+      attributes: template.attributes().cloned(),
+    })
+    .filter(|measurement| measurement.pixel_area().is_ok_and(|area| area > 0.0))
+    .collect()
+}
+
+/// Flatten a polygon with holes into a single outline.
+///
+/// Each hole, wound opposite to the outline, is spliced in through a cut to the outline.
+/// The cut joins the closest pair of outline and hole vertices whose segment crosses no
+/// other edge, so it stays clear of the other holes and of earlier cuts. It runs there and
+/// back, so it adds no area, and [`Measurement::pixel_perimeter`] leaves it out.
+fn keyhole(polygon: &GeoPolygon<f64>) -> Vec<Point> {
+  let polygon = polygon.orient(Direction::Default);
+  let open_ring = |ring: &LineString<f64>| -> Vec<Point> {
+    let mut points: Vec<Point> = ring.coords().map(|c| Point::from(*c)).collect();
+    if points.len() > 1 && points.first() == points.last() {
+      points.pop();
+    }
+    points
+  };
+  let mut outline = open_ring(polygon.exterior());
+  let mut holes: Vec<Vec<Point>> = polygon.interiors().iter().map(open_ring).collect();
+  while !holes.is_empty() {
+    let hole = holes.remove(0);
+    let mut pairs: Vec<(usize, usize)> = (0..outline.len())
+      .flat_map(|i| (0..hole.len()).map(move |j| (i, j)))
+      .collect();
+    pairs.sort_by(|&(i1, j1), &(i2, j2)| {
+      outline[i1]
+        .distance_to(&hole[j1])
+        .total_cmp(&outline[i2].distance_to(&hole[j2]))
+    });
+    let rings = || {
+      std::iter::once(&outline)
+        .chain(&holes)
+        .chain(std::iter::once(&hole))
+    };
+    let clear =
+      |&(i, j): &(usize, usize)| !rings().any(|ring| ring_blocks(ring, outline[i], hole[j]));
+    // Fall back to the closest pair if every cut crosses an edge
+    let Some((i, j)) = pairs
+      .iter()
+      .find(|pair| clear(pair))
+      .or(pairs.first())
+      .copied()
+    else {
+      continue;
+    };
+    let spliced: Vec<Point> = outline[..=i]
+      .iter()
+      .chain(&hole[j..])
+      .chain(&hole[..=j])
+      .chain(&outline[i..])
+      .copied()
+      .collect();
+    outline = spliced;
+  }
+  outline
+}
+
+/// Whether an edge of a closed ring crosses or touches the cut from `from` to `to` anywhere
+/// but at the cut's ends.
+fn ring_blocks(ring: &[Point], from: Point, to: Point) -> bool {
+  let cut = Line::new(Coord::from(from), Coord::from(to));
+  let ends = [cut.start, cut.end];
+  (0..ring.len()).any(|k| {
+    let edge = Line::new(
+      Coord::from(ring[k]),
+      Coord::from(ring[(k + 1) % ring.len()]),
+    );
+    match line_intersection(cut, edge) {
+      Some(LineIntersection::SinglePoint { intersection, .. }) => !ends.contains(&intersection),
+      Some(LineIntersection::Collinear { intersection }) => intersection.start != intersection.end,
+      None => false,
+    }
+  })
+}
+
+/// Split a polygon or rectangle measurement along a cut line.
+///
+/// See [`Measurement::split_by_line`].
+#[napi]
+pub fn split_polygon(measurement: Measurement, cut: Vec<Point>) -> Result<Vec<Measurement>> {
+  measurement.split_by_line(&cut).map_err(Into::into)
+}
+
+/// Split a polyline measurement in two at the point on it nearest to `point`.
+///
+/// See [`Measurement::split_at`].
+#[napi]
+pub fn split_polyline(measurement: Measurement, point: Point) -> Result<Vec<Measurement>> {
+  let (first, second) = measurement.split_at(point)?;
+  Ok(vec![first, second])
+}
+
+/// Merge two polylines that meet end to end, or two areas that share an edge.
+///
+/// See [`Measurement::merge`].
+#[napi]
+pub fn merge_measurements(a: Measurement, b: Measurement) -> Result<Measurement> {
+  a.merge(&b).map_err(Into::into)
+}
+
 /// Generate a random id
 #[napi]
 pub fn generate_random_id() -> String {
@@ -75,6 +454,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+      attributes: None,
     };
 
     let centroid = get_centroid(measurement);
@@ -88,6 +468,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)], // Only 2 points
+      attributes: None,
     };
 
     let centroid = get_centroid(measurement);
@@ -101,6 +482,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(2.0, 2.0)),
+      attributes: None,
     };
     let new_centroid = Point::new(10.0, 20.0);
     let result = reposition_measurement_to_centroid(measurement, new_centroid).unwrap();
@@ -115,8 +497,253 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+      attributes: None,
     };
     let result = reposition_measurement_to_centroid(measurement, Point::new(0.0, 0.0));
     assert!(result.is_err(), "empty geometry should yield error");
   }
+
+  fn assert_close(a: Point, b: Point) {
+    assert!(
+      (a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9,
+      "{a:?} != {b:?}"
+    );
+  }
+
+  fn polygon(points: Vec<Point>) -> Measurement {
+    Measurement::Polygon {
+      id: "1".to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points,
+      attributes: None,
+    }
+  }
+
+  fn polygon_points(measurement: &Measurement) -> &[Point] {
+    match measurement {
+      Measurement::Polygon { points, .. } => points,
+      _ => panic!("expected a polygon"),
+    }
+  }
+
+  #[test]
+  fn test_constrain_angle() {
+    let origin = Point::new(10.0, 10.0);
+    assert_close(
+      constrain_angle(origin, Point::new(20.0, 11.0), None),
+      Point::new(20.0, 10.0),
+    );
+    assert_close(
+      constrain_angle(origin, Point::new(9.0, 0.0), None),
+      Point::new(10.0, 0.0),
+    );
+    // Near the diagonal, snapped onto it keeping the projected length
+    assert_close(
+      constrain_angle(origin, Point::new(0.0, 1.0), None),
+      Point::new(0.5, 0.5),
+    );
+
+    let thirty = constrain_angle(origin, Point::new(20.0, 16.0), Some(vec![30.0, 120.0]));
+    let angle = (thirty.y - origin.y)
+      .atan2(thirty.x - origin.x)
+      .to_degrees();
+    assert!((angle - 30.0).abs() < 1e-9);
+    // An empty angle set falls back to the defaults
+    assert_close(
+      constrain_angle(origin, Point::new(20.0, 11.0), Some(vec![])),
+      Point::new(20.0, 10.0),
+    );
+  }
+
+  #[test]
+  fn test_square_up_polygon() {
+    // A rectangle drawn slightly off, rotated by about 30 degrees
+    let (sin, cos) = 30f64.to_radians().sin_cos();
+    let rotate = |x: f64, y: f64| Point::new(x * cos - y * sin, x * sin + y * cos);
+    let skewed = polygon(vec![
+      rotate(0.0, 0.0),
+      rotate(100.3, 0.4),
+      rotate(99.8, 50.2),
+      rotate(-0.2, 49.9),
+    ]);
+    let squared = square_up_polygon(skewed.clone(), None).unwrap();
+    let points = polygon_points(&squared);
+    assert_eq!(points.len(), 4);
+    for i in 0..4 {
+      let (a, b, c) = (points[i], points[(i + 1) % 4], points[(i + 2) % 4]);
+      let dot = (b.x - a.x) * (c.x - b.x) + (b.y - a.y) * (c.y - b.y);
+      assert!(dot.abs() < 1e-6, "corner {i} is not a right angle");
+    }
+    let ratio = squared.pixel_area().unwrap() / skewed.pixel_area().unwrap();
+    assert!((ratio - 1.0).abs() < DEFAULT_SQUARE_UP_AREA_TOLERANCE);
+
+    // An L-shape with a near-collinear extra vertex on its bottom edge
+    let l_shape = polygon(vec![
+      Point::new(0.0, 0.0),
+      Point::new(20.0, 0.3),
+      Point::new(40.0, 0.0),
+      Point::new(40.1, 20.0),
+      Point::new(20.0, 19.8),
+      Point::new(19.9, 40.0),
+      Point::new(0.2, 40.0),
+    ]);
+    let squared = square_up_polygon(l_shape, None).unwrap();
+    assert_eq!(polygon_points(&squared).len(), 6);
+    assert!((squared.pixel_area().unwrap() - 1200.0).abs() < 12.0);
+  }
+
+  #[test]
+  fn test_square_up_polygon_rejects() {
+    let triangle = polygon(vec![
+      Point::new(0.0, 0.0),
+      Point::new(10.0, 0.0),
+      Point::new(0.0, 10.0),
+    ]);
+    assert!(matches!(
+      square_up(triangle, DEFAULT_SQUARE_UP_AREA_TOLERANCE),
+      Err(TakeoffError::GeometryOperation { .. })
+    ));
+
+    // Corners within the deviation limit, but squaring up changes the area too much
+    let trapezoid = polygon(vec![
+      Point::new(0.0, 0.0),
+      Point::new(10.0, 0.0),
+      Point::new(12.0, 10.0),
+      Point::new(0.0, 10.0),
+    ]);
+    assert!(square_up_polygon(trapezoid.clone(), Some(0.2)).is_ok());
+    assert!(matches!(
+      square_up(trapezoid, 0.001),
+      Err(TakeoffError::GeometryOperation { .. })
+    ));
+
+    let rectangle = Measurement::Rectangle {
+      id: "1".to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+      attributes: None,
+    };
+    assert_eq!(
+      square_up_polygon(rectangle.clone(), None).unwrap(),
+      rectangle
+    );
+    let count = Measurement::Count {
+      id: "1".to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points: (Point::new(0.0, 0.0),),
+      attributes: None,
+    };
+    assert!(square_up_polygon(count, None).is_err());
+  }
+
+  fn rectangle(id: &str, min: (f64, f64), max: (f64, f64)) -> Measurement {
+    Measurement::Rectangle {
+      id: id.to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points: (Point::new(min.0, min.1), Point::new(max.0, max.1)),
+      attributes: None,
+    }
+  }
+
+  fn total_area(measurements: &[Measurement]) -> f64 {
+    measurements.iter().map(|m| m.pixel_area().unwrap()).sum()
+  }
+
+  #[test]
+  fn test_boolean_op() {
+    let a = rectangle("a", (0.0, 0.0), (10.0, 10.0));
+    let b = rectangle("b", (5.0, 0.0), (15.0, 10.0));
+
+    let union = boolean_op(a.clone(), b.clone(), BooleanOperation::Union).unwrap();
+    assert_eq!(union.len(), 1);
+    assert!((total_area(&union) - 150.0).abs() < 1e-9);
+    assert_eq!(union[0].group_id(), "1");
+    assert_ne!(union[0].id(), "a");
+
+    let difference = boolean_op(a.clone(), b.clone(), BooleanOperation::Difference).unwrap();
+    assert!((total_area(&difference) - 50.0).abs() < 1e-9);
+    let intersection = boolean_op(a.clone(), b.clone(), BooleanOperation::Intersection).unwrap();
+    assert!((total_area(&intersection) - 50.0).abs() < 1e-9);
+    let xor = boolean_op(a.clone(), b, BooleanOperation::Xor).unwrap();
+    assert_eq!(xor.len(), 2);
+    assert!((total_area(&xor) - 100.0).abs() < 1e-9);
+
+    let far = rectangle("far", (20.0, 20.0), (30.0, 30.0));
+    assert!(
+      boolean_op(a, far, BooleanOperation::Intersection)
+        .unwrap()
+        .is_empty()
+    );
+  }
+
+  #[test]
+  fn test_boolean_op_hole() {
+    use geo::Contains;
+
+    // Cutting a hole out of the middle leaves one outline whose area excludes the hole
+    let floor = rectangle("floor", (0.0, 0.0), (10.0, 10.0));
+    let column = rectangle("column", (3.0, 3.0), (7.0, 7.0));
+    let result = boolean_measurements(&floor, &column, BooleanOperation::Difference).unwrap();
+    assert_eq!(result.len(), 1);
+    assert!((total_area(&result) - 84.0).abs() < 1e-9);
+    let outline = result[0].to_polygon().unwrap();
+    assert!(outline.contains(&geo::Point::new(1.0, 1.0)));
+    assert!(!outline.contains(&geo::Point::new(5.0, 5.0)));
+  }
+
+  #[test]
+  fn test_boolean_op_hole_perimeter() {
+    // The outline and the hole's edges count, the cut between them does not
+    let floor = rectangle("floor", (0.0, 0.0), (10.0, 10.0));
+    let column = rectangle("column", (3.0, 3.0), (7.0, 7.0));
+    let result = boolean_measurements(&floor, &column, BooleanOperation::Difference).unwrap();
+    assert!((result[0].pixel_perimeter().unwrap() - (40.0 + 16.0)).abs() < 1e-9);
+  }
+
+  #[test]
+  fn test_boolean_op_two_holes() {
+    use crate::validity::{ValidityOptions, geometry_issues};
+
+    // The cut to the first hole must not run through the second one
+    let slab = rectangle("slab", (0.0, 0.0), (100.0, 100.0));
+    let first = rectangle("first", (89.7, 43.2), (97.42, 44.77));
+    let second = rectangle("second", (98.0, 26.3), (99.0, 76.7));
+    let once = boolean_measurements(&slab, &first, BooleanOperation::Difference).unwrap();
+    let twice = boolean_measurements(&once[0], &second, BooleanOperation::Difference).unwrap();
+    assert_eq!(twice.len(), 1);
+    assert!(geometry_issues(&twice[0], &ValidityOptions::default()).is_empty());
+    let holes = (97.42 - 89.7) * (44.77 - 43.2) + (99.0 - 98.0) * (76.7 - 26.3);
+    assert!((total_area(&twice) - (10000.0 - holes)).abs() < 1e-6);
+  }
+
+  #[test]
+  fn test_boolean_op_rejects() {
+    let a = rectangle("a", (0.0, 0.0), (10.0, 10.0));
+    let count = Measurement::Count {
+      id: "c".to_string(),
+      page_id: "1".to_string(),
+      group_id: "1".to_string(),
+      points: (Point::new(0.0, 0.0),),
+      attributes: None,
+    };
+    assert!(matches!(
+      boolean_measurements(&a, &count, BooleanOperation::Union),
+      Err(TakeoffError::GeometryOperation { .. })
+    ));
+    let other_page = Measurement::Rectangle {
+      id: "b".to_string(),
+      page_id: "2".to_string(),
+      group_id: "1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+      attributes: None,
+    };
+    assert!(matches!(
+      boolean_measurements(&a, &other_page, BooleanOperation::Union),
+      Err(TakeoffError::GeometryOperation { .. })
+    ));
+  }
 }
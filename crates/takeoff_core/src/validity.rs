@@ -0,0 +1,615 @@
+//! Geometric validity of measurements.
+//!
+//! [`Measurement::validate`] only checks that a measurement has enough points. The checks
+//! here also find outlines that cross themselves, repeated vertices, edges of almost zero
+//! length and slivers where an outline turns back on itself, and [`repair_measurement`]
+//! removes them.
+
+use crate::coords::{DistanceTrait, Point};
+use crate::error::{TakeoffError, TakeoffResult};
+use crate::measurement::Measurement;
+use crate::utils::polygons_to_measurements;
+use geo::line_intersection::{LineIntersection, line_intersection};
+use geo::{BooleanOps, Line, MultiPolygon};
+use napi::bindgen_prelude::Result;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+
+/// The default length in page pixels below which an edge counts as zero-length.
+pub const DEFAULT_MIN_SEGMENT_LENGTH: f64 = 1e-3;
+
+/// The default angle in degrees below which a vertex where the outline turns back counts
+/// as a sliver.
+pub const DEFAULT_SLIVER_ANGLE: f64 = 1.0;
+
+/// The kind of problem found in a measurement's geometry.
+#[napi(string_enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum GeometryIssueKind {
+  /// Two edges of a polygon outline cross or overlap.
+  SelfIntersection,
+  /// A vertex is at the same position as the vertex before it, or a polygon's last vertex
+  /// repeats its first.
+  DuplicateVertex,
+  /// An edge is shorter than the minimum segment length.
+  ZeroLengthSegment,
+  /// The outline turns back on itself at a vertex.
+  Sliver,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidityOptions {
+  /// The length in page pixels below which an edge counts as zero-length. Defaults to
+  /// [`DEFAULT_MIN_SEGMENT_LENGTH`].
+  pub min_segment_length: Option<f64>,
+  /// The angle in degrees below which a vertex counts as a sliver. Defaults to
+  /// [`DEFAULT_SLIVER_ANGLE`].
+  pub sliver_angle: Option<f64>,
+}
+
+impl ValidityOptions {
+  fn min_segment_length(&self) -> f64 {
+    self
+      .min_segment_length
+      .unwrap_or(DEFAULT_MIN_SEGMENT_LENGTH)
+  }
+
+  fn sliver_angle(&self) -> f64 {
+    self.sliver_angle.unwrap_or(DEFAULT_SLIVER_ANGLE)
+  }
+}
+
+/// A problem found in a measurement's geometry.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeometryIssue {
+  pub measurement_id: String,
+  pub kind: GeometryIssueKind,
+  /// The vertex at fault, or the vertex starting the edge at fault. Rectangle corners are
+  /// numbered from the first corner, through the corner sharing its x, to the second.
+  pub index: u32,
+  /// For self-intersections, the vertex starting the other edge.
+  pub other_index: Option<u32>,
+  /// Where the problem is, in page pixels.
+  pub point: Point,
+}
+
+impl From<GeometryIssue> for TakeoffError {
+  fn from(issue: GeometryIssue) -> Self {
+    let GeometryIssue {
+      measurement_id,
+      index,
+      point,
+      ..
+    } = issue;
+    match issue.kind {
+      GeometryIssueKind::SelfIntersection => TakeoffError::self_intersection(
+        measurement_id,
+        index,
+        issue.other_index.unwrap_or(index),
+        point,
+      ),
+      GeometryIssueKind::DuplicateVertex => {
+        TakeoffError::duplicate_vertex(measurement_id, index, point)
+      }
+      GeometryIssueKind::ZeroLengthSegment => {
+        TakeoffError::zero_length_segment(measurement_id, index, point)
+      }
+      GeometryIssueKind::Sliver => TakeoffError::sliver(measurement_id, index, point),
+    }
+  }
+}
+
+/// Find every problem in a measurement's geometry.
+///
+/// Only polygon outlines are checked for self-intersections; a polyline may cross itself.
+/// Outlines that merely touch themselves at a vertex, such as the zero-width cut that
+/// joins a hole to its outline, are not reported.
+///
+/// # Returns
+///
+/// * `Vec<GeometryIssue>` - The issues, sorted by vertex. Empty for counts and valid
+///   geometry.
+#[napi]
+pub fn find_geometry_issues(
+  measurement: Measurement,
+  options: Option<ValidityOptions>,
+) -> Vec<GeometryIssue> {
+  geometry_issues(&measurement, &options.unwrap_or_default())
+}
+
+/// Repair a measurement's geometry.
+///
+/// Repeated vertices, zero-length edges and slivers are removed. A polygon that still
+/// crosses itself is split into the regions it encloses; the largest keeps the
+/// measurement's id and the others are new polygons with random ids and the page, group
+/// and attributes of the original. Valid measurements are returned unchanged.
+///
+/// # Errors
+///
+/// Returns [`TakeoffError::EmptyGeometry`] if nothing with a length or area is left after
+/// the repair.
+#[napi]
+pub fn make_valid(
+  measurement: Measurement,
+  options: Option<ValidityOptions>,
+) -> Result<Vec<Measurement>> {
+  repair_measurement(&measurement, &options.unwrap_or_default()).map_err(Into::into)
+}
+
+/// Validate a measurement's geometry in depth.
+///
+/// # Errors
+///
+/// Returns the error from [`Measurement::validate`], or the first issue found by
+/// [`geometry_issues`] as a [`TakeoffError::SelfIntersection`],
+/// [`TakeoffError::DuplicateVertex`], [`TakeoffError::ZeroLengthSegment`] or
+/// [`TakeoffError::Sliver`].
+pub fn validate_geometry(
+  measurement: &Measurement,
+  options: &ValidityOptions,
+) -> TakeoffResult<()> {
+  measurement.validate()?;
+  match geometry_issues(measurement, options).into_iter().next() {
+    Some(issue) => Err(issue.into()),
+    None => Ok(()),
+  }
+}
+
+/// Find every problem in a measurement's geometry.
+///
+/// See [`find_geometry_issues`].
+pub fn geometry_issues(measurement: &Measurement, options: &ValidityOptions) -> Vec<GeometryIssue> {
+  let Some((points, closed)) = outline(measurement) else {
+    return vec![];
+  };
+  let issue = |kind, index: usize, other_index: Option<usize>, point| GeometryIssue {
+    measurement_id: measurement.id().to_string(),
+    kind,
+    index: index as u32,
+    other_index: other_index.map(|index| index as u32),
+    point,
+  };
+  let mut issues = Vec::new();
+
+  // The vertices left once repeated vertices and zero-length edges are skipped
+  let mut kept: Vec<usize> = Vec::new();
+  for (index, point) in points.iter().enumerate() {
+    if let Some(&last) = kept.last() {
+      let length = points[last].distance_to(point);
+      if length == 0.0 {
+        issues.push(issue(
+          GeometryIssueKind::DuplicateVertex,
+          index,
+          None,
+          *point,
+        ));
+        continue;
+      }
+      if length < options.min_segment_length() {
+        issues.push(issue(
+          GeometryIssueKind::ZeroLengthSegment,
+          last,
+          None,
+          points[last],
+        ));
+        continue;
+      }
+    }
+    kept.push(index);
+  }
+  if closed && kept.len() > 1 {
+    // The closing edge runs from the last vertex back to the first
+    let (first, last) = (kept[0], kept[kept.len() - 1]);
+    let length = points[last].distance_to(&points[first]);
+    if length == 0.0 {
+      issues.push(issue(
+        GeometryIssueKind::DuplicateVertex,
+        last,
+        None,
+        points[last],
+      ));
+      kept.pop();
+    } else if length < options.min_segment_length() {
+      issues.push(issue(
+        GeometryIssueKind::ZeroLengthSegment,
+        last,
+        None,
+        points[last],
+      ));
+      kept.pop();
+    }
+  }
+
+  let vertices: Vec<Point> = kept.iter().map(|&index| points[index]).collect();
+  for position in sliver_positions(&vertices, closed, options.sliver_angle()) {
+    let index = kept[position];
+    issues.push(issue(GeometryIssueKind::Sliver, index, None, points[index]));
+  }
+
+  if closed {
+    for (a, b, point) in crossings(&vertices) {
+      issues.push(issue(
+        GeometryIssueKind::SelfIntersection,
+        kept[a],
+        Some(kept[b]),
+        point,
+      ));
+    }
+  }
+
+  issues.sort_by_key(|issue| (issue.index, issue.kind, issue.other_index));
+  issues
+}
+
+/// Repair a measurement's geometry.
+///
+/// See [`make_valid`].
+pub fn repair_measurement(
+  measurement: &Measurement,
+  options: &ValidityOptions,
+) -> TakeoffResult<Vec<Measurement>> {
+  let no_remains = || {
+    TakeoffError::empty_geometry(format!(
+      "measurement {} has nothing left once repaired",
+      measurement.id()
+    ))
+  };
+  match measurement {
+    Measurement::Count { .. } => Ok(vec![measurement.clone()]),
+    Measurement::Rectangle { .. } => {
+      // A rectangle can only be invalid by having no width or height
+      if !geometry_issues(measurement, options).is_empty() {
+        return Err(no_remains());
+      }
+      measurement.validate()?;
+      Ok(vec![measurement.clone()])
+    }
+    Measurement::Polyline { points, .. } => {
+      let points = cleaned(points, false, options);
+      if points.len() < 2 {
+        return Err(no_remains());
+      }
+      Ok(vec![with_points(measurement, points)])
+    }
+    Measurement::Polygon { points, .. } => {
+      let points = cleaned(points, true, options);
+      if points.len() < 3 {
+        return Err(no_remains());
+      }
+      let repaired = with_points(measurement, points);
+      let crosses = geometry_issues(&repaired, options)
+        .iter()
+        .any(|issue| issue.kind == GeometryIssueKind::SelfIntersection);
+      if !crosses {
+        return if repaired.pixel_area()? > 0.0 {
+          Ok(vec![repaired])
+        } else {
+          Err(no_remains())
+        };
+      }
+
+      // Overlaying the outline on nothing splits it into the regions it encloses
+      let polygon = repaired.to_polygon()?;
+      let regions = polygon.union(&MultiPolygon::new(vec![]));
+      let mut pieces: Vec<(f64, Measurement)> = polygons_to_measurements(&regions, measurement)
+        .into_iter()
+        .map(|piece| (piece.pixel_area().unwrap_or(0.0), piece))
+        .collect();
+      pieces.sort_by(|a, b| b.0.total_cmp(&a.0));
+      let mut pieces: Vec<Measurement> = pieces.into_iter().map(|(_, piece)| piece).collect();
+      let Some(Measurement::Polygon { id, .. }) = pieces.first_mut() else {
+        return Err(no_remains());
+      };
+      *id = measurement.id().to_string();
+      Ok(pieces)
+    }
+  }
+}
+
+/// The vertices of a measurement's outline, without a closing vertex, and whether the
+/// outline is closed.
+fn outline(measurement: &Measurement) -> Option<(Vec<Point>, bool)> {
+  match measurement {
+    Measurement::Polygon { points, .. } => Some((points.clone(), true)),
+    Measurement::Polyline { points, .. } => Some((points.clone(), false)),
+    Measurement::Rectangle { points: (a, b), .. } => Some((
+      vec![*a, Point::new(a.x, b.y), *b, Point::new(b.x, a.y)],
+      true,
+    )),
+    Measurement::Count { .. } => None,
+  }
+}
+
+/// The same measurement with new points.
+fn with_points(measurement: &Measurement, new_points: Vec<Point>) -> Measurement {
+  let mut measurement = measurement.clone();
+  if let Measurement::Polygon { points, .. } | Measurement::Polyline { points, .. } =
+    &mut measurement
+  {
+    *points = new_points;
+  }
+  measurement
+}
+
+/// The positions of the vertices where the outline turns back on itself.
+fn sliver_positions(vertices: &[Point], closed: bool, sliver_angle: f64) -> Vec<usize> {
+  let count = vertices.len();
+  if count < 3 {
+    return vec![];
+  }
+  let positions = if closed { 0..count } else { 1..count - 1 };
+  positions
+    .filter(|&position| {
+      let vertex = vertices[position];
+      let previous = vertices[(position + count - 1) % count];
+      let next = vertices[(position + 1) % count];
+      let (ax, ay) = (previous.x - vertex.x, previous.y - vertex.y);
+      let (bx, by) = (next.x - vertex.x, next.y - vertex.y);
+      let angle = (ax * by - ay * bx).abs().atan2(ax * bx + ay * by);
+      angle.to_degrees() < sliver_angle
+    })
+    .collect()
+}
+
+/// Remove repeated vertices, zero-length edges and slivers until none are left.
+fn cleaned(points: &[Point], closed: bool, options: &ValidityOptions) -> Vec<Point> {
+  let too_short = |a: &Point, b: &Point| {
+    a.distance_to(b) == 0.0 || a.distance_to(b) < options.min_segment_length()
+  };
+  let mut points = points.to_vec();
+  loop {
+    let before = points.len();
+    let mut kept: Vec<Point> = Vec::with_capacity(before);
+    for point in points {
+      if kept.last().is_none_or(|last| !too_short(last, &point)) {
+        kept.push(point);
+      }
+    }
+    while closed && kept.len() > 1 && too_short(&kept[0], &kept[kept.len() - 1]) {
+      kept.pop();
+    }
+    if let Some(&position) = sliver_positions(&kept, closed, options.sliver_angle()).first() {
+      kept.remove(position);
+    }
+    points = kept;
+    if points.len() == before {
+      return points;
+    }
+  }
+}
+
+/// The pairs of edges of a closed outline that cross, by the positions of their first
+/// vertices, and where they cross.
+fn crossings(vertices: &[Point]) -> Vec<(usize, usize, Point)> {
+  let count = vertices.len();
+  if count < 4 {
+    return vec![];
+  }
+  let edge = |position: usize| Line::new(vertices[position], vertices[(position + 1) % count]);
+  let mut found = Vec::new();
+  for a in 0..count {
+    for b in a + 2..count {
+      if a == 0 && b == count - 1 {
+        continue;
+      }
+      let (first, second) = (edge(a), edge(b));
+      match line_intersection(first, second) {
+        Some(LineIntersection::SinglePoint {
+          intersection,
+          is_proper: true,
+        }) => found.push((a, b, intersection.into())),
+        Some(LineIntersection::SinglePoint { intersection, .. }) => {
+          // A vertex on the other edge: the outline crosses there if the vertex's
+          // neighbours are on opposite sides of that edge
+          let on_edge = |position: usize, other: &Line<f64>| {
+            let vertex = vertices[position];
+            if Point::from(intersection) != vertex
+              || Point::from(other.start) == vertex
+              || Point::from(other.end) == vertex
+            {
+              return false;
+            }
+            let side = |point: Point| {
+              let delta = other.delta();
+              delta.x * (point.y - other.start.y) - delta.y * (point.x - other.start.x)
+            };
+            let previous = side(vertices[(position + count - 1) % count]);
+            let next = side(vertices[(position + 1) % count]);
+            previous * next < 0.0
+          };
+          if on_edge(a, &second) || on_edge(b, &first) {
+            found.push((a, b, intersection.into()));
+          }
+        }
+        Some(LineIntersection::Collinear { intersection }) => {
+          // A zero-width cut to a hole runs along the same edge there and back
+          let reversed = first.start == second.end && first.end == second.start;
+          if !reversed && intersection.start != intersection.end {
+            found.push((a, b, intersection.start.into()));
+          }
+        }
+        None => {}
+      }
+    }
+  }
+  found
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn polygon(points: &[(f64, f64)]) -> Measurement {
+    Measurement::Polygon {
+      id: "m1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: points.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+      attributes: None,
+    }
+  }
+
+  fn kinds(measurement: &Measurement) -> Vec<(GeometryIssueKind, u32)> {
+    geometry_issues(measurement, &ValidityOptions::default())
+      .into_iter()
+      .map(|issue| (issue.kind, issue.index))
+      .collect()
+  }
+
+  #[test]
+  fn test_geometry_issues() {
+    let square = polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+    assert!(kinds(&square).is_empty());
+    assert!(validate_geometry(&square, &ValidityOptions::default()).is_ok());
+
+    let repeated = polygon(&[
+      (0.0, 0.0),
+      (10.0, 0.0),
+      (10.0, 0.0),
+      (10.0, 10.0),
+      (0.0, 10.0),
+    ]);
+    assert_eq!(kinds(&repeated), [(GeometryIssueKind::DuplicateVertex, 2)]);
+    // A closing vertex that repeats the first
+    let closed = polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 0.0)]);
+    assert_eq!(kinds(&closed), [(GeometryIssueKind::DuplicateVertex, 3)]);
+
+    let short = polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 0.0001), (10.0, 10.0)]);
+    assert_eq!(kinds(&short), [(GeometryIssueKind::ZeroLengthSegment, 1)]);
+
+    // A spike out to (20, 5) and straight back
+    let spike = polygon(&[
+      (0.0, 0.0),
+      (10.0, 0.0),
+      (10.0, 5.0),
+      (20.0, 5.0),
+      (10.0, 5.0),
+      (10.0, 10.0),
+    ]);
+    assert!(kinds(&spike).contains(&(GeometryIssueKind::Sliver, 3)));
+
+    let bowtie = polygon(&[(0.0, 0.0), (10.0, 10.0), (10.0, 0.0), (0.0, 10.0)]);
+    let issues = geometry_issues(&bowtie, &ValidityOptions::default());
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].kind, GeometryIssueKind::SelfIntersection);
+    assert_eq!((issues[0].index, issues[0].other_index), (0, Some(2)));
+    assert_eq!(issues[0].point, Point::new(5.0, 5.0));
+    assert!(matches!(
+      validate_geometry(&bowtie, &ValidityOptions::default()),
+      Err(TakeoffError::SelfIntersection {
+        index: 0,
+        other_index: 2,
+        ..
+      })
+    ));
+
+    // The outline crosses the first edge exactly at vertex 4
+    let through_vertex = polygon(&[
+      (0.0, 5.0),
+      (10.0, 5.0),
+      (10.0, 0.0),
+      (5.0, 0.0),
+      (5.0, 5.0),
+      (5.0, 10.0),
+      (0.0, 10.0),
+    ]);
+    let issues = geometry_issues(&through_vertex, &ValidityOptions::default());
+    assert_eq!(issues.len(), 1);
+    assert_eq!((issues[0].index, issues[0].other_index), (0, Some(4)));
+
+    let zero_width = Measurement::Rectangle {
+      id: "r1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(0.0, 10.0)),
+      attributes: None,
+    };
+    assert_eq!(
+      kinds(&zero_width),
+      [
+        (GeometryIssueKind::DuplicateVertex, 2),
+        (GeometryIssueKind::DuplicateVertex, 3)
+      ]
+    );
+  }
+
+  #[test]
+  fn test_keyhole_is_valid() {
+    // A square with a square hole, joined to it by a cut from (0, 0) to (3, 3)
+    let keyhole = polygon(&[
+      (0.0, 0.0),
+      (3.0, 3.0),
+      (3.0, 7.0),
+      (7.0, 7.0),
+      (7.0, 3.0),
+      (3.0, 3.0),
+      (0.0, 0.0),
+      (10.0, 0.0),
+      (10.0, 10.0),
+      (0.0, 10.0),
+    ]);
+    assert!(
+      kinds(&keyhole)
+        .iter()
+        .all(|(kind, _)| *kind != GeometryIssueKind::SelfIntersection)
+    );
+  }
+
+  #[test]
+  fn test_make_valid() {
+    let options = ValidityOptions::default();
+
+    let spike = polygon(&[
+      (0.0, 0.0),
+      (10.0, 0.0),
+      (10.0, 5.0),
+      (20.0, 5.0),
+      (10.0, 5.0),
+      (10.0, 10.0),
+      (0.0, 10.0),
+      (0.0, 10.0),
+    ]);
+    let repaired = repair_measurement(&spike, &options).unwrap();
+    assert_eq!(repaired.len(), 1);
+    assert_eq!(repaired[0].id(), "m1");
+    assert!(geometry_issues(&repaired[0], &options).is_empty());
+    assert!((repaired[0].pixel_area().unwrap() - 100.0).abs() < 1e-9);
+
+    // A bowtie becomes its two triangles
+    let bowtie = polygon(&[(0.0, 0.0), (20.0, 10.0), (20.0, 0.0), (0.0, 10.0)]);
+    let pieces = repair_measurement(&bowtie, &options).unwrap();
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(pieces[0].id(), "m1");
+    assert_ne!(pieces[1].id(), "m1");
+    let total: f64 = pieces.iter().map(|p| p.pixel_area().unwrap()).sum();
+    assert!((total - 100.0).abs() < 1e-9);
+    assert!(
+      pieces
+        .iter()
+        .all(|piece| geometry_issues(piece, &options).is_empty())
+    );
+
+    let polyline = Measurement::Polyline {
+      id: "l1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![
+        Point::new(0.0, 0.0),
+        Point::new(0.0, 0.0),
+        Point::new(10.0, 0.0),
+      ],
+      attributes: None,
+    };
+    let repaired = repair_measurement(&polyline, &options).unwrap();
+    assert_eq!(repaired[0].pixel_perimeter().unwrap(), 10.0);
+    assert!(geometry_issues(&repaired[0], &options).is_empty());
+
+    let collapsed = polygon(&[(0.0, 0.0), (10.0, 0.0), (0.0, 0.0)]);
+    assert!(matches!(
+      repair_measurement(&collapsed, &options),
+      Err(TakeoffError::EmptyGeometry { .. })
+    ));
+  }
+}
@@ -1,5 +1,21 @@
 /* auto-generated by NAPI-RS */
 /* eslint-disable */
+/** Totals for the measurements sharing one value of an attribute. */
+export declare class AttributeAggregate {
+  /** Get the attribute value shared by this bucket, or `None` for measurements without it. */
+  get value(): AttributeValue | null;
+  /** Get the ids of the measurements in this bucket, sorted. */
+  get measurementIds(): Array<string>;
+  /** Get the number of measurements in this bucket. */
+  get count(): number;
+  /** Get the total points of the measurements in this bucket. */
+  get points(): number;
+  /** Get the total area, or `None` if no measurement in this bucket has a scaled area. */
+  get area(): UnitValue | null;
+  /** Get the total length, or `None` if no measurement in this bucket has a scaled length. */
+  get length(): UnitValue | null;
+}
+
 export declare class ContourWrapper {
   /** Create a new contour wrapper from a contour input. */
   constructor(contour: ContourInput);
@@ -12,6 +28,8 @@ export declare class ContourWrapper {
   get id(): string;
   /** Get the page id of the contour. */
   get pageId(): string;
+  /** Get the input the contour was created from. */
+  get contour(): ContourInput;
   /** Get the scale of the contour. */
   get scale(): Scale | null;
   /** Get the surface points of the contour. */
@@ -35,6 +53,22 @@ export declare class ContourWrapper {
     reference: ReferenceSurfaceInput,
     cellSize?: number | undefined | null,
   ): VolumetricUnitResult | null;
+  /**
+   * Render a cut/fill map against a reference surface as a PNG or raw RGBA image.
+   *
+   * The reference is interpreted as in [`volume_against`](Self::volume_against). Depths
+   * in the options and the legend are in the unit of the contour's scale.
+   * Returns None if surface mesh or scale is not available.
+   *
+   * # Errors
+   *
+   * Returns an error if the map cannot be rendered or encoded
+   * ([`TakeoffError::ImageExport`](takeoff_core::TakeoffError::ImageExport)).
+   */
+  getCutFillHeatmap(
+    reference: ReferenceSurfaceInput,
+    options?: HeatmapOptions | undefined | null,
+  ): HeatmapImage | null;
 }
 
 export declare class GroupWrapper {
@@ -64,7 +98,30 @@ export declare class GroupWrapper {
    * Returns `None` if the count has not been computed or if the mutex is poisoned.
    */
   get count(): number | null;
+  /**
+   * Get the volume for this group: its area extruded by its `depth` attribute.
+   *
+   * Returns `None` if the area has not been computed or the group has no length-valued
+   * `depth` attribute.
+   */
+  get volume(): UnitValue | null;
   get group(): Group;
+  /** Evaluate a custom quantity formula (e.g. `area * 1.1 / 32sf`) against this group's totals. */
+  evaluateFormula(formula: string): UnitValue;
+  /**
+   * Find the area measurements of this group that overlap, which the group area counts twice.
+   *
+   * # Arguments
+   *
+   * * `min_pixel_area` - The smallest overlap to report, in square page pixels. Defaults to 0.
+   */
+  getOverlaps(minPixelArea?: number | undefined | null): Array<MeasurementOverlap>;
+  /**
+   * Get the area covered by this group, counting regions outlined more than once only once.
+   *
+   * Returns `None` if no area measurement of the group has a scale.
+   */
+  get dissolvedArea(): UnitValue | null;
 }
 
 export declare class MeasurementWrapper {
@@ -81,6 +138,14 @@ export declare class MeasurementWrapper {
   get groupId(): string;
   get rawArea(): number;
   get rawPerimeter(): number;
+  /**
+   * Get the attributes of this measurement merged over those of its group.
+   *
+   * Keys set on the measurement override the group's value.
+   */
+  getResolvedAttributes(): Record<string, AttributeValue>;
+  /** Evaluate a custom quantity formula (e.g. `ceil(length / 16in) + 1`) for this measurement. */
+  evaluateFormula(formula: string): UnitValue;
 }
 
 export declare class TakeoffStateHandler {
@@ -94,8 +159,222 @@ export declare class TakeoffStateHandler {
    * # Returns
    *
    * * `State` - The new state.
+   *
+   * # Errors
+   *
+   * Returns an error if:
+   * - References are strict and a measurement, scale or contour references a page or group
+   *   that is not in the options ([`TakeoffError::DanglingReference`])
+   * - The group validation mode is `Reject` and a measurement's group does not accept its
+   *   kind ([`TakeoffError::IncompatibleMeasurementType`])
    */
   constructor(options?: StateOptions | undefined | null);
+  /**
+   * Creates a state from a snapshot.
+   *
+   * Computed results are not read from the snapshot; they are recomputed from its entities.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::UnsupportedSnapshotVersion`] if the snapshot is newer than this
+   * build supports, and [`TakeoffError::DanglingReference`] if its references are strict
+   * and one of its entities references a missing page or group.
+   */
+  static fromSnapshot(snapshot: StateSnapshot): TakeoffStateHandler;
+  /**
+   * Creates a state from a snapshot serialized with
+   * [`to_snapshot_json`](Self::to_snapshot_json).
+   *
+   * # Errors
+   *
+   * Returns an error if the document is not a valid snapshot
+   * ([`TakeoffError::InvalidSnapshot`]) or is newer than this build supports
+   * ([`TakeoffError::UnsupportedSnapshotVersion`]).
+   */
+  static fromSnapshotJson(json: string): TakeoffStateHandler;
+  /**
+   * Get a snapshot of every entity, setting and computed result in the state.
+   *
+   * Entities and results are sorted by id, so equal states produce equal snapshots.
+   */
+  toSnapshot(): StateSnapshot;
+  /** Get a snapshot of the state serialized as JSON. */
+  toSnapshotJson(): string;
+  /**
+   * Creates a state from a project file, upgrading older schema versions first.
+   *
+   * # Arguments
+   *
+   * * `data` - The encoded project file.
+   * * `format` - The encoding of `data`. Defaults to `Json`.
+   *
+   * # Errors
+   *
+   * Returns an error if the file is not a valid project file
+   * ([`TakeoffError::InvalidProjectFile`]) or was written with a newer schema
+   * ([`TakeoffError::UnsupportedProjectVersion`]).
+   */
+  static fromProjectFile(data: Buffer, format?: ProjectFormat | undefined | null): TakeoffStateHandler;
+  /**
+   * Encode the state as a project file at the current schema version.
+   *
+   * # Arguments
+   *
+   * * `format` - The encoding to write. Defaults to `Json`.
+   */
+  toProjectFile(format?: ProjectFormat | undefined | null): Buffer;
+  /**
+   * Compare an earlier snapshot with the current state.
+   *
+   * # Arguments
+   *
+   * * `before` - The earlier snapshot, e.g. the bid set.
+   * * `unit` - The unit to report quantities in. Defaults to `Meters`.
+   */
+  diffSnapshot(before: StateSnapshot, unit?: Unit | undefined | null): SnapshotDiff;
+  /**
+   * Compare two snapshots.
+   *
+   * # Arguments
+   *
+   * * `before` - The earlier snapshot, e.g. the bid set.
+   * * `after` - The later snapshot, e.g. the revised set.
+   * * `unit` - The unit to report quantities in. Defaults to `Meters`.
+   */
+  static diffSnapshots(
+    before: StateSnapshot,
+    after: StateSnapshot,
+    unit?: Unit | undefined | null,
+  ): SnapshotDiff;
+  /**
+   * Export a quantity report of the groups or measurements as CSV or XLSX.
+   *
+   * # Arguments
+   *
+   * * `options` - The format, level, unit, columns and sort order of the report.
+   *
+   * # Errors
+   *
+   * Returns an error if:
+   * - `decimals` exceeds the export maximum ([`TakeoffError::InvalidDecimals`])
+   * - The report cannot be encoded ([`TakeoffError::ReportExport`])
+   */
+  exportReport(options?: ReportOptions | undefined | null): Buffer;
+  /**
+   * Export the measurements of a page as a GeoJSON feature collection.
+   *
+   * Coordinates are page pixels. Each feature's properties hold the measurement's ids,
+   * kind, scale, attributes and its real-world `area` and `length`.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The page to export.
+   * * `unit` - The unit of the `area` and `length` properties. Defaults to `Meters`.
+   */
+  exportGeojson(pageId: string, unit?: Unit | undefined | null): string;
+  /**
+   * Import the features of a GeoJSON document as measurements on a page.
+   *
+   * Point, LineString and Polygon features become count, polyline and polygon
+   * measurements, and are upserted together as with
+   * [`upsert_measurements`](Self::upsert_measurements).
+   *
+   * # Arguments
+   *
+   * * `page_id` - The page to place the measurements on.
+   * * `geojson` - A GeoJSON feature collection or feature.
+   * * `group_id` - The group of features without a `group_id` property.
+   *
+   * # Returns
+   *
+   * * `Vec<Measurement>` - The imported measurements.
+   *
+   * # Errors
+   *
+   * Returns an error, leaving the state unchanged, if a feature cannot be imported
+   * ([`TakeoffError::InvalidGeoJson`]) or the measurements are rejected by
+   * [`apply_batch`](Self::apply_batch).
+   */
+  importGeojson(
+    pageId: string,
+    geojson: string,
+    groupId?: string | undefined | null,
+  ): Array<Measurement>;
+  /**
+   * Export the measurements of a page as a DXF drawing, for overlaying on the design
+   * drawings.
+   *
+   * Each group is drawn on its own layer, named after the group. Shapes are labelled with
+   * their area or length and count markers are block inserts. Coordinates are converted
+   * to real-world drawing units through each measurement's scale.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The page to export.
+   * * `options` - The drawing unit, label height and decimals. The drawing unit defaults
+   *   to the unit of the page's default scale, or feet.
+   *
+   * # Errors
+   *
+   * Returns an error if:
+   * - A measurement has invalid geometry ([`TakeoffError::EmptyGeometry`])
+   * - A measurement's scale is invalid ([`TakeoffError::InvalidScale`])
+   * - `text_height` is not positive ([`TakeoffError::InvalidDxf`])
+   * - `decimals` exceeds the export maximum ([`TakeoffError::InvalidDecimals`])
+   */
+  exportDxf(
+    pageId: string,
+    options?: DxfExportOptions | undefined | null,
+  ): string;
+  /**
+   * Render the measurements, area scale viewports and contours of a page as SVG.
+   *
+   * The drawing is in page pixels, sized to the page when its dimensions are known. Each
+   * group is drawn in its own style, and measurements are labelled with their quantities
+   * at their centroid.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The page to render.
+   * * `options` - The label unit and decimals, group styles and which layers to draw.
+   *
+   * # Errors
+   *
+   * Returns an error if:
+   * - A measurement has invalid geometry ([`TakeoffError::EmptyGeometry`])
+   * - `decimals` exceeds the export maximum ([`TakeoffError::InvalidDecimals`])
+   */
+  renderSvg(pageId: string, options?: SvgOptions | undefined | null): string;
+  /**
+   * Import the polylines, hatches and block references of a DXF drawing onto a page.
+   *
+   * Closed polylines and hatch boundaries become polygons, open polylines become
+   * polylines and block references become count markers. The measurements, the groups of
+   * their layers that do not exist yet and, if the page has no default scale, a scale from
+   * the drawing's `$INSUNITS` header are added together as with
+   * [`apply_batch`](Self::apply_batch).
+   *
+   * # Arguments
+   *
+   * * `page_id` - The page to place the measurements on.
+   * * `data` - The contents of an ASCII DXF file.
+   * * `options` - The page pixels per drawing unit and the group of each layer.
+   *
+   * # Returns
+   *
+   * * `DxfImport` - Everything read from the drawing, and warnings for skipped entities.
+   *
+   * # Errors
+   *
+   * Returns an error, leaving the state unchanged, if the drawing cannot be read
+   * ([`TakeoffError::InvalidDxf`]) or the batch is rejected by
+   * [`apply_batch`](Self::apply_batch).
+   */
+  importDxf(
+    pageId: string,
+    data: Buffer,
+    options?: DxfImportOptions | undefined | null,
+  ): DxfImport;
   getMeasurementsByGroupId(groupId: string): Array<MeasurementWrapper>;
   /**
    * Get the measurements by page id.
@@ -109,6 +388,105 @@ export declare class TakeoffStateHandler {
    * * `Vec<MeasurementWrapper>` - The measurements that are on the page.
    */
   getMeasurementsByPageId(pageId: string): Array<MeasurementWrapper>;
+  /**
+   * Get the measurements on a page within a distance of a point, nearest first.
+   *
+   * A point inside a polygon or rectangle is at distance 0 from it.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The id of the page.
+   * * `point` - The point in page pixels.
+   * * `tolerance` - The maximum distance in pixels, 0 by default.
+   */
+  measurementsAtPoint(
+    pageId: string,
+    point: Point,
+    tolerance?: number | undefined | null,
+  ): Array<MeasurementWrapper>;
+  /**
+   * Get the measurements on a page whose geometry intersects a rectangle, sorted by id.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The id of the page.
+   * * `min` - A corner of the rectangle in page pixels.
+   * * `max` - The opposite corner of the rectangle.
+   */
+  measurementsInRect(pageId: string, min: Point, max: Point): Array<MeasurementWrapper>;
+  /**
+   * Get the measurement on a page nearest to a point.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The id of the page.
+   * * `point` - The point in page pixels.
+   * * `max_distance` - The maximum distance in pixels, unlimited by default.
+   */
+  nearestMeasurement(
+    pageId: string,
+    point: Point,
+    maxDistance?: number | undefined | null,
+  ): MeasurementWrapper | null;
+  /**
+   * Snap a cursor to the measurements and contours on a page.
+   *
+   * Vertices win over intersections, midpoints, perpendicular feet and contour lines, in that
+   * order; among candidates of the same kind the nearest wins.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The id of the page.
+   * * `cursor` - The cursor in page pixels.
+   * * `tolerance` - The maximum snap distance in pixels.
+   * * `options` - The kinds to snap to and the measurements to ignore.
+   *
+   * # Returns
+   *
+   * * `None` - If nothing is within the tolerance.
+   * * `Some(candidate)` - The snapped point and what it lies on.
+   */
+  snapPoint(
+    pageId: string,
+    cursor: Point,
+    tolerance: number,
+    options?: SnapOptions | undefined | null,
+  ): SnapCandidate | null;
+  /**
+   * Combine two polygon or rectangle measurements with a boolean operation.
+   *
+   * The results are not added to the state. Each is scaled with the scale its page would
+   * resolve for it, so its area is the real-world area it would have once upserted.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::GeometryOperation`] if a measurement is not an area or the two
+   * are on different pages, and [`TakeoffError::EmptyGeometry`] if a measurement has
+   * invalid geometry.
+   */
+  booleanOp(
+    a: Measurement,
+    b: Measurement,
+    operation: BooleanOperation,
+  ): Array<MeasurementWrapper>;
+  /**
+   * Offset a polygon, rectangle or polyline measurement by a real-world distance.
+   *
+   * The distance is converted to page pixels with the scale the measurement's page
+   * resolves for it. Positive distances grow areas and move polylines to the right of
+   * their drawing direction. The results are not added to the state.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::GeometryOperation`] if the measurement is a count or no scale
+   * applies to it, and [`TakeoffError::EmptyGeometry`] if it has invalid geometry.
+   */
+  offsetMeasurement(
+    measurement: Measurement,
+    distance: number,
+    unit: Unit,
+    join?: OffsetJoin | undefined | null,
+  ): Array<MeasurementWrapper>;
   /**
    * Get the scale for a measurement.
    *
@@ -135,6 +513,26 @@ export declare class TakeoffStateHandler {
    * * `Some(page)` - If the page was found and updated.
    */
   upsertPage(page: Page): Page | null;
+  /**
+   * Removes a page from the state.
+   *
+   * With the default `Legacy` removal policy only the page is removed. With `Cascade` the
+   * page's measurements, scales and contours are removed too, as a single history step;
+   * with `Reject` the page is kept while any of them remain.
+   *
+   * # Arguments
+   *
+   * * `page_id` - The id of the page to remove.
+   *
+   * # Returns
+   * * `None` - If the page was not found.
+   * * `Some(page)` - If the page was found and removed.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::EntityInUse`] if the removal policy is `Reject` and the page
+   * still has dependents.
+   */
   removePage(pageId: string): Page | null;
   getGroup(groupId: string): GroupWrapper | null;
   /**
@@ -148,11 +546,19 @@ export declare class TakeoffStateHandler {
    *
    * * `None` - If the group was not found.
    * * `Some(group)` - If the group was found and updated.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::IncompatibleMeasurementType`] if the group validation mode is
+   * `Reject` and the group's new measurement type does not accept one of its measurements.
    */
   upsertGroup(group: Group): Group | null;
   /**
    * Removes a group from the state.
    *
+   * Unless the removal policy is `Reject`, the group's measurements are removed too, as a
+   * single history step.
+   *
    * # Arguments
    *
    * * `group_id` - The id of the group to remove.
@@ -160,6 +566,11 @@ export declare class TakeoffStateHandler {
    * # Returns
    * * `None` - If the group was not found.
    * * `Some(group)` - If the group was found and removed.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::EntityInUse`] if the removal policy is `Reject` and the group
+   * still has measurements.
    */
   removeGroup(groupId: string): Group | null;
   /**
@@ -173,6 +584,14 @@ export declare class TakeoffStateHandler {
    *
    * * `None` - If the measurement was not found.
    * * `Some(measurement)` - If the measurement was found and updated.
+   *
+   * # Errors
+   *
+   * Returns an error if:
+   * - References are strict and the measurement's page or group does not exist
+   *   ([`TakeoffError::DanglingReference`])
+   * - The group validation mode is `Reject` and the measurement's group does not accept
+   *   its kind ([`TakeoffError::IncompatibleMeasurementType`])
    */
   upsertMeasurement(measurement: Measurement): Measurement | null;
   /**
@@ -200,6 +619,11 @@ export declare class TakeoffStateHandler {
    *
    * * `None` - If the scale was not found.
    * * `Some(scale)` - If the scale was found and updated.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::DanglingReference`] if references are strict and the scale's
+   * page does not exist.
    */
   upsertScale(scale: Scale): Scale | null;
   /**
@@ -222,11 +646,222 @@ export declare class TakeoffStateHandler {
    * * `Vec<MeasurementWrapper>` - The measurements that are missing a scale.
    */
   getMeasurementsMissingScale(): Array<MeasurementWrapper>;
+  /**
+   * Set how measurements incompatible with their group's measurement type are handled.
+   *
+   * Existing measurements are not re-validated; use
+   * [`get_measurement_type_mismatches`](Self::get_measurement_type_mismatches) to find them.
+   */
+  setGroupValidationMode(mode: GroupValidationMode): void;
+  getGroupValidationMode(): GroupValidationMode;
+  /**
+   * Get the measurements whose kind does not match their group's measurement type.
+   *
+   * Measurements whose group does not exist are not reported.
+   *
+   * # Returns
+   *
+   * * `Vec<MeasurementTypeMismatch>` - The mismatches, sorted by measurement id.
+   */
+  getMeasurementTypeMismatches(): Array<MeasurementTypeMismatch>;
+  /** Set what happens to dependents when a page or group is removed. */
+  setRemovalPolicy(policy: RemovalPolicy): void;
+  getRemovalPolicy(): RemovalPolicy;
+  /**
+   * Set whether upserts referencing a missing page or group are rejected.
+   *
+   * Existing entities are not re-validated; use [`validate_state`](Self::validate_state)
+   * to find dangling references.
+   */
+  setStrictReferences(strict: boolean): void;
+  getStrictReferences(): boolean;
+  /**
+   * Find every reference to a page or group that does not exist.
+   *
+   * # Returns
+   *
+   * * `Vec<DanglingReference>` - The dangling references of measurements, scales and
+   *   contours, sorted by entity kind and id. Empty if the state is consistent.
+   */
+  validateState(): Array<DanglingReference>;
+  /**
+   * Find every problem in the geometry of the measurements, such as self-intersecting
+   * outlines, repeated vertices, zero-length edges and slivers.
+   *
+   * # Returns
+   *
+   * * `Vec<GeometryIssue>` - The issues, sorted by measurement id and vertex. Empty if
+   *   every measurement is valid.
+   */
+  validateGeometry(
+    options?: ValidityOptions | undefined | null,
+  ): Array<GeometryIssue>;
+  /**
+   * Find scales that make scale resolution ambiguous, such as several default scales on
+   * one page or partially overlapping area scales.
+   *
+   * Conflicts are warnings: measurements and contours are still assigned a scale
+   * deterministically, preferring the smallest area scale that contains them, then the
+   * default scale with the smallest id.
+   *
+   * # Returns
+   *
+   * * `Vec<ScaleConflict>` - The conflicts, sorted by page id and scale ids.
+   */
+  getScaleConflicts(): Array<ScaleConflict>;
+  /**
+   * Get the measurements whose resolved attributes match every filter.
+   *
+   * A measurement's resolved attributes are its own attributes merged over its group's.
+   *
+   * # Arguments
+   *
+   * * `filters` - The filters to apply. An empty list matches every measurement.
+   *
+   * # Returns
+   *
+   * * `Vec<MeasurementWrapper>` - The matching measurements.
+   */
+  getMeasurementsByAttributes(
+    filters: Array<AttributeFilter>,
+  ): Array<MeasurementWrapper>;
+  /**
+   * Get the groups whose attributes match every filter.
+   *
+   * # Arguments
+   *
+   * * `filters` - The filters to apply. An empty list matches every group.
+   *
+   * # Returns
+   *
+   * * `Vec<GroupWrapper>` - The matching groups.
+   */
+  getGroupsByAttributes(filters: Array<AttributeFilter>): Array<GroupWrapper>;
+  /**
+   * Aggregate measurement totals by the value of an attribute.
+   *
+   * # Arguments
+   *
+   * * `key` - The attribute to bucket by.
+   * * `filters` - Optional filters restricting which measurements are aggregated.
+   *
+   * # Returns
+   *
+   * * `Vec<AttributeAggregate>` - One bucket per distinct value, plus one for measurements
+   *   without the attribute.
+   */
+  aggregateMeasurementsByAttribute(
+    key: string,
+    filters?: Array<AttributeFilter> | undefined | null,
+  ): Array<AttributeAggregate>;
+  /**
+   * Inserts or updates a contour in the state.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::DanglingReference`] if references are strict and the contour's
+   * page does not exist.
+   */
   upsertContour(input: ContourInput): void;
   removeContour(contourId: string): boolean;
   getContour(contourId: string): ContourWrapper | null;
   getContoursByPageId(pageId: string): Array<ContourWrapper>;
   getContoursMissingScale(): Array<ContourWrapper>;
+  /**
+   * Inserts or updates many measurements with a single recomputation pass.
+   *
+   * See [`apply_batch`](Self::apply_batch).
+   */
+  upsertMeasurements(measurements: Array<Measurement>): void;
+  /**
+   * Inserts or updates pages, groups, scales, contours and measurements together.
+   *
+   * The batch is validated as a whole before anything is written, so references to pages
+   * and groups in the same batch are allowed. Measurements are written as by
+   * [`upsert_measurement`](Self::upsert_measurement), but group totals are recomputed
+   * once at the end instead of after every entity, and the batch is recorded as a single
+   * history step.
+   *
+   * # Errors
+   *
+   * Returns an error, leaving the state unchanged, if:
+   * - References are strict and an entity references a page or group that neither exists
+   *   nor is in the batch ([`TakeoffError::DanglingReference`])
+   * - The group validation mode is `Reject` and a measurement's group does not accept its
+   *   kind, including existing measurements of a group whose type the batch changes
+   *   ([`TakeoffError::IncompatibleMeasurementType`])
+   */
+  applyBatch(batch: StateBatch): void;
+  /**
+   * Start grouping changes into a single undo step.
+   *
+   * Transactions nest; changes are recorded as one step when the outermost transaction is
+   * committed.
+   *
+   * # Arguments
+   *
+   * * `label` - An optional label for the step, e.g. "Move 12 measurements".
+   */
+  beginTransaction(label?: string | undefined | null): void;
+  /**
+   * Close the innermost transaction started with
+   * [`begin_transaction`](Self::begin_transaction).
+   *
+   * # Returns
+   *
+   * * `false` - If no transaction was open.
+   */
+  commitTransaction(): boolean;
+  /**
+   * Revert every change made since the outermost open transaction began and close it.
+   *
+   * # Returns
+   *
+   * * `false` - If no transaction was open.
+   */
+  rollbackTransaction(): boolean;
+  /**
+   * Undo the latest step, restoring the previous values and recomputing what the original
+   * change recomputed.
+   *
+   * # Returns
+   *
+   * * `false` - If there was nothing to undo.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::TransactionInProgress`] if a transaction is open.
+   */
+  undo(): boolean;
+  /**
+   * Redo the latest undone step.
+   *
+   * # Returns
+   *
+   * * `false` - If there was nothing to redo.
+   *
+   * # Errors
+   *
+   * Returns [`TakeoffError::TransactionInProgress`] if a transaction is open.
+   */
+  redo(): boolean;
+  canUndo(): boolean;
+  canRedo(): boolean;
+  /** Get the label of the step [`undo`](Self::undo) would revert. */
+  getUndoLabel(): string | null;
+  /** Get the label of the step [`redo`](Self::redo) would reapply. */
+  getRedoLabel(): string | null;
+  /** Drop every undo and redo step and any open transaction. */
+  clearHistory(): void;
+  /**
+   * Take every change event emitted since the last drain, oldest first.
+   *
+   * Recomputations triggered by upserts, removals, undo and redo emit events for the
+   * measurements, groups and contours they touch. An event already pending is not queued
+   * twice, so polling after each batch of changes is enough to refresh the affected ids.
+   */
+  drainEvents(): Array<StateEvent>;
+  hasPendingEvents(): boolean;
 }
 
 export declare class VolumetricUnitResult {
@@ -254,8 +889,93 @@ export declare class UnitValue {
   constructor(value: number, unit: Unit, magnitude: UnitValueItemType);
   display(unit: Unit): string;
   getConvertedValue(to: Unit): number;
+  /** Get the kind of quantity held by this value. */
+  get magnitude(): UnitValueItemType;
 }
 
+/** A predicate on a single attribute, used to query measurements and groups. */
+export type AttributeFilter =
+  /** The attribute is present. */
+  | { type: 'Exists'; key: string }
+  /** The attribute is absent. */
+  | { type: 'Missing'; key: string }
+  /** The attribute is present and equivalent to `value`. */
+  | { type: 'Equals'; key: string; value: AttributeValue }
+  /** The attribute is absent or not equivalent to `value`. */
+  | { type: 'NotEquals'; key: string; value: AttributeValue }
+  /**
+   * The attribute is numeric and within the inclusive range.
+   *
+   * Quantities are converted to `unit` before comparing, or compared in their own unit
+   * when `unit` is omitted.
+   */
+  | { type: 'Range'; key: string; min?: number; max?: number; unit?: Unit }
+  /** The attribute is text containing `value` (case-insensitive). */
+  | { type: 'Contains'; key: string; value: string };
+
+/** A typed custom attribute value. */
+export type AttributeValue =
+  | { type: 'Text'; value: string }
+  | { type: 'Number'; value: number }
+  | { type: 'Boolean'; value: boolean }
+  /** A unit-aware quantity, e.g. a ceiling height of `9 ft`. */
+  | {
+      type: 'Quantity';
+      value: number;
+      unit: Unit;
+      magnitude: UnitValueItemType;
+    };
+
+/**
+ * Combine two polygon or rectangle measurements with a boolean operation.
+ *
+ * Each part of the result becomes a new polygon measurement with a random id and the page,
+ * group and attributes of `a`. A hole is joined to its outline by a zero-width cut, so the
+ * polygon's area already excludes it. Results with no area, such as the intersection of
+ * disjoint measurements, are empty.
+ *
+ * # Errors
+ *
+ * Returns [`TakeoffError::GeometryOperation`] if a measurement is not a polygon or
+ * rectangle or the two are on different pages, and [`TakeoffError::EmptyGeometry`] if a
+ * measurement has invalid geometry.
+ */
+export declare function booleanOp(
+  a: Measurement,
+  b: Measurement,
+  operation: BooleanOperation,
+): Array<Measurement>;
+
+/** A boolean operation between two area measurements. */
+export type BooleanOperation =
+  /** The region covered by either measurement. */
+  | 'Union'
+  /** The region covered by the first measurement but not the second. */
+  | 'Difference'
+  /** The region covered by both measurements. */
+  | 'Intersection'
+  /** The region covered by exactly one of the measurements. */
+  | 'Xor';
+
+/**
+ * Constrain the segment from a previous point to the cursor to a set of directions.
+ *
+ * The cursor is projected onto the nearest line through `previous` at one of the angles.
+ * Angles are in degrees from the positive x axis toward the positive y axis of the page;
+ * each angle also allows the opposite direction.
+ *
+ * # Arguments
+ *
+ * * `previous` - The point the segment starts at.
+ * * `cursor` - The unconstrained end of the segment.
+ * * `angles` - The allowed directions. Defaults to [`DEFAULT_CONSTRAINT_ANGLES`].
+ */
+export declare function constrainAngle(
+  previous: Point,
+  cursor: Point,
+  angles?: Array<number> | undefined | null,
+): Point;
+
 export interface ContourInput {
   id: string;
   name?: string;
@@ -280,14 +1000,144 @@ export interface ContourPointOfInterestInput {
   unit: Unit;
 }
 
+/** The computed values of a contour. */
+export interface ContourResult {
+  contourId: string;
+  /** The id of the scale assigned to the contour. */
+  scaleId?: string;
+  /** Whether a surface mesh could be built for the contour. */
+  hasSurfaceMesh: boolean;
+}
+
+/** A reference from one entity to another entity that does not exist. */
+export interface DanglingReference {
+  /** The kind of the entity holding the reference. */
+  entityKind: EntityKind;
+  entityId: string;
+  /** The kind of the missing entity. */
+  referenceKind: EntityKind;
+  referenceId: string;
+}
+
+/** The area covered by a set of measurements, counting overlapping regions once. */
+export interface DissolvedArea {
+  /** The covered area in square page pixels. */
+  pixelArea: number;
+  /** The covered area in square meters, from the measurements that have a scale. */
+  area?: number;
+}
+
 /** Calculate distance between two points */
 export declare function distance(
   points: [Point, Point] | [Point3D, Point3D],
 ): number;
 
+/** Options for [`write_dxf`]. */
+export interface DxfExportOptions {
+  /**
+   * The drawing unit. Defaults to feet.
+   *
+   * R12 drawings have no `$INSUNITS` header, so the unit only appears in the quantity
+   * labels and is lost when the drawing is read back.
+   */
+  unit?: Unit;
+  /**
+   * The height of quantity labels and the size of count markers, in drawing units. Must
+   * be positive. Defaults to 1.
+   */
+  textHeight?: number;
+  /**
+   * The number of decimal places of quantity labels, at most
+   * [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS). Defaults to
+   * [`DEFAULT_EXPORT_DECIMALS`](crate::unit::DEFAULT_EXPORT_DECIMALS).
+   */
+  decimals?: number;
+}
+
+/** The result of reading a DXF drawing. */
+export interface DxfImport {
+  measurements: Array<Measurement>;
+  /**
+   * One group per group id used, named after the first layer mapped to it. Its
+   * measurement type follows the imported entities: `Count` for block references, `Area`
+   * for closed shapes, or `Linear` once any open polyline is imported. Block references
+   * and shapes on the same group id are split: whichever is imported second goes to a
+   * group whose id and name get a `count` or `shapes` suffix, such as `Walls-count`.
+   */
+  groups: Array<Group>;
+  /**
+   * A default scale for the page derived from the `$INSUNITS` header.
+   *
+   * `None` if the drawing is unitless or uses units without a supported equivalent.
+   */
+  scale?: Scale;
+  /** A message for each entity, or part of one, that was not imported. */
+  warnings: Array<string>;
+}
+
+/** Options for [`read_dxf`]. */
+export interface DxfImportOptions {
+  /** Page pixels per drawing unit. Defaults to 1. */
+  pixelsPerUnit?: number;
+  /**
+   * The group id of each layer. Entities on other layers go to a group whose id is the
+   * layer name.
+   */
+  layerGroups?: Record<string, string>;
+}
+
+/** The kinds of entity held by the takeoff state. */
+export type EntityKind = 'Page' | 'Group' | 'Measurement' | 'Scale' | 'Contour';
+
+/**
+ * Find every problem in a measurement's geometry.
+ *
+ * Only polygon outlines are checked for self-intersections; a polyline may cross itself.
+ * Outlines that merely touch themselves at a vertex, such as the zero-width cut that
+ * joins a hole to its outline, are not reported.
+ *
+ * # Returns
+ *
+ * * `Vec<GeometryIssue>` - The issues, sorted by vertex. Empty for counts and valid
+ *   geometry.
+ */
+export declare function findGeometryIssues(
+  measurement: Measurement,
+  options?: ValidityOptions | undefined | null,
+): Array<GeometryIssue>;
+
 /** Generate a random id */
 export declare function generateRandomId(): string;
 
+/** A problem found in a measurement's geometry. */
+export interface GeometryIssue {
+  measurementId: string;
+  kind: GeometryIssueKind;
+  /**
+   * The vertex at fault, or the vertex starting the edge at fault. Rectangle corners are
+   * numbered from the first corner, through the corner sharing its x, to the second.
+   */
+  index: number;
+  /** For self-intersections, the vertex starting the other edge. */
+  otherIndex?: number;
+  /** Where the problem is, in page pixels. */
+  point: Point;
+}
+
+/** The kind of problem found in a measurement's geometry. */
+export type GeometryIssueKind =
+  /** Two edges of a polygon outline cross or overlap. */
+  | 'SelfIntersection'
+  /**
+   * A vertex is at the same position as the vertex before it, or a polygon's last vertex
+   * repeats its first.
+   */
+  | 'DuplicateVertex'
+  /** An edge is shorter than the minimum segment length. */
+  | 'ZeroLengthSegment'
+  /** The outline turns back on itself at a vertex. */
+  | 'Sliver';
+
 /**
  * Get the centroid of a measurement
  *
@@ -300,8 +1150,103 @@ export interface Group {
   id: string;
   name?: string;
   measurementType: MeasurementType;
+  /** Custom attributes, inherited by every measurement in the group. */
+  attributes?: Record<string, AttributeValue>;
+}
+
+/** The change in a group's totals. */
+export interface GroupDelta {
+  groupId: string;
+  /** The total area in square diff units. */
+  area: QuantityDelta;
+  /** The total length in diff units. */
+  length: QuantityDelta;
+  count: QuantityDelta;
+  /** The total volume in cubic diff units. */
+  volume: QuantityDelta;
+}
+
+/** The computed totals of a group. */
+export interface GroupResult {
+  groupId: string;
+  /** The total area in square meters. */
+  area?: number;
+  /** The total length in meters. */
+  length?: number;
+  points?: number;
+  count?: number;
+  /** The total area extruded by the group's `depth` attribute, in cubic meters. */
+  volume?: number;
+}
+
+/**
+ * How `upsert_measurement` treats a measurement whose kind does not match its group's
+ * [`MeasurementType`].
+ */
+export type GroupValidationMode =
+  /** Accept the measurement and list it in the mismatch report. */
+  | 'Flag'
+  /** Reject the measurement with [`TakeoffError::IncompatibleMeasurementType`]. */
+  | 'Reject';
+
+/** The encoding of a rendered heatmap. */
+export type HeatmapFormat =
+  | 'Png'
+  /** Raw 8-bit RGBA pixels, row by row from the top left. */
+  | 'Rgba';
+
+/** An encoded cut/fill map. */
+export interface HeatmapImage {
+  width: number;
+  height: number;
+  format: HeatmapFormat;
+  /** The depth at which colors saturate, shown at the ends of the legend. */
+  maxDepth: number;
+  data: Buffer;
 }
 
+/**
+ * Options for [`SurfaceMesh::cut_fill_heatmap`].
+ *
+ * Depths are real-world values, in the unit of the contour's scale.
+ */
+export interface HeatmapOptions {
+  /**
+   * The size of one cell in page pixels. Defaults to the size giving
+   * [`DEFAULT_HEATMAP_RESOLUTION`] cells along the longer side.
+   */
+  cellSize?: number;
+  /** The depth at which colors saturate. Defaults to the largest sampled depth. */
+  maxDepth?: number;
+  /**
+   * The depth between isolines. Defaults to a [`DEFAULT_HEATMAP_BANDS`]th of the maximum
+   * depth; 0 draws no isolines.
+   */
+  contourInterval?: number;
+  /** Whether to draw the legend. Defaults to true. */
+  legend?: boolean;
+  /** The encoding of the image. Defaults to PNG. */
+  format?: HeatmapFormat;
+}
+
+/**
+ * Repair a measurement's geometry.
+ *
+ * Repeated vertices, zero-length edges and slivers are removed. A polygon that still
+ * crosses itself is split into the regions it encloses; the largest keeps the
+ * measurement's id and the others are new polygons with random ids and the page, group
+ * and attributes of the original. Valid measurements are returned unchanged.
+ *
+ * # Errors
+ *
+ * Returns [`TakeoffError::EmptyGeometry`] if nothing with a length or area is left after
+ * the repair.
+ */
+export declare function makeValid(
+  measurement: Measurement,
+  options?: ValidityOptions | undefined | null,
+): Array<Measurement>;
+
 export type Measurement =
   | {
       type: 'Count';
@@ -309,6 +1254,8 @@ export type Measurement =
       pageId: string;
       groupId: string;
       points: [Point];
+      /** Custom attributes. Keys set here override those inherited from the group. */
+      attributes?: Record<string, AttributeValue>;
     }
   | {
       type: 'Polygon';
@@ -316,6 +1263,8 @@ export type Measurement =
       pageId: string;
       groupId: string;
       points: Array<Point>;
+      /** Custom attributes. Keys set here override those inherited from the group. */
+      attributes?: Record<string, AttributeValue>;
     }
   | {
       type: 'Polyline';
@@ -323,6 +1272,8 @@ export type Measurement =
       pageId: string;
       groupId: string;
       points: Array<Point>;
+      /** Custom attributes. Keys set here override those inherited from the group. */
+      attributes?: Record<string, AttributeValue>;
     }
   | {
       type: 'Rectangle';
@@ -330,10 +1281,121 @@ export type Measurement =
       pageId: string;
       groupId: string;
       points: [Point, Point];
+      /** Custom attributes. Keys set here override those inherited from the group. */
+      attributes?: Record<string, AttributeValue>;
     };
 
+/** A measurement that was added, removed or modified. */
+export interface MeasurementDiff {
+  measurementId: string;
+  /** The measurement in the earlier snapshot, `None` if it was added. */
+  before?: Measurement;
+  /** The measurement in the later snapshot, `None` if it was removed. */
+  after?: Measurement;
+  beforeScaleId?: string;
+  afterScaleId?: string;
+  /** The area in square diff units. */
+  area: QuantityDelta;
+  /** The length in diff units. */
+  length: QuantityDelta;
+}
+
+/** Two area measurements that cover the same region. */
+export interface MeasurementOverlap {
+  /** The smaller of the two measurement ids. */
+  measurementId: string;
+  otherMeasurementId: string;
+  /** The overlapping area in square page pixels. */
+  pixelArea: number;
+  /** The overlapping area in square meters, using the scale of the first measurement. */
+  area?: number;
+}
+
+/** The computed values of a measurement. */
+export interface MeasurementResult {
+  measurementId: string;
+  /** The id of the scale assigned to the measurement. */
+  scaleId?: string;
+  /** The area in square meters. */
+  area?: number;
+  /** The length in meters. */
+  length?: number;
+}
+
 export type MeasurementType = 'Area' | 'Linear' | 'Count';
 
+/** A measurement whose kind does not match its group's [`MeasurementType`]. */
+export interface MeasurementTypeMismatch {
+  measurementId: string;
+  /** The kind of the measurement (e.g. `Polyline`). */
+  measurementKind: string;
+  groupId: string;
+  /** The measurement type the group expects. */
+  measurementType: MeasurementType;
+}
+
+/**
+ * Merge two polylines that meet end to end, or two areas that share an edge.
+ *
+ * See [`Measurement::merge`].
+ */
+export declare function mergeMeasurements(
+  a: Measurement,
+  b: Measurement,
+): Measurement;
+
+/** How offset edges are joined around a convex corner. */
+export type OffsetJoin =
+  /** Extends the edges to a point. Corners sharper than [`MIN_MITER_ANGLE`] are cut off. */
+  | 'Miter'
+  /** Rounds the corner with an arc centered on the original vertex. */
+  | 'Round'
+  /**
+   * Extends each offset edge by the offset distance past the corner and joins the ends
+   * with a straight segment. Corners of 90 degrees or less meet before that, as with a
+   * miter.
+   */
+  | 'Square'
+  /**
+   * Cuts the corner off flat, joining the ends of the offset edges with a straight
+   * segment.
+   */
+  | 'Bevel';
+
+/**
+ * Offset a polygon, rectangle or polyline measurement by a real-world distance.
+ *
+ * A positive distance grows areas and a negative one shrinks them; an area that shrinks
+ * away entirely gives no measurements, and one pinched in two gives several polygons. A
+ * positive distance moves a polyline to the right of the direction it was drawn in, as seen
+ * on the page, and a negative one to the left. Very large polyline offsets around tight
+ * bends may cross themselves.
+ *
+ * Each result is a new measurement with a random id and the page, group and attributes of
+ * the original.
+ *
+ * # Arguments
+ *
+ * * `measurement` - The measurement to offset.
+ * * `scale` - The scale of the measurement, used to convert the distance to page pixels.
+ * * `distance` - The offset distance in `unit`.
+ * * `unit` - The unit of the distance.
+ * * `join` - How edges are joined around corners. Defaults to [`OffsetJoin::Miter`].
+ *
+ * # Errors
+ *
+ * Returns [`TakeoffError::GeometryOperation`] if the measurement is a count or the distance
+ * is not finite, [`TakeoffError::EmptyGeometry`] if the measurement has invalid geometry,
+ * and a scale error if the scale is invalid.
+ */
+export declare function offsetMeasurement(
+  measurement: Measurement,
+  scale: Scale,
+  distance: number,
+  unit: Unit,
+  join?: OffsetJoin | undefined | null,
+): Array<Measurement>;
+
 export interface Page {
   id: string;
   name?: string;
@@ -360,10 +1422,87 @@ export interface Point3D {
 }
 
 /** Input for creating a reference surface from JS/TS. */
+/** The encoding of a project file. */
+export type ProjectFormat =
+  /** Pretty-printed UTF-8 JSON. */
+  | 'Json'
+  /** Compact binary MessagePack. */
+  | 'MessagePack';
+
+/** A quantity in the earlier and later snapshot. Missing quantities count as zero. */
+export interface QuantityDelta {
+  before: number;
+  after: number;
+  /** `after - before`. */
+  delta: number;
+}
+
 export type ReferenceSurfaceInput =
   | { type: 'Polygon'; points: Array<Point>; elevation: number }
   | { type: 'Rectangle'; points: [Point, Point]; elevation: number };
 
+/** What happens to dependents when a page or group is removed. */
+export type RemovalPolicy =
+  /**
+   * Keep the behavior from before policies existed, which differs by entity: a page is
+   * removed on its own, leaving its measurements, scales and contours dangling, while a
+   * group is removed along with its measurements.
+   */
+  | 'Legacy'
+  /** Remove the dependents along with the page or group. */
+  | 'Cascade'
+  /** Refuse to remove a page or group that still has dependents. */
+  | 'Reject';
+
+/** A column of a report. */
+export type ReportColumn =
+  /** The group name, or its id if it has none. */
+  | 'Group'
+  /** The page name, or its id if it has none. Group rows list every page of the group. */
+  | 'Page'
+  /** The measurement type of the group. */
+  | 'Type'
+  /** The measurement id. Empty for group rows. */
+  | 'Measurement'
+  | 'Area'
+  | 'Length'
+  | 'Count';
+
+/** The encoding of a report. */
+export type ReportFormat =
+  /** Comma-separated UTF-8 text. */
+  | 'Csv'
+  /** An Excel workbook with a single sheet. */
+  | 'Xlsx';
+
+/** Whether a report has one row per group or one row per measurement. */
+export type ReportLevel = 'Groups' | 'Measurements';
+
+/** Options for [`export_report`]. */
+export interface ReportOptions {
+  /** Defaults to `Csv`. */
+  format?: ReportFormat;
+  /** Defaults to `Groups`. */
+  level?: ReportLevel;
+  /** The unit lengths are reported in; areas use its square. Defaults to `Feet`. */
+  unit?: Unit;
+  /** The columns to include, in order. Defaults to every column that applies to the level. */
+  columns?: Array<ReportColumn>;
+  /**
+   * The columns to sort by, most significant first. Defaults to group, page, then
+   * measurement.
+   */
+  sortBy?: Array<ReportColumn>;
+  /** Whether to sort in descending order. Defaults to `false`. */
+  descending?: boolean;
+  /**
+   * The number of decimal places quantities are rounded to, at most
+   * [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS). Defaults to
+   * [`DEFAULT_EXPORT_DECIMALS`](crate::unit::DEFAULT_EXPORT_DECIMALS).
+   */
+  decimals?: number;
+}
+
 /**
  * Reposition a measurement so its centroid is at the given point.
  * Returns a new measurement (same kind and metadata); area, length, and count are unchanged.
@@ -387,23 +1526,236 @@ export type Scale =
     }
   | { type: 'Default'; id: string; pageId: string; scale: ScaleDefinition };
 
+/**
+ * A set of scales on one page that makes scale resolution ambiguous.
+ *
+ * Conflicts are warnings: [`resolve_scale`] still picks a scale deterministically.
+ */
+export type ScaleConflict =
+  /** The page has more than one default scale. The one with the smallest id is used. */
+  | {
+      type: 'MultipleDefaultScales';
+      pageId: string;
+      /** The ids of the default scales, sorted. */
+      scaleIds: Array<string>;
+    }
+  /**
+   * Two area scales overlap without one being strictly smaller and nested inside the
+   * other. In the overlap, the smaller bounding box, then the smaller id, is used.
+   */
+  | {
+      type: 'OverlappingAreaScales';
+      pageId: string;
+      /** The ids of the two area scales, sorted. */
+      scaleIds: Array<string>;
+    };
+
 export interface ScaleDefinition {
   pixelDistance: number;
   realDistance: number;
   unit: Unit;
 }
 
+/** A scale that was added, removed or redefined. */
+export interface ScaleDiff {
+  scaleId: string;
+  /** The scale in the earlier snapshot, `None` if it was added. */
+  before?: Scale;
+  /** The scale in the later snapshot, `None` if it was removed. */
+  after?: Scale;
+}
+
 /** Simplify a polyline using the Ramer-Douglas-Peucker algorithm */
 export declare function simplifyPolyline(
   points: Array<Point>,
   tolerance: number,
 ): Array<Point>;
 
+export interface SnapCandidate {
+  point: Point;
+  kind: SnapKind;
+  /** The distance from the cursor in page pixels. */
+  distance: number;
+  /** The measurement the point lies on. For intersections, the first of the two by id. */
+  measurementId?: string;
+  /** The contour the point lies on. */
+  contourId?: string;
+  /** The elevation of the contour line the point lies on. */
+  elevation?: number;
+}
+
+/** The kind of geometry a snapped point lies on, in order of precedence. */
+export type SnapKind =
+  /** A measurement vertex or count point. */
+  | 'Vertex'
+  /** A crossing of two measurement edges. */
+  | 'Intersection'
+  /** The middle of a measurement edge. */
+  | 'Midpoint'
+  /** The foot of the perpendicular from the cursor to a measurement edge. */
+  | 'Perpendicular'
+  /** The nearest point on a contour line. */
+  | 'Contour';
+
+export interface SnapOptions {
+  /** The kinds to snap to. Defaults to all kinds. */
+  kinds?: Array<SnapKind>;
+  /** Measurements to ignore, such as the one being drawn. */
+  excludeIds?: Array<string>;
+}
+
+/** The differences between two snapshots. Every list is sorted by id. */
+export interface SnapshotDiff {
+  /** The unit lengths are reported in; areas and volumes use its square and cube. */
+  unit: Unit;
+  addedMeasurements: Array<MeasurementDiff>;
+  removedMeasurements: Array<MeasurementDiff>;
+  /** Measurements whose definition, scale or computed quantities changed. */
+  modifiedMeasurements: Array<MeasurementDiff>;
+  scales: Array<ScaleDiff>;
+  /** Groups whose area, length, count or volume changed. */
+  groups: Array<GroupDelta>;
+}
+
+/** The values computed from a snapshot's entities, sorted by id. */
+export interface SnapshotResults {
+  measurements: Array<MeasurementResult>;
+  groups: Array<GroupResult>;
+  contours: Array<ContourResult>;
+}
+
+/**
+ * Split a polygon or rectangle measurement along a cut line.
+ *
+ * See [`Measurement::split_by_line`].
+ */
+export declare function splitPolygon(
+  measurement: Measurement,
+  cut: Array<Point>,
+): Array<Measurement>;
+
+/**
+ * Split a polyline measurement in two at the point on it nearest to `point`.
+ *
+ * See [`Measurement::split_at`].
+ */
+export declare function splitPolyline(
+  measurement: Measurement,
+  point: Point,
+): Array<Measurement>;
+
+/**
+ * Square up a nearly rectangular polygon measurement.
+ *
+ * Edges are aligned to the polygon's dominant pair of perpendicular axes, so every corner
+ * becomes a right angle. Rectilinear outlines such as L-shapes are squared up too; vertices
+ * between two edges along the same axis are dropped. Rectangle measurements are returned
+ * unchanged.
+ *
+ * # Arguments
+ *
+ * * `measurement` - The polygon to square up.
+ * * `area_tolerance` - The relative change in area allowed. Defaults to
+ *   [`DEFAULT_SQUARE_UP_AREA_TOLERANCE`].
+ *
+ * # Errors
+ *
+ * Returns [`TakeoffError::GeometryOperation`] if the measurement is not a polygon, an edge
+ * strays more than [`MAX_SQUARE_UP_EDGE_DEVIATION`] from the axes, or the area changes by
+ * more than the tolerance, and [`TakeoffError::EmptyGeometry`] if the polygon is invalid.
+ */
+export declare function squareUpPolygon(
+  measurement: Measurement,
+  areaTolerance?: number | undefined | null,
+): Measurement;
+
+/** A set of entities to upsert together, with a single recomputation pass at the end. */
+export interface StateBatch {
+  pages?: Array<Page>;
+  groups?: Array<Group>;
+  measurements?: Array<Measurement>;
+  scales?: Array<Scale>;
+  contours?: Array<ContourInput>;
+}
+
+/** A change to a value derived by the takeoff state. */
+export type StateEvent =
+  /** A measurement's area and length were recomputed. */
+  | { type: 'MeasurementRecomputed'; measurementId: string }
+  /** A group's area, length, points or count changed. */
+  | { type: 'GroupTotalsChanged'; groupId: string }
+  /** A measurement or contour was assigned a different scale. */
+  | { type: 'ScaleReassigned'; entityKind: EntityKind; entityId: string; scaleId: string }
+  /** A contour's surface mesh was rebuilt. */
+  | { type: 'ContourMeshRebuilt'; contourId: string };
+
 export interface StateOptions {
   pages: Array<Page>;
   groups: Array<Group>;
   measurements: Array<Measurement>;
   scales: Array<Scale>;
+  contours?: Array<ContourInput>;
+  /** How measurements incompatible with their group are handled. Defaults to `Flag`. */
+  groupValidationMode?: GroupValidationMode;
+  /** What happens to dependents when a page or group is removed. Defaults to `Legacy`. */
+  removalPolicy?: RemovalPolicy;
+  /**
+   * Reject initial entities and upserts that reference a missing page or group. Defaults
+   * to `false`.
+   */
+  strictReferences?: boolean;
+}
+
+/**
+ * The full takeoff state at one point in time.
+ *
+ * Entities are sorted by id so equal states produce identical documents.
+ */
+export interface StateSnapshot {
+  /** The snapshot format version. See [`SNAPSHOT_VERSION`]. */
+  version: number;
+  pages: Array<Page>;
+  groups: Array<Group>;
+  measurements: Array<Measurement>;
+  scales: Array<Scale>;
+  contours: Array<ContourInput>;
+  groupValidationMode: GroupValidationMode;
+  removalPolicy: RemovalPolicy;
+  strictReferences: boolean;
+  /** Values computed from the entities. They are recomputed, not read, on import. */
+  results: SnapshotResults;
+}
+
+/** How the measurements of a group are drawn. */
+export interface SvgGroupStyle {
+  /** A CSS color for strokes, fills and count markers. */
+  color: string;
+  /** The opacity of area fills, from 0 to 1. Defaults to 0.25. */
+  fillOpacity?: number;
+  /** The stroke width in page pixels. Defaults to 2. */
+  strokeWidth?: number;
+}
+
+/** Options for [`render_svg`]. */
+export interface SvgOptions {
+  /** The unit of quantity labels. Defaults to feet. */
+  unit?: Unit;
+  /**
+   * The number of decimal places of quantity labels, at most
+   * [`MAX_EXPORT_DECIMALS`](crate::unit::MAX_EXPORT_DECIMALS). Defaults to
+   * [`DEFAULT_EXPORT_DECIMALS`](crate::unit::DEFAULT_EXPORT_DECIMALS).
+   */
+  decimals?: number;
+  /** The style of each group by id. Other groups are colored from [`SVG_GROUP_PALETTE`]. */
+  groupStyles?: Record<string, SvgGroupStyle>;
+  /** The font size of labels in page pixels. Defaults to [`DEFAULT_SVG_FONT_SIZE`]. */
+  fontSize?: number;
+  /** Whether to draw quantity labels. Defaults to true. */
+  showLabels?: boolean;
+  /** Whether to draw the viewports of area scales. Defaults to true. */
+  showScales?: boolean;
+  /** Whether to draw contour lines and points of interest. Defaults to true. */
+  showContours?: boolean;
 }
 
 /** Measurement units supported by the system */
@@ -416,7 +1768,20 @@ export type Unit =
   | 'Meters'
   | 'Centimeters';
 
-export type UnitValueItemType = 'Area' | 'Length' | 'Volume';
+export type UnitValueItemType = 'Area' | 'Length' | 'Volume' | 'Scalar';
+
+export interface ValidityOptions {
+  /**
+   * The length in page pixels below which an edge counts as zero-length. Defaults to
+   * [`DEFAULT_MIN_SEGMENT_LENGTH`].
+   */
+  minSegmentLength?: number;
+  /**
+   * The angle in degrees below which a vertex counts as a sliver. Defaults to
+   * [`DEFAULT_SLIVER_ANGLE`].
+   */
+  sliverAngle?: number;
+}
 
 /** Result of a volumetric cut/fill calculation. */
 export interface VolumetricResult {
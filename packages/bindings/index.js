@@ -781,6 +781,7 @@ if (!nativeBinding) {
 }
 
 module.exports = nativeBinding;
+module.exports.AttributeAggregate = nativeBinding.AttributeAggregate;
 module.exports.ContourWrapper = nativeBinding.ContourWrapper;
 module.exports.GroupWrapper = nativeBinding.GroupWrapper;
 module.exports.MeasurementWrapper = nativeBinding.MeasurementWrapper;
@@ -789,12 +790,33 @@ module.exports.VolumetricUnitResult = nativeBinding.VolumetricUnitResult;
 module.exports.plus100 = nativeBinding.plus100;
 module.exports.plus200 = nativeBinding.plus200;
 module.exports.UnitValue = nativeBinding.UnitValue;
+module.exports.booleanOp = nativeBinding.booleanOp;
+module.exports.BooleanOperation = nativeBinding.BooleanOperation;
+module.exports.constrainAngle = nativeBinding.constrainAngle;
 module.exports.distance = nativeBinding.distance;
+module.exports.EntityKind = nativeBinding.EntityKind;
+module.exports.findGeometryIssues = nativeBinding.findGeometryIssues;
 module.exports.generateRandomId = nativeBinding.generateRandomId;
+module.exports.GeometryIssueKind = nativeBinding.GeometryIssueKind;
 module.exports.getCentroid = nativeBinding.getCentroid;
+module.exports.GroupValidationMode = nativeBinding.GroupValidationMode;
+module.exports.HeatmapFormat = nativeBinding.HeatmapFormat;
+module.exports.makeValid = nativeBinding.makeValid;
 module.exports.MeasurementType = nativeBinding.MeasurementType;
+module.exports.mergeMeasurements = nativeBinding.mergeMeasurements;
+module.exports.OffsetJoin = nativeBinding.OffsetJoin;
+module.exports.offsetMeasurement = nativeBinding.offsetMeasurement;
+module.exports.ProjectFormat = nativeBinding.ProjectFormat;
+module.exports.RemovalPolicy = nativeBinding.RemovalPolicy;
+module.exports.ReportColumn = nativeBinding.ReportColumn;
+module.exports.ReportFormat = nativeBinding.ReportFormat;
+module.exports.ReportLevel = nativeBinding.ReportLevel;
 module.exports.repositionMeasurementToCentroid =
   nativeBinding.repositionMeasurementToCentroid;
 module.exports.simplifyPolyline = nativeBinding.simplifyPolyline;
+module.exports.SnapKind = nativeBinding.SnapKind;
+module.exports.splitPolygon = nativeBinding.splitPolygon;
+module.exports.splitPolyline = nativeBinding.splitPolyline;
+module.exports.squareUpPolygon = nativeBinding.squareUpPolygon;
 module.exports.Unit = nativeBinding.Unit;
 module.exports.UnitValueItemType = nativeBinding.UnitValueItemType;
@@ -7,7 +7,10 @@ use std::sync::{Arc, Mutex};
 use takeoff_core::contour::{ContourInput, SurfaceMesh};
 use takeoff_core::coords::{Point, Point3D};
 use takeoff_core::error::TakeoffResult;
-use takeoff_core::scale::Scale;
+use takeoff_core::event::StateEvent;
+use takeoff_core::heatmap::{HeatmapImage, HeatmapOptions};
+use takeoff_core::integrity::EntityKind;
+use takeoff_core::scale::{resolve_scale, Scale};
 use takeoff_core::unit::{Unit, UnitValue};
 use takeoff_core::volume::{ReferenceSurface, ReferenceSurfaceInput, VolumetricResult};
 
@@ -96,13 +99,32 @@ impl ContourWrapper {
   /// This will rebuild the surface mesh.
   #[napi]
   pub fn set_scale(&self, scale: Scale) -> Result<()> {
-    *lock_mutex(self.scale.lock(), "scale")? = Some(scale);
+    let scale_id = scale.id();
+    let previous = lock_mutex(self.scale.lock(), "scale")?.replace(scale);
+    if previous.map(|previous| previous.id()) != Some(scale_id.clone()) {
+      self.emit_event(StateEvent::ScaleReassigned {
+        entity_kind: EntityKind::Contour,
+        entity_id: self.id(),
+        scale_id,
+      });
+    }
     self.rebuild_surface_mesh()?;
     Ok(())
   }
 
+  /// Remove the scale of the contour, clearing its surface mesh.
+  pub fn clear_scale(&self) -> Result<()> {
+    let previous = lock_mutex(self.scale.lock(), "scale")?.take();
+    if previous.is_some() {
+      self.rebuild_surface_mesh()?;
+    }
+    Ok(())
+  }
+
+  /// Assign the scale resolved by [`resolve_scale`] for the contour's bounding box.
+  ///
+  /// If no scale resolves, the scale and the surface mesh are cleared.
   pub fn calculate_scale(&self) -> Option<Scale> {
-    let mut current_scale: Option<Scale> = None;
     let contour = lock_mutex(self.contour.lock(), "contour").ok()?;
     let bounding_box = contour.bounding_box()?;
     let page_id = contour.page_id.clone();
@@ -117,23 +139,14 @@ impl ContourWrapper {
       ))
     };
 
-    if let Some(state) = self.state.upgrade() {
-      for scale in state.get_page_scales(&page_id) {
-        if matches!(scale, Scale::Area { .. }) {
-          if scale.is_in_bounding_box(&geometry) {
-            let _ = self.set_scale(scale.clone());
-            return Some(scale);
-          }
-        } else {
-          current_scale = Some(scale.clone());
-        }
-      }
-      if let Some(scale) = current_scale {
-        let _ = self.set_scale(scale.clone());
-        return Some(scale);
-      }
-    }
-    None
+    let state = self.state.upgrade()?;
+    let scales = state.get_page_scales(&page_id);
+    let Some(scale) = resolve_scale(&scales, &geometry).cloned() else {
+      let _ = self.clear_scale();
+      return None;
+    };
+    let _ = self.set_scale(scale.clone());
+    Some(scale)
   }
 
   fn rebuild_surface_mesh(&self) -> TakeoffResult<()> {
@@ -156,9 +169,18 @@ impl ContourWrapper {
       drop(scale_guard);
       *lock_mutex(self.surface_mesh.lock(), "surface_mesh")? = None;
     }
+    self.emit_event(StateEvent::ContourMeshRebuilt {
+      contour_id: self.id(),
+    });
     Ok(())
   }
 
+  fn emit_event(&self, event: StateEvent) {
+    if let Some(state) = self.state.upgrade() {
+      state.emit_event(event);
+    }
+  }
+
   /// Get the id of the contour.
   #[napi(getter)]
   pub fn id(&self) -> String {
@@ -177,6 +199,14 @@ impl ContourWrapper {
       .clone()
   }
 
+  /// Get the input the contour was created from.
+  #[napi(getter)]
+  pub fn get_contour(&self) -> ContourInput {
+    lock_mutex(self.contour.lock(), "contour")
+      .expect("BUG: contour mutex should not be poisoned")
+      .clone()
+  }
+
   /// Get the scale of the contour.
   #[napi(getter)]
   pub fn get_scale(&self) -> Option<Scale> {
@@ -293,6 +323,37 @@ impl ContourWrapper {
       uncovered_area: UnitValue::from_area(unit.get_area_unit(uncovered_area_real as f32)),
     })
   }
+
+  /// Render a cut/fill map against a reference surface as a PNG or raw RGBA image.
+  ///
+  /// The reference is interpreted as in [`volume_against`](Self::volume_against). Depths
+  /// in the options and the legend are in the unit of the contour's scale.
+  /// Returns None if surface mesh or scale is not available.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the map cannot be rendered or encoded
+  /// ([`TakeoffError::ImageExport`](takeoff_core::TakeoffError::ImageExport)).
+  #[napi]
+  pub fn get_cut_fill_heatmap(
+    &self,
+    reference: ReferenceSurfaceInput,
+    options: Option<HeatmapOptions>,
+  ) -> Result<Option<HeatmapImage>> {
+    let mesh_guard = lock_mutex(self.surface_mesh.lock(), "surface_mesh")?;
+    let Some(mesh) = mesh_guard.as_ref() else {
+      return Ok(None);
+    };
+    let scale_guard = lock_mutex(self.scale.lock(), "scale")?;
+    let Some(scale) = scale_guard.as_ref() else {
+      return Ok(None);
+    };
+
+    let options = options.unwrap_or_default();
+    let reference = ReferenceSurface::from(reference);
+    let heatmap = mesh.cut_fill_heatmap(&reference, scale.ratio()?, &options)?;
+    Ok(Some(heatmap.to_image(options.format.unwrap_or_default())?))
+  }
 }
 
 #[cfg(test)]
@@ -345,7 +406,7 @@ mod tests {
   #[test]
   fn test_contour_wrapper_with_scale() {
     let wrapper = ContourWrapper::new(test_contour_input());
-    wrapper.set_scale(test_scale());
+    wrapper.set_scale(test_scale()).unwrap();
     let points = wrapper.get_surface_points();
     assert!(points.is_some());
     let points = points.unwrap();
@@ -357,9 +418,37 @@ mod tests {
   #[test]
   fn test_contour_wrapper_scatter_data_with_scale() {
     let wrapper = ContourWrapper::new(test_contour_input());
-    wrapper.set_scale(test_scale());
+    wrapper.set_scale(test_scale()).unwrap();
     let scatter = wrapper.get_scatter_data(10);
     assert!(scatter.is_some());
     assert!(!scatter.unwrap().is_empty());
   }
+
+  #[test]
+  fn test_contour_wrapper_cut_fill_heatmap() {
+    let reference = ReferenceSurfaceInput::Rectangle {
+      points: (Point::new(0.0, 0.0), Point::new(100.0, 100.0)),
+      elevation: 5.0,
+    };
+    let wrapper = ContourWrapper::new(test_contour_input());
+    assert!(wrapper
+      .get_cut_fill_heatmap(reference.clone(), None)
+      .unwrap()
+      .is_none());
+
+    wrapper.set_scale(test_scale()).unwrap();
+    let options = HeatmapOptions {
+      cell_size: Some(10.0),
+      legend: Some(false),
+      ..HeatmapOptions::default()
+    };
+    let image = wrapper
+      .get_cut_fill_heatmap(reference, Some(options))
+      .unwrap()
+      .unwrap();
+    assert_eq!((image.width, image.height), (10, 10));
+    // Flat terrain 5 ft above the reference
+    assert!((image.max_depth - 5.0).abs() < 1e-6);
+    assert_eq!(&image.data[1..4], b"PNG");
+  }
 }
@@ -5,8 +5,11 @@ use anyhow::Result;
 use napi_derive::napi;
 use std::sync::{Arc, Mutex, Weak};
 use takeoff_core::error::TakeoffResult;
+use takeoff_core::event::StateEvent;
+use takeoff_core::formula::{evaluate_formula, FormulaContext};
 use takeoff_core::group::Group;
-use takeoff_core::unit::UnitValue;
+use takeoff_core::overlap::{dissolved_area, find_overlaps, MeasurementOverlap, ScaledMeasurement};
+use takeoff_core::unit::{Unit, UnitValue, UnitValueItemType};
 use uom::si::f32::{Area, Length};
 
 #[napi]
@@ -25,16 +28,21 @@ pub struct GroupWrapper {
 #[napi]
 impl GroupWrapper {
   pub fn new(group: Group, state: Arc<TakeoffStateHandler>) -> Self {
-    let res = Self {
+    let res = Self::from_group(group, state);
+    let _ = res.recompute_measurements();
+    res
+  }
+
+  /// Create a group wrapper whose totals are not computed yet.
+  pub fn from_group(group: Group, state: Arc<TakeoffStateHandler>) -> Self {
+    Self {
       group,
       state: Arc::downgrade(&state),
       area: Arc::new(Mutex::new(None)),
       length: Arc::new(Mutex::new(None)),
       points: Arc::new(Mutex::new(None)),
       count: Arc::new(Mutex::new(None)),
-    };
-    let _ = res.recompute_measurements();
-    res
+    }
   }
 
   fn calculate_area(&self, measurements: &[MeasurementWrapper]) -> Result<Option<Area>> {
@@ -72,6 +80,8 @@ impl GroupWrapper {
 
   /// Recompute all measurements for this group.
   ///
+  /// Emits [`StateEvent::GroupTotalsChanged`] if any total changed.
+  ///
   /// # Errors
   ///
   /// Returns an error if:
@@ -82,20 +92,19 @@ impl GroupWrapper {
     if let Some(state) = self.state.upgrade() {
       let measurements = state.get_measurements_by_group_id(self.id().to_string());
 
-      {
-        *lock_mutex(self.area.lock(), "area")? = self.calculate_area(&measurements)?;
-      }
-
-      {
-        *lock_mutex(self.length.lock(), "length")? = self.calculate_length(&measurements)?;
-      }
-
-      {
-        *lock_mutex(self.points.lock(), "points")? = self.calculate_points(&measurements);
-      }
+      let mut changed = store(&self.area, "area", self.calculate_area(&measurements)?)?;
+      changed |= store(
+        &self.length,
+        "length",
+        self.calculate_length(&measurements)?,
+      )?;
+      changed |= store(&self.points, "points", self.calculate_points(&measurements))?;
+      changed |= store(&self.count, "count", self.calculate_count(&measurements))?;
 
-      {
-        *lock_mutex(self.count.lock(), "count")? = self.calculate_count(&measurements);
+      if changed {
+        state.emit_event(StateEvent::GroupTotalsChanged {
+          group_id: self.id().to_string(),
+        });
       }
     }
     Ok(())
@@ -133,6 +142,16 @@ impl GroupWrapper {
     None
   }
 
+  #[napi(getter)]
+  /// Get the volume for this group: its area extruded by its `depth` attribute.
+  ///
+  /// Returns `None` if the area has not been computed or the group has no length-valued
+  /// `depth` attribute.
+  pub fn get_volume(&self) -> Option<UnitValue> {
+    let area = (*lock_mutex(self.area.lock(), "area").ok()?)?;
+    Some(UnitValue::from_volume(area * self.group.depth()?))
+  }
+
   #[napi(getter)]
   /// Get the points count for this group.
   ///
@@ -155,4 +174,83 @@ impl GroupWrapper {
   pub fn get_group(&self) -> Group {
     self.group.clone()
   }
+
+  /// Build the formula context for this group from its aggregated totals.
+  ///
+  /// Binds `count` and `points`, plus `length`, `perimeter` and `area` when computed.
+  /// Numeric and quantity attributes of the group are bound under their own names; the
+  /// built-in variables take precedence.
+  pub fn formula_context(&self) -> FormulaContext {
+    let mut context = FormulaContext::new();
+    for (name, value) in self.group.attributes.iter().flatten() {
+      if let Some(value) = value.to_unit_value() {
+        context.set_value(name.clone(), value);
+      }
+    }
+    if let Some(count) = self.get_count() {
+      context = context.with_count(count);
+    }
+    if let Some(points) = self.get_points() {
+      context = context.with_points(points);
+    }
+    if let Ok(Some(length)) = lock_mutex(self.length.lock(), "length").map(|l| *l) {
+      context = context.with_length(length);
+    }
+    if let Ok(Some(area)) = lock_mutex(self.area.lock(), "area").map(|a| *a) {
+      context = context.with_area(area);
+    }
+    context
+  }
+
+  /// Evaluate a custom quantity formula (e.g. `area * 1.1 / 32sf`) against this group's totals.
+  #[napi]
+  pub fn evaluate_formula(&self, formula: String) -> Result<UnitValue> {
+    Ok(evaluate_formula(&formula, &self.formula_context())?)
+  }
+
+  #[napi]
+  /// Find the area measurements of this group that overlap, which the group area counts twice.
+  ///
+  /// # Arguments
+  ///
+  /// * `min_pixel_area` - The smallest overlap to report, in square page pixels. Defaults to 0.
+  pub fn get_overlaps(&self, min_pixel_area: Option<f64>) -> Vec<MeasurementOverlap> {
+    find_overlaps(&self.scaled_measurements(), min_pixel_area.unwrap_or(0.0))
+  }
+
+  #[napi(getter)]
+  /// Get the area covered by this group, counting regions outlined more than once only once.
+  ///
+  /// Returns `None` if no area measurement of the group has a scale.
+  pub fn get_dissolved_area(&self) -> Option<UnitValue> {
+    let area = dissolved_area(&self.scaled_measurements())?.area?;
+    Some(UnitValue::new(area, Unit::Meters, UnitValueItemType::Area))
+  }
+
+  /// Get the measurements of this group with their assigned scales.
+  fn scaled_measurements(&self) -> Vec<ScaledMeasurement> {
+    let Some(state) = self.state.upgrade() else {
+      return Vec::new();
+    };
+    state
+      .get_measurements_by_group_id(self.id().to_string())
+      .iter()
+      .map(|measurement| ScaledMeasurement {
+        measurement: measurement.get_measurement(),
+        scale: measurement.get_scale(),
+      })
+      .collect()
+  }
+}
+
+/// Store `value` in `slot`, returning whether it differs from the previous value.
+fn store<T: PartialEq>(
+  slot: &Mutex<Option<T>>,
+  resource: &str,
+  value: Option<T>,
+) -> TakeoffResult<bool> {
+  let mut current = lock_mutex(slot.lock(), resource)?;
+  let changed = *current != value;
+  *current = value;
+  Ok(changed)
 }
@@ -1,8 +1,13 @@
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex, Weak};
 
 use napi_derive::napi;
+use takeoff_core::attribute::{merge_attributes, AttributeValue};
 use takeoff_core::error::TakeoffResult;
-use takeoff_core::scale::Scale;
+use takeoff_core::event::StateEvent;
+use takeoff_core::formula::{evaluate_formula, FormulaContext};
+use takeoff_core::integrity::EntityKind;
+use takeoff_core::scale::{resolve_scale, Scale};
 use takeoff_core::unit::UnitValue;
 use takeoff_core::{measurement::Measurement, unit::Unit};
 use uom::si::f32::{Area, Length};
@@ -57,6 +62,19 @@ impl MeasurementWrapper {
     let _ = self.recompute_measurements();
   }
 
+  /// Replace the measurement without recomputing its values.
+  ///
+  /// # Returns
+  ///
+  /// * `Measurement` - The replaced measurement.
+  pub fn replace_measurement(&self, measurement: Measurement) -> Measurement {
+    std::mem::replace(
+      &mut *lock_mutex(self.measurement.lock(), "measurement")
+        .expect("BUG: measurement mutex should not be poisoned"),
+      measurement,
+    )
+  }
+
   #[napi(getter)]
   pub fn get_points(&self) -> f64 {
     self.points
@@ -104,8 +122,10 @@ impl MeasurementWrapper {
     Ok(*area)
   }
 
+  /// Assign the scale resolved by [`resolve_scale`] from the scales of the measurement's page.
+  ///
+  /// If no scale resolves, the assigned scale and the values computed with it are cleared.
   pub fn calculate_scale(&self) -> Option<Scale> {
-    let mut current_scale: Option<Scale> = None;
     let measurement = lock_mutex(self.measurement.lock(), "measurement").ok()?;
     let geometry = match measurement.to_geometry() {
       Ok(geom) => geom,
@@ -113,24 +133,14 @@ impl MeasurementWrapper {
     };
     drop(measurement);
 
-    if let Some(state) = self.state.upgrade() {
-      for scale in state.get_page_scales(&self.page_id()) {
-        if matches!(scale, Scale::Area { .. }) {
-          if scale.is_in_bounding_box(&geometry) {
-            self.set_scale(scale.clone());
-            return Some(scale);
-          }
-        } else {
-          current_scale = Some(scale.clone());
-        }
-      }
-    }
-
-    if let Some(scale) = current_scale {
-      self.set_scale(scale.clone());
-      return Some(scale);
-    }
-    None
+    let state = self.state.upgrade()?;
+    let scales = state.get_page_scales(&self.page_id());
+    let Some(scale) = resolve_scale(&scales, &geometry).cloned() else {
+      self.clear_scale();
+      return None;
+    };
+    self.set_scale(scale.clone());
+    Some(scale)
   }
 
   #[napi]
@@ -177,6 +187,51 @@ impl MeasurementWrapper {
     Ok(None)
   }
 
+  /// Get the attributes of this measurement merged over those of its group.
+  ///
+  /// Keys set on the measurement override the group's value.
+  #[napi]
+  pub fn get_resolved_attributes(&self) -> BTreeMap<String, AttributeValue> {
+    let group_attributes = self
+      .state
+      .upgrade()
+      .and_then(|state| state.get_group(self.get_group_id()))
+      .and_then(|group| group.get_group().attributes);
+    let measurement = lock_mutex(self.measurement.lock(), "measurement")
+      .expect("BUG: measurement mutex should not be poisoned");
+    merge_attributes(group_attributes.as_ref(), measurement.attributes())
+  }
+
+  /// Build the formula context for this measurement.
+  ///
+  /// Binds `count` and `points`, plus `length`, `perimeter` and `area` when a scale is
+  /// assigned and the geometry supports them. Numeric and quantity attributes are bound
+  /// under their own names; the built-in variables take precedence.
+  pub fn formula_context(&self) -> FormulaContext {
+    let mut context = FormulaContext::new();
+    for (name, value) in self.get_resolved_attributes() {
+      if let Some(value) = value.to_unit_value() {
+        context.set_value(name, value);
+      }
+    }
+    let mut context = context
+      .with_count(self.get_count())
+      .with_points(self.points);
+    if let Ok(Some(length)) = self.get_length_value() {
+      context = context.with_length(length);
+    }
+    if let Ok(Some(area)) = self.get_area_value() {
+      context = context.with_area(area);
+    }
+    context
+  }
+
+  /// Evaluate a custom quantity formula (e.g. `ceil(length / 16in) + 1`) for this measurement.
+  #[napi]
+  pub fn evaluate_formula(&self, formula: String) -> Result<UnitValue> {
+    Ok(evaluate_formula(&formula, &self.formula_context())?)
+  }
+
   pub fn recompute_measurements(&self) -> TakeoffResult<()> {
     let area = self.calculate_area();
     *lock_mutex(self.area.lock(), "area")? = area?;
@@ -184,20 +239,45 @@ impl MeasurementWrapper {
     let length = self.calculate_length();
     *lock_mutex(self.length.lock(), "length")? = length?;
 
-    // Ignore recomputation errors - they will be handled when group values are accessed
     if let Some(state) = self.state.upgrade() {
+      state.emit_event(StateEvent::MeasurementRecomputed {
+        measurement_id: self.id(),
+      });
+      // Ignore recomputation errors - they will be handled when group values are accessed
       let _ = state.compute_group(&self.get_group_id());
     }
     Ok(())
   }
 
   pub fn set_scale(&self, scale: Scale) {
-    *lock_mutex(self.scale.lock(), "scale").expect("BUG: scale mutex should not be poisoned") =
-      Some(scale);
+    let scale_id = scale.id();
+    let previous = lock_mutex(self.scale.lock(), "scale")
+      .expect("BUG: scale mutex should not be poisoned")
+      .replace(scale);
+    if previous.map(|previous| previous.id()) != Some(scale_id.clone()) {
+      if let Some(state) = self.state.upgrade() {
+        state.emit_event(StateEvent::ScaleReassigned {
+          entity_kind: EntityKind::Measurement,
+          entity_id: self.id(),
+          scale_id,
+        });
+      }
+    }
     // Ignore recomputation errors - they will be handled when values are accessed
     let _ = self.recompute_measurements();
   }
 
+  /// Remove the assigned scale, clearing the area and length computed with it.
+  pub fn clear_scale(&self) {
+    let previous = lock_mutex(self.scale.lock(), "scale")
+      .expect("BUG: scale mutex should not be poisoned")
+      .take();
+    if previous.is_some() {
+      // Ignore recomputation errors - they will be handled when values are accessed
+      let _ = self.recompute_measurements();
+    }
+  }
+
   #[napi(getter)]
   pub fn get_scale(&self) -> Option<Scale> {
     lock_mutex(self.scale.lock(), "scale")
@@ -255,6 +335,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+      attributes: None,
     };
 
     assert_eq!(measurement.pixel_area().unwrap(), 5000.0);
@@ -285,6 +366,10 @@ mod tests {
         .unwrap(),
       6.0
     );
+    let waste = measurement_wrapper
+      .evaluate_formula("area * 1.5 + perimeter * 1m".to_string())
+      .unwrap();
+    assert_eq!(waste.get_converted_value(Unit::Meters), 9.0);
   }
 
   #[test]
@@ -294,6 +379,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: (Point::new(0.0, 0.0), Point::new(100.0, 50.0)),
+      attributes: None,
     };
     let measurement_wrapper =
       MeasurementWrapper::new(measurement, Arc::new(TakeoffStateHandler::default()));
@@ -307,6 +393,16 @@ mod tests {
       measurement_wrapper.convert_length(Unit::Meters).unwrap(),
       None
     );
+    assert!(measurement_wrapper
+      .evaluate_formula("area * 2".to_string())
+      .is_err());
+    assert_eq!(
+      measurement_wrapper
+        .evaluate_formula("points * count".to_string())
+        .unwrap()
+        .get_converted_value(Unit::Meters),
+      4.0
+    );
   }
 
   #[test]
@@ -316,6 +412,7 @@ mod tests {
       page_id: "1".to_string(),
       group_id: "1".to_string(),
       points: vec![Point::new(0.0, 0.0), Point::new(0.0, 1.0)],
+      attributes: None,
     };
     let measurement_wrapper =
       MeasurementWrapper::new(measurement, Arc::new(TakeoffStateHandler::default()));
@@ -1,16 +1,47 @@
 use crate::contour::ContourWrapper;
 use crate::group::GroupWrapper;
 use crate::measurement::MeasurementWrapper;
+use crate::utils::lock_mutex;
 use anyhow::Result;
 use dashmap::DashMap;
+use napi::bindgen_prelude::Buffer;
 use napi_derive::napi;
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use takeoff_core::attribute::{
+  aggregate_by_attribute, matches_all, AttributeAggregate, AttributeFilter, AttributeValue,
+  AttributedMeasurement,
+};
 use takeoff_core::contour::ContourInput;
-use takeoff_core::group::Group;
+use takeoff_core::coords::Point;
+use takeoff_core::diff::SnapshotDiff;
+use takeoff_core::dxf::{
+  read_dxf, write_dxf, DxfExportOptions, DxfImport, DxfImportOptions, DxfMeasurement,
+};
+use takeoff_core::error::TakeoffError;
+use takeoff_core::event::{EventQueue, StateEvent};
+use takeoff_core::geo_json::{from_geojson, to_feature_collection, MeasurementFeature};
+use takeoff_core::group::{Group, GroupValidationMode, MeasurementTypeMismatch};
+use takeoff_core::history::{Change, EntityValue, History};
+use takeoff_core::integrity::{
+  find_dangling_references, DanglingReference, EntityKind, ReferenceTargets, RemovalPolicy,
+};
 use takeoff_core::measurement::Measurement;
+use takeoff_core::offset::{offset_by_pixels, pixel_distance, OffsetJoin};
 use takeoff_core::page::Page;
-use takeoff_core::scale::Scale;
-use takeoff_core::state::StateOptions;
+use takeoff_core::project::{ProjectFile, ProjectFormat};
+use takeoff_core::report::{export_report, ReportLevel, ReportOptions, ReportRow};
+use takeoff_core::scale::{find_scale_conflicts, resolve_scale, Scale, ScaleConflict};
+use takeoff_core::snap::{find_snap, SnapCandidate, SnapOptions};
+use takeoff_core::snapshot::{
+  ContourResult, GroupResult, MeasurementResult, SnapshotResults, StateSnapshot, SNAPSHOT_VERSION,
+};
+use takeoff_core::spatial::SpatialIndex;
+use takeoff_core::state::{StateBatch, StateOptions};
+use takeoff_core::svg::{render_svg, SvgMeasurement, SvgOptions};
+use takeoff_core::unit::Unit;
+use takeoff_core::utils::{boolean_measurements, BooleanOperation};
+use takeoff_core::validity::{geometry_issues, GeometryIssue, ValidityOptions};
 #[napi]
 #[derive(Debug, Clone)]
 pub struct TakeoffStateHandler {
@@ -19,13 +50,21 @@ pub struct TakeoffStateHandler {
   measurements: Arc<DashMap<String, MeasurementWrapper>>,
   scales: Arc<DashMap<String, Scale>>,
   contours: Arc<DashMap<String, ContourWrapper>>,
+  /// R-tree of the measurements on each page, keyed by page id.
+  spatial: Arc<DashMap<String, SpatialIndex>>,
+  group_validation_mode: Arc<Mutex<GroupValidationMode>>,
+  removal_policy: Arc<Mutex<RemovalPolicy>>,
+  strict_references: Arc<Mutex<bool>>,
+  history: Arc<Mutex<History>>,
+  events: Arc<Mutex<EventQueue>>,
+  batching: Arc<Mutex<bool>>,
 
   self_arc: Option<Arc<TakeoffStateHandler>>,
 }
 
 impl Default for TakeoffStateHandler {
   fn default() -> Self {
-    Self::new(None)
+    Self::new(None).expect("BUG: a state without options should always be valid")
   }
 }
 
@@ -40,23 +79,521 @@ impl TakeoffStateHandler {
   /// # Returns
   ///
   /// * `State` - The new state.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if:
+  /// - References are strict and a measurement, scale or contour references a page or group
+  ///   that is not in the options ([`TakeoffError::DanglingReference`])
+  /// - The group validation mode is `Reject` and a measurement's group does not accept its
+  ///   kind ([`TakeoffError::IncompatibleMeasurementType`])
   #[napi(constructor)]
-  pub fn new(options: Option<StateOptions>) -> Self {
+  pub fn new(options: Option<StateOptions>) -> Result<Self> {
     let mut state = Self {
       pages: Arc::new(DashMap::new()),
       groups: Arc::new(DashMap::new()),
       measurements: Arc::new(DashMap::new()),
       scales: Arc::new(DashMap::new()),
       contours: Arc::new(DashMap::new()),
+      spatial: Arc::new(DashMap::new()),
+      group_validation_mode: Arc::new(Mutex::new(GroupValidationMode::default())),
+      removal_policy: Arc::new(Mutex::new(RemovalPolicy::default())),
+      strict_references: Arc::new(Mutex::new(false)),
+      history: Arc::new(Mutex::new(History::default())),
+      events: Arc::new(Mutex::new(EventQueue::new())),
+      batching: Arc::new(Mutex::new(false)),
       self_arc: None,
     };
     state.self_arc = Some(Arc::new(state.clone()));
 
     if let Some(options) = options {
-      state.add_initial_options(options);
+      state.add_initial_options(options)?;
     }
     state.compute_measurements();
-    state
+    state.compute_groups();
+    Ok(state)
+  }
+
+  #[napi(factory)]
+  /// Creates a state from a snapshot.
+  ///
+  /// Computed results are not read from the snapshot; they are recomputed from its entities.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::UnsupportedSnapshotVersion`] if the snapshot is newer than this
+  /// build supports, and [`TakeoffError::DanglingReference`] if its references are strict
+  /// and one of its entities references a missing page or group.
+  pub fn from_snapshot(snapshot: StateSnapshot) -> Result<Self> {
+    snapshot.check_version()?;
+    Self::new(Some(snapshot.to_state_options()))
+  }
+
+  #[napi(factory)]
+  /// Creates a state from a snapshot serialized with
+  /// [`to_snapshot_json`](Self::to_snapshot_json).
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the document is not a valid snapshot
+  /// ([`TakeoffError::InvalidSnapshot`]) or is newer than this build supports
+  /// ([`TakeoffError::UnsupportedSnapshotVersion`]).
+  pub fn from_snapshot_json(json: String) -> Result<Self> {
+    Self::from_snapshot(StateSnapshot::from_json(&json)?)
+  }
+
+  #[napi]
+  /// Get a snapshot of every entity, setting and computed result in the state.
+  ///
+  /// Entities and results are sorted by id, so equal states produce equal snapshots.
+  pub fn to_snapshot(&self) -> StateSnapshot {
+    let mut pages: Vec<Page> = self
+      .pages
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    pages.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut groups: Vec<GroupWrapper> = self
+      .groups
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    groups.sort_by(|a, b| a.id().cmp(b.id()));
+    let mut measurements: Vec<MeasurementWrapper> = self
+      .measurements
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    measurements.sort_by_key(|measurement| measurement.id());
+    let mut scales: Vec<Scale> = self
+      .scales
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    scales.sort_by_key(|scale| scale.id());
+    let mut contours: Vec<ContourWrapper> = self
+      .contours
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    contours.sort_by_key(|contour| contour.id());
+
+    let results = SnapshotResults {
+      measurements: measurements
+        .iter()
+        .map(|measurement| MeasurementResult {
+          measurement_id: measurement.id(),
+          scale_id: measurement.get_scale().map(|scale| scale.id()),
+          area: measurement
+            .get_area()
+            .map(|area| area.get_converted_value(Unit::Meters)),
+          length: measurement
+            .get_length()
+            .ok()
+            .flatten()
+            .map(|length| length.get_converted_value(Unit::Meters)),
+        })
+        .collect(),
+      groups: groups
+        .iter()
+        .map(|group| GroupResult {
+          group_id: group.id().to_string(),
+          area: group
+            .get_area()
+            .map(|area| area.get_converted_value(Unit::Meters)),
+          length: group
+            .get_length()
+            .map(|length| length.get_converted_value(Unit::Meters)),
+          points: group.get_points(),
+          count: group.get_count(),
+          volume: group
+            .get_volume()
+            .map(|volume| volume.get_converted_value(Unit::Meters)),
+        })
+        .collect(),
+      contours: contours
+        .iter()
+        .map(|contour| ContourResult {
+          contour_id: contour.id(),
+          scale_id: contour.get_scale().map(|scale| scale.id()),
+          has_surface_mesh: contour.get_surface_points().is_some(),
+        })
+        .collect(),
+    };
+
+    StateSnapshot {
+      version: SNAPSHOT_VERSION,
+      pages,
+      groups: groups.iter().map(|group| group.get_group()).collect(),
+      measurements: measurements
+        .iter()
+        .map(|measurement| measurement.get_measurement())
+        .collect(),
+      scales,
+      contours: contours
+        .iter()
+        .map(|contour| contour.get_contour())
+        .collect(),
+      group_validation_mode: self.get_group_validation_mode(),
+      removal_policy: self.get_removal_policy(),
+      strict_references: self.get_strict_references(),
+      results,
+    }
+  }
+
+  #[napi]
+  /// Get a snapshot of the state serialized as JSON.
+  pub fn to_snapshot_json(&self) -> Result<String> {
+    Ok(self.to_snapshot().to_json()?)
+  }
+
+  #[napi(factory)]
+  /// Creates a state from a project file, upgrading older schema versions first.
+  ///
+  /// # Arguments
+  ///
+  /// * `data` - The encoded project file.
+  /// * `format` - The encoding of `data`. Defaults to `Json`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the file is not a valid project file
+  /// ([`TakeoffError::InvalidProjectFile`]) or was written with a newer schema
+  /// ([`TakeoffError::UnsupportedProjectVersion`]).
+  pub fn from_project_file(data: Buffer, format: Option<ProjectFormat>) -> Result<Self> {
+    let project = ProjectFile::decode(&data, format.unwrap_or_default())?;
+    Self::new(Some(project.state))
+  }
+
+  #[napi]
+  /// Encode the state as a project file at the current schema version.
+  ///
+  /// # Arguments
+  ///
+  /// * `format` - The encoding to write. Defaults to `Json`.
+  pub fn to_project_file(&self, format: Option<ProjectFormat>) -> Result<Buffer> {
+    let project = ProjectFile::new(self.to_snapshot().to_state_options());
+    Ok(project.encode(format.unwrap_or_default())?.into())
+  }
+
+  #[napi]
+  /// Compare an earlier snapshot with the current state.
+  ///
+  /// # Arguments
+  ///
+  /// * `before` - The earlier snapshot, e.g. the bid set.
+  /// * `unit` - The unit to report quantities in. Defaults to `Meters`.
+  pub fn diff_snapshot(&self, before: StateSnapshot, unit: Option<Unit>) -> SnapshotDiff {
+    Self::diff_snapshots(before, self.to_snapshot(), unit)
+  }
+
+  #[napi]
+  /// Compare two snapshots.
+  ///
+  /// # Arguments
+  ///
+  /// * `before` - The earlier snapshot, e.g. the bid set.
+  /// * `after` - The later snapshot, e.g. the revised set.
+  /// * `unit` - The unit to report quantities in. Defaults to `Meters`.
+  pub fn diff_snapshots(
+    before: StateSnapshot,
+    after: StateSnapshot,
+    unit: Option<Unit>,
+  ) -> SnapshotDiff {
+    SnapshotDiff::new(&before, &after, unit.unwrap_or(Unit::Meters))
+  }
+
+  #[napi]
+  /// Export a quantity report of the groups or measurements as CSV or XLSX.
+  ///
+  /// # Arguments
+  ///
+  /// * `options` - The format, level, unit, columns and sort order of the report.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if:
+  /// - `decimals` exceeds the export maximum ([`TakeoffError::InvalidDecimals`])
+  /// - The report cannot be encoded ([`TakeoffError::ReportExport`])
+  pub fn export_report(&self, options: Option<ReportOptions>) -> Result<Buffer> {
+    let options = options.unwrap_or_default();
+    let page_name = |page_id: &str| {
+      self
+        .pages
+        .get(page_id)
+        .and_then(|page| page.name.clone())
+        .unwrap_or_else(|| page_id.to_string())
+    };
+    let group_name = |group: &Group| group.name.clone().unwrap_or_else(|| group.id.clone());
+    let groups: Vec<GroupWrapper> = self
+      .groups
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    let measurements: Vec<MeasurementWrapper> = self
+      .measurements
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+
+    let rows: Vec<ReportRow> = match options.level.unwrap_or_default() {
+      ReportLevel::Groups => groups
+        .iter()
+        .map(|wrapper| {
+          let group = wrapper.get_group();
+          let pages: BTreeSet<String> = measurements
+            .iter()
+            .filter(|measurement| measurement.get_group_id() == group.id)
+            .map(|measurement| page_name(&measurement.page_id()))
+            .collect();
+          ReportRow {
+            group: group_name(&group),
+            page: pages.into_iter().collect::<Vec<_>>().join(", "),
+            measurement_type: Some(group.measurement_type),
+            measurement: None,
+            area: wrapper.get_area(),
+            length: wrapper.get_length(),
+            count: wrapper.get_count(),
+          }
+        })
+        .collect(),
+      ReportLevel::Measurements => measurements
+        .iter()
+        .map(|measurement| {
+          let group_id = measurement.get_group_id();
+          let group = groups
+            .iter()
+            .find(|group| group.id() == group_id)
+            .map(GroupWrapper::get_group);
+          ReportRow {
+            group: group.as_ref().map_or(group_id.clone(), group_name),
+            page: page_name(&measurement.page_id()),
+            measurement_type: group.as_ref().map(|group| group.measurement_type),
+            measurement: Some(measurement.id()),
+            area: measurement.get_area(),
+            length: measurement.get_length().ok().flatten(),
+            count: Some(measurement.get_count()),
+          }
+        })
+        .collect(),
+    };
+    Ok(export_report(rows, &options)?.into())
+  }
+
+  #[napi]
+  /// Export the measurements of a page as a GeoJSON feature collection.
+  ///
+  /// Coordinates are page pixels. Each feature's properties hold the measurement's ids,
+  /// kind, scale, attributes and its real-world `area` and `length`.
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The page to export.
+  /// * `unit` - The unit of the `area` and `length` properties. Defaults to `Meters`.
+  pub fn export_geojson(&self, page_id: String, unit: Option<Unit>) -> Result<String> {
+    let mut measurements = self.get_measurements_by_page_id(page_id);
+    measurements.sort_by_key(|measurement| measurement.id());
+    let features: Vec<MeasurementFeature> = measurements
+      .iter()
+      .map(|measurement| MeasurementFeature {
+        measurement: measurement.get_measurement(),
+        scale_id: measurement.get_scale().map(|scale| scale.id()),
+        area: measurement.get_area(),
+        length: measurement.get_length().ok().flatten(),
+      })
+      .collect();
+    let collection = to_feature_collection(&features, unit.unwrap_or(Unit::Meters))?;
+    Ok(collection.to_string())
+  }
+
+  #[napi]
+  /// Import the features of a GeoJSON document as measurements on a page.
+  ///
+  /// Point, LineString and Polygon features become count, polyline and polygon
+  /// measurements, and are upserted together as with
+  /// [`upsert_measurements`](Self::upsert_measurements).
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The page to place the measurements on.
+  /// * `geojson` - A GeoJSON feature collection or feature.
+  /// * `group_id` - The group of features without a `group_id` property.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<Measurement>` - The imported measurements.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error, leaving the state unchanged, if a feature cannot be imported
+  /// ([`TakeoffError::InvalidGeoJson`]) or the measurements are rejected by
+  /// [`apply_batch`](Self::apply_batch).
+  pub fn import_geojson(
+    &self,
+    page_id: String,
+    geojson: String,
+    group_id: Option<String>,
+  ) -> Result<Vec<Measurement>> {
+    let measurements = from_geojson(&geojson, &page_id, group_id.as_deref())?;
+    self.upsert_measurements(measurements.clone())?;
+    Ok(measurements)
+  }
+
+  #[napi]
+  /// Export the measurements of a page as a DXF drawing, for overlaying on the design
+  /// drawings.
+  ///
+  /// Each group is drawn on its own layer, named after the group. Shapes are labelled with
+  /// their area or length and count markers are block inserts. Coordinates are converted
+  /// to real-world drawing units through each measurement's scale.
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The page to export.
+  /// * `options` - The drawing unit, label height and decimals. The drawing unit defaults
+  ///   to the unit of the page's default scale, or feet.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if:
+  /// - A measurement has invalid geometry ([`TakeoffError::EmptyGeometry`])
+  /// - A measurement's scale is invalid ([`TakeoffError::InvalidScale`])
+  /// - `text_height` is not positive ([`TakeoffError::InvalidDxf`])
+  /// - `decimals` exceeds the export maximum ([`TakeoffError::InvalidDecimals`])
+  pub fn export_dxf(&self, page_id: String, options: Option<DxfExportOptions>) -> Result<String> {
+    let mut options = options.unwrap_or_default();
+    options.unit = options.unit.or_else(|| {
+      self
+        .get_page_scales(&page_id)
+        .iter()
+        .find(|scale| matches!(scale, Scale::Default { .. }))
+        .map(Scale::get_unit)
+    });
+    let mut measurements = self.get_measurements_by_page_id(page_id);
+    measurements.sort_by_key(|measurement| measurement.id());
+    let measurements: Vec<DxfMeasurement> = measurements
+      .iter()
+      .map(|measurement| {
+        let group_id = measurement.get_group_id();
+        let layer = self
+          .groups
+          .get(&group_id)
+          .and_then(|group| group.get_group().name)
+          .unwrap_or(group_id);
+        DxfMeasurement {
+          measurement: measurement.get_measurement(),
+          layer,
+          scale: measurement.get_scale(),
+          area: measurement.get_area(),
+          length: measurement.get_length().ok().flatten(),
+        }
+      })
+      .collect();
+    Ok(write_dxf(&measurements, &options)?)
+  }
+
+  #[napi]
+  /// Render the measurements, area scale viewports and contours of a page as SVG.
+  ///
+  /// The drawing is in page pixels, sized to the page when its dimensions are known. Each
+  /// group is drawn in its own style, and measurements are labelled with their quantities
+  /// at their centroid.
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The page to render.
+  /// * `options` - The label unit and decimals, group styles and which layers to draw.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if:
+  /// - A measurement has invalid geometry ([`TakeoffError::EmptyGeometry`])
+  /// - `decimals` exceeds the export maximum ([`TakeoffError::InvalidDecimals`])
+  pub fn render_svg(&self, page_id: String, options: Option<SvgOptions>) -> Result<String> {
+    let page = self.pages.get(&page_id).map(|page| page.clone());
+    let scales = self.get_page_scales(&page_id);
+    let mut contours: Vec<ContourInput> = self
+      .get_contours_by_page_id(page_id.clone())
+      .iter()
+      .map(ContourWrapper::get_contour)
+      .collect();
+    contours.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut measurements = self.get_measurements_by_page_id(page_id);
+    measurements.sort_by_key(|measurement| measurement.id());
+    let measurements: Vec<SvgMeasurement> = measurements
+      .iter()
+      .map(|measurement| SvgMeasurement {
+        measurement: measurement.get_measurement(),
+        area: measurement.get_area(),
+        length: measurement.get_length().ok().flatten(),
+      })
+      .collect();
+    Ok(render_svg(
+      page.as_ref(),
+      &measurements,
+      &scales,
+      &contours,
+      &options.unwrap_or_default(),
+    )?)
+  }
+
+  #[napi]
+  /// Import the polylines, hatches and block references of a DXF drawing onto a page.
+  ///
+  /// Closed polylines and hatch boundaries become polygons, open polylines become
+  /// polylines and block references become count markers. The measurements, the groups of
+  /// their layers that do not exist yet and, if the page has no default scale, a scale from
+  /// the drawing's `$INSUNITS` header are added together as with
+  /// [`apply_batch`](Self::apply_batch).
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The page to place the measurements on.
+  /// * `data` - The contents of an ASCII DXF file.
+  /// * `options` - The page pixels per drawing unit and the group of each layer.
+  ///
+  /// # Returns
+  ///
+  /// * `DxfImport` - Everything read from the drawing, and warnings for skipped entities.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error, leaving the state unchanged, if the drawing cannot be read
+  /// ([`TakeoffError::InvalidDxf`]) or the batch is rejected by
+  /// [`apply_batch`](Self::apply_batch).
+  pub fn import_dxf(
+    &self,
+    page_id: String,
+    data: Buffer,
+    options: Option<DxfImportOptions>,
+  ) -> Result<DxfImport> {
+    let data = String::from_utf8_lossy(&data);
+    let import = read_dxf(&data, &page_id, &options.unwrap_or_default())?;
+    let has_default_scale = self
+      .get_page_scales(&page_id)
+      .iter()
+      .any(|scale| matches!(scale, Scale::Default { .. }));
+    self.apply_batch(StateBatch {
+      groups: Some(
+        import
+          .groups
+          .iter()
+          .filter(|group| !self.groups.contains_key(&group.id))
+          .cloned()
+          .collect(),
+      ),
+      measurements: Some(import.measurements.clone()),
+      scales: Some(
+        import
+          .scale
+          .iter()
+          .filter(|_| !has_default_scale)
+          .cloned()
+          .collect(),
+      ),
+      ..StateBatch::default()
+    })?;
+    Ok(import)
   }
 
   #[napi]
@@ -88,7 +625,239 @@ impl TakeoffStateHandler {
       .collect()
   }
 
-  fn add_initial_options(&self, options: StateOptions) {
+  #[napi]
+  /// Get the measurements on a page within a distance of a point, nearest first.
+  ///
+  /// A point inside a polygon or rectangle is at distance 0 from it.
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The id of the page.
+  /// * `point` - The point in page pixels.
+  /// * `tolerance` - The maximum distance in pixels, 0 by default.
+  pub fn measurements_at_point(
+    &self,
+    page_id: String,
+    point: Point,
+    tolerance: Option<f64>,
+  ) -> Vec<MeasurementWrapper> {
+    let ids = match self.spatial.get(&page_id) {
+      Some(index) => index.at_point(point, tolerance.unwrap_or(0.0)),
+      None => return Vec::new(),
+    };
+    self.get_measurements_by_ids(&ids)
+  }
+
+  #[napi]
+  /// Get the measurements on a page whose geometry intersects a rectangle, sorted by id.
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The id of the page.
+  /// * `min` - A corner of the rectangle in page pixels.
+  /// * `max` - The opposite corner of the rectangle.
+  pub fn measurements_in_rect(
+    &self,
+    page_id: String,
+    min: Point,
+    max: Point,
+  ) -> Vec<MeasurementWrapper> {
+    let ids = match self.spatial.get(&page_id) {
+      Some(index) => index.in_rect(min, max),
+      None => return Vec::new(),
+    };
+    self.get_measurements_by_ids(&ids)
+  }
+
+  #[napi]
+  /// Get the measurement on a page nearest to a point.
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The id of the page.
+  /// * `point` - The point in page pixels.
+  /// * `max_distance` - The maximum distance in pixels, unlimited by default.
+  pub fn nearest_measurement(
+    &self,
+    page_id: String,
+    point: Point,
+    max_distance: Option<f64>,
+  ) -> Option<MeasurementWrapper> {
+    let (id, distance) = self.spatial.get(&page_id)?.nearest(point)?;
+    if max_distance.is_some_and(|max_distance| distance > max_distance) {
+      return None;
+    }
+    self.get_measurement(id)
+  }
+
+  #[napi]
+  /// Snap a cursor to the measurements and contours on a page.
+  ///
+  /// Vertices win over intersections, midpoints, perpendicular feet and contour lines, in that
+  /// order; among candidates of the same kind the nearest wins.
+  ///
+  /// # Arguments
+  ///
+  /// * `page_id` - The id of the page.
+  /// * `cursor` - The cursor in page pixels.
+  /// * `tolerance` - The maximum snap distance in pixels.
+  /// * `options` - The kinds to snap to and the measurements to ignore.
+  ///
+  /// # Returns
+  ///
+  /// * `None` - If nothing is within the tolerance.
+  /// * `Some(candidate)` - The snapped point and what it lies on.
+  pub fn snap_point(
+    &self,
+    page_id: String,
+    cursor: Point,
+    tolerance: f64,
+    options: Option<SnapOptions>,
+  ) -> Option<SnapCandidate> {
+    let measurements: Vec<Measurement> = self
+      .measurements_at_point(page_id.clone(), cursor, Some(tolerance))
+      .iter()
+      .map(MeasurementWrapper::get_measurement)
+      .collect();
+    let contours: Vec<ContourInput> = self
+      .get_contours_by_page_id(page_id)
+      .iter()
+      .map(ContourWrapper::get_contour)
+      .collect();
+    find_snap(
+      cursor,
+      tolerance,
+      &measurements,
+      &contours,
+      &options.unwrap_or_default(),
+    )
+  }
+
+  #[napi]
+  /// Combine two polygon or rectangle measurements with a boolean operation.
+  ///
+  /// The results are not added to the state. Each is scaled with the scale its page would
+  /// resolve for it, so its area is the real-world area it would have once upserted.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::GeometryOperation`] if a measurement is not an area or the two
+  /// are on different pages, and [`TakeoffError::EmptyGeometry`] if a measurement has
+  /// invalid geometry.
+  pub fn boolean_op(
+    &self,
+    a: Measurement,
+    b: Measurement,
+    operation: BooleanOperation,
+  ) -> Result<Vec<MeasurementWrapper>> {
+    let results = boolean_measurements(&a, &b, operation)?;
+    Ok(self.preview_measurements(results))
+  }
+
+  #[napi]
+  /// Offset a polygon, rectangle or polyline measurement by a real-world distance.
+  ///
+  /// The distance is converted to page pixels with the scale the measurement's page
+  /// resolves for it. Positive distances grow areas and move polylines to the right of
+  /// their drawing direction. The results are not added to the state.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::GeometryOperation`] if the measurement is a count or no scale
+  /// applies to it, and [`TakeoffError::EmptyGeometry`] if it has invalid geometry.
+  pub fn offset_measurement(
+    &self,
+    measurement: Measurement,
+    distance: f64,
+    unit: Unit,
+    join: Option<OffsetJoin>,
+  ) -> Result<Vec<MeasurementWrapper>> {
+    let scales = self.get_page_scales(measurement.page_id());
+    let scale = resolve_scale(&scales, &measurement.to_geometry()?).ok_or_else(|| {
+      TakeoffError::geometry_operation(
+        "offset",
+        format!("measurement {} has no scale", measurement.id()),
+      )
+    })?;
+    let pixels = pixel_distance(distance, unit, scale)?;
+    let results = offset_by_pixels(&measurement, pixels, join.unwrap_or_default())?;
+    Ok(self.preview_measurements(results))
+  }
+
+  /// Wrap measurements that are not in the state, scaled as they would be on their pages.
+  fn preview_measurements(&self, measurements: Vec<Measurement>) -> Vec<MeasurementWrapper> {
+    measurements
+      .into_iter()
+      .map(|measurement| {
+        let scales = self.get_page_scales(measurement.page_id());
+        let scale = measurement
+          .to_geometry()
+          .ok()
+          .and_then(|geometry| resolve_scale(&scales, &geometry).cloned());
+        let wrapper = MeasurementWrapper::default(measurement);
+        if let Some(scale) = scale {
+          wrapper.set_scale(scale);
+        }
+        wrapper
+      })
+      .collect()
+  }
+
+  fn get_measurements_by_ids(&self, ids: &[String]) -> Vec<MeasurementWrapper> {
+    ids
+      .iter()
+      .filter_map(|id| self.measurements.get(id).map(|entry| entry.value().clone()))
+      .collect()
+  }
+
+  fn add_initial_options(&self, options: StateOptions) -> Result<()> {
+    if options.strict_references == Some(true) {
+      let targets = ReferenceTargets {
+        pages: options.pages.iter().map(|page| page.id.as_str()).collect(),
+        groups: options
+          .groups
+          .iter()
+          .map(|group| group.id.as_str())
+          .collect(),
+      };
+      let contours = options.contours.iter().flatten();
+      let dangling = options
+        .measurements
+        .iter()
+        .flat_map(|measurement| targets.check_measurement(measurement))
+        .chain(
+          options
+            .scales
+            .iter()
+            .filter_map(|scale| targets.check_scale(scale)),
+        )
+        .chain(contours.filter_map(|contour| targets.check_contour(contour)))
+        .next();
+      if let Some(reference) = dangling {
+        return Err(TakeoffError::from(reference).into());
+      }
+    }
+    if options.group_validation_mode == Some(GroupValidationMode::Reject) {
+      for measurement in &options.measurements {
+        let group = options
+          .groups
+          .iter()
+          .rev()
+          .find(|group| group.id == measurement.group_id());
+        if let Some(group) = group {
+          group.check_measurement(measurement)?;
+        }
+      }
+    }
+    if let Some(mode) = options.group_validation_mode {
+      self.set_group_validation_mode(mode);
+    }
+    if let Some(policy) = options.removal_policy {
+      self.set_removal_policy(policy);
+    }
+    if let Some(strict) = options.strict_references {
+      self.set_strict_references(strict);
+    }
     for page in options.pages {
       self.pages.insert(page.id.clone(), page);
     }
@@ -102,11 +871,18 @@ impl TakeoffStateHandler {
       );
     }
     for measurement in options.measurements {
+      self.index_measurement(None, Some(&measurement));
       self.measurements.insert(
         measurement.id().to_string(),
         MeasurementWrapper::new(measurement, self.self_arc.clone().unwrap()),
       );
     }
+    for contour in options.contours.unwrap_or_default() {
+      let wrapper = ContourWrapper::from_input(contour.clone(), self.self_arc.clone().unwrap());
+      wrapper.calculate_scale();
+      self.contours.insert(contour.id, wrapper);
+    }
+    Ok(())
   }
 
   fn compute_measurements(&self) {
@@ -115,6 +891,17 @@ impl TakeoffStateHandler {
     }
   }
 
+  fn compute_groups(&self) {
+    let group_ids: Vec<String> = self
+      .groups
+      .iter()
+      .map(|entry| entry.key().clone())
+      .collect();
+    for group_id in group_ids {
+      let _ = self.compute_group(&group_id);
+    }
+  }
+
   fn compute_page(&self, page_id: &str) {
     let measurements = self
       .measurements
@@ -150,13 +937,16 @@ impl TakeoffStateHandler {
     // self.find_measurement_scale(measurement)
   }
 
+  /// Get the scales of a page, sorted by id.
   pub fn get_page_scales(&self, page_id: &str) -> Vec<Scale> {
-    self
+    let mut scales = self
       .scales
       .iter()
       .filter(|entry| entry.value().page_id() == page_id)
       .map(|entry| entry.value().clone())
-      .collect::<Vec<Scale>>()
+      .collect::<Vec<Scale>>();
+    scales.sort_by_key(|scale| scale.id());
+    scales
   }
 
   #[napi]
@@ -171,44 +961,177 @@ impl TakeoffStateHandler {
   /// * `None` - If the page was not found.
   /// * `Some(page)` - If the page was found and updated.
   pub fn upsert_page(&self, page: Page) -> Option<Page> {
-    self.pages.insert(page.id.clone(), page)
-  }
-
-  #[napi]
-  pub fn remove_page(&self, page_id: String) -> Option<Page> {
-    self.pages.remove(&page_id).map(|(_, page)| page)
-  }
-
-  #[napi]
-  pub fn get_group(&self, group_id: String) -> Option<GroupWrapper> {
-    self
-      .groups
-      .get(&group_id)
-      .map(|entry| entry.value().clone())
+    let id = page.id.clone();
+    let previous = self.write_page(&id, Some(page.clone()));
+    self.record(Change::new(
+      EntityKind::Page,
+      id,
+      previous.clone().map(EntityValue::Page),
+      Some(EntityValue::Page(page)),
+    ));
+    previous
   }
 
   #[napi]
-  /// Inserts or updates a group in the state.
+  /// Removes a page from the state.
+  ///
+  /// With the default `Legacy` removal policy only the page is removed. With `Cascade` the
+  /// page's measurements, scales and contours are removed too, as a single history step;
+  /// with `Reject` the page is kept while any of them remain.
   ///
   /// # Arguments
   ///
-  /// * `group` - The group to insert or update.
+  /// * `page_id` - The id of the page to remove.
   ///
   /// # Returns
+  /// * `None` - If the page was not found.
+  /// * `Some(page)` - If the page was found and removed.
   ///
-  /// * `None` - If the group was not found.
-  /// * `Some(group)` - If the group was found and updated.
-  pub fn upsert_group(&self, group: Group) -> Option<Group> {
-    let group_clone = group.clone();
-    self.groups.insert(
-      group.id.clone(),
-      GroupWrapper::new(group, self.self_arc.clone().unwrap()),
-    );
-    Some(group_clone)
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::EntityInUse`] if the removal policy is `Reject` and the page
+  /// still has dependents.
+  pub fn remove_page(&self, page_id: String) -> Result<Option<Page>> {
+    self.grouped(|| self.remove_page_and_dependents(page_id))
   }
 
-  #[napi]
-  /// Removes a group from the state.
+  fn remove_page_and_dependents(&self, page_id: String) -> Result<Option<Page>> {
+    if !self.pages.contains_key(&page_id) {
+      return Ok(None);
+    }
+    let measurement_ids: Vec<String> = self
+      .measurements
+      .iter()
+      .filter(|entry| entry.value().page_id() == page_id)
+      .map(|entry| entry.key().clone())
+      .collect();
+    let scale_ids: Vec<String> = self
+      .scales
+      .iter()
+      .filter(|entry| entry.value().page_id() == page_id)
+      .map(|entry| entry.key().clone())
+      .collect();
+    let contour_ids: Vec<String> = self
+      .contours
+      .iter()
+      .filter(|entry| entry.value().page_id() == page_id)
+      .map(|entry| entry.key().clone())
+      .collect();
+
+    let policy = self.get_removal_policy();
+    if policy == RemovalPolicy::Reject {
+      let mut dependent_ids: Vec<String> = measurement_ids
+        .into_iter()
+        .chain(scale_ids)
+        .chain(contour_ids)
+        .collect();
+      if !dependent_ids.is_empty() {
+        dependent_ids.sort();
+        return Err(
+          TakeoffError::EntityInUse {
+            entity_kind: EntityKind::Page,
+            entity_id: page_id,
+            dependent_ids,
+          }
+          .into(),
+        );
+      }
+    } else if policy == RemovalPolicy::Cascade {
+      for measurement_id in measurement_ids {
+        self.remove_measurement(measurement_id);
+      }
+      for scale_id in scale_ids {
+        if let Some((_, scale)) = self.scales.remove(&scale_id) {
+          self.record(Change::new(
+            EntityKind::Scale,
+            scale_id,
+            Some(EntityValue::Scale(scale)),
+            None,
+          ));
+        }
+      }
+      for contour_id in contour_ids {
+        if let Some(contour) = self.write_contour(&contour_id, None) {
+          self.record(Change::new(
+            EntityKind::Contour,
+            contour_id,
+            Some(EntityValue::Contour(contour)),
+            None,
+          ));
+        }
+      }
+    }
+
+    let previous = self.write_page(&page_id, None);
+    self.record(Change::new(
+      EntityKind::Page,
+      page_id,
+      previous.clone().map(EntityValue::Page),
+      None,
+    ));
+    Ok(previous)
+  }
+
+  #[napi]
+  pub fn get_group(&self, group_id: String) -> Option<GroupWrapper> {
+    self
+      .groups
+      .get(&group_id)
+      .map(|entry| entry.value().clone())
+  }
+
+  #[napi]
+  /// Inserts or updates a group in the state.
+  ///
+  /// # Arguments
+  ///
+  /// * `group` - The group to insert or update.
+  ///
+  /// # Returns
+  ///
+  /// * `None` - If the group was not found.
+  /// * `Some(group)` - If the group was found and updated.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::IncompatibleMeasurementType`] if the group validation mode is
+  /// `Reject` and the group's new measurement type does not accept one of its measurements.
+  pub fn upsert_group(&self, group: Group) -> Result<Option<Group>> {
+    if self.get_group_validation_mode() == GroupValidationMode::Reject {
+      self.check_group_measurements(&group, |_| true)?;
+    }
+    let id = group.id.clone();
+    let previous = self.write_group(&id, Some(group.clone()));
+    self.record(Change::new(
+      EntityKind::Group,
+      id,
+      previous.map(EntityValue::Group),
+      Some(EntityValue::Group(group.clone())),
+    ));
+    Ok(Some(group))
+  }
+
+  /// Check the existing measurements of `group`, those selected by `include`, against its
+  /// measurement type.
+  fn check_group_measurements(&self, group: &Group, include: impl Fn(&str) -> bool) -> Result<()> {
+    let mut measurements: Vec<Measurement> = self
+      .measurements
+      .iter()
+      .filter(|entry| entry.value().get_group_id() == group.id && include(entry.key()))
+      .map(|entry| entry.value().get_measurement())
+      .collect();
+    measurements.sort_by(|a, b| a.id().cmp(b.id()));
+    for measurement in &measurements {
+      group.check_measurement(measurement)?;
+    }
+    Ok(())
+  }
+
+  #[napi]
+  /// Removes a group from the state.
+  ///
+  /// Unless the removal policy is `Reject`, the group's measurements are removed too, as a
+  /// single history step.
   ///
   /// # Arguments
   ///
@@ -217,23 +1140,51 @@ impl TakeoffStateHandler {
   /// # Returns
   /// * `None` - If the group was not found.
   /// * `Some(group)` - If the group was found and removed.
-  pub fn remove_group(&self, group_id: String) -> Option<Group> {
-    let res = self.groups.remove(&group_id);
-    if let Some((_, group)) = res {
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::EntityInUse`] if the removal policy is `Reject` and the group
+  /// still has measurements.
+  pub fn remove_group(&self, group_id: String) -> Result<Option<Group>> {
+    self.grouped(|| self.remove_group_and_measurements(group_id))
+  }
+
+  fn remove_group_and_measurements(&self, group_id: String) -> Result<Option<Group>> {
+    if !self.groups.contains_key(&group_id) {
+      return Ok(None);
+    }
+    let mut to_remove: Vec<String> = self
+      .measurements
+      .iter()
+      .filter(|entry| entry.value().get_group_id() == group_id)
+      .map(|entry| entry.key().clone())
+      .collect();
+    if self.get_removal_policy() == RemovalPolicy::Reject && !to_remove.is_empty() {
+      to_remove.sort();
+      return Err(
+        TakeoffError::EntityInUse {
+          entity_kind: EntityKind::Group,
+          entity_id: group_id,
+          dependent_ids: to_remove,
+        }
+        .into(),
+      );
+    }
+
+    let previous = self.write_group(&group_id, None);
+    if let Some(group) = &previous {
+      self.record(Change::new(
+        EntityKind::Group,
+        group_id,
+        Some(EntityValue::Group(group.clone())),
+        None,
+      ));
       // also remove any measurements tied to this group
-      let to_remove: Vec<String> = self
-        .measurements
-        .iter()
-        .filter(|entry| entry.value().get_group_id() == group_id)
-        .map(|entry| entry.key().clone())
-        .collect();
       for mid in to_remove {
         self.remove_measurement(mid);
       }
-      return Some(group.get_group());
     }
-
-    None
+    Ok(previous)
   }
 
   #[napi]
@@ -247,25 +1198,221 @@ impl TakeoffStateHandler {
   ///
   /// * `None` - If the measurement was not found.
   /// * `Some(measurement)` - If the measurement was found and updated.
-  pub fn upsert_measurement(&self, measurement: Measurement) -> Option<Measurement> {
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if:
+  /// - References are strict and the measurement's page or group does not exist
+  ///   ([`TakeoffError::DanglingReference`])
+  /// - The group validation mode is `Reject` and the measurement's group does not accept
+  ///   its kind ([`TakeoffError::IncompatibleMeasurementType`])
+  pub fn upsert_measurement(&self, measurement: Measurement) -> Result<Option<Measurement>> {
+    self.check_references(|targets| targets.check_measurement(&measurement))?;
+    if self.get_group_validation_mode() == GroupValidationMode::Reject {
+      if let Some(group) = self.groups.get(measurement.group_id()) {
+        group.get_group().check_measurement(&measurement)?;
+      }
+    }
+
     let id = measurement.id().to_string();
+    let previous = self.write_measurement(&id, Some(measurement.clone()));
+    let updated = previous.is_some();
+    self.record(Change::new(
+      EntityKind::Measurement,
+      id,
+      previous.map(EntityValue::Measurement),
+      Some(EntityValue::Measurement(measurement.clone())),
+    ));
+    Ok(updated.then_some(measurement))
+  }
 
-    if let Some(prev_measurement) = self.measurements.get(&id) {
-      prev_measurement.set_measurement(measurement.clone());
-      return Some(prev_measurement.get_measurement());
-    }
+  #[napi]
+  /// Set how measurements incompatible with their group's measurement type are handled.
+  ///
+  /// Existing measurements are not re-validated; use
+  /// [`get_measurement_type_mismatches`](Self::get_measurement_type_mismatches) to find them.
+  pub fn set_group_validation_mode(&self, mode: GroupValidationMode) {
+    *lock_mutex(self.group_validation_mode.lock(), "group_validation_mode")
+      .expect("BUG: group_validation_mode mutex should not be poisoned") = mode;
+  }
 
-    let res = self.measurements.insert(
-      measurement.id().to_string(),
-      MeasurementWrapper::new(measurement.clone(), self.self_arc.clone().unwrap()),
-    );
-    self.compute_measurement(&id);
-    let _ = self.compute_group(measurement.group_id());
+  #[napi]
+  pub fn get_group_validation_mode(&self) -> GroupValidationMode {
+    lock_mutex(self.group_validation_mode.lock(), "group_validation_mode")
+      .map(|mode| *mode)
+      .unwrap_or_default()
+  }
+
+  #[napi]
+  /// Get the measurements whose kind does not match their group's measurement type.
+  ///
+  /// Measurements whose group does not exist are not reported.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<MeasurementTypeMismatch>` - The mismatches, sorted by measurement id.
+  pub fn get_measurement_type_mismatches(&self) -> Vec<MeasurementTypeMismatch> {
+    let measurements: Vec<Measurement> = self
+      .measurements
+      .iter()
+      .map(|entry| entry.value().get_measurement())
+      .collect();
+    let mut mismatches: Vec<MeasurementTypeMismatch> = measurements
+      .iter()
+      .filter_map(|measurement| {
+        self
+          .groups
+          .get(measurement.group_id())
+          .and_then(|group| group.get_group().mismatch(measurement))
+      })
+      .collect();
+    mismatches.sort_by(|a, b| a.measurement_id.cmp(&b.measurement_id));
+    mismatches
+  }
+
+  #[napi]
+  /// Set what happens to dependents when a page or group is removed.
+  pub fn set_removal_policy(&self, policy: RemovalPolicy) {
+    *lock_mutex(self.removal_policy.lock(), "removal_policy")
+      .expect("BUG: removal_policy mutex should not be poisoned") = policy;
+  }
+
+  #[napi]
+  pub fn get_removal_policy(&self) -> RemovalPolicy {
+    lock_mutex(self.removal_policy.lock(), "removal_policy")
+      .map(|policy| *policy)
+      .unwrap_or_default()
+  }
+
+  #[napi]
+  /// Set whether upserts referencing a missing page or group are rejected.
+  ///
+  /// Existing entities are not re-validated; use [`validate_state`](Self::validate_state)
+  /// to find dangling references.
+  pub fn set_strict_references(&self, strict: bool) {
+    *lock_mutex(self.strict_references.lock(), "strict_references")
+      .expect("BUG: strict_references mutex should not be poisoned") = strict;
+  }
+
+  #[napi]
+  pub fn get_strict_references(&self) -> bool {
+    lock_mutex(self.strict_references.lock(), "strict_references")
+      .map(|strict| *strict)
+      .unwrap_or_default()
+  }
+
+  #[napi]
+  /// Find every reference to a page or group that does not exist.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<DanglingReference>` - The dangling references of measurements, scales and
+  ///   contours, sorted by entity kind and id. Empty if the state is consistent.
+  pub fn validate_state(&self) -> Vec<DanglingReference> {
+    let measurements: Vec<Measurement> = self
+      .measurements
+      .iter()
+      .map(|entry| entry.value().get_measurement())
+      .collect();
+    let scales: Vec<Scale> = self
+      .scales
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    let contours: Vec<ContourInput> = self
+      .contours
+      .iter()
+      .map(|entry| entry.value().get_contour())
+      .collect();
+    self.with_reference_targets(|targets| {
+      find_dangling_references(targets, &measurements, &scales, &contours)
+    })
+  }
+
+  #[napi]
+  /// Find every problem in the geometry of the measurements, such as self-intersecting
+  /// outlines, repeated vertices, zero-length edges and slivers.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<GeometryIssue>` - The issues, sorted by measurement id and vertex. Empty if
+  ///   every measurement is valid.
+  pub fn validate_geometry(&self, options: Option<ValidityOptions>) -> Vec<GeometryIssue> {
+    let options = options.unwrap_or_default();
+    let mut measurements: Vec<Measurement> = self
+      .measurements
+      .iter()
+      .map(|entry| entry.value().get_measurement())
+      .collect();
+    measurements.sort_by(|a, b| a.id().cmp(b.id()));
+    measurements
+      .iter()
+      .flat_map(|measurement| geometry_issues(measurement, &options))
+      .collect()
+  }
+
+  #[napi]
+  /// Find scales that make scale resolution ambiguous, such as several default scales on
+  /// one page or partially overlapping area scales.
+  ///
+  /// Conflicts are warnings: measurements and contours are still assigned a scale
+  /// deterministically, preferring the smallest area scale that contains them, then the
+  /// default scale with the smallest id.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<ScaleConflict>` - The conflicts, sorted by page id and scale ids.
+  pub fn get_scale_conflicts(&self) -> Vec<ScaleConflict> {
+    let scales: Vec<Scale> = self
+      .scales
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    find_scale_conflicts(&scales)
+  }
+
+  fn with_reference_targets<T>(&self, f: impl FnOnce(&ReferenceTargets) -> T) -> T {
+    self.with_batch_reference_targets(&StateBatch::default(), f)
+  }
+
+  /// Like [`with_reference_targets`](Self::with_reference_targets), also counting the pages
+  /// and groups of `batch` as existing.
+  fn with_batch_reference_targets<T>(
+    &self,
+    batch: &StateBatch,
+    f: impl FnOnce(&ReferenceTargets) -> T,
+  ) -> T {
+    let pages: Vec<String> = self
+      .pages
+      .iter()
+      .map(|entry| entry.key().clone())
+      .chain(batch.pages.iter().flatten().map(|page| page.id.clone()))
+      .collect();
+    let groups: Vec<String> = self
+      .groups
+      .iter()
+      .map(|entry| entry.key().clone())
+      .chain(batch.groups.iter().flatten().map(|group| group.id.clone()))
+      .collect();
+    let targets = ReferenceTargets {
+      pages: pages.iter().map(String::as_str).collect(),
+      groups: groups.iter().map(String::as_str).collect(),
+    };
+    f(&targets)
+  }
 
-    if let Some(measurement) = res {
-      return Some(measurement.get_measurement());
+  /// Reject the first dangling reference found by `check` when references are strict.
+  fn check_references<I: IntoIterator<Item = DanglingReference>>(
+    &self,
+    check: impl FnOnce(&ReferenceTargets) -> I,
+  ) -> Result<()> {
+    if !self.get_strict_references() {
+      return Ok(());
+    }
+    match self.with_reference_targets(check).into_iter().next() {
+      Some(reference) => Err(TakeoffError::from(reference).into()),
+      None => Ok(()),
     }
-    None
   }
 
   #[napi]
@@ -280,13 +1427,14 @@ impl TakeoffStateHandler {
   /// * `None` - If the measurement was not found.
   /// * `Some(measurement)` - If the measurement was found and removed.
   pub fn remove_measurement(&self, measurement_id: String) -> Option<Measurement> {
-    let res = self.measurements.remove(&measurement_id);
-    if let Some((_, measurement)) = res {
-      // Ignore recomputation errors - they will be handled when group values are accessed
-      let _ = self.compute_group(&measurement.get_group_id());
-      return Some(measurement.get_measurement());
-    }
-    None
+    let previous = self.write_measurement(&measurement_id, None);
+    self.record(Change::new(
+      EntityKind::Measurement,
+      measurement_id,
+      previous.clone().map(EntityValue::Measurement),
+      None,
+    ));
+    previous
   }
 
   #[napi]
@@ -308,12 +1456,22 @@ impl TakeoffStateHandler {
   ///
   /// * `None` - If the scale was not found.
   /// * `Some(scale)` - If the scale was found and updated.
-  pub fn upsert_scale(&self, scale: Scale) -> Option<Scale> {
-    let page_id = scale.page_id();
-    let res = self.scales.insert(scale.id(), scale);
-    self.compute_page(&page_id);
-    self.compute_contours(&page_id);
-    res
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::DanglingReference`] if references are strict and the scale's
+  /// page does not exist.
+  pub fn upsert_scale(&self, scale: Scale) -> Result<Option<Scale>> {
+    self.check_references(|targets| targets.check_scale(&scale))?;
+    let id = scale.id();
+    let previous = self.write_scale(&id, Some(scale.clone()));
+    self.record(Change::new(
+      EntityKind::Scale,
+      id,
+      previous.clone().map(EntityValue::Scale),
+      Some(EntityValue::Scale(scale)),
+    ));
+    Ok(previous)
   }
 
   #[napi]
@@ -327,13 +1485,14 @@ impl TakeoffStateHandler {
   /// * `None` - If the scale was not found.
   /// * `Some(scale)` - If the scale was found and removed.
   pub fn remove_scale(&self, scale_id: String) -> Option<Scale> {
-    let scale = self.scales.remove(&scale_id);
-    if let Some((_, scale)) = scale {
-      self.compute_page(&scale.page_id());
-      self.compute_contours(&scale.page_id());
-      return Some(scale);
-    }
-    None
+    let previous = self.write_scale(&scale_id, None);
+    self.record(Change::new(
+      EntityKind::Scale,
+      scale_id,
+      previous.clone().map(EntityValue::Scale),
+      None,
+    ));
+    previous
   }
 
   #[napi]
@@ -352,24 +1511,132 @@ impl TakeoffStateHandler {
   }
 
   #[napi]
-  pub fn upsert_contour(&self, input: ContourInput) {
-    // let input: takeoff_core::contour::ContourInput = contour.into();
-    let id = input.id.clone();
+  /// Get the measurements whose resolved attributes match every filter.
+  ///
+  /// A measurement's resolved attributes are its own attributes merged over its group's.
+  ///
+  /// # Arguments
+  ///
+  /// * `filters` - The filters to apply. An empty list matches every measurement.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<MeasurementWrapper>` - The matching measurements.
+  pub fn get_measurements_by_attributes(
+    &self,
+    filters: Vec<AttributeFilter>,
+  ) -> Vec<MeasurementWrapper> {
+    self
+      .resolved_measurement_attributes()
+      .into_iter()
+      .filter(|(_, attributes)| matches_all(&filters, attributes))
+      .map(|(measurement, _)| measurement)
+      .collect()
+  }
 
-    if let Some(existing) = self.contours.get(&id) {
-      existing.set_contour(input);
-      existing.calculate_scale();
-      return;
-    }
-    // let state = ;
-    let wrapper = ContourWrapper::from_input(input, self.self_arc.clone().unwrap());
-    wrapper.calculate_scale();
-    self.contours.insert(id, wrapper);
+  #[napi]
+  /// Get the groups whose attributes match every filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `filters` - The filters to apply. An empty list matches every group.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<GroupWrapper>` - The matching groups.
+  pub fn get_groups_by_attributes(&self, filters: Vec<AttributeFilter>) -> Vec<GroupWrapper> {
+    self
+      .groups
+      .iter()
+      .filter(|entry| {
+        let attributes = entry.value().get_group().attributes.unwrap_or_default();
+        matches_all(&filters, &attributes)
+      })
+      .map(|entry| entry.value().clone())
+      .collect()
+  }
+
+  #[napi]
+  /// Aggregate measurement totals by the value of an attribute.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - The attribute to bucket by.
+  /// * `filters` - Optional filters restricting which measurements are aggregated.
+  ///
+  /// # Returns
+  ///
+  /// * `Vec<AttributeAggregate>` - One bucket per distinct value, plus one for measurements
+  ///   without the attribute.
+  pub fn aggregate_measurements_by_attribute(
+    &self,
+    key: String,
+    filters: Option<Vec<AttributeFilter>>,
+  ) -> Vec<AttributeAggregate> {
+    let filters = filters.unwrap_or_default();
+    let measurements: Vec<AttributedMeasurement> = self
+      .resolved_measurement_attributes()
+      .into_iter()
+      .filter(|(_, attributes)| matches_all(&filters, attributes))
+      .map(|(measurement, attributes)| AttributedMeasurement {
+        id: measurement.id().to_string(),
+        attributes,
+        points: measurement.get_points(),
+        area: measurement.get_area_value().ok().flatten(),
+        length: measurement.get_length_value().ok().flatten(),
+      })
+      .collect();
+    aggregate_by_attribute(&key, &measurements)
+  }
+
+  fn resolved_measurement_attributes(
+    &self,
+  ) -> Vec<(MeasurementWrapper, BTreeMap<String, AttributeValue>)> {
+    let measurements: Vec<MeasurementWrapper> = self
+      .measurements
+      .iter()
+      .map(|entry| entry.value().clone())
+      .collect();
+    measurements
+      .into_iter()
+      .map(|measurement| {
+        let attributes = measurement.get_resolved_attributes();
+        (measurement, attributes)
+      })
+      .collect()
+  }
+
+  #[napi]
+  /// Inserts or updates a contour in the state.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::DanglingReference`] if references are strict and the contour's
+  /// page does not exist.
+  pub fn upsert_contour(&self, input: ContourInput) -> Result<()> {
+    self.check_references(|targets| targets.check_contour(&input))?;
+    let id = input.id.clone();
+    let previous = self.write_contour(&id, Some(input.clone()));
+    self.record(Change::new(
+      EntityKind::Contour,
+      id,
+      previous.map(EntityValue::Contour),
+      Some(EntityValue::Contour(input)),
+    ));
+    Ok(())
   }
 
   #[napi]
   pub fn remove_contour(&self, contour_id: String) -> bool {
-    self.contours.remove(&contour_id).is_some()
+    let previous = self.write_contour(&contour_id, None);
+    let removed = previous.is_some();
+    self.record(Change::new(
+      EntityKind::Contour,
+      contour_id,
+      previous.map(EntityValue::Contour),
+      None,
+    ));
+    removed
   }
 
   #[napi]
@@ -400,34 +1667,548 @@ impl TakeoffStateHandler {
       .collect()
   }
 
-  fn compute_contours(&self, page_id: &str) {
-    let contours: Vec<ContourWrapper> = self
-      .contours
-      .iter()
-      .filter(|entry| entry.value().page_id() == page_id)
-      .map(|entry| entry.value().clone())
-      .collect();
-    for contour in contours {
-      contour.calculate_scale();
-    }
+  #[napi]
+  /// Inserts or updates many measurements with a single recomputation pass.
+  ///
+  /// See [`apply_batch`](Self::apply_batch).
+  pub fn upsert_measurements(&self, measurements: Vec<Measurement>) -> Result<()> {
+    self.apply_batch(StateBatch {
+      measurements: Some(measurements),
+      ..StateBatch::default()
+    })
   }
-}
 
-#[napi]
-#[cfg(not(target_family = "wasm"))]
-impl TakeoffStateHandler {
-  fn compute_measurement(&self, measurement_id: &str) {
-    let measurement = self.measurements.get(measurement_id);
-    if let Some(measurement) = measurement {
-      std::thread::scope(|s| {
-        s.spawn(|| {
-          measurement.calculate_scale();
-        });
+  #[napi]
+  /// Inserts or updates pages, groups, scales, contours and measurements together.
+  ///
+  /// The batch is validated as a whole before anything is written, so references to pages
+  /// and groups in the same batch are allowed. Measurements are written as by
+  /// [`upsert_measurement`](Self::upsert_measurement), but group totals are recomputed
+  /// once at the end instead of after every entity, and the batch is recorded as a single
+  /// history step.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error, leaving the state unchanged, if:
+  /// - References are strict and an entity references a page or group that neither exists
+  ///   nor is in the batch ([`TakeoffError::DanglingReference`])
+  /// - The group validation mode is `Reject` and a measurement's group does not accept its
+  ///   kind, including existing measurements of a group whose type the batch changes
+  ///   ([`TakeoffError::IncompatibleMeasurementType`])
+  pub fn apply_batch(&self, batch: StateBatch) -> Result<()> {
+    self.validate_batch(&batch)?;
+    self.grouped(|| self.write_batch(batch));
+    Ok(())
+  }
+
+  fn validate_batch(&self, batch: &StateBatch) -> Result<()> {
+    if self.get_strict_references() {
+      let dangling = self.with_batch_reference_targets(batch, |targets| {
+        let measurements = batch.measurements.iter().flatten();
+        let scales = batch.scales.iter().flatten();
+        let contours = batch.contours.iter().flatten();
+        measurements
+          .flat_map(|measurement| targets.check_measurement(measurement))
+          .chain(scales.filter_map(|scale| targets.check_scale(scale)))
+          .chain(contours.filter_map(|contour| targets.check_contour(contour)))
+          .next()
       });
+      if let Some(reference) = dangling {
+        return Err(TakeoffError::from(reference).into());
+      }
     }
-  }
 
-  pub fn compute_group(&self, group_id: &str) -> Result<()> {
+    if self.get_group_validation_mode() == GroupValidationMode::Reject {
+      for measurement in batch.measurements.iter().flatten() {
+        let group = batch
+          .groups
+          .iter()
+          .flatten()
+          .rev()
+          .find(|group| group.id == measurement.group_id())
+          .cloned()
+          .or_else(|| {
+            self
+              .groups
+              .get(measurement.group_id())
+              .map(|group| group.get_group())
+          });
+        if let Some(group) = group {
+          group.check_measurement(measurement)?;
+        }
+      }
+      let batch_ids: BTreeSet<&str> = batch
+        .measurements
+        .iter()
+        .flatten()
+        .map(|measurement| measurement.id())
+        .collect();
+      let mut checked = BTreeSet::new();
+      for group in batch.groups.iter().flatten().rev() {
+        if checked.insert(group.id.as_str()) {
+          self.check_group_measurements(group, |id| !batch_ids.contains(id))?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn write_batch(&self, batch: StateBatch) {
+    self.set_batching(true);
+    let mut pages: BTreeSet<String> = BTreeSet::new();
+    let mut groups: BTreeSet<String> = BTreeSet::new();
+
+    for page in batch.pages.unwrap_or_default() {
+      self.upsert_page(page);
+    }
+    for group in batch.groups.unwrap_or_default() {
+      let id = group.id.clone();
+      let previous = self.groups.get(&id).map(|entry| entry.get_group());
+      self.groups.insert(
+        id.clone(),
+        GroupWrapper::from_group(group.clone(), self.self_arc.clone().unwrap()),
+      );
+      self.record(Change::new(
+        EntityKind::Group,
+        id.clone(),
+        previous.map(EntityValue::Group),
+        Some(EntityValue::Group(group)),
+      ));
+      groups.insert(id);
+    }
+    for scale in batch.scales.unwrap_or_default() {
+      let id = scale.id();
+      pages.insert(scale.page_id());
+      let previous = self.scales.insert(id.clone(), scale.clone());
+      if let Some(previous) = &previous {
+        pages.insert(previous.page_id());
+      }
+      self.record(Change::new(
+        EntityKind::Scale,
+        id,
+        previous.map(EntityValue::Scale),
+        Some(EntityValue::Scale(scale)),
+      ));
+    }
+    for contour in batch.contours.unwrap_or_default() {
+      let id = contour.id.clone();
+      let previous = self.write_contour(&id, Some(contour.clone()));
+      self.record(Change::new(
+        EntityKind::Contour,
+        id,
+        previous.map(EntityValue::Contour),
+        Some(EntityValue::Contour(contour)),
+      ));
+    }
+    for measurement in batch.measurements.unwrap_or_default() {
+      let id = measurement.id().to_string();
+      groups.insert(measurement.group_id().to_string());
+      let previous = self.write_measurement(&id, Some(measurement.clone()));
+      if let Some(previous) = &previous {
+        groups.insert(previous.group_id().to_string());
+      }
+      self.record(Change::new(
+        EntityKind::Measurement,
+        id,
+        previous.map(EntityValue::Measurement),
+        Some(EntityValue::Measurement(measurement)),
+      ));
+    }
+
+    // Re-resolve the pages whose scales changed, then recompute each affected group once
+    for page_id in &pages {
+      for measurement in self.get_measurements_by_page_id(page_id.clone()) {
+        groups.insert(measurement.get_group_id());
+        measurement.calculate_scale();
+      }
+      self.compute_contours(page_id);
+    }
+    self.set_batching(false);
+    for group_id in groups {
+      let _ = self.compute_group(&group_id);
+    }
+  }
+
+  fn set_batching(&self, batching: bool) {
+    if let Ok(mut current) = lock_mutex(self.batching.lock(), "batching") {
+      *current = batching;
+    }
+  }
+
+  /// Whether group recomputation is deferred until the end of a batch.
+  fn is_batching(&self) -> bool {
+    lock_mutex(self.batching.lock(), "batching")
+      .map(|batching| *batching)
+      .unwrap_or(false)
+  }
+
+  #[napi]
+  /// Start grouping changes into a single undo step.
+  ///
+  /// Transactions nest; changes are recorded as one step when the outermost transaction is
+  /// committed.
+  ///
+  /// # Arguments
+  ///
+  /// * `label` - An optional label for the step, e.g. "Move 12 measurements".
+  pub fn begin_transaction(&self, label: Option<String>) {
+    if let Ok(mut history) = lock_mutex(self.history.lock(), "history") {
+      history.begin(label);
+    }
+  }
+
+  #[napi]
+  /// Close the innermost transaction started with
+  /// [`begin_transaction`](Self::begin_transaction).
+  ///
+  /// # Returns
+  ///
+  /// * `false` - If no transaction was open.
+  pub fn commit_transaction(&self) -> bool {
+    lock_mutex(self.history.lock(), "history")
+      .map(|mut history| history.commit())
+      .unwrap_or(false)
+  }
+
+  #[napi]
+  /// Revert every change made since the outermost open transaction began and close it.
+  ///
+  /// # Returns
+  ///
+  /// * `false` - If no transaction was open.
+  pub fn rollback_transaction(&self) -> bool {
+    let step = lock_mutex(self.history.lock(), "history")
+      .ok()
+      .and_then(|mut history| history.discard());
+    match step {
+      Some(step) => {
+        self.revert(&step.changes);
+        true
+      }
+      None => false,
+    }
+  }
+
+  #[napi]
+  /// Undo the latest step, restoring the previous values and recomputing what the original
+  /// change recomputed.
+  ///
+  /// # Returns
+  ///
+  /// * `false` - If there was nothing to undo.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::TransactionInProgress`] if a transaction is open.
+  pub fn undo(&self) -> Result<bool> {
+    let step = {
+      let mut history = lock_mutex(self.history.lock(), "history")?;
+      if history.in_transaction() {
+        return Err(TakeoffError::TransactionInProgress.into());
+      }
+      history.undo()
+    };
+    match step {
+      Some(step) => {
+        self.revert(&step.changes);
+        Ok(true)
+      }
+      None => Ok(false),
+    }
+  }
+
+  #[napi]
+  /// Redo the latest undone step.
+  ///
+  /// # Returns
+  ///
+  /// * `false` - If there was nothing to redo.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`TakeoffError::TransactionInProgress`] if a transaction is open.
+  pub fn redo(&self) -> Result<bool> {
+    let step = {
+      let mut history = lock_mutex(self.history.lock(), "history")?;
+      if history.in_transaction() {
+        return Err(TakeoffError::TransactionInProgress.into());
+      }
+      history.redo()
+    };
+    match step {
+      Some(step) => {
+        for change in &step.changes {
+          self.apply(change, change.after.clone());
+        }
+        Ok(true)
+      }
+      None => Ok(false),
+    }
+  }
+
+  #[napi]
+  pub fn can_undo(&self) -> bool {
+    lock_mutex(self.history.lock(), "history")
+      .map(|history| history.can_undo())
+      .unwrap_or(false)
+  }
+
+  #[napi]
+  pub fn can_redo(&self) -> bool {
+    lock_mutex(self.history.lock(), "history")
+      .map(|history| history.can_redo())
+      .unwrap_or(false)
+  }
+
+  #[napi]
+  /// Get the label of the step [`undo`](Self::undo) would revert.
+  pub fn get_undo_label(&self) -> Option<String> {
+    lock_mutex(self.history.lock(), "history")
+      .ok()
+      .and_then(|history| history.undo_label())
+  }
+
+  #[napi]
+  /// Get the label of the step [`redo`](Self::redo) would reapply.
+  pub fn get_redo_label(&self) -> Option<String> {
+    lock_mutex(self.history.lock(), "history")
+      .ok()
+      .and_then(|history| history.redo_label())
+  }
+
+  #[napi]
+  /// Drop every undo and redo step and any open transaction.
+  pub fn clear_history(&self) {
+    if let Ok(mut history) = lock_mutex(self.history.lock(), "history") {
+      history.clear();
+    }
+  }
+
+  #[napi]
+  /// Take every change event emitted since the last drain, oldest first.
+  ///
+  /// Recomputations triggered by upserts, removals, undo and redo emit events for the
+  /// measurements, groups and contours they touch. An event already pending is not queued
+  /// twice, so polling after each batch of changes is enough to refresh the affected ids.
+  pub fn drain_events(&self) -> Vec<StateEvent> {
+    lock_mutex(self.events.lock(), "events")
+      .map(|mut events| events.drain())
+      .unwrap_or_default()
+  }
+
+  #[napi]
+  pub fn has_pending_events(&self) -> bool {
+    lock_mutex(self.events.lock(), "events")
+      .map(|events| !events.is_empty())
+      .unwrap_or(false)
+  }
+
+  pub(crate) fn emit_event(&self, event: StateEvent) {
+    if let Ok(mut events) = lock_mutex(self.events.lock(), "events") {
+      events.push(event);
+    }
+  }
+
+  fn record(&self, change: Change) {
+    if let Ok(mut history) = lock_mutex(self.history.lock(), "history") {
+      history.record(change);
+    }
+  }
+
+  /// Run `f` inside a transaction so the changes it records form a single step.
+  fn grouped<T>(&self, f: impl FnOnce() -> T) -> T {
+    self.begin_transaction(None);
+    let result = f();
+    self.commit_transaction();
+    result
+  }
+
+  fn revert(&self, changes: &[Change]) {
+    for change in changes.iter().rev() {
+      self.apply(change, change.before.clone());
+    }
+  }
+
+  /// Set an entity to `value`, or remove it if `None`, without recording the change.
+  fn apply(&self, change: &Change, value: Option<EntityValue>) {
+    let id = change.entity_id.as_str();
+    match value {
+      Some(EntityValue::Page(page)) => {
+        self.write_page(id, Some(page));
+      }
+      Some(EntityValue::Group(group)) => {
+        self.write_group(id, Some(group));
+      }
+      Some(EntityValue::Measurement(measurement)) => {
+        self.write_measurement(id, Some(measurement));
+      }
+      Some(EntityValue::Scale(scale)) => {
+        self.write_scale(id, Some(scale));
+      }
+      Some(EntityValue::Contour(contour)) => {
+        self.write_contour(id, Some(contour));
+      }
+      None => match change.entity_kind {
+        EntityKind::Page => {
+          self.write_page(id, None);
+        }
+        EntityKind::Group => {
+          self.write_group(id, None);
+        }
+        EntityKind::Measurement => {
+          self.write_measurement(id, None);
+        }
+        EntityKind::Scale => {
+          self.write_scale(id, None);
+        }
+        EntityKind::Contour => {
+          self.write_contour(id, None);
+        }
+      },
+    }
+  }
+
+  fn write_page(&self, id: &str, page: Option<Page>) -> Option<Page> {
+    match page {
+      Some(page) => self.pages.insert(id.to_string(), page),
+      None => self.pages.remove(id).map(|(_, page)| page),
+    }
+  }
+
+  fn write_group(&self, id: &str, group: Option<Group>) -> Option<Group> {
+    let previous = self.groups.get(id).map(|entry| entry.get_group());
+    match group {
+      Some(group) => {
+        self.groups.insert(
+          id.to_string(),
+          GroupWrapper::new(group, self.self_arc.clone().unwrap()),
+        );
+      }
+      None => {
+        self.groups.remove(id);
+      }
+    }
+    previous
+  }
+
+  fn write_measurement(&self, id: &str, measurement: Option<Measurement>) -> Option<Measurement> {
+    let Some(measurement) = measurement else {
+      let (_, removed) = self.measurements.remove(id)?;
+      self.index_measurement(Some(&removed.get_measurement()), None);
+      // Ignore recomputation errors - they will be handled when group values are accessed
+      let _ = self.compute_group(&removed.get_group_id());
+      return Some(removed.get_measurement());
+    };
+
+    let existing = self.measurements.get(id).map(|entry| entry.value().clone());
+    if let Some(existing) = existing {
+      let group_id = measurement.group_id().to_string();
+      self.index_measurement(Some(&existing.get_measurement()), Some(&measurement));
+      let previous = existing.replace_measurement(measurement);
+      // The measurement may have moved to another page, scale or group
+      existing.calculate_scale();
+      if previous.group_id() != group_id {
+        let _ = self.compute_group(previous.group_id());
+      }
+      let _ = self.compute_group(&group_id);
+      return Some(previous);
+    }
+
+    let group_id = measurement.group_id().to_string();
+    self.index_measurement(None, Some(&measurement));
+    self.measurements.insert(
+      id.to_string(),
+      MeasurementWrapper::new(measurement, self.self_arc.clone().unwrap()),
+    );
+    self.compute_measurement(id);
+    let _ = self.compute_group(&group_id);
+    None
+  }
+
+  /// Move a measurement between the spatial indexes of its previous and current pages.
+  ///
+  /// Measurements with invalid geometry are left out of the index.
+  fn index_measurement(&self, previous: Option<&Measurement>, measurement: Option<&Measurement>) {
+    if let Some(previous) = previous {
+      if let Some(mut index) = self.spatial.get_mut(previous.page_id()) {
+        index.remove(previous.id());
+      }
+    }
+    if let Some(measurement) = measurement {
+      let _ = self
+        .spatial
+        .entry(measurement.page_id().to_string())
+        .or_default()
+        .insert(measurement);
+    }
+  }
+
+  fn write_scale(&self, id: &str, scale: Option<Scale>) -> Option<Scale> {
+    let mut page_ids: BTreeSet<String> = scale.iter().map(Scale::page_id).collect();
+    let previous = match scale {
+      Some(scale) => self.scales.insert(id.to_string(), scale),
+      None => self.scales.remove(id).map(|(_, scale)| scale),
+    };
+    page_ids.extend(previous.iter().map(Scale::page_id));
+    for page_id in &page_ids {
+      self.compute_page(page_id);
+      self.compute_contours(page_id);
+    }
+    previous
+  }
+
+  fn write_contour(&self, id: &str, contour: Option<ContourInput>) -> Option<ContourInput> {
+    let Some(contour) = contour else {
+      return self
+        .contours
+        .remove(id)
+        .map(|(_, removed)| removed.get_contour());
+    };
+
+    if let Some(existing) = self.contours.get(id) {
+      let previous = existing.get_contour();
+      existing.set_contour(contour);
+      existing.calculate_scale();
+      return Some(previous);
+    }
+
+    let wrapper = ContourWrapper::from_input(contour, self.self_arc.clone().unwrap());
+    wrapper.calculate_scale();
+    self.contours.insert(id.to_string(), wrapper);
+    None
+  }
+
+  fn compute_contours(&self, page_id: &str) {
+    let contours: Vec<ContourWrapper> = self
+      .contours
+      .iter()
+      .filter(|entry| entry.value().page_id() == page_id)
+      .map(|entry| entry.value().clone())
+      .collect();
+    for contour in contours {
+      contour.calculate_scale();
+    }
+  }
+}
+
+#[napi]
+#[cfg(not(target_family = "wasm"))]
+impl TakeoffStateHandler {
+  fn compute_measurement(&self, measurement_id: &str) {
+    let measurement = self.measurements.get(measurement_id);
+    if let Some(measurement) = measurement {
+      std::thread::scope(|s| {
+        s.spawn(|| {
+          measurement.calculate_scale();
+        });
+      });
+    }
+  }
+
+  pub fn compute_group(&self, group_id: &str) -> Result<()> {
+    if self.is_batching() {
+      return Ok(());
+    }
     let group = self.groups.get(group_id);
     if let Some(group) = group {
       std::thread::scope(|s| {
@@ -452,6 +2233,9 @@ impl TakeoffStateHandler {
   }
 
   pub fn compute_group(&self, group_id: &str) -> Result<()> {
+    if self.is_batching() {
+      return Ok(());
+    }
     let group = self.groups.get(group_id);
     if let Some(group) = group {
       group.recompute_measurements().unwrap();
@@ -469,6 +2253,7 @@ mod tests {
   use takeoff_core::measurement::Measurement::*;
   use takeoff_core::scale::Scale::*;
   use takeoff_core::scale::ScaleDefinition;
+  use takeoff_core::snap::SnapKind;
   use takeoff_core::unit::Unit;
 
   #[test]
@@ -478,26 +2263,35 @@ mod tests {
       groups: vec![],
       measurements: vec![],
       scales: vec![],
-    }));
-    state.upsert_scale(Area {
-      id: "1".to_string(),
-      page_id: "1".to_string(),
-      bounding_box: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
-      scale: ScaleDefinition {
-        pixel_distance: 1.0,
-        real_distance: 1.0,
-        unit: Unit::Meters,
-      },
-    });
-    state.upsert_scale(Default {
-      id: "2".to_string(),
-      page_id: "1".to_string(),
-      scale: ScaleDefinition {
-        pixel_distance: 1.0,
-        real_distance: 1.0,
-        unit: Unit::Meters,
-      },
-    });
+      group_validation_mode: None,
+      removal_policy: None,
+      strict_references: None,
+      contours: None,
+    }))
+    .unwrap();
+    state
+      .upsert_scale(Area {
+        id: "1".to_string(),
+        page_id: "1".to_string(),
+        bounding_box: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Meters,
+        },
+      })
+      .unwrap();
+    state
+      .upsert_scale(Default {
+        id: "2".to_string(),
+        page_id: "1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Meters,
+        },
+      })
+      .unwrap();
     let measurement = Polygon {
       id: "1".to_string(),
       page_id: "1".to_string(),
@@ -508,8 +2302,9 @@ mod tests {
         Point::new(1.0, 1.0),
         Point::new(0.5, 1.0),
       ],
+      attributes: None,
     };
-    state.upsert_measurement(measurement.clone());
+    state.upsert_measurement(measurement.clone()).unwrap();
     let scale = state.get_measurement_scale(measurement.id().to_string());
     assert_eq!(
       scale,
@@ -555,21 +2350,32 @@ mod tests {
       id: "1".to_string(),
       name: None,
       measurement_type: MeasurementType::Area,
+      attributes: None,
     };
-    state.upsert_group(group);
+    state.upsert_group(group).unwrap();
     let group = state.groups.get("1").unwrap();
     let group_clone = group.clone();
     assert_eq!(
       group.get_area().unwrap().get_converted_value(Unit::Meters),
       0.25
     );
+    assert_eq!(
+      group
+        .evaluate_formula("ceil(area / 0.1sqm)".to_string())
+        .unwrap()
+        .get_converted_value(Unit::Meters),
+      3.0
+    );
 
-    state.upsert_measurement(Measurement::Rectangle {
-      id: "12".to_string(),
-      page_id: "1".to_string(),
-      group_id: "1".to_string(),
-      points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
-    });
+    state
+      .upsert_measurement(Measurement::Rectangle {
+        id: "12".to_string(),
+        page_id: "1".to_string(),
+        group_id: "1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+        attributes: None,
+      })
+      .unwrap();
 
     let initial_group_area = {
       group_clone
@@ -601,16 +2407,22 @@ mod tests {
       groups: vec![],
       measurements: vec![],
       scales: vec![],
-    }));
+      group_validation_mode: None,
+      removal_policy: None,
+      strict_references: None,
+      contours: None,
+    }))
+    .unwrap();
     let group = Group {
       id: "1".to_string(),
       name: None,
       measurement_type: MeasurementType::Area,
+      attributes: None,
     };
-    state.upsert_group(group);
+    state.upsert_group(group).unwrap();
     // let group = state.groups.get("1").unwrap();
 
-    let group_removed = state.remove_group("1".to_string());
+    let group_removed = state.remove_group("1".to_string()).unwrap();
     assert!(group_removed.is_some());
     let group = state.get_group("1".to_string());
     assert!(group.is_none());
@@ -618,42 +2430,1665 @@ mod tests {
 
   #[test]
   fn test_upsert_contour_with_deferred_scale() {
-    let state = TakeoffStateHandler::new(None);
-    state.upsert_contour(ContourInput {
-      id: "c1".to_string(),
-      name: None,
-      page_id: "1".to_string(),
-      lines: vec![ContourLineInput {
-        elevation: 10.0,
-        unit: Unit::Feet,
-        points: vec![
-          Point::new(0.0, 0.0),
-          Point::new(100.0, 0.0),
-          Point::new(100.0, 100.0),
-          Point::new(0.0, 100.0),
-        ],
-      }],
-      points_of_interest: vec![],
-    });
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_contour(ContourInput {
+        id: "c1".to_string(),
+        name: None,
+        page_id: "1".to_string(),
+        lines: vec![ContourLineInput {
+          elevation: 10.0,
+          unit: Unit::Feet,
+          points: vec![
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(100.0, 100.0),
+            Point::new(0.0, 100.0),
+          ],
+        }],
+        points_of_interest: vec![],
+      })
+      .unwrap();
 
     // No scale yet — mesh should be None
     let contour = state.get_contour("c1".to_string()).unwrap();
     assert!(contour.get_surface_points().is_none());
 
     // Add a scale for the page
-    state.upsert_scale(Default {
-      id: "s1".to_string(),
-      page_id: "1".to_string(),
-      scale: ScaleDefinition {
-        pixel_distance: 1.0,
-        real_distance: 1.0,
-        unit: Unit::Feet,
-      },
-    });
-
-    // Now mesh should be available
-    let contour = state.get_contour("c1".to_string()).unwrap();
-    assert!(contour.get_surface_points().is_some());
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+
+    // Now mesh should be available
+    let contour = state.get_contour("c1".to_string()).unwrap();
+    assert!(contour.get_surface_points().is_some());
     assert_eq!(contour.get_surface_points().unwrap().len(), 4);
   }
+
+  #[test]
+  fn test_attribute_filter_and_aggregate() {
+    use takeoff_core::attribute::{AttributeFilter, AttributeValue};
+
+    let attributes = |entries: &[(&str, AttributeValue)]| {
+      Some(
+        entries
+          .iter()
+          .map(|(k, v)| (k.to_string(), v.clone()))
+          .collect(),
+      )
+    };
+    let state = TakeoffStateHandler::new(Some(StateOptions {
+      pages: vec![],
+      groups: vec![Group {
+        id: "g1".to_string(),
+        name: None,
+        measurement_type: MeasurementType::Area,
+        attributes: attributes(&[("finish", AttributeValue::text("CPT-1"))]),
+      }],
+      measurements: vec![],
+      scales: vec![Default {
+        id: "s1".to_string(),
+        page_id: "1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Meters,
+        },
+      }],
+      group_validation_mode: None,
+      removal_policy: None,
+      strict_references: None,
+      contours: None,
+    }))
+    .unwrap();
+    let rectangle = |id: &str, size: f64, attributes| Rectangle {
+      id: id.to_string(),
+      page_id: "1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(size, size)),
+      attributes,
+    };
+    state
+      .upsert_measurement(rectangle(
+        "m1",
+        2.0,
+        attributes(&[("room", AttributeValue::text("Kitchen"))]),
+      ))
+      .unwrap();
+    state
+      .upsert_measurement(rectangle(
+        "m2",
+        3.0,
+        attributes(&[
+          ("room", AttributeValue::text("Kitchen")),
+          ("finish", AttributeValue::text("VCT-2")),
+          ("waste", AttributeValue::number(1.1)),
+        ]),
+      ))
+      .unwrap();
+    state
+      .upsert_measurement(rectangle("m3", 1.0, None))
+      .unwrap();
+
+    let carpet = state.get_measurements_by_attributes(vec![AttributeFilter::Equals {
+      key: "finish".to_string(),
+      value: AttributeValue::text("CPT-1"),
+    }]);
+    let mut ids: Vec<String> = carpet.iter().map(|m| m.id()).collect();
+    ids.sort();
+    assert_eq!(ids, vec!["m1", "m3"]);
+
+    let groups = state.get_groups_by_attributes(vec![AttributeFilter::Exists {
+      key: "finish".to_string(),
+    }]);
+    assert_eq!(groups.len(), 1);
+
+    let by_room = state.aggregate_measurements_by_attribute("room".to_string(), None);
+    assert_eq!(by_room.len(), 2);
+    assert_eq!(by_room[0].value(), Some(AttributeValue::text("Kitchen")));
+    assert_eq!(by_room[0].measurement_ids(), vec!["m1", "m2"]);
+    assert_eq!(
+      by_room[0].area().unwrap().get_converted_value(Unit::Meters),
+      13.0
+    );
+    assert_eq!(by_room[1].value(), None);
+    assert_eq!(by_room[1].count(), 1.0);
+
+    let m2 = state.get_measurement("m2".to_string()).unwrap();
+    assert_eq!(
+      m2.get_resolved_attributes().get("finish"),
+      Some(&AttributeValue::text("VCT-2"))
+    );
+    let with_waste = m2.evaluate_formula("area * waste".to_string()).unwrap();
+    assert!((with_waste.get_converted_value(Unit::Meters) - 9.9).abs() < 1e-4);
+  }
+
+  #[test]
+  fn test_group_validation_mode() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: None,
+        measurement_type: MeasurementType::Area,
+        attributes: None,
+      })
+      .unwrap();
+    let polyline = |id: &str| Polyline {
+      id: id.to_string(),
+      page_id: "1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+      attributes: None,
+    };
+
+    // Flag mode (default) accepts the polyline and reports it
+    assert_eq!(state.get_group_validation_mode(), GroupValidationMode::Flag);
+    state.upsert_measurement(polyline("m1")).unwrap();
+    let mismatches = state.get_measurement_type_mismatches();
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].measurement_id, "m1");
+    assert_eq!(mismatches[0].measurement_kind, "Polyline");
+    assert_eq!(mismatches[0].measurement_type, MeasurementType::Area);
+
+    // Reject mode refuses new incompatible measurements
+    state.set_group_validation_mode(GroupValidationMode::Reject);
+    let err = state.upsert_measurement(polyline("m2")).unwrap_err();
+    assert!(err.to_string().contains("incompatible with Area group g1"));
+    assert!(state.get_measurement("m2".to_string()).is_none());
+
+    // Measurements of unknown groups are not validated
+    let mut orphan = polyline("m3");
+    if let Polyline { group_id, .. } = &mut orphan {
+      *group_id = "missing".to_string();
+    }
+    assert!(state.upsert_measurement(orphan).is_ok());
+    assert_eq!(state.get_measurement_type_mismatches().len(), 1);
+  }
+
+  #[test]
+  fn test_group_validation_mode_rejects_existing_measurements() {
+    let group = |measurement_type| Group {
+      id: "g1".to_string(),
+      name: None,
+      measurement_type,
+      attributes: None,
+    };
+    let polyline = Polyline {
+      id: "m1".to_string(),
+      page_id: "1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0)],
+      attributes: None,
+    };
+    let options = |mode| StateOptions {
+      pages: vec![],
+      groups: vec![group(MeasurementType::Area)],
+      measurements: vec![polyline.clone()],
+      scales: vec![],
+      contours: None,
+      group_validation_mode: Some(mode),
+      removal_policy: None,
+      strict_references: None,
+    };
+
+    // Reject mode validates the measurements passed at construction
+    let err = TakeoffStateHandler::new(Some(options(GroupValidationMode::Reject))).unwrap_err();
+    assert!(err.to_string().contains("incompatible with Area group g1"));
+
+    // Reject mode refuses a type change that its existing measurements do not fit
+    let state = TakeoffStateHandler::new(Some(StateOptions {
+      groups: vec![group(MeasurementType::Linear)],
+      ..options(GroupValidationMode::Reject)
+    }))
+    .unwrap();
+    let err = state
+      .upsert_group(group(MeasurementType::Area))
+      .unwrap_err();
+    assert!(err.to_string().contains("incompatible with Area group g1"));
+    assert_eq!(
+      state
+        .get_group("g1".to_string())
+        .unwrap()
+        .get_group()
+        .measurement_type,
+      MeasurementType::Linear
+    );
+    let err = state
+      .apply_batch(StateBatch {
+        groups: Some(vec![group(MeasurementType::Area)]),
+        ..StateBatch::default()
+      })
+      .unwrap_err();
+    assert!(err.to_string().contains("incompatible with Area group g1"));
+  }
+
+  #[test]
+  fn test_validate_geometry() {
+    use takeoff_core::validity::GeometryIssueKind;
+
+    let state = TakeoffStateHandler::new(None).unwrap();
+    let polygon = |id: &str, points: &[(f64, f64)]| Polygon {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: points.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+      attributes: None,
+    };
+    state
+      .upsert_measurement(polygon(
+        "b",
+        &[(0.0, 0.0), (10.0, 10.0), (10.0, 0.0), (0.0, 10.0)],
+      ))
+      .unwrap();
+    state
+      .upsert_measurement(polygon(
+        "a",
+        &[(0.0, 0.0), (10.0, 0.0), (10.0, 0.0), (10.0, 10.0)],
+      ))
+      .unwrap();
+    state
+      .upsert_measurement(polygon("c", &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]))
+      .unwrap();
+
+    let issues = state.validate_geometry(None);
+    let found: Vec<(&str, GeometryIssueKind)> = issues
+      .iter()
+      .map(|issue| (issue.measurement_id.as_str(), issue.kind))
+      .collect();
+    assert_eq!(
+      found,
+      [
+        ("a", GeometryIssueKind::DuplicateVertex),
+        ("b", GeometryIssueKind::SelfIntersection)
+      ]
+    );
+  }
+
+  #[test]
+  fn test_referential_integrity() {
+    use takeoff_core::integrity::{EntityKind, RemovalPolicy};
+
+    let page = |id: &str| Page {
+      id: id.to_string(),
+      name: None,
+      width: None,
+      height: None,
+      viewport: None,
+    };
+    let count = |id: &str, page_id: &str, group_id: &str| Count {
+      id: id.to_string(),
+      page_id: page_id.to_string(),
+      group_id: group_id.to_string(),
+      points: (Point::new(0.0, 0.0),),
+      attributes: None,
+    };
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state.upsert_page(page("p1"));
+    state.upsert_page(page("p2"));
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: None,
+        measurement_type: MeasurementType::Count,
+        attributes: None,
+      })
+      .unwrap();
+    state.upsert_measurement(count("m1", "p1", "g1")).unwrap();
+    state.upsert_measurement(count("m2", "p2", "g1")).unwrap();
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+
+    // Dangling references are accepted by default and reported by validate_state
+    state.upsert_measurement(count("m3", "p9", "g9")).unwrap();
+    let dangling = state.validate_state();
+    assert_eq!(dangling.len(), 2);
+    assert_eq!(dangling[0].entity_id, "m3");
+    assert_eq!(dangling[0].reference_kind, EntityKind::Page);
+    assert_eq!(dangling[1].reference_kind, EntityKind::Group);
+    state.remove_measurement("m3".to_string());
+
+    // Strict references reject them
+    state.set_strict_references(true);
+    let err = state
+      .upsert_measurement(count("m4", "p1", "g9"))
+      .unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "measurement m4 references missing group g9"
+    );
+    assert!(state.get_measurement("m4".to_string()).is_none());
+
+    // Reject policy keeps pages and groups that are still referenced
+    state.set_removal_policy(RemovalPolicy::Reject);
+    let err = state.remove_page("p1".to_string()).unwrap_err();
+    assert_eq!(err.to_string(), "page p1 is still referenced by 2 entities");
+    assert!(state.remove_group("g1".to_string()).is_err());
+
+    // Legacy policy (default) removes only the page, but a group with its measurements
+    state.set_removal_policy(RemovalPolicy::Legacy);
+    assert!(state.remove_page("p2".to_string()).unwrap().is_some());
+    assert!(state.get_measurement("m2".to_string()).is_some());
+    assert_eq!(state.validate_state().len(), 1);
+    state.upsert_page(page("p2"));
+
+    // Cascade policy removes the page's measurements and scales
+    state.set_removal_policy(RemovalPolicy::Cascade);
+    assert!(state.remove_page("p1".to_string()).unwrap().is_some());
+    assert!(state.get_measurement("m1".to_string()).is_none());
+    assert!(state.get_page_scales("p1").is_empty());
+    assert!(state.get_measurement("m2".to_string()).is_some());
+    assert!(state.validate_state().is_empty());
+
+    // Legacy policy removes a group along with its measurements
+    state.set_removal_policy(RemovalPolicy::Legacy);
+    assert!(state.remove_group("g1".to_string()).unwrap().is_some());
+    assert!(state.get_measurement("m2".to_string()).is_none());
+
+    // Strict references also check the initial entities
+    let options = |strict_references| StateOptions {
+      pages: vec![page("p1")],
+      groups: vec![],
+      measurements: vec![count("m1", "p1", "g1")],
+      scales: vec![],
+      contours: None,
+      group_validation_mode: None,
+      removal_policy: None,
+      strict_references,
+    };
+    let err = TakeoffStateHandler::new(Some(options(Some(true)))).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "measurement m1 references missing group g1"
+    );
+    let lenient = TakeoffStateHandler::new(Some(options(None))).unwrap();
+    assert_eq!(lenient.validate_state().len(), 1);
+  }
+
+  #[test]
+  fn test_undo_redo() {
+    use takeoff_core::integrity::RemovalPolicy;
+
+    let rectangle = |id: &str, width: f64| Rectangle {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(width, 1.0)),
+      attributes: None,
+    };
+    let group_area = |state: &TakeoffStateHandler| {
+      state
+        .get_group("g1".to_string())
+        .and_then(|group| group.get_area())
+        .map(|area| area.get_converted_value(Unit::Meters))
+    };
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state.upsert_page(Page {
+      id: "p1".to_string(),
+      name: None,
+      width: None,
+      height: None,
+      viewport: None,
+    });
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: None,
+        measurement_type: MeasurementType::Area,
+        attributes: None,
+      })
+      .unwrap();
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Meters,
+        },
+      })
+      .unwrap();
+    state.upsert_measurement(rectangle("m1", 1.0)).unwrap();
+    state.upsert_measurement(rectangle("m2", 1.0)).unwrap();
+    assert_eq!(group_area(&state), Some(2.0));
+
+    // A transaction is undone and redone as one step
+    state.begin_transaction(Some("Resize 2 measurements".to_string()));
+    state.upsert_measurement(rectangle("m1", 2.0)).unwrap();
+    state.upsert_measurement(rectangle("m2", 2.0)).unwrap();
+    assert!(state.undo().is_err());
+    assert!(state.commit_transaction());
+    assert_eq!(group_area(&state), Some(4.0));
+    assert_eq!(
+      state.get_undo_label(),
+      Some("Resize 2 measurements".to_string())
+    );
+
+    assert!(state.undo().unwrap());
+    assert_eq!(group_area(&state), Some(2.0));
+    assert!(state.redo().unwrap());
+    assert_eq!(group_area(&state), Some(4.0));
+
+    // Undoing a scale removal restores the scale
+    state.remove_scale("s1".to_string());
+    assert!(state.get_page_scales("p1").is_empty());
+    assert!(state.undo().unwrap());
+    assert_eq!(state.get_page_scales("p1").len(), 1);
+    assert!(state.get_measurements_missing_scale().is_empty());
+
+    // Undoing the insert of the only scale leaves the measurements without a scale or an
+    // area
+    state.remove_scale("s1".to_string());
+    assert_eq!(state.get_measurements_missing_scale().len(), 2);
+    state
+      .upsert_scale(Area {
+        id: "s2".to_string(),
+        page_id: "p1".to_string(),
+        bounding_box: (Point::new(0.0, 0.0), Point::new(10.0, 10.0)),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 2.0,
+          unit: Unit::Meters,
+        },
+      })
+      .unwrap();
+    assert_eq!(group_area(&state), Some(16.0));
+    assert!(state.undo().unwrap());
+    let m1 = state.get_measurement("m1".to_string()).unwrap();
+    assert_eq!(m1.get_scale(), None);
+    assert!(m1.get_area().is_none());
+    assert_eq!(state.get_measurements_missing_scale().len(), 2);
+    assert!(state.undo().unwrap());
+    assert_eq!(
+      m1.get_scale().map(|scale| scale.id()),
+      Some("s1".to_string())
+    );
+    assert_eq!(group_area(&state), Some(4.0));
+
+    // A cascading removal is a single step
+    state.set_removal_policy(RemovalPolicy::Cascade);
+    state.remove_page("p1".to_string()).unwrap();
+    assert!(state.get_measurement("m1".to_string()).is_none());
+    assert!(state.undo().unwrap());
+    assert!(state.get_measurement("m1".to_string()).is_some());
+    assert_eq!(state.get_page_scales("p1").len(), 1);
+    assert_eq!(group_area(&state), Some(4.0));
+
+    // Rolled back transactions are reverted and not recorded
+    state.begin_transaction(None);
+    state.remove_measurement("m2".to_string());
+    assert!(state.rollback_transaction());
+    assert!(state.get_measurement("m2".to_string()).is_some());
+    assert_eq!(group_area(&state), Some(4.0));
+
+    // The rollback recorded nothing, so redo re-applies the page removal undone above
+    assert!(state.redo().unwrap());
+    assert!(!state.pages.contains_key("p1"));
+
+    state.clear_history();
+    assert!(!state.undo().unwrap());
+  }
+
+  #[test]
+  fn test_undo_measurement_moves() {
+    let square = |page_id: &str, group_id: &str| Rectangle {
+      id: "m1".to_string(),
+      page_id: page_id.to_string(),
+      group_id: group_id.to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+      attributes: None,
+    };
+    let group_area = |state: &TakeoffStateHandler, group_id: &str| {
+      state
+        .get_group(group_id.to_string())
+        .and_then(|group| group.get_area())
+        .map(|area| area.get_converted_value(Unit::Meters))
+    };
+    let scale_id = |state: &TakeoffStateHandler| {
+      state
+        .get_measurement("m1".to_string())
+        .and_then(|measurement| measurement.get_scale())
+        .map(|scale| scale.id())
+    };
+    let state = TakeoffStateHandler::new(None).unwrap();
+    for id in ["g1", "g2"] {
+      state
+        .upsert_group(Group {
+          id: id.to_string(),
+          name: None,
+          measurement_type: MeasurementType::Area,
+          attributes: None,
+        })
+        .unwrap();
+    }
+    for (id, page_id, real_distance) in [("s1", "p1", 1.0), ("s2", "p2", 2.0)] {
+      state
+        .upsert_scale(Default {
+          id: id.to_string(),
+          page_id: page_id.to_string(),
+          scale: ScaleDefinition {
+            pixel_distance: 1.0,
+            real_distance,
+            unit: Unit::Meters,
+          },
+        })
+        .unwrap();
+    }
+    state.upsert_measurement(square("p1", "g1")).unwrap();
+
+    // Moving to another group recomputes both groups, and so does undoing the move
+    state.upsert_measurement(square("p1", "g2")).unwrap();
+    assert_eq!(group_area(&state, "g1"), None);
+    assert_eq!(group_area(&state, "g2"), Some(1.0));
+    assert!(state.undo().unwrap());
+    assert_eq!(group_area(&state, "g1"), Some(1.0));
+    assert_eq!(group_area(&state, "g2"), None);
+
+    // Moving to another page resolves the scale of that page
+    state.upsert_measurement(square("p2", "g1")).unwrap();
+    assert_eq!(scale_id(&state), Some("s2".to_string()));
+    assert_eq!(group_area(&state, "g1"), Some(4.0));
+    assert!(state.undo().unwrap());
+    assert_eq!(scale_id(&state), Some("s1".to_string()));
+    assert_eq!(group_area(&state, "g1"), Some(1.0));
+    assert!(state.redo().unwrap());
+    assert_eq!(scale_id(&state), Some("s2".to_string()));
+  }
+
+  #[test]
+  fn test_upsert_and_batch_agree() {
+    let square = |x: f64, group_id: &str| Rectangle {
+      id: "m1".to_string(),
+      page_id: "p1".to_string(),
+      group_id: group_id.to_string(),
+      points: (Point::new(x, 0.0), Point::new(x + 1.0, 1.0)),
+      attributes: None,
+    };
+    let setup = || {
+      let state = TakeoffStateHandler::new(None).unwrap();
+      for id in ["g1", "g2"] {
+        state
+          .upsert_group(Group {
+            id: id.to_string(),
+            name: None,
+            measurement_type: MeasurementType::Area,
+            attributes: None,
+          })
+          .unwrap();
+      }
+      let definition = |real_distance: f64| ScaleDefinition {
+        pixel_distance: 1.0,
+        real_distance,
+        unit: Unit::Meters,
+      };
+      state
+        .upsert_scale(Default {
+          id: "s1".to_string(),
+          page_id: "p1".to_string(),
+          scale: definition(1.0),
+        })
+        .unwrap();
+      state
+        .upsert_scale(Area {
+          id: "a1".to_string(),
+          page_id: "p1".to_string(),
+          bounding_box: (Point::new(10.0, 0.0), Point::new(20.0, 10.0)),
+          scale: definition(2.0),
+        })
+        .unwrap();
+      state.upsert_measurement(square(0.0, "g1")).unwrap();
+      state
+    };
+    let summary = |state: &TakeoffStateHandler| {
+      let area = |group_id: &str| {
+        state
+          .get_group(group_id.to_string())
+          .and_then(|group| group.get_area())
+          .map(|area| area.get_converted_value(Unit::Meters))
+      };
+      let scale_id = state
+        .get_measurement("m1".to_string())
+        .and_then(|measurement| measurement.get_scale())
+        .map(|scale| scale.id());
+      (scale_id, area("g1"), area("g2"))
+    };
+
+    // Move m1 into the area scale and into another group
+    let single = setup();
+    single.upsert_measurement(square(12.0, "g2")).unwrap();
+    let batch = setup();
+    batch.upsert_measurements(vec![square(12.0, "g2")]).unwrap();
+    let expected = (Some("a1".to_string()), None, Some(4.0));
+    assert_eq!(summary(&single), expected);
+    assert_eq!(summary(&batch), expected);
+  }
+
+  #[test]
+  fn test_drain_events() {
+    use takeoff_core::event::StateEvent;
+    use takeoff_core::integrity::EntityKind;
+
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: None,
+        measurement_type: MeasurementType::Area,
+        attributes: None,
+      })
+      .unwrap();
+    state
+      .upsert_measurement(Rectangle {
+        id: "m1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(2.0, 2.0)),
+        attributes: None,
+      })
+      .unwrap();
+    state
+      .upsert_contour(ContourInput {
+        id: "c1".to_string(),
+        name: None,
+        page_id: "p1".to_string(),
+        lines: vec![ContourLineInput {
+          elevation: 10.0,
+          unit: Unit::Feet,
+          points: vec![
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(100.0, 100.0),
+          ],
+        }],
+        points_of_interest: vec![],
+      })
+      .unwrap();
+    let events = state.drain_events();
+    assert!(events.contains(&StateEvent::GroupTotalsChanged {
+      group_id: "g1".to_string(),
+    }));
+    assert!(!state.has_pending_events());
+
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+    let events = state.drain_events();
+    assert!(events.contains(&StateEvent::ScaleReassigned {
+      entity_kind: EntityKind::Measurement,
+      entity_id: "m1".to_string(),
+      scale_id: "s1".to_string(),
+    }));
+    assert!(events.contains(&StateEvent::MeasurementRecomputed {
+      measurement_id: "m1".to_string(),
+    }));
+    assert!(events.contains(&StateEvent::ScaleReassigned {
+      entity_kind: EntityKind::Contour,
+      entity_id: "c1".to_string(),
+      scale_id: "s1".to_string(),
+    }));
+    assert!(events.contains(&StateEvent::ContourMeshRebuilt {
+      contour_id: "c1".to_string(),
+    }));
+    assert!(events.contains(&StateEvent::GroupTotalsChanged {
+      group_id: "g1".to_string(),
+    }));
+
+    // Recomputing without a change to the totals emits no group event
+    state
+      .upsert_measurement(Rectangle {
+        id: "m1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(1.0, 1.0), Point::new(3.0, 3.0)),
+        attributes: None,
+      })
+      .unwrap();
+    let events = state.drain_events();
+    assert_eq!(
+      events,
+      vec![StateEvent::MeasurementRecomputed {
+        measurement_id: "m1".to_string(),
+      }]
+    );
+  }
+
+  #[test]
+  fn test_apply_batch() {
+    let rectangle = |id: usize, group_id: &str| Rectangle {
+      id: format!("m{id}"),
+      page_id: "p1".to_string(),
+      group_id: group_id.to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(1.0, 1.0)),
+      attributes: None,
+    };
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state.set_strict_references(true);
+
+    // References to pages and groups in the same batch are allowed
+    state
+      .apply_batch(StateBatch {
+        pages: Some(vec![Page {
+          id: "p1".to_string(),
+          name: None,
+          width: None,
+          height: None,
+          viewport: None,
+        }]),
+        groups: Some(vec![Group {
+          id: "g1".to_string(),
+          name: None,
+          measurement_type: MeasurementType::Area,
+          attributes: None,
+        }]),
+        measurements: Some((0..50).map(|id| rectangle(id, "g1")).collect()),
+        scales: Some(vec![Default {
+          id: "s1".to_string(),
+          page_id: "p1".to_string(),
+          scale: ScaleDefinition {
+            pixel_distance: 1.0,
+            real_distance: 1.0,
+            unit: Unit::Meters,
+          },
+        }]),
+        contours: None,
+      })
+      .unwrap();
+    let group = state.get_group("g1".to_string()).unwrap();
+    assert_eq!(group.get_count(), Some(50.0));
+    assert_eq!(
+      group.get_area().unwrap().get_converted_value(Unit::Meters),
+      50.0
+    );
+    assert!(state.get_measurements_missing_scale().is_empty());
+
+    // An invalid batch is rejected as a whole
+    let err = state
+      .upsert_measurements(vec![rectangle(50, "g1"), rectangle(51, "g9")])
+      .unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "measurement m51 references missing group g9"
+    );
+    assert!(state.get_measurement("m50".to_string()).is_none());
+
+    // Updates keep the measurement wrappers and recompute the totals once
+    let wrapper = state.get_measurement("m0".to_string()).unwrap();
+    state
+      .upsert_measurements(
+        (0..50)
+          .map(|id| Rectangle {
+            id: format!("m{id}"),
+            page_id: "p1".to_string(),
+            group_id: "g1".to_string(),
+            points: (Point::new(0.0, 0.0), Point::new(2.0, 1.0)),
+            attributes: None,
+          })
+          .collect(),
+      )
+      .unwrap();
+    assert_eq!(
+      wrapper
+        .get_area()
+        .unwrap()
+        .get_converted_value(Unit::Meters),
+      2.0
+    );
+    let group_area = |state: &TakeoffStateHandler| {
+      state
+        .get_group("g1".to_string())
+        .unwrap()
+        .get_area()
+        .unwrap()
+        .get_converted_value(Unit::Meters)
+    };
+    assert_eq!(group_area(&state), 100.0);
+
+    // The batch is a single history step
+    assert!(state.undo().unwrap());
+    assert_eq!(group_area(&state), 50.0);
+  }
+
+  #[test]
+  fn test_snapshot_roundtrip() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .apply_batch(StateBatch {
+        pages: Some(vec![Page {
+          id: "p1".to_string(),
+          name: Some("Level 1".to_string()),
+          width: None,
+          height: None,
+          viewport: None,
+        }]),
+        groups: Some(vec![Group {
+          id: "g1".to_string(),
+          name: None,
+          measurement_type: MeasurementType::Area,
+          attributes: None,
+        }]),
+        measurements: Some(vec![Rectangle {
+          id: "m1".to_string(),
+          page_id: "p1".to_string(),
+          group_id: "g1".to_string(),
+          points: (Point::new(0.0, 0.0), Point::new(2.0, 3.0)),
+          attributes: None,
+        }]),
+        scales: Some(vec![Default {
+          id: "s1".to_string(),
+          page_id: "p1".to_string(),
+          scale: ScaleDefinition {
+            pixel_distance: 1.0,
+            real_distance: 1.0,
+            unit: Unit::Meters,
+          },
+        }]),
+        contours: Some(vec![ContourInput {
+          id: "c1".to_string(),
+          name: None,
+          page_id: "p1".to_string(),
+          lines: vec![ContourLineInput {
+            elevation: 10.0,
+            unit: Unit::Feet,
+            points: vec![
+              Point::new(0.0, 0.0),
+              Point::new(100.0, 0.0),
+              Point::new(100.0, 100.0),
+            ],
+          }],
+          points_of_interest: vec![],
+        }]),
+      })
+      .unwrap();
+    state.set_removal_policy(RemovalPolicy::Reject);
+
+    let snapshot = state.to_snapshot();
+    assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+    assert_eq!(snapshot.results.measurements[0].area, Some(6.0));
+    assert_eq!(snapshot.results.groups[0].area, Some(6.0));
+    assert!(snapshot.results.contours[0].has_surface_mesh);
+
+    let json = state.to_snapshot_json().unwrap();
+    let restored = TakeoffStateHandler::from_snapshot_json(json).unwrap();
+    assert_eq!(restored.to_snapshot(), snapshot);
+    assert_eq!(restored.get_removal_policy(), RemovalPolicy::Reject);
+  }
+
+  #[test]
+  fn test_project_file_roundtrip() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state.upsert_page(Page {
+      id: "p1".to_string(),
+      name: None,
+      width: None,
+      height: None,
+      viewport: None,
+    });
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+    state.set_strict_references(true);
+
+    for format in [ProjectFormat::Json, ProjectFormat::MessagePack] {
+      let data = state.to_project_file(Some(format)).unwrap();
+      let restored = TakeoffStateHandler::from_project_file(data, Some(format)).unwrap();
+      assert_eq!(restored.to_snapshot(), state.to_snapshot());
+    }
+  }
+
+  #[test]
+  fn test_diff_snapshot() {
+    use takeoff_core::attribute::AttributeValue;
+    use takeoff_core::group::DEPTH_ATTRIBUTE;
+    use takeoff_core::unit::UnitValueItemType;
+
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state.upsert_page(Page {
+      id: "p1".to_string(),
+      name: None,
+      width: None,
+      height: None,
+      viewport: None,
+    });
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: None,
+        measurement_type: MeasurementType::Area,
+        attributes: Some(
+          [(
+            DEPTH_ATTRIBUTE.to_string(),
+            AttributeValue::quantity(0.5, Unit::Meters, UnitValueItemType::Length),
+          )]
+          .into(),
+        ),
+      })
+      .unwrap();
+    let scale = |pixel_distance: f64| Default {
+      id: "s1".to_string(),
+      page_id: "p1".to_string(),
+      scale: ScaleDefinition {
+        pixel_distance,
+        real_distance: 1.0,
+        unit: Unit::Meters,
+      },
+    };
+    let rectangle = |id: &str, width: f64| Rectangle {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(0.0, 0.0), Point::new(width, 2.0)),
+      attributes: None,
+    };
+    state.upsert_scale(scale(1.0)).unwrap();
+    state.upsert_measurement(rectangle("m1", 2.0)).unwrap();
+    state.upsert_measurement(rectangle("m2", 1.0)).unwrap();
+    let bid = state.to_snapshot();
+    assert_eq!(bid.results.groups[0].volume, Some(3.0));
+    assert!(state.diff_snapshot(bid.clone(), None).is_empty());
+
+    state.remove_measurement("m2".to_string());
+    state.upsert_measurement(rectangle("m3", 3.0)).unwrap();
+    let diff = state.diff_snapshot(bid, None);
+    assert_eq!(diff.added_measurements[0].measurement_id, "m3");
+    assert_eq!(diff.removed_measurements[0].measurement_id, "m2");
+    assert!(diff.modified_measurements.is_empty());
+    assert!(diff.scales.is_empty());
+    let group = &diff.groups[0];
+    assert!((group.area.delta - 4.0).abs() < 1e-6);
+    assert!((group.volume.delta - 2.0).abs() < 1e-6);
+    assert!(group.count.is_unchanged());
+  }
+
+  #[test]
+  fn test_scale_resolution_order() {
+    use takeoff_core::scale::ScaleConflict;
+
+    let definition = |pixel_distance: f64| ScaleDefinition {
+      pixel_distance,
+      real_distance: 1.0,
+      unit: Unit::Meters,
+    };
+    let scales = vec![
+      Default {
+        id: "d2".to_string(),
+        page_id: "p1".to_string(),
+        scale: definition(2.0),
+      },
+      Area {
+        id: "outer".to_string(),
+        page_id: "p1".to_string(),
+        bounding_box: (Point::new(0.0, 0.0), Point::new(100.0, 100.0)),
+        scale: definition(3.0),
+      },
+      Area {
+        id: "inner".to_string(),
+        page_id: "p1".to_string(),
+        bounding_box: (Point::new(0.0, 0.0), Point::new(10.0, 10.0)),
+        scale: definition(4.0),
+      },
+      Default {
+        id: "d1".to_string(),
+        page_id: "p1".to_string(),
+        scale: definition(1.0),
+      },
+    ];
+    let rectangle = |id: &str, offset: f64| Rectangle {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (
+        Point::new(offset + 1.0, offset + 1.0),
+        Point::new(offset + 2.0, offset + 2.0),
+      ),
+      attributes: None,
+    };
+
+    // The result does not depend on insertion order
+    for reversed in [false, true] {
+      let state = TakeoffStateHandler::new(None).unwrap();
+      let mut ordered = scales.clone();
+      if reversed {
+        ordered.reverse();
+      }
+      for scale in ordered {
+        state.upsert_scale(scale).unwrap();
+      }
+      for (id, offset) in [("m1", 0.0), ("m2", 50.0), ("m3", 500.0)] {
+        state.upsert_measurement(rectangle(id, offset)).unwrap();
+      }
+      let scale_id = |id: &str| state.get_measurement_scale(id.to_string()).map(|s| s.id());
+      assert_eq!(scale_id("m1"), Some("inner".to_string()));
+      assert_eq!(scale_id("m2"), Some("outer".to_string()));
+      assert_eq!(scale_id("m3"), Some("d1".to_string()));
+
+      assert_eq!(
+        state.get_scale_conflicts(),
+        vec![ScaleConflict::MultipleDefaultScales {
+          page_id: "p1".to_string(),
+          scale_ids: vec!["d1".to_string(), "d2".to_string()],
+        }]
+      );
+    }
+  }
+
+  #[test]
+  fn test_export_report() {
+    use takeoff_core::report::{ReportColumn, ReportFormat};
+
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state.upsert_page(Page {
+      id: "p1".to_string(),
+      name: Some("Level 1".to_string()),
+      width: None,
+      height: None,
+      viewport: None,
+    });
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: Some("Slab".to_string()),
+        measurement_type: MeasurementType::Area,
+        attributes: None,
+      })
+      .unwrap();
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+    for (id, width) in [("m1", 2.0), ("m2", 3.0)] {
+      state
+        .upsert_measurement(Rectangle {
+          id: id.to_string(),
+          page_id: "p1".to_string(),
+          group_id: "g1".to_string(),
+          points: (Point::new(0.0, 0.0), Point::new(width, 10.0)),
+          attributes: None,
+        })
+        .unwrap();
+    }
+
+    let csv = state.export_report(None).unwrap();
+    assert_eq!(
+      std::str::from_utf8(&csv).unwrap(),
+      "Group,Page,Type,Area,Length,Count\nSlab,Level 1,Area,50 ft²,50 ft,2\n"
+    );
+
+    let csv = state
+      .export_report(Some(ReportOptions {
+        level: Some(ReportLevel::Measurements),
+        columns: Some(vec![ReportColumn::Measurement, ReportColumn::Area]),
+        sort_by: Some(vec![ReportColumn::Area]),
+        descending: Some(true),
+        ..ReportOptions::default()
+      }))
+      .unwrap();
+    assert_eq!(
+      std::str::from_utf8(&csv).unwrap(),
+      "Measurement,Area\nm2,30 ft²\nm1,20 ft²\n"
+    );
+
+    let xlsx = state
+      .export_report(Some(ReportOptions {
+        format: Some(ReportFormat::Xlsx),
+        ..ReportOptions::default()
+      }))
+      .unwrap();
+    assert!(!xlsx.is_empty());
+  }
+
+  #[test]
+  fn test_geojson_roundtrip() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 1.0,
+          unit: Unit::Meters,
+        },
+      })
+      .unwrap();
+    let measurements = vec![
+      Rectangle {
+        id: "m1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(2.0, 3.0)),
+        attributes: None,
+      },
+      Polyline {
+        id: "m2".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g2".to_string(),
+        points: vec![Point::new(0.0, 0.0), Point::new(3.0, 4.0)],
+        attributes: None,
+      },
+    ];
+    state.upsert_measurements(measurements.clone()).unwrap();
+
+    let geojson = state.export_geojson("p1".to_string(), None).unwrap();
+    let document: serde_json::Value = serde_json::from_str(&geojson).unwrap();
+    let properties = &document["features"][0]["properties"];
+    assert_eq!(properties["area"], 6.0);
+    assert_eq!(properties["scale_id"], "s1");
+    assert_eq!(document["features"][1]["properties"]["length"], 5.0);
+
+    let copy = TakeoffStateHandler::new(None).unwrap();
+    let imported = copy
+      .import_geojson("p1".to_string(), geojson, None)
+      .unwrap();
+    assert_eq!(imported, measurements);
+    assert_eq!(copy.get_measurements_by_page_id("p1".to_string()).len(), 2);
+  }
+
+  #[test]
+  fn test_import_dxf() {
+    use std::collections::BTreeMap;
+
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_group(Group {
+        id: "walls".to_string(),
+        name: Some("Walls".to_string()),
+        measurement_type: MeasurementType::Linear,
+        attributes: None,
+      })
+      .unwrap();
+    let dxf = [
+      "0",
+      "SECTION",
+      "2",
+      "HEADER",
+      "9",
+      "$INSUNITS",
+      "70",
+      "2",
+      "0",
+      "ENDSEC",
+      "0",
+      "SECTION",
+      "2",
+      "ENTITIES",
+      "0",
+      "LWPOLYLINE",
+      "8",
+      "A-WALL",
+      "70",
+      "1",
+      "10",
+      "0",
+      "20",
+      "0",
+      "10",
+      "10",
+      "20",
+      "0",
+      "10",
+      "10",
+      "20",
+      "10",
+      "10",
+      "0",
+      "20",
+      "10",
+      "0",
+      "INSERT",
+      "8",
+      "E-DEVICE",
+      "2",
+      "OUTLET",
+      "10",
+      "5",
+      "20",
+      "5",
+      "0",
+      "ENDSEC",
+      "0",
+      "EOF",
+    ]
+    .join("\n");
+    let options = DxfImportOptions {
+      pixels_per_unit: Some(10.0),
+      layer_groups: Some(BTreeMap::from([(
+        "A-WALL".to_string(),
+        "walls".to_string(),
+      )])),
+    };
+
+    let import = state
+      .import_dxf(
+        "p1".to_string(),
+        dxf.as_bytes().to_vec().into(),
+        Some(options.clone()),
+      )
+      .unwrap();
+    assert!(import.warnings.is_empty());
+    // The existing group is kept; the device layer gets a new count group
+    let walls = state.get_group("walls".to_string()).unwrap();
+    assert_eq!(walls.get_group().measurement_type, MeasurementType::Linear);
+    let devices = state.get_group("E-DEVICE".to_string()).unwrap();
+    assert_eq!(devices.get_group().measurement_type, MeasurementType::Count);
+    assert_eq!(devices.get_count(), Some(1.0));
+    // 10 ft x 10 ft, at 10 pixels per foot
+    let perimeter = walls.get_length().unwrap().get_converted_value(Unit::Feet);
+    assert!((perimeter - 40.0).abs() < 1e-3);
+
+    // A second import keeps the page's existing scale
+    state
+      .import_dxf(
+        "p1".to_string(),
+        dxf.as_bytes().to_vec().into(),
+        Some(options),
+      )
+      .unwrap();
+    assert_eq!(state.get_page_scales("p1").len(), 1);
+    assert_eq!(state.get_measurements_by_page_id("p1".to_string()).len(), 4);
+  }
+
+  #[test]
+  fn test_export_dxf() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: Some("Flooring".to_string()),
+        measurement_type: MeasurementType::Area,
+        attributes: None,
+      })
+      .unwrap();
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 1.0,
+          real_distance: 0.01,
+          unit: Unit::Meters,
+        },
+      })
+      .unwrap();
+    state
+      .upsert_measurement(Rectangle {
+        id: "m1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(200.0, 300.0)),
+        attributes: None,
+      })
+      .unwrap();
+
+    let dxf = state.export_dxf("p1".to_string(), None).unwrap();
+    // Drawn in meters, the page's scale unit, on the group's layer
+    assert!(dxf.contains("0\nLAYER\n2\nFlooring\n"));
+    assert!(dxf.contains("1\n6.00 sq m\n"));
+    assert!(dxf.contains("10\n2\n20\n-3\n"));
+
+    let options = DxfExportOptions {
+      unit: Some(Unit::Centimeters),
+      ..DxfExportOptions::default()
+    };
+    let dxf = state.export_dxf("p1".to_string(), Some(options)).unwrap();
+    assert!(dxf.contains("10\n200\n20\n-300\n"));
+  }
+
+  #[test]
+  fn test_render_svg() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state.upsert_page(Page {
+      id: "p1".to_string(),
+      name: None,
+      width: Some(400.0),
+      height: Some(300.0),
+      viewport: None,
+    });
+    state
+      .upsert_scale(Area {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 10.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+        bounding_box: (Point::new(0.0, 0.0), Point::new(200.0, 200.0)),
+      })
+      .unwrap();
+    state
+      .upsert_measurement(Polyline {
+        id: "m1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: vec![Point::new(10.0, 10.0), Point::new(110.0, 10.0)],
+        attributes: None,
+      })
+      .unwrap();
+
+    let svg = state.render_svg("p1".to_string(), None).unwrap();
+    assert!(svg.contains(r#"width="400" height="300""#));
+    assert!(svg.contains(r#"<rect data-scale-id="s1""#));
+    assert!(svg.contains(r#"<polyline data-measurement-id="m1" points="10,10 110,10""#));
+    assert!(svg.contains(r#"<text data-measurement-id="m1" x="60" y="10">10.00 ft</text>"#));
+  }
+
+  #[test]
+  fn test_spatial_queries() {
+    let ids = |measurements: Vec<MeasurementWrapper>| -> Vec<String> {
+      measurements
+        .iter()
+        .map(|m| m.get_measurement().id().to_string())
+        .collect()
+    };
+    let state = TakeoffStateHandler::new(Some(StateOptions {
+      pages: vec![],
+      groups: vec![],
+      measurements: vec![Rectangle {
+        id: "r1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(10.0, 10.0)),
+        attributes: None,
+      }],
+      scales: vec![],
+      group_validation_mode: None,
+      removal_policy: None,
+      strict_references: None,
+      contours: None,
+    }))
+    .unwrap();
+    state
+      .upsert_measurement(Polyline {
+        id: "l1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g2".to_string(),
+        points: vec![Point::new(20.0, 0.0), Point::new(20.0, 10.0)],
+        attributes: None,
+      })
+      .unwrap();
+
+    let at = |x: f64, y: f64, tolerance: f64| {
+      ids(state.measurements_at_point("p1".to_string(), Point::new(x, y), Some(tolerance)))
+    };
+    assert_eq!(at(5.0, 5.0, 0.0), ["r1"]);
+    assert_eq!(at(15.0, 5.0, 5.0), ["l1", "r1"]);
+    assert_eq!(
+      ids(state.measurements_in_rect(
+        "p1".to_string(),
+        Point::new(15.0, 0.0),
+        Point::new(25.0, 5.0)
+      )),
+      ["l1"]
+    );
+    let nearest = |x: f64, max_distance: Option<f64>| {
+      state
+        .nearest_measurement("p1".to_string(), Point::new(x, 5.0), max_distance)
+        .map(|m| m.get_measurement().id().to_string())
+    };
+    assert_eq!(nearest(12.0, None).as_deref(), Some("r1"));
+    assert_eq!(nearest(30.0, Some(5.0)), None);
+
+    // Moving a measurement to another page moves it between indexes
+    state
+      .upsert_measurement(Polyline {
+        id: "l1".to_string(),
+        page_id: "p2".to_string(),
+        group_id: "g2".to_string(),
+        points: vec![Point::new(20.0, 0.0), Point::new(20.0, 10.0)],
+        attributes: None,
+      })
+      .unwrap();
+    assert_eq!(at(20.0, 5.0, 0.0), Vec::<String>::new());
+    assert_eq!(
+      ids(state.measurements_at_point("p2".to_string(), Point::new(20.0, 5.0), None)),
+      ["l1"]
+    );
+
+    // Removal and undo keep the index in sync
+    state.remove_measurement("r1".to_string());
+    assert!(at(5.0, 5.0, 0.0).is_empty());
+    assert!(state.undo().unwrap());
+    assert_eq!(at(5.0, 5.0, 0.0), ["r1"]);
+
+    assert!(state
+      .nearest_measurement("missing".to_string(), Point::new(0.0, 0.0), None)
+      .is_none());
+  }
+
+  #[test]
+  fn test_snap_point() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_measurement(Rectangle {
+        id: "r1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g1".to_string(),
+        points: (Point::new(0.0, 0.0), Point::new(10.0, 10.0)),
+        attributes: None,
+      })
+      .unwrap();
+    state
+      .upsert_measurement(Polyline {
+        id: "l1".to_string(),
+        page_id: "p1".to_string(),
+        group_id: "g2".to_string(),
+        points: vec![Point::new(100.0, 0.0), Point::new(100.0, 10.0)],
+        attributes: None,
+      })
+      .unwrap();
+
+    let vertex = state
+      .snap_point("p1".to_string(), Point::new(9.0, 9.5), 2.0, None)
+      .unwrap();
+    assert_eq!(vertex.kind, SnapKind::Vertex);
+    assert_eq!(vertex.point, Point::new(10.0, 10.0));
+
+    let foot = state
+      .snap_point("p1".to_string(), Point::new(101.0, 3.0), 2.0, None)
+      .unwrap();
+    assert_eq!(foot.kind, SnapKind::Perpendicular);
+    assert_eq!(foot.point, Point::new(100.0, 3.0));
+    assert_eq!(foot.measurement_id.as_deref(), Some("l1"));
+
+    let options = SnapOptions {
+      kinds: None,
+      exclude_ids: Some(vec!["r1".to_string()]),
+    };
+    assert!(state
+      .snap_point("p1".to_string(), Point::new(9.0, 9.5), 2.0, Some(options))
+      .is_none());
+    assert!(state
+      .snap_point("p2".to_string(), Point::new(9.0, 9.5), 2.0, None)
+      .is_none());
+  }
+
+  #[test]
+  fn test_boolean_op() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 10.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+    let rectangle = |id: &str, min: f64, max: f64| Rectangle {
+      id: id.to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: (Point::new(min, min), Point::new(max, max)),
+      attributes: None,
+    };
+
+    // Two 10 ft slabs overlapping by 5 ft each way
+    let union = state
+      .boolean_op(
+        rectangle("a", 0.0, 100.0),
+        rectangle("b", 50.0, 150.0),
+        BooleanOperation::Union,
+      )
+      .unwrap();
+    assert_eq!(union.len(), 1);
+    assert!((union[0].raw_area().unwrap() - 17500.0).abs() < 1e-6);
+    let area = union[0].convert_area(Unit::Feet).unwrap().unwrap();
+    assert!((area - 175.0).abs() < 1e-3);
+    // The result is only a preview
+    assert!(state.get_measurement(union[0].id()).is_none());
+
+    assert!(state
+      .boolean_op(
+        rectangle("a", 0.0, 100.0),
+        Count {
+          id: "c".to_string(),
+          page_id: "p1".to_string(),
+          group_id: "g1".to_string(),
+          points: (Point::new(0.0, 0.0),),
+          attributes: None,
+        },
+        BooleanOperation::Difference,
+      )
+      .is_err());
+  }
+  #[test]
+  fn test_offset_measurement() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    let polygon = Polygon {
+      id: "a".to_string(),
+      page_id: "p1".to_string(),
+      group_id: "g1".to_string(),
+      points: vec![
+        Point::new(0.0, 0.0),
+        Point::new(100.0, 0.0),
+        Point::new(100.0, 100.0),
+        Point::new(0.0, 100.0),
+      ],
+      attributes: None,
+    };
+    // Without a scale there is no way to convert the distance
+    assert!(state
+      .offset_measurement(polygon.clone(), 1.0, Unit::Feet, None)
+      .is_err());
+
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 10.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+    // A 10 ft room grown by 1 ft on every side
+    let grown = state
+      .offset_measurement(polygon.clone(), 1.0, Unit::Feet, Some(OffsetJoin::Miter))
+      .unwrap();
+    assert_eq!(grown.len(), 1);
+    let area = grown[0].convert_area(Unit::Feet).unwrap().unwrap();
+    assert!((area - 144.0).abs() < 1e-3);
+    assert!(state.get_measurement(grown[0].id()).is_none());
+
+    let shrunk = state
+      .offset_measurement(polygon, -6.0, Unit::Inches, None)
+      .unwrap();
+    let area = shrunk[0].convert_area(Unit::Feet).unwrap().unwrap();
+    assert!((area - 81.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn test_group_overlaps() {
+    let state = TakeoffStateHandler::new(None).unwrap();
+    state
+      .upsert_group(Group {
+        id: "g1".to_string(),
+        name: None,
+        measurement_type: MeasurementType::Area,
+        attributes: None,
+      })
+      .unwrap();
+    state
+      .upsert_scale(Default {
+        id: "s1".to_string(),
+        page_id: "p1".to_string(),
+        scale: ScaleDefinition {
+          pixel_distance: 10.0,
+          real_distance: 1.0,
+          unit: Unit::Feet,
+        },
+      })
+      .unwrap();
+    // The same 10 ft room outlined by two estimators
+    for id in ["m1", "m2"] {
+      state
+        .upsert_measurement(Rectangle {
+          id: id.to_string(),
+          page_id: "p1".to_string(),
+          group_id: "g1".to_string(),
+          points: (Point::new(0.0, 0.0), Point::new(100.0, 100.0)),
+          attributes: None,
+        })
+        .unwrap();
+    }
+
+    let group = state.get_group("g1".to_string()).unwrap();
+    let area = group.get_area().unwrap().get_converted_value(Unit::Feet);
+    assert!((area - 200.0).abs() < 1e-2);
+    let dissolved = group
+      .get_dissolved_area()
+      .unwrap()
+      .get_converted_value(Unit::Feet);
+    assert!((dissolved - 100.0).abs() < 1e-2);
+
+    let overlaps = group.get_overlaps(None);
+    assert_eq!(overlaps.len(), 1);
+    assert_eq!(overlaps[0].measurement_id, "m1");
+    assert_eq!(overlaps[0].other_measurement_id, "m2");
+    assert!((overlaps[0].pixel_area - 10000.0).abs() < 1e-6);
+  }
 }
@@ -125,6 +125,7 @@ const {
   },
 });
 module.exports = __napiModule.exports;
+module.exports.AttributeAggregate = __napiModule.exports.AttributeAggregate;
 module.exports.ContourWrapper = __napiModule.exports.ContourWrapper;
 module.exports.GroupWrapper = __napiModule.exports.GroupWrapper;
 module.exports.MeasurementWrapper = __napiModule.exports.MeasurementWrapper;
@@ -133,12 +134,33 @@ module.exports.VolumetricUnitResult = __napiModule.exports.VolumetricUnitResult;
 module.exports.plus100 = __napiModule.exports.plus100;
 module.exports.plus200 = __napiModule.exports.plus200;
 module.exports.UnitValue = __napiModule.exports.UnitValue;
+module.exports.booleanOp = __napiModule.exports.booleanOp;
+module.exports.BooleanOperation = __napiModule.exports.BooleanOperation;
+module.exports.constrainAngle = __napiModule.exports.constrainAngle;
 module.exports.distance = __napiModule.exports.distance;
+module.exports.EntityKind = __napiModule.exports.EntityKind;
+module.exports.findGeometryIssues = __napiModule.exports.findGeometryIssues;
 module.exports.generateRandomId = __napiModule.exports.generateRandomId;
+module.exports.GeometryIssueKind = __napiModule.exports.GeometryIssueKind;
 module.exports.getCentroid = __napiModule.exports.getCentroid;
+module.exports.GroupValidationMode = __napiModule.exports.GroupValidationMode;
+module.exports.HeatmapFormat = __napiModule.exports.HeatmapFormat;
+module.exports.makeValid = __napiModule.exports.makeValid;
 module.exports.MeasurementType = __napiModule.exports.MeasurementType;
+module.exports.mergeMeasurements = __napiModule.exports.mergeMeasurements;
+module.exports.OffsetJoin = __napiModule.exports.OffsetJoin;
+module.exports.offsetMeasurement = __napiModule.exports.offsetMeasurement;
+module.exports.ProjectFormat = __napiModule.exports.ProjectFormat;
+module.exports.RemovalPolicy = __napiModule.exports.RemovalPolicy;
+module.exports.ReportColumn = __napiModule.exports.ReportColumn;
+module.exports.ReportFormat = __napiModule.exports.ReportFormat;
+module.exports.ReportLevel = __napiModule.exports.ReportLevel;
 module.exports.repositionMeasurementToCentroid =
   __napiModule.exports.repositionMeasurementToCentroid;
 module.exports.simplifyPolyline = __napiModule.exports.simplifyPolyline;
+module.exports.SnapKind = __napiModule.exports.SnapKind;
+module.exports.splitPolygon = __napiModule.exports.splitPolygon;
+module.exports.splitPolyline = __napiModule.exports.splitPolyline;
+module.exports.squareUpPolygon = __napiModule.exports.squareUpPolygon;
 module.exports.Unit = __napiModule.exports.Unit;
 module.exports.UnitValueItemType = __napiModule.exports.UnitValueItemType;
@@ -78,12 +78,14 @@ fn measurement_from_entry(entry: &BaselineEntry) -> Measurement {
       page_id,
       group_id,
       points,
+      attributes: None,
     },
     "Polyline" => Measurement::Polyline {
       id,
       page_id,
       group_id,
       points,
+      attributes: None,
     },
     "Rectangle" => {
       assert_eq!(points.len(), 2, "Rectangle must have exactly 2 points");
@@ -92,6 +94,7 @@ fn measurement_from_entry(entry: &BaselineEntry) -> Measurement {
         page_id,
         group_id,
         points: (points[0], points[1]),
+        attributes: None,
       }
     }
     "Count" => {
@@ -101,6 +104,7 @@ fn measurement_from_entry(entry: &BaselineEntry) -> Measurement {
         page_id,
         group_id,
         points: (points[0],),
+        attributes: None,
       }
     }
     _ => panic!("unknown kind: {}", entry.kind),